@@ -30,5 +30,7 @@ pub enum Event {
     MouseMoved { x: f32, y: f32 },
     MousePressed { button: Option<MouseButton> },
     MouseReleased { button: Option<MouseButton> },
+    WindowResized { width: u32, height: u32 },
+    FramebufferResized { width: u32, height: u32 },
     None,
 }
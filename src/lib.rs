@@ -1,3 +1,13 @@
+#[cfg(not(any(
+    feature = "opengl",
+    feature = "software",
+    feature = "recording",
+    feature = "wgpu"
+)))]
+compile_error!(
+    "At least one of the opengl, software, recording and wgpu features has to be enabled"
+);
+
 mod platform;
 
 pub mod event;
@@ -13,7 +13,7 @@ use ::once_cell::sync::Lazy;
 
 use crate::{
     event::{Event, Keycode, MouseButton},
-    render::window::Window,
+    render::window::{ContextRequest, FramebufferResize, Window},
 };
 
 static EVENT_MAP: Lazy<Mutex<HashMap<usize, Vec<Event>>>> =
@@ -23,16 +23,41 @@ pub struct GlfwWindow {
     window: Option<glfw::Window>,
     glfw: Glfw,
     event_reciever: Receiver<(f64, WindowEvent)>,
+    framebuffer_resize: FramebufferResize,
 }
 
 impl GlfwWindow {
     pub fn new() -> Box<dyn Window> {
-        let glfw = glfw::init(Some(glfw::Callback {
+        return GlfwWindow::new_with_hints(0, false, ContextRequest::default());
+    }
+
+    // A debug context makes the driver report errors and warnings through
+    // the GL debug output, at some cost in performance.
+    pub fn new_with_hints(samples: u32, debug: bool, context: ContextRequest) -> Box<dyn Window> {
+        let mut glfw = glfw::init(Some(glfw::Callback {
             f: GlfwWindow::error_callback,
             data: String::new(),
         }))
         .expect("Failed to init GLFW");
 
+        // None would leave the sample count to the driver.
+        glfw.window_hint(glfw::WindowHint::Samples(Some(if samples > 1 { samples } else { 0 })));
+        glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(debug));
+        match context {
+            ContextRequest::Core { major, minor } => {
+                glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGl));
+                glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+                glfw.window_hint(glfw::WindowHint::OpenGlProfile(
+                    glfw::OpenGlProfileHint::Core,
+                ));
+                glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
+            }
+            ContextRequest::Es { major, minor } => {
+                glfw.window_hint(glfw::WindowHint::ClientApi(glfw::ClientApiHint::OpenGlEs));
+                glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+            }
+        }
+
         let (mut window, events) = glfw
             .create_window(800, 600, "title", glfw::WindowMode::Windowed)
             .expect("Failed to create GLFW window");
@@ -57,6 +82,7 @@ impl GlfwWindow {
             window: Some(window),
             event_reciever: events,
             glfw: glfw,
+            framebuffer_resize: FramebufferResize::default(),
         });
     }
 
@@ -138,6 +164,17 @@ impl Window for GlfwWindow {
                         button: GlfwWindow::translate_keycode_mouse(button),
                     })
                 }
+                WindowEvent::Size(width, height) => events.push(Event::WindowResized {
+                    width: width as u32,
+                    height: height as u32,
+                }),
+                WindowEvent::FramebufferSize(width, height) => {
+                    self.framebuffer_resize.notify(width as u32, height as u32);
+                    events.push(Event::FramebufferResized {
+                        width: width as u32,
+                        height: height as u32,
+                    })
+                }
                 WindowEvent::Close => events.push(Event::Quit),
                 _ => events.push(Event::None),
             }
@@ -208,6 +245,10 @@ impl Window for GlfwWindow {
         }
     }
 
+    fn get_framebuffer_resize(&self) -> FramebufferResize {
+        return self.framebuffer_resize.clone();
+    }
+
     fn get_size(&self) -> (f32, f32) {
         match self.window.as_ref() {
             Some(window) => {
@@ -217,4 +258,14 @@ impl Window for GlfwWindow {
             None => (0.0, 0.0),
         }
     }
+
+    fn get_framebuffer_size(&self) -> (u32, u32) {
+        match self.window.as_ref() {
+            Some(window) => {
+                let (width, height) = window.get_framebuffer_size();
+                return (width as u32, height as u32);
+            }
+            None => (0, 0),
+        }
+    }
 }
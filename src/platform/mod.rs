@@ -1,2 +1,14 @@
+#[cfg(feature = "glfw")]
 pub mod glfw;
+#[cfg(any(feature = "software", feature = "recording", feature = "wgpu"))]
+pub mod null_window;
+#[cfg(feature = "opengl")]
 pub mod opengl;
+#[cfg(feature = "headless")]
+pub mod osmesa;
+#[cfg(feature = "recording")]
+pub mod recording;
+#[cfg(feature = "software")]
+pub mod software;
+#[cfg(feature = "wgpu")]
+pub mod wgpu;
@@ -0,0 +1,56 @@
+use std::{ffi::c_void, ptr};
+
+use crate::{
+    event::Event,
+    render::window::Window,
+};
+
+// Window without a display or GL context for the CPU backends, which
+// draw into their own surfaces.
+pub struct NullWindow {
+    width: u32,
+    height: u32,
+    closed: bool,
+}
+
+impl NullWindow {
+    pub fn new(width: u32, height: u32) -> Box<dyn Window> {
+        return Box::new(NullWindow {
+            width,
+            height,
+            closed: false,
+        });
+    }
+}
+
+impl Window for NullWindow {
+    fn close(&mut self) {
+        self.closed = true;
+    }
+
+    fn get_size(&self) -> (f32, f32) {
+        return (self.width as f32, self.height as f32);
+    }
+
+    fn get_framebuffer_size(&self) -> (u32, u32) {
+        return (self.width, self.height);
+    }
+
+    fn get_events(&mut self) -> Vec<Event> {
+        return Vec::new();
+    }
+
+    fn get_proc_addr(&mut self, _procname: &str) -> *const c_void {
+        return ptr::null();
+    }
+
+    fn should_close(&self) -> bool {
+        return self.closed;
+    }
+
+    fn update(&mut self) {}
+
+    fn set_title(&mut self, _title: &str) {}
+
+    fn toggle_mouse_grab(&mut self) {}
+}
@@ -0,0 +1,292 @@
+use std::{
+    ffi::c_void,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, ThreadId},
+};
+
+use once_cell::{
+    sync::{Lazy, OnceCell},
+    unsync,
+};
+
+use crate::render::{
+    buffer::BufferDataType,
+    texture::{PixelBuffer, Texture, TextureFormat},
+};
+
+use super::{
+    debug, frame_stats, state_cache,
+    texture::OglTexture,
+    vertex_array::OglVertexArray,
+};
+
+const DEFAULT_UPLOAD_BUDGET: usize = 4 * 1024 * 1024;
+
+type DecodeJob = (
+    Box<dyn FnOnce() -> Option<PixelBuffer> + Send>,
+    Arc<AsyncTextureState>,
+    ThreadId,
+);
+
+static UPLOAD_BUDGET: AtomicUsize = AtomicUsize::new(DEFAULT_UPLOAD_BUDGET);
+static PENDING_UPLOADS: Lazy<Mutex<Vec<PendingUpload>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+thread_local! {
+    // Belongs to the context current on this thread.
+    static PLACEHOLDER: unsync::OnceCell<OglTexture> = unsync::OnceCell::new();
+}
+
+// A single worker decodes jobs in submission order and hands the pixels
+// over to the GL thread that created the texture through the pending
+// upload list. A panicking job or one with broken pixels only fails its
+// own texture, the worker keeps going.
+static DECODE_QUEUE: Lazy<Mutex<Sender<DecodeJob>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<DecodeJob>();
+    thread::spawn(move || {
+        for (decode, state, thread) in receiver {
+            match panic::catch_unwind(AssertUnwindSafe(decode)) {
+                Ok(Some(pixels)) if !has_valid_size(&pixels) => log::warn!(
+                    target: debug::LOG_TARGET,
+                    "Decoded texture is {}x{} with {} bytes of data, using the placeholder",
+                    pixels.width,
+                    pixels.height,
+                    pixels.data.as_bytes().len()
+                ),
+                Ok(Some(pixels)) => PENDING_UPLOADS.lock().unwrap().push(PendingUpload {
+                    pixels,
+                    state,
+                    thread,
+                    buffer: 0,
+                    uploaded_rows: 0,
+                }),
+                Ok(None) => {
+                    log::warn!(target: debug::LOG_TARGET, "Failed to decode texture, using the placeholder")
+                }
+                Err(_) => {
+                    log::warn!(target: debug::LOG_TARGET, "Texture decoding panicked, using the placeholder")
+                }
+            }
+        }
+    });
+
+    Mutex::new(sender)
+});
+
+// Empty images would divide the upload budget by zero and short data would
+// be read out of bounds, both on the GL thread.
+fn has_valid_size(pixels: &PixelBuffer) -> bool {
+    let size = (pixels.width as usize)
+        .checked_mul(pixels.height as usize)
+        .and_then(|size| size.checked_mul(pixels.format.get_channel_count() as usize))
+        .and_then(|size| size.checked_mul(pixels.data.get_data_type().get_size()));
+
+    return match size {
+        Some(size) => size > 0 && pixels.data.as_bytes().len() >= size,
+        None => false,
+    };
+}
+
+struct AsyncTextureState {
+    texture: OnceCell<OglTexture>,
+    ready: AtomicBool,
+    // Applied once the texture exists.
+    label: Mutex<Option<String>>,
+}
+
+struct PendingUpload {
+    pixels: PixelBuffer,
+    state: Arc<AsyncTextureState>,
+    thread: ThreadId,
+    buffer: u32,
+    uploaded_rows: u32,
+}
+
+#[derive(Clone)]
+pub struct OglAsyncTexture {
+    state: Arc<AsyncTextureState>,
+    placeholder: OglTexture,
+}
+
+impl OglAsyncTexture {
+    pub fn new<F>(decode: F) -> OglAsyncTexture
+    where
+        F: FnOnce() -> Option<PixelBuffer> + Send + 'static,
+    {
+        let placeholder = PLACEHOLDER.with(|placeholder| {
+            placeholder
+                .get_or_init(|| {
+                    let pixel: [u8; 4] = [128, 128, 128, 255];
+                    OglTexture::new(1, 1, pixel.as_ptr(), TextureFormat::RGBA, BufferDataType::U8)
+                })
+                .clone()
+        });
+
+        let state = Arc::new(AsyncTextureState {
+            texture: OnceCell::new(),
+            ready: AtomicBool::new(false),
+            label: Mutex::new(None),
+        });
+
+        let sent = DECODE_QUEUE.lock().unwrap().send((Box::new(decode), state.clone(), thread::current().id()));
+        if sent.is_err() {
+            log::warn!(target: debug::LOG_TARGET, "Texture decode thread has stopped, using the placeholder");
+        }
+
+        return OglAsyncTexture { state, placeholder };
+    }
+
+    fn get_texture(&self) -> &OglTexture {
+        if self.is_ready() {
+            return self.state.texture.get().unwrap();
+        }
+
+        return &self.placeholder;
+    }
+}
+
+impl Texture for OglAsyncTexture {
+    fn get_id(&self) -> u32 {
+        return self.get_texture().get_id();
+    }
+
+    fn bind(&self, slot: u32) {
+        self.get_texture().bind(slot);
+    }
+
+    fn unbind(&self) {
+        self.get_texture().unbind();
+    }
+
+    fn get_size(&self, level: u32) -> (u32, u32) {
+        return self.get_texture().get_size(level);
+    }
+
+    fn get_format(&self) -> TextureFormat {
+        return self.get_texture().get_format();
+    }
+
+    fn read_pixels(&self, level: u32, data_type: BufferDataType) -> PixelBuffer {
+        return self.get_texture().read_pixels(level, data_type);
+    }
+
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType) {
+        if !self.is_ready() {
+            panic!("Can't upload into a texture that is still loading");
+        }
+
+        self.get_texture().upload_layer(layer, data, data_type);
+    }
+
+    fn is_ready(&self) -> bool {
+        return self.state.ready.load(Ordering::Acquire);
+    }
+
+    fn set_label(&self, label: &str) {
+        match self.state.texture.get() {
+            Some(texture) => texture.set_label(label),
+            None => *self.state.label.lock().unwrap() = Some(String::from(label)),
+        }
+    }
+}
+
+pub fn set_upload_budget(bytes: usize) {
+    UPLOAD_BUDGET.store(bytes.max(1), Ordering::Relaxed);
+}
+
+// Streams decoded textures through pixel unpack buffers, uploading at most
+// the budget worth of rows each call. Only textures created on the calling
+// thread are uploaded, they belong to its context.
+pub fn process_uploads() {
+    let mut pending = PENDING_UPLOADS.lock().unwrap();
+    let mut budget = UPLOAD_BUDGET.load(Ordering::Relaxed);
+
+    let thread = thread::current().id();
+
+    while budget > 0 {
+        let index = match pending.iter().position(|upload| upload.thread == thread) {
+            Some(index) => index,
+            None => break,
+        };
+        let upload = &mut pending[index];
+        let pixels = &upload.pixels;
+        let data_type = pixels.data.get_data_type();
+        let row_size = pixels.width as usize
+            * pixels.format.get_channel_count() as usize
+            * OglVertexArray::get_type_size(data_type);
+
+        if upload.buffer == 0 {
+            let texture = OglTexture::new(
+                pixels.width,
+                pixels.height,
+                ptr::null(),
+                pixels.format,
+                data_type,
+            );
+            if let Some(label) = upload.state.label.lock().unwrap().as_deref() {
+                debug::set_label(gl::TEXTURE, texture.get_id(), label);
+            }
+            let _ = upload.state.texture.set(texture);
+
+            unsafe {
+                gl::GenBuffers(1, &mut upload.buffer);
+                state_cache::bind_buffer(gl::PIXEL_UNPACK_BUFFER, upload.buffer);
+                gl::BufferData(
+                    gl::PIXEL_UNPACK_BUFFER,
+                    (row_size * pixels.height as usize) as isize,
+                    ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+            }
+        }
+
+        let rows = (budget / row_size).clamp(1, (pixels.height - upload.uploaded_rows) as usize);
+        let offset = upload.uploaded_rows as usize * row_size;
+        let size = rows * row_size;
+        let texture = upload.state.texture.get().unwrap();
+        unsafe {
+            state_cache::bind_buffer(gl::PIXEL_UNPACK_BUFFER, upload.buffer);
+            gl::BufferSubData(
+                gl::PIXEL_UNPACK_BUFFER,
+                offset as isize,
+                size as isize,
+                pixels.data.as_bytes()[offset..offset + size].as_ptr() as *const c_void,
+            );
+
+            texture.bind(0);
+            let mut alignment = 0;
+            gl::GetIntegerv(gl::UNPACK_ALIGNMENT, &mut alignment);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                upload.uploaded_rows as i32,
+                pixels.width as i32,
+                rows as i32,
+                OglTexture::translate_format(pixels.format),
+                OglVertexArray::translate_type(data_type),
+                offset as *const c_void,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, alignment);
+            state_cache::bind_buffer(gl::PIXEL_UNPACK_BUFFER, 0);
+        }
+
+        frame_stats::record_texture_upload(size);
+        upload.uploaded_rows += rows as u32;
+        budget = budget.saturating_sub(size);
+
+        if upload.uploaded_rows == pixels.height {
+            unsafe {
+                gl::DeleteBuffers(1, &upload.buffer);
+            }
+            upload.state.ready.store(true, Ordering::Release);
+            pending.remove(index);
+        }
+    }
+}
@@ -4,6 +4,8 @@ use gl::types;
 
 use crate::render::buffer::{Bindable, Buffer, BufferLayoutNode, BufferType, BufferUsage};
 
+use super::{debug, frame_stats, state_cache};
+
 pub struct OglBuffer {
     id: u32,
     buffer_type: types::GLenum,
@@ -24,6 +26,7 @@ impl OglBuffer {
             buffer_type: match buffer_type {
                 BufferType::Vertex => gl::ARRAY_BUFFER,
                 BufferType::Index => gl::ELEMENT_ARRAY_BUFFER,
+                BufferType::Indirect => gl::DRAW_INDIRECT_BUFFER,
             },
             element_count: OglBuffer::calculate_element_count(
                 data.len(),
@@ -34,7 +37,7 @@ impl OglBuffer {
         };
         unsafe {
             gl::GenBuffers(1, &mut buffer.id);
-            gl::BindBuffer(buffer.buffer_type, buffer.id);
+            state_cache::bind_buffer(buffer.buffer_type, buffer.id);
             gl::BufferData(
                 buffer.buffer_type,
                 if size.is_some() {
@@ -50,6 +53,7 @@ impl OglBuffer {
                 Self::translate_usage(buffer_usage),
             );
         }
+        frame_stats::record_buffer_upload(data.len() * size_of::<T>());
 
         return buffer;
     }
@@ -76,21 +80,27 @@ impl OglBuffer {
     }
 }
 
+impl Drop for OglBuffer {
+    fn drop(&mut self) {
+        state_cache::delete_buffer(self.id);
+    }
+}
+
 impl Bindable for OglBuffer {
     fn bind(&self) {
-        unsafe {
-            gl::BindBuffer(self.buffer_type, self.id);
-        }
+        state_cache::bind_buffer(self.buffer_type, self.id);
     }
 
     fn unbind(&self) {
-        unsafe {
-            gl::BindBuffer(self.buffer_type, 0);
-        }
+        state_cache::bind_buffer(self.buffer_type, 0);
     }
 }
 
 impl Buffer for OglBuffer {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
     fn get_layout(&self) -> &Vec<BufferLayoutNode> {
         return &self.buffer_layout;
     }
@@ -109,6 +119,7 @@ impl Buffer for OglBuffer {
                 data.as_ptr() as *const c_void,
             );
         }
+        frame_stats::record_buffer_upload(data.len() * size_of::<f32>());
 
         self.element_count = if offset == 0 {
             OglBuffer::calculate_element_count(data.len(), self.buffer_layout.clone(), BufferType::Vertex)
@@ -116,4 +127,8 @@ impl Buffer for OglBuffer {
             OglBuffer::calculate_element_count(offset + data.len(), self.buffer_layout.clone(), BufferType::Vertex)
         };
     }
+
+    fn set_label(&self, label: &str) {
+        debug::set_label(gl::BUFFER, self.id, label);
+    }
 }
@@ -0,0 +1,221 @@
+use std::{borrow::Cow, ffi::c_void};
+
+use crate::render::{
+    buffer::BufferDataType,
+    texture::{Texture, TextureDimension, TextureFormat},
+    texture_container::{CompressedFormat, CompressedTexture, TextureLoadError},
+    texture_decoder::{decode_to_rgba, get_decoded_data_type},
+};
+
+use super::{
+    context, frame_stats, has_extension, texture::OglTexture, vertex_array::OglVertexArray,
+};
+
+// S3TC is an extension and isn't part of the core bindings.
+const COMPRESSED_RGB_S3TC_DXT1: u32 = 0x83f0;
+const COMPRESSED_RGBA_S3TC_DXT1: u32 = 0x83f1;
+const COMPRESSED_RGBA_S3TC_DXT3: u32 = 0x83f2;
+const COMPRESSED_RGBA_S3TC_DXT5: u32 = 0x83f3;
+const COMPRESSED_SRGB_S3TC_DXT1: u32 = 0x8c4c;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1: u32 = 0x8c4d;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3: u32 = 0x8c4e;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5: u32 = 0x8c4f;
+
+impl OglTexture {
+    // Uploads the mip chain as is when the driver supports the format and
+    // decompresses it on the CPU otherwise, to RGBA8 or RGBA16F for BC6H.
+    pub fn new_compressed(texture: &CompressedTexture) -> Result<OglTexture, TextureLoadError> {
+        let supported = Self::is_compressed_format_supported(texture.format, texture.srgb);
+        let decoded_type = get_decoded_data_type(texture.format);
+
+        // Decoded before the texture exists so malformed data doesn't leak it.
+        let image_count = (texture.layers * texture.faces) as usize;
+        let mut levels = Vec::new();
+        for (level, data) in texture.levels.iter().enumerate() {
+            let (width, height) = texture.get_level_size(level);
+            let image_size = texture.format.get_image_size(width, height);
+            if data.len() < image_size * image_count {
+                return Err(TextureLoadError::Truncated);
+            }
+
+            if supported {
+                levels.push((Cow::Borrowed(data.as_slice()), image_size));
+                continue;
+            }
+
+            let mut pixels = Vec::new();
+            for image in 0..image_count {
+                let image_data = &data[image * image_size..(image + 1) * image_size];
+                let decoded = decode_to_rgba(texture.format, width, height, image_data)?;
+                pixels.extend_from_slice(decoded.as_bytes());
+            }
+            let image_size = width as usize * height as usize * 4 * decoded_type.get_size();
+            levels.push((Cow::Owned(pixels), image_size));
+        }
+
+        let dimension = match (texture.faces, texture.array) {
+            (6, false) => TextureDimension::Cube,
+            (6, true) => TextureDimension::CubeArray,
+            (_, true) => TextureDimension::Texture2DArray,
+            _ => TextureDimension::Texture2D,
+        };
+        let result = OglTexture::generate(dimension, TextureFormat::RGBA);
+        let target = result.get_target();
+
+        let layered = dimension == TextureDimension::Texture2DArray
+            || dimension == TextureDimension::CubeArray;
+
+        result.bind(0);
+        for (level, (data, image_size)) in levels.iter().enumerate() {
+            let (width, height) = texture.get_level_size(level);
+            let image_size = *image_size;
+
+            for (target, first_image, images) in Self::get_level_uploads(dimension, image_count) {
+                let pointer = data[first_image * image_size..].as_ptr() as *const c_void;
+                unsafe {
+                    match (supported, layered) {
+                        (true, false) => gl::CompressedTexImage2D(
+                            target,
+                            level as i32,
+                            Self::translate_compressed_format(texture.format, texture.srgb),
+                            width as i32,
+                            height as i32,
+                            0,
+                            image_size as i32,
+                            pointer,
+                        ),
+                        (true, true) => gl::CompressedTexImage3D(
+                            target,
+                            level as i32,
+                            Self::translate_compressed_format(texture.format, texture.srgb),
+                            width as i32,
+                            height as i32,
+                            images as i32,
+                            0,
+                            (image_size * images) as i32,
+                            pointer,
+                        ),
+                        (false, false) => gl::TexImage2D(
+                            target,
+                            level as i32,
+                            Self::get_decoded_format(texture.srgb, decoded_type) as i32,
+                            width as i32,
+                            height as i32,
+                            0,
+                            gl::RGBA,
+                            OglVertexArray::translate_type(decoded_type),
+                            pointer,
+                        ),
+                        (false, true) => gl::TexImage3D(
+                            target,
+                            level as i32,
+                            Self::get_decoded_format(texture.srgb, decoded_type) as i32,
+                            width as i32,
+                            height as i32,
+                            images as i32,
+                            0,
+                            gl::RGBA,
+                            OglVertexArray::translate_type(decoded_type),
+                            pointer,
+                        ),
+                    }
+                }
+                frame_stats::record_texture_upload(image_size * images);
+            }
+        }
+
+        let min_filter = if texture.levels.len() > 1 {
+            gl::LINEAR_MIPMAP_LINEAR
+        } else {
+            gl::LINEAR
+        };
+        unsafe {
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, texture.levels.len() as i32 - 1);
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        return Ok(result);
+    }
+
+    pub fn is_compressed_format_supported(format: CompressedFormat, srgb: bool) -> bool {
+        let info = context::get();
+        // ES 3.0 has ETC2 in core, BC formats only through extensions.
+        let version = if info.es { 0 } else { info.major * 10 + info.minor };
+
+        match format {
+            CompressedFormat::Bc1Rgb
+            | CompressedFormat::Bc1Rgba
+            | CompressedFormat::Bc2
+            | CompressedFormat::Bc3 => {
+                has_extension("GL_EXT_texture_compression_s3tc")
+                    && (!srgb
+                        || has_extension("GL_EXT_texture_sRGB")
+                        || has_extension("GL_EXT_texture_compression_s3tc_srgb"))
+            }
+            CompressedFormat::Bc4 | CompressedFormat::Bc5 => {
+                version >= 30
+                    || has_extension("GL_ARB_texture_compression_rgtc")
+                    || has_extension("GL_EXT_texture_compression_rgtc")
+            }
+            CompressedFormat::Bc6hUnsigned | CompressedFormat::Bc6hSigned | CompressedFormat::Bc7 => {
+                version >= 42
+                    || has_extension("GL_ARB_texture_compression_bptc")
+                    || has_extension("GL_EXT_texture_compression_bptc")
+            }
+            CompressedFormat::Etc2Rgb | CompressedFormat::Etc2RgbA1 | CompressedFormat::Etc2Rgba => {
+                info.es || version >= 43 || has_extension("GL_ARB_ES3_compatibility")
+            }
+        }
+    }
+
+    fn translate_compressed_format(format: CompressedFormat, srgb: bool) -> u32 {
+        match (format, srgb) {
+            (CompressedFormat::Bc1Rgb, false) => COMPRESSED_RGB_S3TC_DXT1,
+            (CompressedFormat::Bc1Rgb, true) => COMPRESSED_SRGB_S3TC_DXT1,
+            (CompressedFormat::Bc1Rgba, false) => COMPRESSED_RGBA_S3TC_DXT1,
+            (CompressedFormat::Bc1Rgba, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT1,
+            (CompressedFormat::Bc2, false) => COMPRESSED_RGBA_S3TC_DXT3,
+            (CompressedFormat::Bc2, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT3,
+            (CompressedFormat::Bc3, false) => COMPRESSED_RGBA_S3TC_DXT5,
+            (CompressedFormat::Bc3, true) => COMPRESSED_SRGB_ALPHA_S3TC_DXT5,
+            (CompressedFormat::Bc4, _) => gl::COMPRESSED_RED_RGTC1,
+            (CompressedFormat::Bc5, _) => gl::COMPRESSED_RG_RGTC2,
+            (CompressedFormat::Bc6hUnsigned, _) => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+            (CompressedFormat::Bc6hSigned, _) => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+            (CompressedFormat::Bc7, false) => gl::COMPRESSED_RGBA_BPTC_UNORM,
+            (CompressedFormat::Bc7, true) => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+            (CompressedFormat::Etc2Rgb, false) => gl::COMPRESSED_RGB8_ETC2,
+            (CompressedFormat::Etc2Rgb, true) => gl::COMPRESSED_SRGB8_ETC2,
+            (CompressedFormat::Etc2RgbA1, false) => gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2RgbA1, true) => gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+            (CompressedFormat::Etc2Rgba, false) => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            (CompressedFormat::Etc2Rgba, true) => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+        }
+    }
+
+    fn get_decoded_format(srgb: bool, data_type: BufferDataType) -> u32 {
+        if data_type == BufferDataType::F32 {
+            return gl::RGBA16F;
+        }
+
+        return if srgb { gl::SRGB8_ALPHA8 } else { gl::RGBA8 };
+    }
+
+    // Cube faces are uploaded one by one, layered textures take the whole
+    // level at once. Entries are the target, first image and image count.
+    fn get_level_uploads(dimension: TextureDimension, image_count: usize) -> Vec<(u32, usize, usize)> {
+        match dimension {
+            TextureDimension::Cube => (0..6)
+                .map(|face| (gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32, face, 1))
+                .collect(),
+            TextureDimension::Texture2DArray | TextureDimension::CubeArray => {
+                vec![(OglTexture::translate_dimension(dimension), 0, image_count)]
+            }
+            _ => vec![(gl::TEXTURE_2D, 0, 1)],
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{cell::RefCell, ffi::CStr};
+
+use crate::render::capabilities::Capabilities;
+
+use super::{debug, get_version, has_extension};
+
+// EXT_texture_filter_anisotropic, core since 4.6 but missing from the
+// bindings.
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84ff;
+
+thread_local! {
+    // Describes the context current on this thread.
+    static CONTEXT_INFO: RefCell<ContextInfo> = RefCell::new(ContextInfo::default());
+    static CAPABILITIES: RefCell<Capabilities> = RefCell::new(Capabilities::default());
+}
+
+// What the current context supports, queried once at init so the hot paths
+// only read a few flags.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ContextInfo {
+    pub major: i32,
+    pub minor: i32,
+    pub es: bool,
+    pub direct_state_access: bool,
+    pub indexed_blending: bool,
+    pub timer_queries: bool,
+    pub base_vertex: bool,
+    pub draw_indirect: bool,
+    pub multi_draw_indirect: bool,
+}
+
+impl ContextInfo {
+    pub fn is_at_least(&self, major: i32, minor: i32) -> bool {
+        return (self.major, self.minor) >= (major, minor);
+    }
+}
+
+pub fn init() {
+    let (major, minor) = get_version();
+    let es = unsafe { get_string(gl::GetString(gl::VERSION)).starts_with("OpenGL ES") };
+
+    let info = if es {
+        ContextInfo {
+            major,
+            minor,
+            es,
+            direct_state_access: false,
+            indexed_blending: (major, minor) >= (3, 2)
+                || has_extension("GL_OES_draw_buffers_indexed")
+                || has_extension("GL_EXT_draw_buffers_indexed"),
+            timer_queries: false,
+            base_vertex: (major, minor) >= (3, 2),
+            draw_indirect: (major, minor) >= (3, 1),
+            multi_draw_indirect: false,
+        }
+    } else {
+        ContextInfo {
+            major,
+            minor,
+            es,
+            direct_state_access: (major, minor) >= (4, 5)
+                || has_extension("GL_ARB_direct_state_access"),
+            indexed_blending: major >= 4 || has_extension("GL_ARB_draw_buffers_blend"),
+            timer_queries: (major, minor) >= (3, 3) || has_extension("GL_ARB_timer_query"),
+            base_vertex: (major, minor) >= (3, 2)
+                || has_extension("GL_ARB_draw_elements_base_vertex"),
+            draw_indirect: major >= 4 || has_extension("GL_ARB_draw_indirect"),
+            multi_draw_indirect: (major, minor) >= (4, 3)
+                || has_extension("GL_ARB_multi_draw_indirect"),
+        }
+    };
+
+    CONTEXT_INFO.with(|current| *current.borrow_mut() = info);
+    CAPABILITIES.with(|current| *current.borrow_mut() = query_capabilities(&info));
+}
+
+pub fn get() -> ContextInfo {
+    return CONTEXT_INFO.with(|info| *info.borrow());
+}
+
+pub fn get_capabilities() -> Capabilities {
+    return CAPABILITIES.with(|capabilities| capabilities.borrow().clone());
+}
+
+fn query_capabilities(info: &ContextInfo) -> Capabilities {
+    let get_integer = |name: u32| {
+        let mut value = 0;
+        unsafe {
+            gl::GetIntegerv(name, &mut value);
+        }
+        value.max(0) as u32
+    };
+
+    let mut extensions = Vec::new();
+    for index in 0..get_integer(gl::NUM_EXTENSIONS) {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+        if !extension.is_null() {
+            extensions.push(unsafe { get_string(extension) });
+        }
+    }
+
+    let anisotropy_supported = (!info.es && info.is_at_least(4, 6))
+        || extensions.iter().any(|extension| {
+            extension == "GL_EXT_texture_filter_anisotropic"
+                || extension == "GL_ARB_texture_filter_anisotropic"
+        });
+    let max_anisotropy = match anisotropy_supported {
+        true => {
+            let mut value = 0.0;
+            unsafe {
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut value);
+            }
+            Some(value)
+        }
+        false => None,
+    };
+
+    return unsafe {
+        Capabilities {
+            version: get_string(gl::GetString(gl::VERSION)),
+            shading_language_version: get_string(gl::GetString(gl::SHADING_LANGUAGE_VERSION)),
+            vendor: get_string(gl::GetString(gl::VENDOR)),
+            renderer: get_string(gl::GetString(gl::RENDERER)),
+            extensions,
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_texture_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_anisotropy,
+        }
+    };
+}
+
+unsafe fn get_string(string: *const u8) -> String {
+    if string.is_null() {
+        return String::new();
+    }
+
+    return CStr::from_ptr(string as *const i8)
+        .to_string_lossy()
+        .into_owned();
+}
+
+// Shaders are written against desktop GLSL 330 core. On ES the version line
+// is replaced and default precisions are declared, since ES has none for
+// floats in fragment shaders and none for most samplers at all. Sources
+// without a version line get the one matching the context.
+pub fn prepare_shader_source(source: &str) -> String {
+    let info = get();
+    let source = source.trim_start();
+    let (version, body) = match source.strip_prefix("#version") {
+        Some(rest) => match rest.find('\n') {
+            Some(line_end) => (Some(rest[..line_end].trim()), &rest[line_end + 1..]),
+            None => (Some(rest.trim()), ""),
+        },
+        None => (None, source),
+    };
+
+    if !info.es {
+        return match version {
+            Some(_) => String::from(source),
+            None => format!("#version 330 core\n{}", body),
+        };
+    }
+
+    match version {
+        Some(version) if version.ends_with(" es") => return String::from(source),
+        Some(version) if !version.starts_with("330") => {
+            log::warn!(
+                target: debug::LOG_TARGET,
+                "Translating GLSL {} to GLSL ES 3.00, the shader may not compile",
+                version
+            );
+        }
+        _ => {}
+    }
+
+    return format!(
+        "#version 300 es\n\
+         precision highp float;\n\
+         precision highp int;\n\
+         precision highp sampler2DArray;\n\
+         precision highp sampler3D;\n\
+         precision highp samplerCubeShadow;\n\
+         precision highp sampler2DShadow;\n\
+         {}",
+        body
+    );
+}
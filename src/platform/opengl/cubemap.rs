@@ -0,0 +1,150 @@
+use crate::render::{
+    buffer::{Bindable, BufferDataType},
+    shader::Shader,
+    texture::{Texture, TextureDimension, TextureFormat},
+};
+
+use super::{
+    shader::OglShader,
+    state_cache,
+    texture::OglTexture,
+};
+
+// Renders one cubemap face per draw with a fullscreen triangle, the face
+// directions follow the major axis table from the OpenGL specification.
+const EQUIRECTANGULAR_SHADER: &str = "#shader vertex
+#version 330 core
+
+out vec2 v_uv;
+
+void main() {
+    vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+    v_uv = position;
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+
+#shader fragment
+#version 330 core
+
+in vec2 v_uv;
+out vec4 color;
+
+uniform sampler2D u_equirectangular;
+uniform int u_face;
+
+const float PI = 3.14159265359;
+
+vec3 get_direction(int face, vec2 uv) {
+    switch (face) {
+        case 0: return vec3(1.0, -uv.y, -uv.x);
+        case 1: return vec3(-1.0, -uv.y, uv.x);
+        case 2: return vec3(uv.x, 1.0, uv.y);
+        case 3: return vec3(uv.x, -1.0, -uv.y);
+        case 4: return vec3(uv.x, -uv.y, 1.0);
+        default: return vec3(-uv.x, -uv.y, -1.0);
+    }
+}
+
+void main() {
+    vec3 direction = normalize(get_direction(u_face, v_uv));
+    vec2 uv = vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, 0.5 - asin(direction.y) / PI);
+    color = vec4(texture(u_equirectangular, uv).rgb, 1.0);
+}
+";
+
+impl OglTexture {
+    pub fn new_cubemap(
+        size: u32,
+        faces: [*const u8; 6],
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> OglTexture {
+        let cubemap = OglTexture::new_with_dimension(
+            TextureDimension::Cube,
+            size,
+            size,
+            1,
+            std::ptr::null(),
+            format,
+            data_type,
+        );
+
+        for (face, data) in faces.iter().enumerate() {
+            cubemap.upload_layer(face as u32, *data, data_type);
+        }
+
+        return cubemap;
+    }
+
+    pub fn new_cubemap_from_equirectangular(
+        width: u32,
+        height: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+        size: u32,
+    ) -> OglTexture {
+        let source = OglTexture::new(width, height, data, format, data_type);
+        let cubemap = OglTexture::new_with_dimension(
+            TextureDimension::Cube,
+            size,
+            size,
+            1,
+            std::ptr::null(),
+            TextureFormat::RGB,
+            BufferDataType::F32,
+        );
+        let shader = OglShader::new_from_source(EQUIRECTANGULAR_SHADER);
+        let program = state_cache::get_program();
+
+        let mut viewport = [0; 4];
+        let mut framebuffer_binding = 0;
+        let mut framebuffer = 0;
+        let mut vertex_array = 0;
+        unsafe {
+            let depth_test = gl::IsEnabled(gl::DEPTH_TEST);
+            let blend = gl::IsEnabled(gl::BLEND);
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer_binding);
+
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::GenVertexArrays(1, &mut vertex_array);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            state_cache::bind_vertex_array(vertex_array);
+            state_cache::set_capability(gl::DEPTH_TEST, false);
+            state_cache::set_capability(gl::BLEND, false);
+            gl::Viewport(0, 0, size as i32, size as i32);
+
+            shader.bind();
+            source.bind(0);
+            for face in 0..6 {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    cubemap.get_id(),
+                    0,
+                );
+                shader.submit_uniform_i32("u_face", face as i32);
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer_binding as u32);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            state_cache::set_capability(gl::DEPTH_TEST, depth_test == gl::TRUE);
+            state_cache::set_capability(gl::BLEND, blend == gl::TRUE);
+
+            state_cache::bind_vertex_array(0);
+            gl::DeleteVertexArrays(1, &vertex_array);
+            gl::DeleteFramebuffers(1, &framebuffer);
+        }
+
+        // An unknown program stays unknown, dropping ours unbinds it. The
+        // source texture and the shader are deleted when dropped.
+        if let Some(program) = program {
+            state_cache::bind_program(program);
+        }
+
+        return cubemap;
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    ffi::{c_void, CStr},
+    ptr,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use gl::types;
+use log::Level;
+
+use crate::render::debug::DebugSeverity;
+
+use super::{context, has_extension};
+
+pub(crate) use crate::render::debug::LOG_TARGET;
+
+static DEBUG_OUTPUT: AtomicBool = AtomicBool::new(false);
+static MIN_SEVERITY: AtomicU8 = AtomicU8::new(DebugSeverity::Low as u8);
+
+pub fn is_supported() -> bool {
+    let info = context::get();
+    let core_version = if info.es { (3, 2) } else { (4, 3) };
+    return info.is_at_least(core_version.0, core_version.1) || has_extension("GL_KHR_debug");
+}
+
+// Installs the message callback. Drivers only report much in debug
+// contexts, but some also do without one once debug output is enabled.
+pub fn init() {
+    if !is_supported() {
+        log::info!(target: LOG_TARGET, "KHR_debug unavailable, checking glGetError instead");
+        DEBUG_OUTPUT.store(false, Ordering::Relaxed);
+        return;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // Reports from the offending call instead of some later point.
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), ptr::null());
+    }
+    DEBUG_OUTPUT.store(true, Ordering::Relaxed);
+}
+
+pub fn set_min_severity(severity: DebugSeverity) {
+    MIN_SEVERITY.store(severity as u8, Ordering::Relaxed);
+}
+
+// Logs every pending error, the fallback for contexts without debug output
+// where the callback can't report them. Returns whether any was found.
+pub fn check_errors(location: &str) -> bool {
+    if DEBUG_OUTPUT.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    let mut found = false;
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+
+        found = true;
+        log::error!(target: LOG_TARGET, "{} at {}", translate_error(error), location);
+    }
+
+    return found;
+}
+
+// Runs GL calls and reports the errors they raised, for places where a
+// failure would otherwise only show up as a black screen.
+pub fn checked<T>(location: &str, call: impl FnOnce() -> T) -> T {
+    let result = call();
+    check_errors(location);
+
+    return result;
+}
+
+// Names the object in debug messages and in tools like RenderDoc.
+// Identifier is the object namespace, e.g. gl::BUFFER or gl::TEXTURE.
+pub fn set_label(identifier: u32, id: u32, label: &str) {
+    if !DEBUG_OUTPUT.load(Ordering::Relaxed) {
+        return;
+    }
+
+    unsafe {
+        gl::ObjectLabel(
+            identifier,
+            id,
+            label.len() as i32,
+            label.as_ptr() as *const types::GLchar,
+        );
+    }
+}
+
+extern "system" fn debug_callback(
+    source: types::GLenum,
+    message_type: types::GLenum,
+    id: types::GLuint,
+    severity: types::GLenum,
+    length: types::GLsizei,
+    message: *const types::GLchar,
+    _user_param: *mut c_void,
+) {
+    let severity = translate_severity(severity);
+    if (severity as u8) < MIN_SEVERITY.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        } else {
+            let bytes = std::slice::from_raw_parts(message as *const u8, length as usize);
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    };
+
+    let level = match severity {
+        DebugSeverity::High => Level::Error,
+        DebugSeverity::Medium => Level::Warn,
+        DebugSeverity::Low => Level::Info,
+        DebugSeverity::Notification => Level::Debug,
+    };
+
+    log::log!(
+        target: LOG_TARGET,
+        level,
+        "[{} {} {}] {}",
+        translate_source(source),
+        translate_type(message_type),
+        id,
+        message.trim_end()
+    );
+}
+
+fn translate_severity(severity: types::GLenum) -> DebugSeverity {
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+        gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+        gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+        _ => DebugSeverity::Notification,
+    }
+}
+
+fn translate_source(source: types::GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn translate_type(message_type: types::GLenum) -> &'static str {
+    match message_type {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+fn translate_error(error: types::GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown GL error",
+    }
+}
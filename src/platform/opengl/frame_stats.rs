@@ -0,0 +1,218 @@
+use std::{cell::RefCell, collections::VecDeque, time::Duration};
+
+use crate::render::stats::{FrameStats, ScopeTiming};
+
+use super::{context, debug, pipeline, state_cache};
+
+// Frames whose timestamps haven't been read back yet, older ones are
+// dropped so a stalled GPU can't grow the queue forever.
+const MAX_PENDING_FRAMES: usize = 8;
+
+thread_local! {
+    // Queries belong to the context current on this thread.
+    static FRAME_STATS: RefCell<FrameStatsCollector> = RefCell::new(FrameStatsCollector::default());
+}
+
+fn with_collector<T>(f: impl FnOnce(&mut FrameStatsCollector) -> T) -> T {
+    return FRAME_STATS.with(|collector| f(&mut collector.borrow_mut()));
+}
+
+struct PendingFrame {
+    stats: FrameStats,
+    start_query: u32,
+    end_query: u32,
+    scopes: Vec<(String, u32, u32)>,
+}
+
+#[derive(Default)]
+struct FrameStatsCollector {
+    current: FrameStats,
+    in_frame: bool,
+    issued_calls: u64,
+    start_query: u32,
+    open_scopes: Vec<(String, u32)>,
+    closed_scopes: Vec<(String, u32, u32)>,
+    pending: VecDeque<PendingFrame>,
+    free_queries: Vec<u32>,
+    last: FrameStats,
+}
+
+impl FrameStatsCollector {
+    // Query 0 stands in for a timestamp on contexts without timer queries,
+    // frames written with it have no GPU time.
+    fn write_timestamp(&mut self) -> u32 {
+        if !context::get().timer_queries {
+            return 0;
+        }
+
+        let query = match self.free_queries.pop() {
+            Some(query) => query,
+            None => {
+                let mut query = 0;
+                unsafe {
+                    gl::GenQueries(1, &mut query);
+                }
+                query
+            }
+        };
+
+        unsafe {
+            gl::QueryCounter(query, gl::TIMESTAMP);
+        }
+
+        return query;
+    }
+
+    fn get_elapsed(start_query: u32, end_query: u32) -> Duration {
+        let mut start = 0;
+        let mut end = 0;
+        unsafe {
+            gl::GetQueryObjectui64v(start_query, gl::QUERY_RESULT, &mut start);
+            gl::GetQueryObjectui64v(end_query, gl::QUERY_RESULT, &mut end);
+        }
+
+        return Duration::from_nanos(end.saturating_sub(start));
+    }
+
+    fn is_available(query: u32) -> bool {
+        let mut available = 0;
+        unsafe {
+            gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+        }
+
+        return available != 0;
+    }
+
+    fn release_queries(&mut self, frame: &PendingFrame) {
+        if frame.end_query == 0 {
+            return;
+        }
+
+        self.free_queries.push(frame.start_query);
+        self.free_queries.push(frame.end_query);
+        for (_, start_query, end_query) in &frame.scopes {
+            self.free_queries.push(*start_query);
+            self.free_queries.push(*end_query);
+        }
+    }
+
+    // Queries finish in order, so only the oldest frame needs checking.
+    fn collect_finished(&mut self) {
+        while let Some(frame) = self.pending.front() {
+            if frame.end_query != 0 && !Self::is_available(frame.end_query) {
+                break;
+            }
+
+            let mut frame = self.pending.pop_front().unwrap();
+            if frame.end_query != 0 {
+                frame.stats.gpu_time =
+                    Some(Self::get_elapsed(frame.start_query, frame.end_query));
+                for (name, start_query, end_query) in &frame.scopes {
+                    frame.stats.scopes.push(ScopeTiming {
+                        name: name.clone(),
+                        gpu_time: Self::get_elapsed(*start_query, *end_query),
+                    });
+                }
+            }
+
+            self.release_queries(&frame);
+            self.last = frame.stats;
+        }
+    }
+}
+
+// Called by every begin, only the first one after a present starts a
+// frame so all render targets drawn in between count towards it.
+pub fn begin_frame() {
+    with_collector(|collector| {
+        if collector.in_frame {
+            return;
+        }
+
+        collector.in_frame = true;
+        collector.issued_calls = state_cache::get_stats().get_issued_calls();
+        collector.start_query = collector.write_timestamp();
+    });
+}
+
+// Called through RenderAPI::end_frame, work issued since the previous
+// frame ended counts towards this one.
+pub fn end_frame() {
+    with_collector(|collector| {
+        if !collector.in_frame {
+            return;
+        }
+
+        while let Some((name, start_query)) = collector.open_scopes.pop() {
+            log::warn!(target: debug::LOG_TARGET, "Frame ended inside GPU timing scope {}", name);
+            let end_query = collector.write_timestamp();
+            collector.closed_scopes.push((name, start_query, end_query));
+        }
+
+        let mut stats = std::mem::take(&mut collector.current);
+        stats.state_changes = state_cache::get_stats().get_issued_calls() - collector.issued_calls;
+        let end_query = collector.write_timestamp();
+        let frame = PendingFrame {
+            stats,
+            start_query: collector.start_query,
+            end_query,
+            scopes: std::mem::take(&mut collector.closed_scopes),
+        };
+        collector.pending.push_back(frame);
+        collector.in_frame = false;
+
+        if collector.pending.len() > MAX_PENDING_FRAMES {
+            let frame = collector.pending.pop_front().unwrap();
+            collector.release_queries(&frame);
+        }
+
+        collector.collect_finished();
+    });
+}
+
+pub fn begin_scope(name: &str) {
+    with_collector(|collector| {
+        let query = collector.write_timestamp();
+        collector.open_scopes.push((String::from(name), query));
+    });
+}
+
+pub fn end_scope() {
+    with_collector(|collector| {
+        match collector.open_scopes.pop() {
+            Some((name, start_query)) => {
+                let end_query = collector.write_timestamp();
+                collector.closed_scopes.push((name, start_query, end_query));
+            }
+            None => log::warn!(target: debug::LOG_TARGET, "GPU timing scope ended without being started"),
+        }
+    });
+}
+
+pub fn record_draw(vertices: u64) {
+    record_multi_draw([vertices].into_iter());
+}
+
+// A single draw call of several ranges, triangles are counted per range
+// since strips don't share vertices across them.
+pub fn record_multi_draw(ranges: impl Iterator<Item = u64>) {
+    with_collector(|collector| {
+        collector.current.draw_calls += 1;
+        for vertices in ranges {
+            collector.current.vertices += vertices;
+            collector.current.triangles += pipeline::get_triangle_count(vertices);
+        }
+    });
+}
+
+pub fn record_buffer_upload(bytes: usize) {
+    with_collector(|collector| collector.current.buffer_bytes_uploaded += bytes as u64);
+}
+
+pub fn record_texture_upload(bytes: usize) {
+    with_collector(|collector| collector.current.texture_bytes_uploaded += bytes as u64);
+}
+
+pub fn get_last_frame() -> FrameStats {
+    return with_collector(|collector| collector.last.clone());
+}
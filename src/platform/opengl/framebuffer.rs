@@ -0,0 +1,516 @@
+use std::{
+    cell::{Cell, RefCell},
+    ptr,
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::render::{
+    buffer::BufferDataType,
+    framebuffer::{
+        ColorAttachment, DepthStencilFormat, Framebuffer, FramebufferDescriptor, FramebufferError,
+        FramebufferSize,
+    },
+    texture::{Texture, TextureDimension, TextureFormat},
+};
+
+use super::{
+    context, debug, state_cache,
+    texture::OglTexture,
+    vertex_array::OglVertexArray,
+};
+
+thread_local! {
+    // Window of the context current on this thread and the framebuffers
+    // following its size.
+    static WINDOW_SIZE: Cell<(u32, u32)> = Cell::new((0, 0));
+    static WINDOW_FRAMEBUFFERS: RefCell<Vec<Weak<Mutex<FramebufferState>>>> = RefCell::new(Vec::new());
+}
+
+// Multisampled attachments live in renderbuffers since they can't be
+// sampled like regular textures anyway.
+enum AttachmentStorage {
+    Texture(OglTexture),
+    Renderbuffer(u32),
+}
+
+struct FramebufferState {
+    id: u32,
+    width: u32,
+    height: u32,
+    samples: u32,
+    color_attachments: Vec<(AttachmentStorage, ColorAttachment)>,
+    depth_attachment: Option<AttachmentStorage>,
+    depth_stencil: DepthStencilFormat,
+    scale: Option<f32>,
+}
+
+pub struct OglFramebuffer {
+    state: Arc<Mutex<FramebufferState>>,
+}
+
+impl OglFramebuffer {
+    pub fn new(descriptor: FramebufferDescriptor) -> Result<OglFramebuffer, FramebufferError> {
+        let (width, height, scale) = match descriptor.size {
+            FramebufferSize::Fixed(width, height) => (width, height, None),
+            FramebufferSize::Window(scale) => {
+                let (width, height) = Self::scale_window_size(get_window_size(), scale);
+                (width, height, Some(scale))
+            }
+        };
+
+        if descriptor.color_attachments.is_empty()
+            && descriptor.depth_stencil == DepthStencilFormat::None
+        {
+            return Err(FramebufferError::NoAttachments);
+        }
+
+        let capabilities = context::get_capabilities();
+        let max_attachments = capabilities.max_color_attachments;
+        if descriptor.color_attachments.len() > max_attachments as usize {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: descriptor.color_attachments.len(),
+                max: max_attachments as usize,
+            });
+        }
+
+        let max_samples = capabilities.max_samples;
+        if descriptor.samples > max_samples {
+            return Err(FramebufferError::TooManySamples {
+                requested: descriptor.samples,
+                max: max_samples,
+            });
+        }
+
+        let mut id = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+        }
+
+        let samples = descriptor.samples;
+        let color_attachments = descriptor
+            .color_attachments
+            .iter()
+            .map(|attachment| (Self::create_storage(attachment.format, samples), *attachment))
+            .collect();
+
+        let depth_attachment = match descriptor.depth_stencil {
+            DepthStencilFormat::None => None,
+            depth_stencil => Some(Self::create_storage(
+                Self::get_depth_format(depth_stencil).0,
+                samples,
+            )),
+        };
+
+        let mut state = FramebufferState {
+            id,
+            width: 0,
+            height: 0,
+            samples,
+            color_attachments,
+            depth_attachment,
+            depth_stencil: descriptor.depth_stencil,
+            scale,
+        };
+        state.attach();
+        state.allocate(width, height)?;
+
+        let state = Arc::new(Mutex::new(state));
+        if scale.is_some() {
+            WINDOW_FRAMEBUFFERS.with(|framebuffers| framebuffers.borrow_mut().push(Arc::downgrade(&state)));
+        }
+
+        return Ok(OglFramebuffer { state });
+    }
+
+    fn create_storage(format: TextureFormat, samples: u32) -> AttachmentStorage {
+        if samples > 1 {
+            let mut renderbuffer = 0;
+            unsafe {
+                gl::GenRenderbuffers(1, &mut renderbuffer);
+            }
+
+            return AttachmentStorage::Renderbuffer(renderbuffer);
+        }
+
+        let texture = OglTexture::generate(TextureDimension::Texture2D, format);
+        texture.bind(0);
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        return AttachmentStorage::Texture(texture);
+    }
+
+    // Format, data type and upload type used to allocate depth storage.
+    fn get_depth_format(depth_stencil: DepthStencilFormat) -> (TextureFormat, BufferDataType, u32) {
+        match depth_stencil {
+            DepthStencilFormat::Depth32F => (TextureFormat::Depth, BufferDataType::F32, gl::FLOAT),
+            DepthStencilFormat::Depth24Stencil8 => (
+                TextureFormat::DepthStencil,
+                BufferDataType::U32,
+                gl::UNSIGNED_INT_24_8,
+            ),
+            _ => (TextureFormat::Depth, BufferDataType::U32, gl::UNSIGNED_INT),
+        }
+    }
+
+    // Renderbuffer storage only accepts sized formats.
+    fn get_sized_format(format: TextureFormat, data_type: BufferDataType) -> u32 {
+        match OglTexture::translate_internal_format(format, data_type) {
+            gl::RGBA => gl::RGBA8,
+            gl::RGB => gl::RGB8,
+            gl::RED => gl::R8,
+            internal_format => internal_format,
+        }
+    }
+
+    fn scale_window_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+        return (
+            (width as f32 * scale).round() as u32,
+            (height as f32 * scale).round() as u32,
+        );
+    }
+}
+
+// Texture attachments are deleted by their last copy, renderbuffers are
+// only ever owned by the framebuffer.
+impl Drop for FramebufferState {
+    fn drop(&mut self) {
+        let storages = self
+            .color_attachments
+            .iter()
+            .map(|(storage, _)| storage)
+            .chain(self.depth_attachment.iter());
+        unsafe {
+            for storage in storages {
+                if let AttachmentStorage::Renderbuffer(renderbuffer) = storage {
+                    gl::DeleteRenderbuffers(1, renderbuffer);
+                }
+            }
+            gl::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
+
+impl FramebufferState {
+    fn attach(&self) {
+        let previous = get_bound_framebuffer();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            for (index, (storage, _)) in self.color_attachments.iter().enumerate() {
+                Self::attach_storage(gl::COLOR_ATTACHMENT0 + index as u32, storage);
+            }
+
+            if let Some(storage) = &self.depth_attachment {
+                let attachment = match self.depth_stencil {
+                    DepthStencilFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+                    _ => gl::DEPTH_ATTACHMENT,
+                };
+                Self::attach_storage(attachment, storage);
+            }
+
+            // glDrawBuffer doesn't exist on ES, the array form works on both.
+            if self.color_attachments.is_empty() {
+                gl::DrawBuffers(1, &gl::NONE);
+                gl::ReadBuffer(gl::NONE);
+            } else {
+                let draw_buffers: Vec<u32> = (0..self.color_attachments.len())
+                    .map(|index| gl::COLOR_ATTACHMENT0 + index as u32)
+                    .collect();
+                gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous);
+        }
+    }
+
+    fn attach_storage(attachment: u32, storage: &AttachmentStorage) {
+        unsafe {
+            match storage {
+                AttachmentStorage::Texture(texture) => gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::TEXTURE_2D,
+                    texture.get_id(),
+                    0,
+                ),
+                AttachmentStorage::Renderbuffer(renderbuffer) => gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    attachment,
+                    gl::RENDERBUFFER,
+                    *renderbuffer,
+                ),
+            }
+        }
+    }
+
+    // Storage is reallocated in place so textures handed out earlier keep
+    // pointing at the attachments after a resize.
+    fn allocate(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::EmptySize);
+        }
+
+        for (storage, attachment) in &self.color_attachments {
+            self.allocate_storage(
+                storage,
+                width,
+                height,
+                attachment.format,
+                attachment.data_type,
+                OglVertexArray::translate_type(attachment.data_type),
+            );
+        }
+
+        if let Some(storage) = &self.depth_attachment {
+            let (format, data_type, gl_type) = OglFramebuffer::get_depth_format(self.depth_stencil);
+            self.allocate_storage(storage, width, height, format, data_type, gl_type);
+        }
+
+        self.width = width;
+        self.height = height;
+
+        return self.check_status();
+    }
+
+    fn allocate_storage(
+        &self,
+        storage: &AttachmentStorage,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+        gl_type: u32,
+    ) {
+        unsafe {
+            match storage {
+                AttachmentStorage::Texture(texture) => {
+                    texture.bind(0);
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        0,
+                        OglTexture::translate_internal_format(format, data_type) as i32,
+                        width as i32,
+                        height as i32,
+                        0,
+                        OglTexture::translate_format(format),
+                        gl_type,
+                        ptr::null(),
+                    );
+                    state_cache::bind_texture(None, gl::TEXTURE_2D, 0);
+                }
+                AttachmentStorage::Renderbuffer(renderbuffer) => {
+                    gl::BindRenderbuffer(gl::RENDERBUFFER, *renderbuffer);
+                    gl::RenderbufferStorageMultisample(
+                        gl::RENDERBUFFER,
+                        self.samples as i32,
+                        OglFramebuffer::get_sized_format(format, data_type),
+                        width as i32,
+                        height as i32,
+                    );
+                    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+                }
+            }
+        }
+    }
+
+    fn check_status(&self) -> Result<(), FramebufferError> {
+        let previous = get_bound_framebuffer();
+        let status = unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous);
+            status
+        };
+
+        let reason = match status {
+            gl::FRAMEBUFFER_COMPLETE => return Ok(()),
+            gl::FRAMEBUFFER_UNDEFINED => "the default framebuffer doesn't exist",
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => {
+                "an attachment is incomplete or has a format that can't be rendered to"
+            }
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "no image is attached",
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+            gl::FRAMEBUFFER_UNSUPPORTED => {
+                "the combination of attachment formats isn't supported by the driver"
+            }
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => {
+                "attachments don't share the same sample count"
+            }
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => {
+                "attachments mix layered and non-layered images"
+            }
+            _ => "unknown status",
+        };
+
+        return Err(FramebufferError::Incomplete(format!(
+            "{} (0x{:x})",
+            reason, status
+        )));
+    }
+}
+
+impl Framebuffer for OglFramebuffer {
+    fn get_id(&self) -> u32 {
+        return self.state.lock().unwrap().id;
+    }
+
+    fn bind(&self) {
+        let state = self.state.lock().unwrap();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, state.id);
+            gl::Viewport(0, 0, state.width as i32, state.height as i32);
+        }
+    }
+
+    fn unbind(&self) {
+        let (width, height) = get_window_size();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, width as i32, height as i32);
+        }
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        let state = self.state.lock().unwrap();
+        return (state.width, state.height);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        return self.state.lock().unwrap().allocate(width, height);
+    }
+
+    fn get_samples(&self) -> u32 {
+        return self.state.lock().unwrap().samples;
+    }
+
+    fn get_color_attachment_count(&self) -> usize {
+        return self.state.lock().unwrap().color_attachments.len();
+    }
+
+    fn get_color_attachment(&self, index: usize) -> Option<Box<dyn Texture>> {
+        let state = self.state.lock().unwrap();
+        match &state.color_attachments.get(index)?.0 {
+            AttachmentStorage::Texture(texture) => Some(Box::new(texture.clone())),
+            AttachmentStorage::Renderbuffer(_) => None,
+        }
+    }
+
+    fn get_depth_attachment(&self) -> Option<Box<dyn Texture>> {
+        let state = self.state.lock().unwrap();
+        match &state.depth_attachment {
+            Some(AttachmentStorage::Texture(texture)) => Some(Box::new(texture.clone())),
+            Some(AttachmentStorage::Renderbuffer(_)) | None => None,
+        }
+    }
+
+    fn resolve(&self, target: Option<&Box<dyn Framebuffer>>) -> Result<(), FramebufferError> {
+        // Query the target first, it may be this framebuffer itself.
+        let (target_id, (target_width, target_height), target_attachments) = match target {
+            Some(target) => (
+                target.get_id(),
+                target.get_size(),
+                target.get_color_attachment_count(),
+            ),
+            None => (0, get_window_size(), 1),
+        };
+
+        let state = self.state.lock().unwrap();
+        let same_size = state.width == target_width && state.height == target_height;
+        if state.samples > 1 && !same_size {
+            return Err(FramebufferError::SizeMismatch);
+        }
+
+        let filter = if same_size { gl::NEAREST } else { gl::LINEAR };
+        let previous = get_bound_framebuffer();
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, state.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target_id);
+            for index in 0..state.color_attachments.len().min(target_attachments) {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as u32);
+                // ES requires attachment i at position i of the array.
+                let draw_buffers: Vec<u32> = if target_id == 0 {
+                    vec![gl::BACK]
+                } else {
+                    (0..=index)
+                        .map(|slot| match slot == index {
+                            true => gl::COLOR_ATTACHMENT0 + index as u32,
+                            false => gl::NONE,
+                        })
+                        .collect()
+                };
+                gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+
+                gl::BlitFramebuffer(
+                    0,
+                    0,
+                    state.width as i32,
+                    state.height as i32,
+                    0,
+                    0,
+                    target_width as i32,
+                    target_height as i32,
+                    gl::COLOR_BUFFER_BIT,
+                    filter,
+                );
+            }
+
+            if target_id != 0 {
+                let draw_buffers: Vec<u32> = (0..target_attachments)
+                    .map(|index| gl::COLOR_ATTACHMENT0 + index as u32)
+                    .collect();
+                gl::DrawBuffers(draw_buffers.len() as i32, draw_buffers.as_ptr());
+            }
+            if !state.color_attachments.is_empty() {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous);
+        }
+
+        return Ok(());
+    }
+}
+
+pub fn get_window_size() -> (u32, u32) {
+    return WINDOW_SIZE.with(|size| size.get());
+}
+
+// Window sized framebuffers that were dropped are pruned here, a minimized
+// window keeps the previous size instead of failing with an empty target.
+pub fn resize_window_framebuffers(width: u32, height: u32) {
+    WINDOW_SIZE.with(|size| size.set((width, height)));
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    let framebuffers = WINDOW_FRAMEBUFFERS.with(|framebuffers| {
+        let mut framebuffers = framebuffers.borrow_mut();
+        framebuffers.retain(|framebuffer| framebuffer.strong_count() > 0);
+        framebuffers.clone()
+    });
+    for framebuffer in framebuffers.iter().filter_map(|framebuffer| framebuffer.upgrade()) {
+        let mut state = framebuffer.lock().unwrap();
+        let (width, height) =
+            OglFramebuffer::scale_window_size((width, height), state.scale.unwrap_or(1.0));
+        if let Err(error) = state.allocate(width, height) {
+            log::warn!(
+                target: debug::LOG_TARGET,
+                "Failed to resize framebuffer with error\n{}",
+                error
+            );
+        }
+    }
+}
+
+pub(super) fn get_bound_framebuffer() -> u32 {
+    let mut framebuffer = 0;
+    unsafe {
+        gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut framebuffer);
+    }
+
+    return framebuffer as u32;
+}
@@ -1,29 +1,61 @@
 extern crate gl;
 
+pub mod async_texture;
 pub mod buffer;
+pub mod compressed_texture;
+pub mod context;
+pub mod cubemap;
+pub mod debug;
+pub mod frame_stats;
+pub mod framebuffer;
+pub mod pipeline;
+pub mod render_state;
 pub mod shader;
+pub mod state_cache;
 pub mod texture;
 pub mod vertex_array;
 
-use crate::render::mesh::Drawable;
+use std::ffi::CStr;
+
+use crate::render::buffer::BufferDataType;
+use crate::render::capabilities::Capabilities;
+use crate::render::debug::DebugSeverity;
+use crate::render::framebuffer::Framebuffer;
 use crate::render::render_api::RenderAPI;
-use crate::render::window::Window;
+use crate::render::render_queue::RenderQueue;
+use crate::render::render_state::{
+    BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+    StencilState,
+};
+use crate::render::stats::{FrameStats, StateCacheStats};
+use crate::render::texture::{PixelBuffer, PixelData, TextureFormat};
+use crate::render::window::{FramebufferResize, Window};
 
 pub struct OglAPI {
     initalized: bool,
+    framebuffer_resize: FramebufferResize,
 }
 
 impl RenderAPI for OglAPI {
     fn init(&self, window: &mut Box<dyn Window>) {
         gl::load_with(|s| window.get_proc_addr(s));
-        unsafe {
-            gl::ClearColor(0.2, 0.3, 0.3, 1.0);
-            
-            gl::Enable(gl::DEPTH_TEST);
-
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        context::init();
+        debug::init();
+        let (width, height) = window.get_framebuffer_size();
+        self.resize(width, height);
+        // ES always filters across cube faces and multisamples whenever the
+        // target has samples, neither can be toggled.
+        if !context::get().es {
+            unsafe {
+                gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+                gl::Enable(gl::MULTISAMPLE);
+            }
         }
+        state_cache::invalidate();
+
+        self.set_clear_color(0.2, 0.3, 0.3, 1.0);
+        self.set_depth_state(DepthState::default());
+        self.set_blend_state(None, BlendState::ALPHA);
     }
 
     fn clear(&self) {
@@ -31,35 +63,311 @@ impl RenderAPI for OglAPI {
             panic!("Ogl API was not initalized!");
         }
 
+        // GL clears honour the write masks and the scissor, which the last
+        // pipeline may have left restricted, e.g. text turns depth writes
+        // off. They are lifted for the clear and restored afterwards, color
+        // masks set per target aren't cached and stay all on.
+        let depth = state_cache::get_depth_state().unwrap_or_default();
+        let color_mask = state_cache::get_color_mask().unwrap_or(ColorMask::ALL);
+        let scissor = state_cache::get_scissor().flatten();
+        let stencil = state_cache::get_stencil_state().unwrap_or_default();
+        self.set_depth_state(DepthState { write: true, ..depth });
+        self.set_color_mask(None, ColorMask::ALL);
+        self.set_scissor(None);
+        self.set_stencil_state(StencilState {
+            write_mask: u32::MAX,
+            ..stencil
+        });
+
         unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
         }
+
+        self.set_depth_state(depth);
+        self.set_color_mask(None, color_mask);
+        self.set_scissor(scissor);
+        self.set_stencil_state(stencil);
     }
 
-    fn begin(&self) {
+    fn begin<'a>(&self) -> RenderQueue<'a> {
         if !self.initalized {
             panic!("Ogl API was not initalized!");
         }
 
+        if let Some((width, height)) = self.framebuffer_resize.take() {
+            self.resize(width, height);
+        }
+
+        frame_stats::begin_frame();
+        async_texture::process_uploads();
         self.clear();
+
+        return RenderQueue::new();
     }
 
-    fn end(&self) {
+    fn end(&self, mut queue: RenderQueue) {
         if !self.initalized {
             panic!("Ogl API was not initalized!");
         }
 
+        debug::checked("end", || queue.execute());
         unsafe {
             gl::Flush();
         }
     }
 
-    fn submit(&self, drawable: &mut Box<dyn Drawable>) {
+    fn set_render_target(&self, target: Option<&Box<dyn Framebuffer>>) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        match target {
+            Some(framebuffer) => framebuffer.bind(),
+            None => {
+                let (width, height) = framebuffer::get_window_size();
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                    gl::Viewport(0, 0, width as i32, height as i32);
+                }
+            }
+        }
+    }
+
+    fn capture_frame(&self) -> PixelBuffer {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        // A minimized window has nothing to read.
+        let (width, height) = framebuffer::get_window_size();
+        if width == 0 || height == 0 {
+            return PixelBuffer {
+                width,
+                height,
+                format: TextureFormat::RGBA,
+                data: PixelData::U8(Vec::new()),
+            };
+        }
+
+        let mut data = PixelData::new((width * height * 4) as usize, BufferDataType::U8);
+        unsafe {
+            let mut previous = 0;
+            gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                data.as_mut_ptr(),
+            );
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+        }
+
+        let mut frame = PixelBuffer {
+            width,
+            height,
+            format: TextureFormat::RGBA,
+            data,
+        };
+        frame.flip_vertically();
+
+        return frame;
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        framebuffer::resize_window_framebuffers(width, height);
+        if framebuffer::get_bound_framebuffer() == 0 {
+            unsafe {
+                gl::Viewport(0, 0, width as i32, height as i32);
+            }
+        }
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        unsafe {
+            gl::Viewport(x, y, width as i32, height as i32);
+        }
+    }
+
+    fn set_clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        unsafe {
+            gl::ClearColor(red, green, blue, alpha);
+        }
+    }
+
+    fn set_clear_depth(&self, depth: f32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        unsafe {
+            if context::get().es || context::get().is_at_least(4, 1) {
+                gl::ClearDepthf(depth);
+            } else {
+                gl::ClearDepth(depth as f64);
+            }
+        }
+    }
+
+    fn set_clear_stencil(&self, stencil: i32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        unsafe {
+            gl::ClearStencil(stencil);
+        }
+    }
+
+    fn set_depth_state(&self, state: DepthState) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_depth_state(state, render_state::apply_depth_state);
+    }
+
+    fn set_blend_state(&self, target: Option<u32>, state: BlendState) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        match target {
+            Some(_) => {
+                render_state::apply_blend_state(target, state);
+                state_cache::forget_blend_state();
+            }
+            None => state_cache::set_blend_state(state, |state| {
+                render_state::apply_blend_state(None, state)
+            }),
+        }
+    }
+
+    fn set_blend_constant(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        unsafe {
+            gl::BlendColor(red, green, blue, alpha);
+        }
+    }
+
+    fn set_color_mask(&self, target: Option<u32>, mask: ColorMask) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        match target {
+            Some(_) => {
+                render_state::apply_color_mask(target, mask);
+                state_cache::forget_color_mask();
+            }
+            None => state_cache::set_color_mask(mask, |mask| {
+                render_state::apply_color_mask(None, mask)
+            }),
+        }
+    }
+
+    fn set_cull_mode(&self, mode: CullMode) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_cull_mode(mode, render_state::apply_cull_mode);
+    }
+
+    fn set_front_face(&self, face: FrontFace) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_front_face(face, render_state::apply_front_face);
+    }
+
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_polygon_mode(mode, render_state::apply_polygon_mode);
+    }
+
+    fn set_scissor(&self, rect: Option<ScissorRect>) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_scissor(rect, render_state::apply_scissor);
+    }
+
+    fn set_stencil_state(&self, state: StencilState) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        state_cache::set_stencil_state(state, render_state::apply_stencil_state);
+    }
+
+    fn capabilities(&self) -> Capabilities {
         if !self.initalized {
             panic!("Ogl API was not initalized!");
         }
 
-        drawable.draw();
+        return context::get_capabilities();
+    }
+
+    fn get_state_cache_stats(&self) -> StateCacheStats {
+        return state_cache::get_stats();
+    }
+
+    fn reset_state_cache_stats(&self) {
+        state_cache::reset_stats();
+    }
+
+    fn set_debug_severity(&self, severity: DebugSeverity) {
+        debug::set_min_severity(severity);
+    }
+
+    fn get_frame_stats(&self) -> FrameStats {
+        return frame_stats::get_last_frame();
+    }
+
+    fn end_frame(&self) {
+        frame_stats::end_frame();
+    }
+
+    fn begin_scope(&self, name: &str) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        frame_stats::begin_scope(name);
+    }
+
+    fn end_scope(&self) {
+        if !self.initalized {
+            panic!("Ogl API was not initalized!");
+        }
+
+        frame_stats::end_scope();
     }
 
     fn disable_align_restrictions(&self) {
@@ -86,9 +394,40 @@ impl RenderAPI for OglAPI {
 
 impl OglAPI {
     pub fn new(window: &mut Box<dyn Window>) -> OglAPI {
-        let api = OglAPI { initalized: true };
+        let api = OglAPI {
+            initalized: true,
+            framebuffer_resize: window.get_framebuffer_resize(),
+        };
         api.init(window);
 
         return api;
     }
 }
+
+pub fn get_version() -> (i32, i32) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+
+    return (major, minor);
+}
+
+pub fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        for index in 0..count {
+            let extension = gl::GetStringi(gl::EXTENSIONS, index as u32);
+            if !extension.is_null()
+                && CStr::from_ptr(extension as *const i8).to_bytes() == name.as_bytes()
+            {
+                return true;
+            }
+        }
+    }
+
+    return false;
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+
+use crate::render::{
+    buffer::Bindable,
+    pipeline::{Pipeline, PipelineDescriptor, PrimitiveTopology},
+    shader::Shader,
+};
+
+use super::{render_state, state_cache};
+
+thread_local! {
+    // Topology of the pipeline bound in the context current on this thread,
+    // read by every draw call.
+    static TOPOLOGY: Cell<PrimitiveTopology> = Cell::new(PrimitiveTopology::Triangles);
+}
+
+pub fn get_primitive_mode() -> u32 {
+    return OglPipeline::translate_topology(TOPOLOGY.with(|topology| topology.get()));
+}
+
+pub fn get_triangle_count(vertices: u64) -> u64 {
+    return TOPOLOGY.with(|topology| topology.get().get_triangle_count(vertices));
+}
+
+// The state goes through the state cache, so binding the same pipeline
+// twice in a row only binds the program.
+pub struct OglPipeline {
+    shader: Box<dyn Shader>,
+    descriptor: PipelineDescriptor,
+}
+
+impl OglPipeline {
+    pub fn new(shader: Box<dyn Shader>, descriptor: PipelineDescriptor) -> OglPipeline {
+        return OglPipeline { shader, descriptor };
+    }
+
+    fn translate_topology(topology: PrimitiveTopology) -> u32 {
+        match topology {
+            PrimitiveTopology::Triangles => gl::TRIANGLES,
+            PrimitiveTopology::TriangleStrip => gl::TRIANGLE_STRIP,
+            PrimitiveTopology::Lines => gl::LINES,
+            PrimitiveTopology::LineStrip => gl::LINE_STRIP,
+            PrimitiveTopology::Points => gl::POINTS,
+        }
+    }
+}
+
+impl Pipeline for OglPipeline {
+    fn get_shader(&self) -> &Box<dyn Shader> {
+        return &self.shader;
+    }
+
+    fn get_descriptor(&self) -> &PipelineDescriptor {
+        return &self.descriptor;
+    }
+}
+
+impl Bindable for OglPipeline {
+    fn bind(&self) {
+        let descriptor = &self.descriptor;
+        self.shader.bind();
+        TOPOLOGY.with(|topology| topology.set(descriptor.topology));
+
+        state_cache::set_depth_state(descriptor.depth, render_state::apply_depth_state);
+        state_cache::set_blend_state(descriptor.blend, |state| {
+            render_state::apply_blend_state(None, state)
+        });
+        state_cache::set_color_mask(descriptor.color_mask, |mask| {
+            render_state::apply_color_mask(None, mask)
+        });
+        state_cache::set_stencil_state(descriptor.stencil, render_state::apply_stencil_state);
+        state_cache::set_cull_mode(descriptor.cull_mode, render_state::apply_cull_mode);
+        state_cache::set_front_face(descriptor.front_face, render_state::apply_front_face);
+        state_cache::set_polygon_mode(descriptor.polygon_mode, render_state::apply_polygon_mode);
+    }
+
+    fn unbind(&self) {
+        self.shader.unbind();
+        TOPOLOGY.with(|topology| topology.set(PrimitiveTopology::Triangles));
+    }
+}
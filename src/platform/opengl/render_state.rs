@@ -0,0 +1,209 @@
+use crate::render::render_state::{
+    BlendFactor, BlendOperation, BlendState, ColorMask, CompareFunction, CullMode, DepthState,
+    FrontFace, PolygonMode, ScissorRect, StencilFaceState, StencilOperation, StencilState,
+};
+
+use super::{
+    context, debug,
+    state_cache::{self, set_capability},
+};
+
+pub fn apply_depth_state(state: DepthState) {
+    unsafe {
+        set_capability(gl::DEPTH_TEST, state.test);
+        gl::DepthMask(state.write as u8);
+        gl::DepthFunc(translate_compare_function(state.compare));
+    }
+}
+
+// Draw buffer targets are set through the indexed variants, None applies
+// the state to every target at once. Contexts without indexed blending
+// apply a target's state to all of them.
+pub fn apply_blend_state(target: Option<u32>, state: BlendState) {
+    let target = target.filter(|_| context::get().indexed_blending);
+    let color_operation = translate_blend_operation(state.color_operation);
+    let alpha_operation = translate_blend_operation(state.alpha_operation);
+    let src_color = translate_blend_factor(state.src_color);
+    let dst_color = translate_blend_factor(state.dst_color);
+    let src_alpha = translate_blend_factor(state.src_alpha);
+    let dst_alpha = translate_blend_factor(state.dst_alpha);
+    unsafe {
+        match target {
+            Some(target) => {
+                if state.enabled {
+                    gl::Enablei(gl::BLEND, target);
+                } else {
+                    gl::Disablei(gl::BLEND, target);
+                }
+                state_cache::forget_capability(gl::BLEND);
+                gl::BlendEquationSeparatei(target, color_operation, alpha_operation);
+                gl::BlendFuncSeparatei(target, src_color, dst_color, src_alpha, dst_alpha);
+            }
+            None => {
+                set_capability(gl::BLEND, state.enabled);
+                gl::BlendEquationSeparate(color_operation, alpha_operation);
+                gl::BlendFuncSeparate(src_color, dst_color, src_alpha, dst_alpha);
+            }
+        }
+    }
+}
+
+pub fn apply_color_mask(target: Option<u32>, mask: ColorMask) {
+    let target = target.filter(|_| context::get().indexed_blending);
+    unsafe {
+        match target {
+            Some(target) => gl::ColorMaski(
+                target,
+                mask.red as u8,
+                mask.green as u8,
+                mask.blue as u8,
+                mask.alpha as u8,
+            ),
+            None => gl::ColorMask(
+                mask.red as u8,
+                mask.green as u8,
+                mask.blue as u8,
+                mask.alpha as u8,
+            ),
+        }
+    }
+}
+
+pub fn apply_cull_mode(mode: CullMode) {
+    unsafe {
+        match mode {
+            CullMode::None => set_capability(gl::CULL_FACE, false),
+            CullMode::Front => {
+                set_capability(gl::CULL_FACE, true);
+                gl::CullFace(gl::FRONT);
+            }
+            CullMode::Back => {
+                set_capability(gl::CULL_FACE, true);
+                gl::CullFace(gl::BACK);
+            }
+            CullMode::FrontAndBack => {
+                set_capability(gl::CULL_FACE, true);
+                gl::CullFace(gl::FRONT_AND_BACK);
+            }
+        }
+    }
+}
+
+pub fn apply_front_face(face: FrontFace) {
+    unsafe {
+        match face {
+            FrontFace::Clockwise => gl::FrontFace(gl::CW),
+            FrontFace::CounterClockwise => gl::FrontFace(gl::CCW),
+        }
+    }
+}
+
+// ES only rasterizes filled polygons.
+pub fn apply_polygon_mode(mode: PolygonMode) {
+    if context::get().es {
+        if mode != PolygonMode::Fill {
+            log::warn!(target: debug::LOG_TARGET, "Polygon mode {:?} is not supported on OpenGL ES", mode);
+        }
+        return;
+    }
+
+    let mode = match mode {
+        PolygonMode::Fill => gl::FILL,
+        PolygonMode::Line => gl::LINE,
+        PolygonMode::Point => gl::POINT,
+    };
+    unsafe {
+        gl::PolygonMode(gl::FRONT_AND_BACK, mode);
+    }
+}
+
+pub fn apply_scissor(rect: Option<ScissorRect>) {
+    unsafe {
+        match rect {
+            Some(rect) => {
+                set_capability(gl::SCISSOR_TEST, true);
+                gl::Scissor(rect.x, rect.y, rect.width as i32, rect.height as i32);
+            }
+            None => set_capability(gl::SCISSOR_TEST, false),
+        }
+    }
+}
+
+pub fn apply_stencil_state(state: StencilState) {
+    unsafe {
+        set_capability(gl::STENCIL_TEST, state.enabled);
+        gl::StencilMask(state.write_mask);
+        apply_stencil_face(gl::FRONT, state.front, state.reference, state.read_mask);
+        apply_stencil_face(gl::BACK, state.back, state.reference, state.read_mask);
+    }
+}
+
+fn apply_stencil_face(face: u32, state: StencilFaceState, reference: i32, read_mask: u32) {
+    unsafe {
+        gl::StencilFuncSeparate(
+            face,
+            translate_compare_function(state.compare),
+            reference,
+            read_mask,
+        );
+        gl::StencilOpSeparate(
+            face,
+            translate_stencil_operation(state.fail),
+            translate_stencil_operation(state.depth_fail),
+            translate_stencil_operation(state.pass),
+        );
+    }
+}
+
+pub fn translate_compare_function(function: CompareFunction) -> u32 {
+    match function {
+        CompareFunction::Never => gl::NEVER,
+        CompareFunction::Less => gl::LESS,
+        CompareFunction::Equal => gl::EQUAL,
+        CompareFunction::LessEqual => gl::LEQUAL,
+        CompareFunction::Greater => gl::GREATER,
+        CompareFunction::NotEqual => gl::NOTEQUAL,
+        CompareFunction::GreaterEqual => gl::GEQUAL,
+        CompareFunction::Always => gl::ALWAYS,
+    }
+}
+
+pub fn translate_blend_factor(factor: BlendFactor) -> u32 {
+    match factor {
+        BlendFactor::Zero => gl::ZERO,
+        BlendFactor::One => gl::ONE,
+        BlendFactor::SrcColor => gl::SRC_COLOR,
+        BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+        BlendFactor::DstColor => gl::DST_COLOR,
+        BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+        BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+        BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+        BlendFactor::DstAlpha => gl::DST_ALPHA,
+        BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+        BlendFactor::ConstantColor => gl::CONSTANT_COLOR,
+        BlendFactor::OneMinusConstantColor => gl::ONE_MINUS_CONSTANT_COLOR,
+    }
+}
+
+pub fn translate_blend_operation(operation: BlendOperation) -> u32 {
+    match operation {
+        BlendOperation::Add => gl::FUNC_ADD,
+        BlendOperation::Subtract => gl::FUNC_SUBTRACT,
+        BlendOperation::ReverseSubtract => gl::FUNC_REVERSE_SUBTRACT,
+        BlendOperation::Min => gl::MIN,
+        BlendOperation::Max => gl::MAX,
+    }
+}
+
+pub fn translate_stencil_operation(operation: StencilOperation) -> u32 {
+    match operation {
+        StencilOperation::Keep => gl::KEEP,
+        StencilOperation::Zero => gl::ZERO,
+        StencilOperation::Replace => gl::REPLACE,
+        StencilOperation::Increment => gl::INCR,
+        StencilOperation::IncrementWrap => gl::INCR_WRAP,
+        StencilOperation::Decrement => gl::DECR,
+        StencilOperation::DecrementWrap => gl::DECR_WRAP,
+        StencilOperation::Invert => gl::INVERT,
+    }
+}
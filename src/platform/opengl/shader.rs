@@ -4,7 +4,12 @@ use std::ffi::CString;
 
 use gl::types;
 
-use crate::render::{buffer::Bindable, shader::Shader};
+use crate::render::{
+    buffer::Bindable,
+    shader::{Shader, ShaderAttribute},
+};
+
+use super::{context, debug, state_cache};
 
 const VERTEX_KEY: &str = "vertex";
 const FRAGMENT_KEY: &str = "fragment";
@@ -15,17 +20,26 @@ pub struct OglShader {
 
 #[allow(temporary_cstring_as_ptr)]
 impl Shader for OglShader {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
     fn load_from_file(&mut self, file_path: &str) {
         let file_content = fs::read_to_string(file_path).expect("Couldn't read shader file");
+        self.load_from_source(&file_content);
+    }
+
+    fn load_from_source(&mut self, source: &str) {
         let mut map: HashMap<&str, CString> = HashMap::new();
-        let _split: Vec<&str> = file_content
+        let _split: Vec<&str> = source
             .split("#shader ")
             .filter_map(|x| match x.trim().is_empty() {
                 true => None,
                 false => {
                     let line_end = x.find("\n").unwrap();
                     let split = x.split_at(line_end);
-                    map.insert(split.0, CString::new(split.1).unwrap());
+                    let stage_source = context::prepare_shader_source(split.1);
+                    map.insert(split.0, CString::new(stage_source).unwrap());
 
                     return Some(x);
                 }
@@ -56,6 +70,57 @@ impl Shader for OglShader {
         }
     }
 
+    fn set_label(&self, label: &str) {
+        debug::set_label(gl::PROGRAM, self.id, label);
+    }
+
+    // Built-in inputs like gl_VertexID have no location and are skipped.
+    fn get_attributes(&self) -> Option<Vec<ShaderAttribute>> {
+        let mut attributes = Vec::new();
+        let mut count = 0;
+        let mut max_length = 0;
+        unsafe {
+            gl::GetProgramiv(self.id, gl::ACTIVE_ATTRIBUTES, &mut count);
+            gl::GetProgramiv(self.id, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+        }
+
+        for index in 0..count as u32 {
+            let mut name = vec![0u8; max_length.max(1) as usize];
+            let mut length = 0;
+            let mut size = 0;
+            let mut attribute_type = 0;
+            let location = unsafe {
+                gl::GetActiveAttrib(
+                    self.id,
+                    index,
+                    max_length.max(1),
+                    &mut length,
+                    &mut size,
+                    &mut attribute_type,
+                    name.as_mut_ptr() as *mut types::GLchar,
+                );
+                gl::GetAttribLocation(self.id, name.as_ptr() as *const types::GLchar)
+            };
+            if location < 0 {
+                continue;
+            }
+
+            name.truncate(length as usize);
+            let name = String::from_utf8_lossy(&name).into_owned();
+            let (columns, components) = OglShader::get_attribute_shape(attribute_type);
+            for column in 0..size as u32 * columns {
+                attributes.push(ShaderAttribute {
+                    name: name.clone(),
+                    location: location as u32 + column,
+                    components,
+                    integer: OglShader::is_integer_attribute(attribute_type),
+                });
+            }
+        }
+
+        return Some(attributes);
+    }
+
     fn submit_uniform_mat4x4(&self, name: &str, data: nalgebra::Matrix4<f32>) {
         unsafe {
             let uniform_location = gl::GetUniformLocation(self.id, CString::new(name).unwrap().as_ptr());
@@ -63,6 +128,13 @@ impl Shader for OglShader {
         }
     }
 
+    fn submit_uniform_i32(&self, name: &str, value: i32) {
+        unsafe {
+            let uniform_location = gl::GetUniformLocation(self.id, CString::new(name).unwrap().as_ptr());
+            gl::Uniform1i(uniform_location, value);
+        }
+    }
+
     fn submit_uniform_vec3(&self, name: &str, data: nalgebra::Vector3<f32>) {
         unsafe {
             let uniform_location = gl::GetUniformLocation(self.id, CString::new(name).unwrap().as_ptr());
@@ -71,17 +143,19 @@ impl Shader for OglShader {
     }
 }
 
+impl Drop for OglShader {
+    fn drop(&mut self) {
+        state_cache::delete_program(self.id);
+    }
+}
+
 impl Bindable for OglShader {
     fn bind(&self) {
-        unsafe {
-            gl::UseProgram(self.id);
-        }
+        state_cache::bind_program(self.id);
     }
 
     fn unbind(&self) {
-        unsafe {
-            gl::UseProgram(0);
-        }
+        state_cache::bind_program(0);
     }
 }
 
@@ -103,6 +177,46 @@ impl OglShader {
         return shader;
     }
 
+    pub fn new_from_source(source: &str) -> OglShader {
+        let mut shader = OglShader::new();
+        shader.load_from_source(source);
+
+        return shader;
+    }
+
+    // Columns and components per column of an attribute type.
+    fn get_attribute_shape(attribute_type: types::GLenum) -> (u32, u32) {
+        match attribute_type {
+            gl::FLOAT_VEC2 | gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 | gl::DOUBLE_VEC2 => (1, 2),
+            gl::FLOAT_VEC3 | gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 | gl::DOUBLE_VEC3 => (1, 3),
+            gl::FLOAT_VEC4 | gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 | gl::DOUBLE_VEC4 => (1, 4),
+            gl::FLOAT_MAT2 => (2, 2),
+            gl::FLOAT_MAT3 => (3, 3),
+            gl::FLOAT_MAT4 => (4, 4),
+            gl::FLOAT_MAT2x3 => (2, 3),
+            gl::FLOAT_MAT2x4 => (2, 4),
+            gl::FLOAT_MAT3x2 => (3, 2),
+            gl::FLOAT_MAT3x4 => (3, 4),
+            gl::FLOAT_MAT4x2 => (4, 2),
+            gl::FLOAT_MAT4x3 => (4, 3),
+            _ => (1, 1),
+        }
+    }
+
+    fn is_integer_attribute(attribute_type: types::GLenum) -> bool {
+        return matches!(
+            attribute_type,
+            gl::INT
+                | gl::INT_VEC2
+                | gl::INT_VEC3
+                | gl::INT_VEC4
+                | gl::UNSIGNED_INT
+                | gl::UNSIGNED_INT_VEC2
+                | gl::UNSIGNED_INT_VEC3
+                | gl::UNSIGNED_INT_VEC4
+        );
+    }
+
     fn check_compile_errors(id: u32, shader_type: &str) {
         let mut status: i32 = gl::TRUE as i32;
         unsafe {
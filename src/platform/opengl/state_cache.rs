@@ -0,0 +1,277 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::render::{
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    stats::StateCacheStats,
+};
+
+thread_local! {
+    // Mirrors the bindings and render state last set through the backend so
+    // redundant calls can be skipped. Missing entries mean the state is
+    // unknown and the next call always reaches the driver. Bindings belong
+    // to a context, which is current on one thread only.
+    static STATE_CACHE: RefCell<StateCache> = RefCell::new(StateCache::default());
+}
+
+fn with_cache<T>(f: impl FnOnce(&mut StateCache) -> T) -> T {
+    return STATE_CACHE.with(|cache| f(&mut cache.borrow_mut()));
+}
+
+// Objects dropped while the thread exits outlive the cache and the context,
+// nothing is left to delete then.
+fn with_live_cache(f: impl FnOnce(&mut StateCache)) {
+    let _ = STATE_CACHE.try_with(|cache| f(&mut cache.borrow_mut()));
+}
+
+#[derive(Default)]
+struct StateCache {
+    program: Option<u32>,
+    vertex_array: Option<u32>,
+    buffers: HashMap<u32, u32>,
+    active_texture: Option<u32>,
+    textures: HashMap<(u32, u32), u32>,
+    capabilities: HashMap<u32, bool>,
+
+    depth_state: Option<DepthState>,
+    blend_state: Option<BlendState>,
+    color_mask: Option<ColorMask>,
+    cull_mode: Option<CullMode>,
+    front_face: Option<FrontFace>,
+    polygon_mode: Option<PolygonMode>,
+    scissor: Option<Option<ScissorRect>>,
+    stencil_state: Option<StencilState>,
+
+    stats: StateCacheStats,
+}
+
+pub fn bind_program(id: u32) {
+    with_cache(|cache| {
+        if cache.program == Some(id) {
+            cache.stats.skipped_program_binds += 1;
+            return;
+        }
+
+        cache.program = Some(id);
+        cache.stats.program_binds += 1;
+        unsafe {
+            gl::UseProgram(id);
+        }
+    });
+}
+
+pub fn get_program() -> Option<u32> {
+    return with_cache(|cache| cache.program);
+}
+
+// Deleted names are reused by the driver, so the cache must not keep them
+// bound.
+pub fn delete_program(id: u32) {
+    with_live_cache(|cache| {
+        unsafe {
+            if cache.program == Some(id) {
+                cache.program = Some(0);
+                gl::UseProgram(0);
+            }
+            gl::DeleteProgram(id);
+        }
+    });
+}
+
+// The element buffer binding belongs to the vertex array, so it becomes
+// unknown whenever another array is bound.
+pub fn bind_vertex_array(id: u32) {
+    with_cache(|cache| {
+        if cache.vertex_array == Some(id) {
+            cache.stats.skipped_vertex_array_binds += 1;
+            return;
+        }
+
+        cache.vertex_array = Some(id);
+        cache.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        cache.stats.vertex_array_binds += 1;
+        unsafe {
+            gl::BindVertexArray(id);
+        }
+    });
+}
+
+pub fn delete_vertex_array(id: u32) {
+    with_live_cache(|cache| {
+        if cache.vertex_array == Some(id) {
+            cache.vertex_array = Some(0);
+            cache.buffers.remove(&gl::ELEMENT_ARRAY_BUFFER);
+        }
+        unsafe {
+            gl::DeleteVertexArrays(1, &id);
+        }
+    });
+}
+
+pub fn bind_buffer(target: u32, id: u32) {
+    with_cache(|cache| {
+        if cache.buffers.get(&target) == Some(&id) {
+            cache.stats.skipped_buffer_binds += 1;
+            return;
+        }
+
+        cache.buffers.insert(target, id);
+        cache.stats.buffer_binds += 1;
+        unsafe {
+            gl::BindBuffer(target, id);
+        }
+    });
+}
+
+// Deleting a buffer unbinds it from every target.
+pub fn delete_buffer(id: u32) {
+    with_live_cache(|cache| {
+        for binding in cache.buffers.values_mut().filter(|binding| **binding == id) {
+            *binding = 0;
+        }
+        unsafe {
+            gl::DeleteBuffers(1, &id);
+        }
+    });
+}
+
+// Binds to the given unit, None uses whichever unit is active, which is
+// enough when the texture is only bound to be edited or queried.
+pub fn bind_texture(unit: Option<u32>, target: u32, id: u32) {
+    with_cache(|cache| {
+        if let Some(unit) = unit {
+            if cache.active_texture != Some(unit) {
+                cache.active_texture = Some(unit);
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                }
+            }
+        }
+
+        let unit = match cache.active_texture {
+            Some(unit) => unit,
+            None => {
+                let mut unit = 0;
+                unsafe {
+                    gl::GetIntegerv(gl::ACTIVE_TEXTURE, &mut unit);
+                }
+                let unit = unit as u32 - gl::TEXTURE0;
+                cache.active_texture = Some(unit);
+                unit
+            }
+        };
+
+        if cache.textures.get(&(unit, target)) == Some(&id) {
+            cache.stats.skipped_texture_binds += 1;
+            return;
+        }
+
+        cache.textures.insert((unit, target), id);
+        cache.stats.texture_binds += 1;
+        unsafe {
+            gl::BindTexture(target, id);
+        }
+    });
+}
+
+// Deleting a texture unbinds it from every unit.
+pub fn delete_texture(id: u32) {
+    with_live_cache(|cache| {
+        for binding in cache.textures.values_mut().filter(|binding| **binding == id) {
+            *binding = 0;
+        }
+        unsafe {
+            gl::DeleteTextures(1, &id);
+        }
+    });
+}
+
+pub fn set_capability(capability: u32, enabled: bool) {
+    with_cache(|cache| {
+        if cache.capabilities.get(&capability) == Some(&enabled) {
+            cache.stats.skipped_state_changes += 1;
+            return;
+        }
+
+        cache.capabilities.insert(capability, enabled);
+        cache.stats.state_changes += 1;
+        unsafe {
+            if enabled {
+                gl::Enable(capability);
+            } else {
+                gl::Disable(capability);
+            }
+        }
+    });
+}
+
+// Indexed state changes bypass the cache, this drops what they overrode.
+pub fn forget_capability(capability: u32) {
+    with_cache(|cache| cache.capabilities.remove(&capability));
+}
+
+macro_rules! cached_state {
+    ($name:ident, $field:ident, $type:ty) => {
+        // Runs apply only if the state differs from the cached one.
+        pub fn $name(state: $type, apply: impl FnOnce($type)) {
+            let changed = with_cache(|cache| {
+                if cache.$field == Some(state) {
+                    cache.stats.skipped_state_changes += 1;
+                    return false;
+                }
+
+                cache.$field = Some(state);
+                cache.stats.state_changes += 1;
+                true
+            });
+            if changed {
+                apply(state);
+            }
+        }
+    };
+    ($name:ident, $getter:ident, $field:ident, $type:ty) => {
+        cached_state!($name, $field, $type);
+
+        // None while the state is unknown.
+        pub fn $getter() -> Option<$type> {
+            return with_cache(|cache| cache.$field);
+        }
+    };
+}
+
+cached_state!(set_depth_state, get_depth_state, depth_state, DepthState);
+cached_state!(set_blend_state, blend_state, BlendState);
+cached_state!(set_color_mask, get_color_mask, color_mask, ColorMask);
+cached_state!(set_cull_mode, cull_mode, CullMode);
+cached_state!(set_front_face, front_face, FrontFace);
+cached_state!(set_polygon_mode, polygon_mode, PolygonMode);
+cached_state!(set_scissor, get_scissor, scissor, Option<ScissorRect>);
+cached_state!(set_stencil_state, get_stencil_state, stencil_state, StencilState);
+
+pub fn forget_blend_state() {
+    with_cache(|cache| cache.blend_state = None);
+}
+
+pub fn forget_color_mask() {
+    with_cache(|cache| cache.color_mask = None);
+}
+
+// Call after GL state was changed behind the backend's back, e.g. by
+// another library sharing the context.
+pub fn invalidate() {
+    with_cache(|cache| {
+        let stats = cache.stats;
+        *cache = StateCache::default();
+        cache.stats = stats;
+    });
+}
+
+pub fn get_stats() -> StateCacheStats {
+    return with_cache(|cache| cache.stats);
+}
+
+pub fn reset_stats() {
+    with_cache(|cache| cache.stats = StateCacheStats::default());
+}
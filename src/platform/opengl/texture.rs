@@ -1,20 +1,27 @@
-use crate::render::{buffer::BufferDataType, texture::Texture};
-
-use super::vertex_array::OglVertexArray;
-
-#[allow(dead_code)]
-pub enum TextureFormat {
-    RGBA,
-    RGB,
-    Red,
-    Green,
-    Blue,
-    Alpha,
+use std::{ffi::c_void, ptr, sync::Arc};
+
+use crate::render::{
+    buffer::BufferDataType,
+    texture::{PixelBuffer, PixelData, Texture, TextureDimension, TextureFormat},
+};
+
+use super::{context, debug, frame_stats, state_cache, vertex_array::OglVertexArray};
+
+// Copies of a texture share its name, which is deleted once the last one
+// is dropped.
+struct TextureName(u32);
+
+impl Drop for TextureName {
+    fn drop(&mut self) {
+        state_cache::delete_texture(self.0);
+    }
 }
 
 #[derive(Clone)]
 pub struct OglTexture {
-    id: u32,
+    name: Arc<TextureName>,
+    format: TextureFormat,
+    dimension: TextureDimension,
 }
 
 impl OglTexture {
@@ -25,38 +32,167 @@ impl OglTexture {
         format: TextureFormat,
         data_type: BufferDataType,
     ) -> OglTexture {
+        return OglTexture::new_with_dimension(
+            TextureDimension::Texture2D,
+            width,
+            height,
+            1,
+            data,
+            format,
+            data_type,
+        );
+    }
+
+    // Depth is the layer count for array textures, the depth of 3D textures
+    // and the cube count of cube arrays. Layers are expected to be tightly
+    // packed one after another in data, which may be null.
+    pub fn new_with_dimension(
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> OglTexture {
+        let texture = OglTexture::generate(dimension, format);
+
+        let target = texture.get_target();
+        let internal_format = Self::translate_internal_format(format, data_type) as i32;
+        let layer_size = (width * height * format.get_channel_count()) as usize
+            * OglVertexArray::get_type_size(data_type);
+        let gl_format = Self::translate_format(format);
+        let gl_type = OglVertexArray::translate_type(data_type);
+        texture.bind(0);
+        unsafe {
+            match dimension {
+                TextureDimension::Texture1D => gl::TexImage1D(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    0,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Texture2D => gl::TexImage2D(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    0,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Texture2DArray | TextureDimension::Texture3D => gl::TexImage3D(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    depth as i32,
+                    0,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Cube => {
+                    for face in 0..6 {
+                        gl::TexImage2D(
+                            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                            0,
+                            internal_format,
+                            width as i32,
+                            height as i32,
+                            0,
+                            gl_format,
+                            gl_type,
+                            if data.is_null() {
+                                ptr::null()
+                            } else {
+                                data.add(face * layer_size) as *const c_void
+                            },
+                        );
+                    }
+                }
+                TextureDimension::CubeArray => gl::TexImage3D(
+                    target,
+                    0,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                    (depth * 6) as i32,
+                    0,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+            }
+
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        if !data.is_null() {
+            let layers = match dimension {
+                TextureDimension::Texture1D | TextureDimension::Texture2D => 1,
+                TextureDimension::Texture2DArray | TextureDimension::Texture3D => depth,
+                TextureDimension::Cube => 6,
+                TextureDimension::CubeArray => depth * 6,
+            };
+            frame_stats::record_texture_upload(layer_size * layers as usize);
+        }
+
+        return texture;
+    }
+
+    pub(super) fn generate(dimension: TextureDimension, format: TextureFormat) -> OglTexture {
         let mut id = 0;
         unsafe {
             gl::GenTextures(1, &mut id);
         }
 
-        let texture = OglTexture { id };
+        return OglTexture {
+            name: Arc::new(TextureName(id)),
+            format,
+            dimension,
+        };
+    }
 
-        let format = Self::translate_format(format);
-        let data_type = OglVertexArray::translate_type(data_type);
-        texture.bind(0);
-        unsafe {
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                format as i32,
-                width as i32,
-                height as i32,
-                0,
-                format,
-                data_type,
-                data as *const std::ffi::c_void,
-            );
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+    pub fn get_target(&self) -> u32 {
+        return Self::translate_dimension(self.dimension);
+    }
+
+    pub fn translate_dimension(dimension: TextureDimension) -> u32 {
+        match dimension {
+            TextureDimension::Texture1D => gl::TEXTURE_1D,
+            TextureDimension::Texture2D => gl::TEXTURE_2D,
+            TextureDimension::Texture2DArray => gl::TEXTURE_2D_ARRAY,
+            TextureDimension::Texture3D => gl::TEXTURE_3D,
+            TextureDimension::Cube => gl::TEXTURE_CUBE_MAP,
+            TextureDimension::CubeArray => gl::TEXTURE_CUBE_MAP_ARRAY,
         }
+    }
 
-        return texture;
+    pub fn translate_internal_format(format: TextureFormat, data_type: BufferDataType) -> u32 {
+        match (format, data_type) {
+            (TextureFormat::RGBA, BufferDataType::F32) => gl::RGBA16F,
+            (TextureFormat::RGB, BufferDataType::F32) => gl::RGB16F,
+            (TextureFormat::Red, BufferDataType::F32) => gl::R16F,
+            (TextureFormat::Depth, BufferDataType::F32) => gl::DEPTH_COMPONENT32F,
+            (TextureFormat::Depth, _) => gl::DEPTH_COMPONENT24,
+            (TextureFormat::DepthStencil, _) => gl::DEPTH24_STENCIL8,
+            _ => Self::translate_format(format),
+        }
     }
 
-    fn translate_format(format: TextureFormat) -> u32 {
+    pub fn translate_format(format: TextureFormat) -> u32 {
         match format {
             TextureFormat::RGBA => gl::RGBA,
             TextureFormat::RGB => gl::RGB,
@@ -64,21 +200,253 @@ impl OglTexture {
             TextureFormat::Green => gl::GREEN,
             TextureFormat::Blue => gl::BLUE,
             TextureFormat::Alpha => gl::ALPHA,
+            TextureFormat::Depth => gl::DEPTH_COMPONENT,
+            TextureFormat::DepthStencil => gl::DEPTH_STENCIL,
+        }
+    }
+
+    // Cube faces are addressed through their own targets, everything else
+    // is queried through the texture target itself.
+    fn get_level_target(&self) -> u32 {
+        match self.dimension {
+            TextureDimension::Cube => gl::TEXTURE_CUBE_MAP_POSITIVE_X,
+            _ => self.get_target(),
+        }
+    }
+
+    // ES has no glGetTexImage, the layer is attached to a temporary
+    // framebuffer and read from there instead. Only color formats can be
+    // read this way.
+    unsafe fn read_layer_through_framebuffer(
+        &self,
+        level: u32,
+        layer: u32,
+        (width, height): (u32, u32),
+        gl_format: u32,
+        gl_type: u32,
+        pointer: *mut c_void,
+    ) {
+        if self.format == TextureFormat::Depth || self.format == TextureFormat::DepthStencil {
+            log::warn!(target: debug::LOG_TARGET, "Depth textures can't be read back on OpenGL ES");
+            return;
+        }
+
+        let mut previous = 0;
+        let mut framebuffer = 0;
+        gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+        match self.dimension {
+            TextureDimension::Cube => gl::FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                self.name.0,
+                level as i32,
+            ),
+            TextureDimension::Texture2D => gl::FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.name.0,
+                level as i32,
+            ),
+            _ => gl::FramebufferTextureLayer(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                self.name.0,
+                level as i32,
+                layer as i32,
+            ),
+        }
+        gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+        gl::ReadPixels(0, 0, width as i32, height as i32, gl_format, gl_type, pointer);
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+        gl::DeleteFramebuffers(1, &framebuffer);
+    }
+
+    fn get_layer_count(&self, level: u32) -> u32 {
+        let mut depth = 1;
+        unsafe {
+            state_cache::bind_texture(None, self.get_target(), self.name.0);
+            gl::GetTexLevelParameteriv(
+                self.get_level_target(),
+                level as i32,
+                gl::TEXTURE_DEPTH,
+                &mut depth,
+            );
         }
+
+        return match self.dimension {
+            TextureDimension::Cube => 6,
+            _ => depth as u32,
+        };
     }
 }
 
 impl Texture for OglTexture {
+    fn get_id(&self) -> u32 {
+        return self.name.0;
+    }
+
     fn bind(&self, slot: u32) {
+        state_cache::bind_texture(Some(slot), self.get_target(), self.name.0);
+    }
+
+    fn unbind(&self) {
+        state_cache::bind_texture(None, self.get_target(), 0);
+    }
+
+    fn get_size(&self, level: u32) -> (u32, u32) {
+        let mut width = 0;
+        let mut height = 0;
         unsafe {
-            gl::ActiveTexture(gl::TEXTURE0 + slot);
-            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            state_cache::bind_texture(None, self.get_target(), self.name.0);
+            gl::GetTexLevelParameteriv(
+                self.get_level_target(),
+                level as i32,
+                gl::TEXTURE_WIDTH,
+                &mut width,
+            );
+            gl::GetTexLevelParameteriv(
+                self.get_level_target(),
+                level as i32,
+                gl::TEXTURE_HEIGHT,
+                &mut height,
+            );
         }
+
+        return (width as u32, height as u32);
     }
 
-    fn unbind(&self) {
+    fn get_format(&self) -> TextureFormat {
+        return self.format;
+    }
+
+    // Layers, slices and cube faces are stacked vertically.
+    fn read_pixels(&self, level: u32, data_type: BufferDataType) -> PixelBuffer {
+        let (width, height) = self.get_size(level);
+        let layers = self.get_layer_count(level);
+        let layer_length = (width * height * self.format.get_channel_count()) as usize;
+        let mut data = PixelData::new(layer_length * layers as usize, data_type);
+        let data_type = data.get_data_type();
+
+        let gl_format = Self::translate_format(self.format);
+        let gl_type = OglVertexArray::translate_type(data_type);
+        let layer_size = layer_length * OglVertexArray::get_type_size(data_type);
+        let pointer = data.as_mut_ptr() as *mut u8;
         unsafe {
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            state_cache::bind_texture(None, self.get_target(), self.name.0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            match self.dimension {
+                _ if context::get().es => {
+                    for layer in 0..layers {
+                        self.read_layer_through_framebuffer(
+                            level,
+                            layer,
+                            (width, height),
+                            gl_format,
+                            gl_type,
+                            pointer.add(layer as usize * layer_size) as *mut c_void,
+                        );
+                    }
+                }
+                TextureDimension::Cube => {
+                    for face in 0..6 {
+                        gl::GetTexImage(
+                            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                            level as i32,
+                            gl_format,
+                            gl_type,
+                            pointer.add(face * layer_size) as *mut c_void,
+                        );
+                    }
+                }
+                _ => gl::GetTexImage(
+                    self.get_target(),
+                    level as i32,
+                    gl_format,
+                    gl_type,
+                    pointer as *mut c_void,
+                ),
+            }
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
         }
+
+        return PixelBuffer {
+            width,
+            height: height * layers,
+            format: self.format,
+            data,
+        };
+    }
+
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType) {
+        let (width, height) = self.get_size(0);
+        let gl_format = Self::translate_format(self.format);
+        let gl_type = OglVertexArray::translate_type(data_type);
+        unsafe {
+            state_cache::bind_texture(None, self.get_target(), self.name.0);
+            match self.dimension {
+                TextureDimension::Texture1D => gl::TexSubImage1D(
+                    gl::TEXTURE_1D,
+                    0,
+                    0,
+                    width as i32,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Texture2D => gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Cube => gl::TexSubImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+                    0,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+                TextureDimension::Texture2DArray
+                | TextureDimension::Texture3D
+                | TextureDimension::CubeArray => gl::TexSubImage3D(
+                    self.get_target(),
+                    0,
+                    0,
+                    0,
+                    layer as i32,
+                    width as i32,
+                    height as i32,
+                    1,
+                    gl_format,
+                    gl_type,
+                    data as *const c_void,
+                ),
+            }
+        }
+        frame_stats::record_texture_upload(
+            (width * height * self.format.get_channel_count()) as usize
+                * OglVertexArray::get_type_size(data_type),
+        );
+    }
+
+    fn is_ready(&self) -> bool {
+        return true;
+    }
+
+    fn set_label(&self, label: &str) {
+        debug::set_label(gl::TEXTURE, self.name.0, label);
     }
 }
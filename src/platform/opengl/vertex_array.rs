@@ -2,7 +2,12 @@ use std::{ffi::c_void, mem::size_of, ptr};
 
 use gl::types;
 
-use crate::render::buffer::{Bindable, Buffer, BufferDataType, BufferType, VertexArray};
+use crate::render::buffer::{
+    Bindable, Buffer, BufferDataType, BufferType, DrawArraysIndirectCommand,
+    DrawElementsIndirectCommand, DrawRange, VertexArray,
+};
+
+use super::{context, debug, frame_stats, pipeline, state_cache};
 
 // TODO: Check if buffers are ogl
 pub struct OglVertexArray {
@@ -13,13 +18,20 @@ pub struct OglVertexArray {
     element_count: usize,
 }
 
+// The buffers are deleted after the array, they are dropped with it.
+impl Drop for OglVertexArray {
+    fn drop(&mut self) {
+        state_cache::delete_vertex_array(self.id);
+    }
+}
+
 impl Bindable for OglVertexArray {
     fn bind(&self) {
-        unsafe { gl::BindVertexArray(self.id) }
+        state_cache::bind_vertex_array(self.id);
     }
 
     fn unbind(&self) {
-        unsafe { gl::BindVertexArray(0) }
+        state_cache::bind_vertex_array(0);
     }
 }
 
@@ -28,29 +40,146 @@ impl VertexArray for OglVertexArray {
         self.bind();
         match &self.index_buffer {
             Some(ib) => {
-                let data_type = match ib.get_layout().first() {
-                    Some(node) => Self::translate_type(node.data_type),
-                    None => gl::UNSIGNED_INT,
-                };
+                let (data_type, _) = self.get_index_type();
                 unsafe {
                     gl::DrawElements(
-                        gl::TRIANGLES,
+                        pipeline::get_primitive_mode(),
                         ib.get_element_count() as i32,
                         data_type,
                         ptr::null(),
                     )
                 }
+                frame_stats::record_draw(ib.get_element_count() as u64);
+            }
+            None => {
+                unsafe {
+                    gl::DrawArrays(pipeline::get_primitive_mode(), 0, self.element_count as i32);
+                }
+                frame_stats::record_draw(self.element_count as u64);
+            }
+        }
+    }
+
+    // Base vertices need OpenGL 3.2 or OpenGL ES 3.2.
+    fn draw_range(&self, range: DrawRange) {
+        self.bind();
+        let mode = pipeline::get_primitive_mode();
+        match &self.index_buffer {
+            Some(_) => {
+                let (data_type, size) = self.get_index_type();
+                let offset = (range.first as usize * size) as *const c_void;
+                if range.base_vertex == 0 {
+                    unsafe {
+                        gl::DrawElements(mode, range.count as i32, data_type, offset);
+                    }
+                } else if context::get().base_vertex {
+                    unsafe {
+                        gl::DrawElementsBaseVertex(mode, range.count as i32, data_type, offset, range.base_vertex);
+                    }
+                } else {
+                    log::warn!(target: debug::LOG_TARGET, "Base vertices are not supported by this OpenGL context");
+                    return;
+                }
             }
             None => unsafe {
-                gl::DrawArrays(gl::TRIANGLES, 0, self.element_count as i32);
+                gl::DrawArrays(mode, range.first as i32, range.count as i32);
             },
         }
+        frame_stats::record_draw(range.count as u64);
+    }
+
+    // OpenGL ES has no multi draws, the ranges are drawn one by one there.
+    fn multi_draw(&self, ranges: &[DrawRange]) {
+        let context = context::get();
+        if context.es || (self.index_buffer.is_some() && !context.base_vertex) {
+            for range in ranges {
+                self.draw_range(*range);
+            }
+            return;
+        }
+
+        self.bind();
+        let mode = pipeline::get_primitive_mode();
+        let counts: Vec<i32> = ranges.iter().map(|range| range.count as i32).collect();
+        match &self.index_buffer {
+            Some(_) => {
+                let (data_type, size) = self.get_index_type();
+                let offsets: Vec<*const c_void> = ranges
+                    .iter()
+                    .map(|range| (range.first as usize * size) as *const c_void)
+                    .collect();
+                let base_vertices: Vec<i32> = ranges.iter().map(|range| range.base_vertex).collect();
+                unsafe {
+                    gl::MultiDrawElementsBaseVertex(
+                        mode,
+                        counts.as_ptr(),
+                        data_type,
+                        offsets.as_ptr(),
+                        ranges.len() as i32,
+                        base_vertices.as_ptr(),
+                    );
+                }
+            }
+            None => {
+                let firsts: Vec<i32> = ranges.iter().map(|range| range.first as i32).collect();
+                unsafe {
+                    gl::MultiDrawArrays(mode, firsts.as_ptr(), counts.as_ptr(), ranges.len() as i32);
+                }
+            }
+        }
+        frame_stats::record_multi_draw(ranges.iter().map(|range| range.count as u64));
+    }
+
+    // Needs OpenGL 4.0 or OpenGL ES 3.1, without multi draw indirect the
+    // commands are issued one by one. Every command counts as a draw, their
+    // vertex counts only live on the GPU so none are recorded.
+    fn draw_indirect(&self, buffer: &Box<dyn Buffer>, offset: usize, count: usize) {
+        let context = context::get();
+        if !context.draw_indirect {
+            log::warn!(target: debug::LOG_TARGET, "Indirect draws are not supported by this OpenGL context");
+            return;
+        }
+
+        self.bind();
+        state_cache::bind_buffer(gl::DRAW_INDIRECT_BUFFER, buffer.get_id());
+        let mode = pipeline::get_primitive_mode();
+        match &self.index_buffer {
+            Some(_) => {
+                let (data_type, _) = self.get_index_type();
+                let stride = size_of::<DrawElementsIndirectCommand>();
+                unsafe {
+                    if context.multi_draw_indirect {
+                        gl::MultiDrawElementsIndirect(mode, data_type, offset as *const c_void, count as i32, 0);
+                    } else {
+                        for index in 0..count {
+                            gl::DrawElementsIndirect(mode, data_type, (offset + index * stride) as *const c_void);
+                        }
+                    }
+                }
+            }
+            None => {
+                let stride = size_of::<DrawArraysIndirectCommand>();
+                unsafe {
+                    if context.multi_draw_indirect {
+                        gl::MultiDrawArraysIndirect(mode, offset as *const c_void, count as i32, 0);
+                    } else {
+                        for index in 0..count {
+                            gl::DrawArraysIndirect(mode, (offset + index * stride) as *const c_void);
+                        }
+                    }
+                }
+            }
+        }
+        for _ in 0..count {
+            frame_stats::record_draw(0);
+        }
     }
 
     fn add_buffer(&mut self, buffer: Box<dyn Buffer>, buffer_type: BufferType) {
         self.bind();
         buffer.bind();
         let mut pointer = 0;
+        let direct_state_access = context::get().direct_state_access;
         match buffer_type {
             BufferType::Vertex => {
                 let layout = buffer.get_layout();
@@ -61,7 +190,11 @@ impl VertexArray for OglVertexArray {
                 self.element_count += buffer.get_element_count();
                 for node in layout {
                     unsafe {
-                        gl::EnableVertexArrayAttrib(self.id, self.attribs);
+                        if direct_state_access {
+                            gl::EnableVertexArrayAttrib(self.id, self.attribs);
+                        } else {
+                            gl::EnableVertexAttribArray(self.attribs);
+                        }
                         gl::VertexAttribPointer(
                             self.attribs,
                             node.elements as i32,
@@ -79,6 +212,12 @@ impl VertexArray for OglVertexArray {
             BufferType::Index => {
                 self.index_buffer = Some(buffer);
             }
+            BufferType::Indirect => {
+                log::warn!(
+                    target: debug::LOG_TARGET,
+                    "Indirect buffers are passed to draw_indirect, not added to vertex arrays"
+                );
+            }
         }
     }
 
@@ -102,14 +241,28 @@ impl OglVertexArray {
             index_buffer: None,
             element_count: 0,
         };
+        // Without DSA the name only becomes an object once it's bound, which
+        // the bind below does.
         unsafe {
-            gl::CreateVertexArrays(1, &mut array.id);
-            gl::BindVertexArray(array.id);
+            if context::get().direct_state_access {
+                gl::CreateVertexArrays(1, &mut array.id);
+            } else {
+                gl::GenVertexArrays(1, &mut array.id);
+            }
         }
+        state_cache::bind_vertex_array(array.id);
 
         return array;
     }
 
+    fn get_index_type(&self) -> (types::GLenum, usize) {
+        let data_type = self
+            .index_buffer
+            .as_ref()
+            .map_or(BufferDataType::U32, |buffer| buffer.get_index_type());
+        return (Self::translate_type(data_type), Self::get_type_size(data_type));
+    }
+
     pub fn translate_type(data_type: BufferDataType) -> types::GLenum {
         match data_type {
             BufferDataType::F32 => gl::FLOAT,
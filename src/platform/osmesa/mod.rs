@@ -0,0 +1,131 @@
+extern crate osmesa_sys;
+
+use std::{
+    ffi::{c_void, CString},
+    ptr,
+};
+
+use osmesa_sys::{
+    OSMesaContext, OSMesaCreateContextAttribs, OSMesaDestroyContext, OSMesaGetProcAddress,
+    OSMesaMakeCurrent, OsMesa, OSMESA_CONTEXT_MAJOR_VERSION, OSMESA_CONTEXT_MINOR_VERSION,
+    OSMESA_CORE_PROFILE, OSMESA_DEPTH_BITS, OSMESA_FORMAT, OSMESA_PROFILE, OSMESA_RGBA,
+    OSMESA_STENCIL_BITS,
+};
+
+use crate::{
+    event::Event,
+    render::window::Window,
+};
+
+// GL_UNSIGNED_BYTE, the window layer doesn't link against the gl bindings.
+const UNSIGNED_BYTE: u32 = 0x1401;
+
+// Renders into a client side RGBA buffer through Mesa's off-screen
+// interface, so no display server is needed. The buffer acts as the
+// default framebuffer of the context.
+pub struct OsMesaWindow {
+    context: OSMesaContext,
+    buffer: Vec<u8>,
+    width: u32,
+    height: u32,
+    closed: bool,
+}
+
+impl OsMesaWindow {
+    pub fn new(width: u32, height: u32) -> Box<dyn Window> {
+        if let Err(error) = OsMesa::try_loading() {
+            panic!("Failed to load OSMesa: {:?}", error);
+        }
+
+        let attributes = [
+            OSMESA_FORMAT,
+            OSMESA_RGBA as i32,
+            OSMESA_DEPTH_BITS,
+            24,
+            OSMESA_STENCIL_BITS,
+            8,
+            OSMESA_PROFILE,
+            OSMESA_CORE_PROFILE,
+            OSMESA_CONTEXT_MAJOR_VERSION,
+            4,
+            OSMESA_CONTEXT_MINOR_VERSION,
+            5,
+            0,
+        ];
+
+        let context = unsafe { OSMesaCreateContextAttribs(attributes.as_ptr(), ptr::null_mut()) };
+        if context.is_null() {
+            panic!("Failed to create OSMesa context");
+        }
+
+        let mut window = Box::new(OsMesaWindow {
+            context,
+            buffer: vec![0; (width * height * 4) as usize],
+            width,
+            height,
+            closed: false,
+        });
+
+        let current = unsafe {
+            OSMesaMakeCurrent(
+                window.context,
+                window.buffer.as_mut_ptr() as *mut c_void,
+                UNSIGNED_BYTE,
+                width as i32,
+                height as i32,
+            )
+        };
+        if current == 0 {
+            panic!("Failed to make OSMesa context current");
+        }
+
+        return window;
+    }
+}
+
+// Textures, buffers and framebuffers delete their objects when dropped, so
+// the context stays alive until the window goes, after the API and its
+// resources.
+impl Drop for OsMesaWindow {
+    fn drop(&mut self) {
+        unsafe {
+            OSMesaDestroyContext(self.context);
+        }
+    }
+}
+
+impl Window for OsMesaWindow {
+    fn close(&mut self) {
+        self.closed = true;
+    }
+
+    fn get_size(&self) -> (f32, f32) {
+        return (self.width as f32, self.height as f32);
+    }
+
+    fn get_framebuffer_size(&self) -> (u32, u32) {
+        return (self.width, self.height);
+    }
+
+    fn get_events(&mut self) -> Vec<Event> {
+        return Vec::new();
+    }
+
+    fn get_proc_addr(&mut self, procname: &str) -> *const c_void {
+        let name = CString::new(procname).unwrap();
+        match unsafe { OSMesaGetProcAddress(name.as_ptr()) } {
+            Some(function) => function as *const c_void,
+            None => ptr::null(),
+        }
+    }
+
+    fn should_close(&self) -> bool {
+        return self.closed;
+    }
+
+    fn update(&mut self) {}
+
+    fn set_title(&mut self, _title: &str) {}
+
+    fn toggle_mouse_grab(&mut self) {}
+}
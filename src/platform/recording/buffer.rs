@@ -0,0 +1,300 @@
+use std::{
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use crate::render::{
+    buffer::{
+        read_indirect_commands, Bindable, Buffer, BufferLayoutNode, BufferType, BufferUsage,
+        DrawRange, VertexArray,
+    },
+    pipeline::PrimitiveTopology,
+    recording::{Command, DrawCommand},
+};
+
+use super::{record, with_context, Context, CONTEXT};
+
+pub struct RecordingBuffer {
+    id: u32,
+    buffer_layout: Vec<BufferLayoutNode>,
+    element_count: usize,
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl RecordingBuffer {
+    pub fn new<T>(
+        data: &mut Vec<T>,
+        buffer_type: BufferType,
+        buffer_layout: Vec<BufferLayoutNode>,
+        buffer_usage: BufferUsage,
+        size: Option<usize>,
+    ) -> RecordingBuffer {
+        let length = data.len() * size_of::<T>();
+        let mut bytes = vec![0; size.unwrap_or(length).max(length)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, bytes.as_mut_ptr(), length);
+        }
+
+        let element_count =
+            Self::calculate_element_count(data.len(), buffer_layout.clone(), buffer_type);
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.stats.buffer_bytes_uploaded += length as u64;
+            context.record(Command::CreateBuffer {
+                id,
+                buffer_type,
+                usage: buffer_usage,
+                data: bytes.clone(),
+            });
+            id
+        });
+
+        let data = Arc::new(Mutex::new(bytes));
+        with_context(|context| context.buffers.insert(id, data.clone()));
+
+        return RecordingBuffer {
+            id,
+            buffer_layout,
+            element_count,
+            data,
+        };
+    }
+
+    fn calculate_element_count(
+        data_len: usize,
+        layout: Vec<BufferLayoutNode>,
+        buffer_type: BufferType,
+    ) -> usize {
+        let layout_size = BufferLayoutNode::get_layout_size(layout);
+        if buffer_type == BufferType::Vertex {
+            return data_len / layout_size;
+        } else {
+            return data_len;
+        }
+    }
+}
+
+impl Drop for RecordingBuffer {
+    fn drop(&mut self) {
+        let _ = CONTEXT.try_with(|context| {
+            if let Ok(mut context) = context.try_borrow_mut() {
+                context.buffers.remove(&self.id);
+            }
+        });
+    }
+}
+
+impl Bindable for RecordingBuffer {
+    fn bind(&self) {
+        record(Command::BindBuffer(self.id));
+    }
+
+    fn unbind(&self) {
+        record(Command::UnbindBuffer(self.id));
+    }
+}
+
+impl Buffer for RecordingBuffer {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn get_layout(&self) -> &Vec<BufferLayoutNode> {
+        return &self.buffer_layout;
+    }
+
+    fn get_element_count(&self) -> usize {
+        return self.element_count;
+    }
+
+    // Offset is in bytes, like glBufferSubData.
+    fn add_sub_data(&mut self, data: Vec<f32>, offset: usize) {
+        let length = data.len() * size_of::<f32>();
+        {
+            let mut bytes = self.data.lock().unwrap();
+            if bytes.len() < offset + length {
+                bytes.resize(offset + length, 0);
+            }
+            for (index, value) in data.iter().enumerate() {
+                let start = offset + index * size_of::<f32>();
+                bytes[start..start + size_of::<f32>()].copy_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        self.element_count = if offset == 0 {
+            Self::calculate_element_count(data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        } else {
+            Self::calculate_element_count(offset + data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        };
+
+        with_context(|context| {
+            context.stats.buffer_bytes_uploaded += length as u64;
+            context.record(Command::UpdateBuffer {
+                id: self.id,
+                offset,
+                data,
+            });
+        });
+    }
+
+    fn set_label(&self, label: &str) {
+        record(Command::SetLabel {
+            id: self.id,
+            label: String::from(label),
+        });
+    }
+}
+
+pub struct RecordingVertexArray {
+    id: u32,
+    vertex_buffers: Vec<Box<dyn Buffer>>,
+    index_buffer: Option<Box<dyn Buffer>>,
+    element_count: usize,
+}
+
+impl RecordingVertexArray {
+    pub fn new() -> RecordingVertexArray {
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.record(Command::CreateVertexArray(id));
+            id
+        });
+
+        return RecordingVertexArray {
+            id,
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            element_count: 0,
+        };
+    }
+}
+
+impl RecordingVertexArray {
+    // Captures the bound state for a draw of the range.
+    fn capture(&self, context: &mut Context, range: DrawRange, instances: u32) -> DrawCommand {
+        let vertex_buffers = self
+            .vertex_buffers
+            .iter()
+            .map(|buffer| match context.buffers.get(&buffer.get_id()) {
+                Some(data) => data.lock().unwrap().clone(),
+                None => panic!("Buffer doesn't belong to the recording renderer"),
+            })
+            .collect();
+
+        return DrawCommand {
+            vertex_array: self.id,
+            shader: context.shader,
+            pipeline: context.pipeline.map(|(id, _)| id),
+            topology: context.pipeline.map_or(PrimitiveTopology::Triangles, |(_, topology)| topology),
+            textures: context.textures.iter().map(|(slot, id)| (*slot, *id)).collect(),
+            target: context.target,
+            first: range.first,
+            count: range.count as usize,
+            base_vertex: range.base_vertex,
+            instances,
+            indexed: self.index_buffer.is_some(),
+            vertex_buffers,
+        };
+    }
+
+    // Multi and indirect draws are a single call however many draws they
+    // hold.
+    fn count_draw(context: &mut Context, draws: &[&DrawCommand]) {
+        let vertices: u64 = draws.iter().map(|draw| draw.count as u64 * draw.instances as u64).sum();
+        context.stats.draw_calls += 1;
+        context.stats.vertices += vertices;
+        context.stats.triangles += vertices / 3;
+    }
+}
+
+impl Bindable for RecordingVertexArray {
+    fn bind(&self) {
+        record(Command::BindVertexArray(self.id));
+    }
+
+    fn unbind(&self) {
+        record(Command::UnbindVertexArray(self.id));
+    }
+}
+
+impl VertexArray for RecordingVertexArray {
+    fn draw(&self) {
+        let count = match &self.index_buffer {
+            Some(buffer) => buffer.get_element_count(),
+            None => self.element_count,
+        };
+        let range = DrawRange {
+            first: 0,
+            count: count as u32,
+            base_vertex: 0,
+        };
+
+        self.draw_range(range);
+    }
+
+    fn draw_range(&self, range: DrawRange) {
+        with_context(|context| {
+            let draw = self.capture(context, range, 1);
+            Self::count_draw(context, &[&draw]);
+            context.record(Command::Draw(draw));
+        });
+    }
+
+    fn multi_draw(&self, ranges: &[DrawRange]) {
+        with_context(|context| {
+            let draws: Vec<_> = ranges.iter().map(|range| self.capture(context, *range, 1)).collect();
+            Self::count_draw(context, &draws.iter().collect::<Vec<_>>());
+            context.record(Command::MultiDraw(draws));
+        });
+    }
+
+    // The commands are read back when the draw is recorded, so the log
+    // shows what would have been drawn.
+    fn draw_indirect(&self, buffer: &Box<dyn Buffer>, offset: usize, count: usize) {
+        with_context(|context| {
+            let bytes = match context.buffers.get(&buffer.get_id()) {
+                Some(data) => data.lock().unwrap().clone(),
+                None => panic!("Buffer doesn't belong to the recording renderer"),
+            };
+            let draws: Vec<_> = read_indirect_commands(&bytes, offset, count, self.index_buffer.is_some())
+                .into_iter()
+                .map(|(range, instances)| self.capture(context, range, instances))
+                .collect();
+            Self::count_draw(context, &draws.iter().collect::<Vec<_>>());
+            context.record(Command::DrawIndirect {
+                buffer: buffer.get_id(),
+                offset,
+                draws,
+            });
+        });
+    }
+
+    fn add_buffer(&mut self, buffer: Box<dyn Buffer>, buffer_type: BufferType) {
+        record(Command::AddBuffer {
+            vertex_array: self.id,
+            buffer: buffer.get_id(),
+            buffer_type,
+        });
+
+        match buffer_type {
+            BufferType::Vertex => {
+                self.element_count += buffer.get_element_count();
+                self.vertex_buffers.push(buffer);
+            }
+            BufferType::Index => self.index_buffer = Some(buffer),
+            BufferType::Indirect => {
+                log::warn!("Indirect buffers are passed to draw_indirect, not added to vertex arrays");
+            }
+        }
+    }
+
+    fn get_buffers(&self) -> &Vec<Box<dyn Buffer>> {
+        return &self.vertex_buffers;
+    }
+
+    fn add_buffer_sub_data(&mut self, data: Vec<f32>, offset: usize, buffer_index: usize) {
+        let old_buffer_size = self.vertex_buffers[buffer_index].get_element_count();
+        self.vertex_buffers[buffer_index].add_sub_data(data, offset);
+        self.element_count += self.vertex_buffers[buffer_index].get_element_count() - old_buffer_size;
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::{Arc, Mutex};
+
+use crate::render::{
+    buffer::BufferDataType,
+    framebuffer::{
+        DepthStencilFormat, Framebuffer, FramebufferDescriptor, FramebufferError, FramebufferSize,
+    },
+    recording::Command,
+    texture::{Texture, TextureFormat},
+};
+
+use super::{record, texture::RecordingTexture, with_context};
+
+// Matches the minimum OpenGL guarantees.
+pub const MAX_COLOR_ATTACHMENTS: usize = 8;
+
+pub struct FramebufferState {
+    id: u32,
+    width: u32,
+    height: u32,
+    samples: u32,
+    color_attachments: Vec<(TextureFormat, BufferDataType)>,
+    depth_stencil: DepthStencilFormat,
+    scale: Option<f32>,
+
+    color_textures: Vec<RecordingTexture>,
+    depth_texture: Option<RecordingTexture>,
+}
+
+impl FramebufferState {
+    // Attachments are recreated on every resize like on OpenGL.
+    fn allocate(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::EmptySize);
+        }
+
+        self.color_textures = self
+            .color_attachments
+            .iter()
+            .map(|(format, data_type)| {
+                RecordingTexture::new(width, height, std::ptr::null(), *format, *data_type)
+            })
+            .collect();
+        self.depth_texture = match self.depth_stencil {
+            DepthStencilFormat::None => None,
+            DepthStencilFormat::Depth24Stencil8 => Some(RecordingTexture::new(
+                width,
+                height,
+                std::ptr::null(),
+                TextureFormat::DepthStencil,
+                BufferDataType::U32,
+            )),
+            DepthStencilFormat::Depth32F => Some(RecordingTexture::new(
+                width,
+                height,
+                std::ptr::null(),
+                TextureFormat::Depth,
+                BufferDataType::F32,
+            )),
+            DepthStencilFormat::Depth24 => Some(RecordingTexture::new(
+                width,
+                height,
+                std::ptr::null(),
+                TextureFormat::Depth,
+                BufferDataType::U32,
+            )),
+        };
+
+        self.width = width;
+        self.height = height;
+
+        return Ok(());
+    }
+}
+
+pub struct RecordingFramebuffer {
+    state: Arc<Mutex<FramebufferState>>,
+}
+
+impl RecordingFramebuffer {
+    pub fn new(descriptor: FramebufferDescriptor) -> Result<RecordingFramebuffer, FramebufferError> {
+        let (width, height, scale) = match descriptor.size {
+            FramebufferSize::Fixed(width, height) => (width, height, None),
+            FramebufferSize::Window(scale) => {
+                let size = with_context(|context| (context.width, context.height));
+                let (width, height) = Self::scale_window_size(size, scale);
+                (width, height, Some(scale))
+            }
+        };
+
+        if descriptor.color_attachments.is_empty()
+            && descriptor.depth_stencil == DepthStencilFormat::None
+        {
+            return Err(FramebufferError::NoAttachments);
+        }
+
+        if descriptor.color_attachments.len() > MAX_COLOR_ATTACHMENTS {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: descriptor.color_attachments.len(),
+                max: MAX_COLOR_ATTACHMENTS,
+            });
+        }
+
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::EmptySize);
+        }
+
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.record(Command::CreateFramebuffer { id, width, height });
+            id
+        });
+
+        let mut state = FramebufferState {
+            id,
+            width: 0,
+            height: 0,
+            samples: descriptor.samples,
+            color_attachments: descriptor
+                .color_attachments
+                .iter()
+                .map(|attachment| (attachment.format, attachment.data_type))
+                .collect(),
+            depth_stencil: descriptor.depth_stencil,
+            scale,
+            color_textures: Vec::new(),
+            depth_texture: None,
+        };
+        state.allocate(width, height)?;
+
+        let state = Arc::new(Mutex::new(state));
+        if scale.is_some() {
+            with_context(|context| context.window_framebuffers.push(Arc::downgrade(&state)));
+        }
+
+        return Ok(RecordingFramebuffer { state });
+    }
+
+    fn scale_window_size(size: (u32, u32), scale: f32) -> (u32, u32) {
+        return (
+            ((size.0 as f32 * scale) as u32).max(1),
+            ((size.1 as f32 * scale) as u32).max(1),
+        );
+    }
+}
+
+impl Framebuffer for RecordingFramebuffer {
+    fn get_id(&self) -> u32 {
+        return self.state.lock().unwrap().id;
+    }
+
+    fn bind(&self) {
+        let id = self.get_id();
+        with_context(|context| {
+            context.target = Some(id);
+            context.record(Command::BindFramebuffer(id));
+        });
+    }
+
+    fn unbind(&self) {
+        let id = self.get_id();
+        with_context(|context| {
+            context.target = None;
+            context.record(Command::UnbindFramebuffer(id));
+        });
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        let state = self.state.lock().unwrap();
+        return (state.width, state.height);
+    }
+
+    fn get_samples(&self) -> u32 {
+        return self.state.lock().unwrap().samples;
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        let mut state = self.state.lock().unwrap();
+        record(Command::ResizeFramebuffer {
+            id: state.id,
+            width,
+            height,
+        });
+
+        return state.allocate(width, height);
+    }
+
+    fn get_color_attachment_count(&self) -> usize {
+        return self.state.lock().unwrap().color_textures.len();
+    }
+
+    fn get_color_attachment(&self, index: usize) -> Option<Box<dyn Texture>> {
+        let state = self.state.lock().unwrap();
+        return state
+            .color_textures
+            .get(index)
+            .map(|texture| Box::new(texture.clone()) as Box<dyn Texture>);
+    }
+
+    fn get_depth_attachment(&self) -> Option<Box<dyn Texture>> {
+        return self
+            .state
+            .lock()
+            .unwrap()
+            .depth_texture
+            .clone()
+            .map(|texture| Box::new(texture) as Box<dyn Texture>);
+    }
+
+    fn resolve(&self, target: Option<&Box<dyn Framebuffer>>) -> Result<(), FramebufferError> {
+        record(Command::ResolveFramebuffer {
+            id: self.get_id(),
+            target: target.map(|framebuffer| framebuffer.get_id()),
+        });
+
+        return Ok(());
+    }
+}
+
+pub fn resize_window_framebuffers(width: u32, height: u32) {
+    let framebuffers = with_context(|context| {
+        context
+            .window_framebuffers
+            .retain(|framebuffer| framebuffer.strong_count() > 0);
+        context.window_framebuffers.clone()
+    });
+
+    for framebuffer in framebuffers.iter().filter_map(|framebuffer| framebuffer.upgrade()) {
+        let mut state = framebuffer.lock().unwrap();
+        let (width, height) =
+            RecordingFramebuffer::scale_window_size((width, height), state.scale.unwrap_or(1.0));
+        record(Command::ResizeFramebuffer {
+            id: state.id,
+            width,
+            height,
+        });
+        if let Err(error) = state.allocate(width, height) {
+            log::warn!("Failed to resize framebuffer with error\n{}", error);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+pub mod buffer;
+pub mod framebuffer;
+pub mod pipeline;
+pub mod shader;
+pub mod texture;
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::render::{
+    buffer::BufferDataType,
+    capabilities::Capabilities,
+    debug::DebugSeverity,
+    framebuffer::Framebuffer,
+    pipeline::PrimitiveTopology,
+    recording::Command,
+    render_api::RenderAPI,
+    render_queue::RenderQueue,
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    stats::{FrameStats, StateCacheStats},
+    texture::{PixelBuffer, PixelData, TextureFormat},
+    window::{FramebufferResize, Window},
+};
+
+use self::framebuffer::FramebufferState;
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+pub fn with_context<T>(function: impl FnOnce(&mut Context) -> T) -> T {
+    return CONTEXT.with(|context| function(&mut context.borrow_mut()));
+}
+
+#[derive(Default)]
+pub struct Context {
+    next_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub commands: Vec<Command>,
+
+    // Bound state, captured by every draw.
+    pub shader: Option<u32>,
+    pub pipeline: Option<(u32, PrimitiveTopology)>,
+    pub textures: BTreeMap<u32, u32>,
+    pub target: Option<u32>,
+
+    pub buffers: HashMap<u32, Arc<Mutex<Vec<u8>>>>,
+    pub window_framebuffers: Vec<Weak<Mutex<FramebufferState>>>,
+
+    pub stats: FrameStats,
+    last_stats: FrameStats,
+}
+
+impl Context {
+    pub fn generate_id(&mut self) -> u32 {
+        self.next_id += 1;
+        return self.next_id;
+    }
+
+    pub fn record(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+}
+
+pub fn record(command: Command) {
+    with_context(|context| context.record(command));
+}
+
+pub struct RecordingAPI {
+    initalized: bool,
+    framebuffer_resize: FramebufferResize,
+}
+
+impl RenderAPI for RecordingAPI {
+    // Starts a new log, so every API created in a test sees only its own
+    // calls.
+    fn init(&self, window: &mut Box<dyn Window>) {
+        let (width, height) = window.get_framebuffer_size();
+        with_context(|context| {
+            *context = Context::default();
+            context.width = width;
+            context.height = height;
+        });
+    }
+
+    fn clear(&self) {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        record(Command::Clear);
+    }
+
+    fn begin<'a>(&self) -> RenderQueue<'a> {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        if let Some((width, height)) = self.framebuffer_resize.take() {
+            self.resize(width, height);
+        }
+
+        with_context(|context| {
+            context.stats = FrameStats::default();
+            context.record(Command::Begin);
+        });
+        self.clear();
+
+        return RenderQueue::new();
+    }
+
+    fn end(&self, mut queue: RenderQueue) {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        queue.execute();
+        with_context(|context| {
+            context.record(Command::End);
+            context.last_stats = std::mem::take(&mut context.stats);
+        });
+    }
+
+    fn set_render_target(&self, target: Option<&Box<dyn Framebuffer>>) {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        let id = target.map(|framebuffer| framebuffer.get_id());
+        with_context(|context| {
+            context.target = id;
+            context.record(Command::SetRenderTarget(id));
+        });
+    }
+
+    // Nothing is drawn, the frame is always transparent black.
+    fn capture_frame(&self) -> PixelBuffer {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        let (width, height) = with_context(|context| (context.width, context.height));
+        return PixelBuffer {
+            width,
+            height,
+            format: TextureFormat::RGBA,
+            data: PixelData::new((width * height * 4) as usize, BufferDataType::U8),
+        };
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        with_context(|context| {
+            context.width = width;
+            context.height = height;
+            context.record(Command::Resize { width, height });
+        });
+        framebuffer::resize_window_framebuffers(width, height);
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        record(Command::SetViewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn set_clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        record(Command::SetClearColor([red, green, blue, alpha]));
+    }
+
+    fn set_clear_depth(&self, depth: f32) {
+        record(Command::SetClearDepth(depth));
+    }
+
+    fn set_clear_stencil(&self, stencil: i32) {
+        record(Command::SetClearStencil(stencil));
+    }
+
+    fn set_depth_state(&self, state: DepthState) {
+        record(Command::SetDepthState(state));
+    }
+
+    fn set_blend_state(&self, target: Option<u32>, state: BlendState) {
+        record(Command::SetBlendState { target, state });
+    }
+
+    fn set_blend_constant(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        record(Command::SetBlendConstant([red, green, blue, alpha]));
+    }
+
+    fn set_color_mask(&self, target: Option<u32>, mask: ColorMask) {
+        record(Command::SetColorMask { target, mask });
+    }
+
+    fn set_cull_mode(&self, mode: CullMode) {
+        record(Command::SetCullMode(mode));
+    }
+
+    fn set_front_face(&self, face: FrontFace) {
+        record(Command::SetFrontFace(face));
+    }
+
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        record(Command::SetPolygonMode(mode));
+    }
+
+    fn set_scissor(&self, rect: Option<ScissorRect>) {
+        record(Command::SetScissor(rect));
+    }
+
+    fn set_stencil_state(&self, state: StencilState) {
+        record(Command::SetStencilState(state));
+    }
+
+    // Reports the OpenGL minimums so tests see the limits any GPU has.
+    fn capabilities(&self) -> Capabilities {
+        if !self.initalized {
+            panic!("Recording API was not initalized!");
+        }
+
+        return Capabilities {
+            vendor: String::from("librender"),
+            renderer: String::from("Recording"),
+            max_color_attachments: framebuffer::MAX_COLOR_ATTACHMENTS as u32,
+            ..Capabilities::minimum()
+        };
+    }
+
+    // Every call is recorded, nothing is skipped.
+    fn get_state_cache_stats(&self) -> StateCacheStats {
+        return StateCacheStats::default();
+    }
+
+    fn reset_state_cache_stats(&self) {}
+
+    fn get_frame_stats(&self) -> FrameStats {
+        return with_context(|context| context.last_stats.clone());
+    }
+
+    // Statistics are taken at every end.
+    fn end_frame(&self) {}
+
+    fn set_debug_severity(&self, _severity: DebugSeverity) {}
+
+    fn begin_scope(&self, name: &str) {
+        record(Command::BeginScope(String::from(name)));
+    }
+
+    fn end_scope(&self) {
+        record(Command::EndScope);
+    }
+
+    fn enable_align_restrictions(&self) {
+        record(Command::SetAlignRestrictions(true));
+    }
+
+    fn disable_align_restrictions(&self) {
+        record(Command::SetAlignRestrictions(false));
+    }
+}
+
+impl RecordingAPI {
+    pub fn new(window: &mut Box<dyn Window>) -> RecordingAPI {
+        let api = RecordingAPI {
+            initalized: true,
+            framebuffer_resize: window.get_framebuffer_resize(),
+        };
+        api.init(window);
+
+        return api;
+    }
+}
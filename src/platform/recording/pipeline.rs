@@ -0,0 +1,64 @@
+use crate::render::{
+    buffer::Bindable,
+    pipeline::{Pipeline, PipelineDescriptor},
+    recording::Command,
+    shader::Shader,
+};
+
+use super::with_context;
+
+// Binding records the pipeline instead of the state it sets, draws record
+// which pipeline they were issued with.
+pub struct RecordingPipeline {
+    id: u32,
+    shader: Box<dyn Shader>,
+    descriptor: PipelineDescriptor,
+}
+
+impl RecordingPipeline {
+    pub fn new(shader: Box<dyn Shader>, descriptor: PipelineDescriptor) -> RecordingPipeline {
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.record(Command::CreatePipeline {
+                id,
+                shader: shader.get_id(),
+                descriptor: descriptor.clone(),
+            });
+            id
+        });
+
+        return RecordingPipeline {
+            id,
+            shader,
+            descriptor,
+        };
+    }
+}
+
+impl Pipeline for RecordingPipeline {
+    fn get_shader(&self) -> &Box<dyn Shader> {
+        return &self.shader;
+    }
+
+    fn get_descriptor(&self) -> &PipelineDescriptor {
+        return &self.descriptor;
+    }
+}
+
+impl Bindable for RecordingPipeline {
+    fn bind(&self) {
+        self.shader.bind();
+        with_context(|context| {
+            context.pipeline = Some((self.id, self.descriptor.topology));
+            context.record(Command::BindPipeline(self.id));
+        });
+    }
+
+    fn unbind(&self) {
+        self.shader.unbind();
+        with_context(|context| {
+            context.pipeline = None;
+            context.record(Command::UnbindPipeline(self.id));
+        });
+    }
+}
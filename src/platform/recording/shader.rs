@@ -0,0 +1,108 @@
+use nalgebra::{Matrix4, Vector3};
+
+use crate::render::{
+    buffer::Bindable,
+    recording::Command,
+    shader::{Shader, ShaderAttribute, UniformValue},
+};
+
+use super::{record, with_context};
+
+// Files are recorded by path and never read, so tests don't need them.
+pub struct RecordingShader {
+    id: u32,
+}
+
+impl RecordingShader {
+    pub fn new() -> RecordingShader {
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.record(Command::CreateShader(id));
+            id
+        });
+
+        return RecordingShader { id };
+    }
+
+    pub fn new_from_file(file_path: &str) -> RecordingShader {
+        let mut shader = RecordingShader::new();
+        shader.load_from_file(file_path);
+
+        return shader;
+    }
+
+    pub fn new_from_source(source: &str) -> RecordingShader {
+        let mut shader = RecordingShader::new();
+        shader.load_from_source(source);
+
+        return shader;
+    }
+
+    fn set_uniform(&self, name: &str, value: UniformValue) {
+        record(Command::SetUniform {
+            shader: self.id,
+            name: String::from(name),
+            value,
+        });
+    }
+}
+
+impl Shader for RecordingShader {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn load_from_file(&mut self, file_path: &str) {
+        record(Command::LoadShaderFile {
+            id: self.id,
+            path: String::from(file_path),
+        });
+    }
+
+    fn load_from_source(&mut self, source: &str) {
+        record(Command::LoadShaderSource {
+            id: self.id,
+            source: String::from(source),
+        });
+    }
+
+    fn set_label(&self, label: &str) {
+        record(Command::SetLabel {
+            id: self.id,
+            label: String::from(label),
+        });
+    }
+
+    // Shaders are never compiled, so pipelines aren't validated.
+    fn get_attributes(&self) -> Option<Vec<ShaderAttribute>> {
+        return None;
+    }
+
+    fn submit_uniform_i32(&self, name: &str, value: i32) {
+        self.set_uniform(name, UniformValue::I32(value));
+    }
+
+    fn submit_uniform_vec3(&self, name: &str, value: Vector3<f32>) {
+        self.set_uniform(name, UniformValue::Vec3(value));
+    }
+
+    fn submit_uniform_mat4x4(&self, name: &str, data: Matrix4<f32>) {
+        self.set_uniform(name, UniformValue::Mat4(data));
+    }
+}
+
+impl Bindable for RecordingShader {
+    fn bind(&self) {
+        with_context(|context| {
+            context.shader = Some(self.id);
+            context.record(Command::BindShader(self.id));
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context.shader = None;
+            context.record(Command::UnbindShader(self.id));
+        });
+    }
+}
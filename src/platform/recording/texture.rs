@@ -0,0 +1,244 @@
+use std::slice;
+
+use crate::render::{
+    buffer::BufferDataType,
+    recording::Command,
+    texture::{PixelBuffer, PixelData, Texture, TextureDimension, TextureFormat},
+    texture_container::CompressedTexture,
+    texture_decoder::get_decoded_data_type,
+};
+
+use super::{record, with_context};
+
+#[derive(Clone)]
+pub struct RecordingTexture {
+    id: u32,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+}
+
+impl RecordingTexture {
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> RecordingTexture {
+        return RecordingTexture::new_with_dimension(
+            TextureDimension::Texture2D,
+            width,
+            height,
+            1,
+            data,
+            format,
+            data_type,
+        );
+    }
+
+    // Data is read with the same layout as the OpenGL textures, layers
+    // tightly packed one after another.
+    pub fn new_with_dimension(
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> RecordingTexture {
+        let (height, layers) = match dimension {
+            TextureDimension::Texture1D => (1, 1),
+            TextureDimension::Texture2D => (height, 1),
+            TextureDimension::Texture2DArray | TextureDimension::Texture3D => (height, depth),
+            TextureDimension::Cube => (height, 6),
+            TextureDimension::CubeArray => (height, depth * 6),
+        };
+
+        let texture = RecordingTexture {
+            id: with_context(|context| context.generate_id()),
+            width,
+            height,
+            format,
+            data_type,
+        };
+
+        let data = if data.is_null() {
+            None
+        } else {
+            Some(texture.copy_layers(data, layers, data_type))
+        };
+        texture.record_creation(dimension, layers, data);
+
+        return texture;
+    }
+
+    // Faces are recorded one after another, all of them or none.
+    pub fn new_cubemap(
+        size: u32,
+        faces: [*const u8; 6],
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> RecordingTexture {
+        let texture = RecordingTexture {
+            id: with_context(|context| context.generate_id()),
+            width: size,
+            height: size,
+            format,
+            data_type,
+        };
+
+        let data = if faces.iter().any(|face| face.is_null()) {
+            None
+        } else {
+            Some(
+                faces
+                    .iter()
+                    .flat_map(|face| texture.copy_layers(*face, 1, data_type))
+                    .collect(),
+            )
+        };
+        texture.record_creation(TextureDimension::Cube, 6, data);
+
+        return texture;
+    }
+
+    // The conversions run on the GPU, only the resulting cubemap is recorded.
+    pub fn new_converted_cubemap(
+        size: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> RecordingTexture {
+        return RecordingTexture::new_cubemap(size, [std::ptr::null(); 6], format, data_type);
+    }
+
+    pub fn new_compressed(texture: &CompressedTexture) -> RecordingTexture {
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.stats.texture_bytes_uploaded +=
+                texture.levels.iter().map(|level| level.len() as u64).sum::<u64>();
+            context.record(Command::CreateCompressedTexture {
+                id,
+                format: texture.format,
+                width: texture.width,
+                height: texture.height,
+                levels: texture.levels.len(),
+            });
+            id
+        });
+
+        return RecordingTexture {
+            id,
+            width: texture.width,
+            height: texture.height,
+            format: TextureFormat::RGBA,
+            data_type: get_decoded_data_type(texture.format),
+        };
+    }
+
+    pub fn from_pixels(pixels: &PixelBuffer) -> RecordingTexture {
+        return RecordingTexture::new(
+            pixels.width,
+            pixels.height,
+            pixels.data.as_bytes().as_ptr(),
+            pixels.format,
+            pixels.data.get_data_type(),
+        );
+    }
+
+    fn record_creation(&self, dimension: TextureDimension, layers: u32, data: Option<Vec<u8>>) {
+        with_context(|context| {
+            if let Some(data) = &data {
+                context.stats.texture_bytes_uploaded += data.len() as u64;
+            }
+            context.record(Command::CreateTexture {
+                id: self.id,
+                dimension,
+                width: self.width,
+                height: self.height,
+                layers,
+                format: self.format,
+                data_type: self.data_type,
+                data,
+            });
+        });
+    }
+
+    fn copy_layers(&self, data: *const u8, layers: u32, data_type: BufferDataType) -> Vec<u8> {
+        let length = Self::get_layer_size(self.width, self.height, self.format, data_type)
+            * layers as usize;
+        return unsafe { slice::from_raw_parts(data, length) }.to_vec();
+    }
+
+    fn get_layer_size(width: u32, height: u32, format: TextureFormat, data_type: BufferDataType) -> usize {
+        return (width * height * format.get_channel_count()) as usize
+            * data_type.get_size();
+    }
+}
+
+impl Texture for RecordingTexture {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn bind(&self, slot: u32) {
+        with_context(|context| {
+            context.textures.insert(slot, self.id);
+            context.record(Command::BindTexture { id: self.id, slot });
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context.textures.retain(|_, id| *id != self.id);
+            context.record(Command::UnbindTexture(self.id));
+        });
+    }
+
+    fn get_size(&self, level: u32) -> (u32, u32) {
+        return ((self.width >> level).max(1), (self.height >> level).max(1));
+    }
+
+    fn get_format(&self) -> TextureFormat {
+        return self.format;
+    }
+
+    // Nothing is stored, reads return zeros of the right size.
+    fn read_pixels(&self, level: u32, data_type: BufferDataType) -> PixelBuffer {
+        let (width, height) = self.get_size(level);
+        return PixelBuffer {
+            width,
+            height,
+            format: self.format,
+            data: PixelData::new(
+                (width * height * self.format.get_channel_count()) as usize,
+                data_type,
+            ),
+        };
+    }
+
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType) {
+        let data = self.copy_layers(data, 1, data_type);
+        with_context(|context| {
+            context.stats.texture_bytes_uploaded += data.len() as u64;
+            context.record(Command::UploadTextureLayer {
+                id: self.id,
+                layer,
+                data,
+            });
+        });
+    }
+
+    fn is_ready(&self) -> bool {
+        return true;
+    }
+
+    fn set_label(&self, label: &str) {
+        record(Command::SetLabel {
+            id: self.id,
+            label: String::from(label),
+        });
+    }
+}
@@ -0,0 +1,351 @@
+use std::{
+    mem::size_of,
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use nalgebra::Vector4;
+
+use crate::render::{
+    buffer::{
+        read_indirect_commands, Bindable, Buffer, BufferDataType, BufferLayoutNode, BufferType,
+        BufferUsage, DataReader, DrawRange, VertexArray,
+    },
+    pipeline::PrimitiveTopology,
+    software::VertexOutput,
+};
+
+use super::{rasterizer, record_draw, with_context, CONTEXT};
+
+pub struct SoftwareBuffer {
+    id: u32,
+    buffer_layout: Vec<BufferLayoutNode>,
+    element_count: usize,
+    data: Arc<RwLock<Vec<u8>>>,
+}
+
+impl SoftwareBuffer {
+    pub fn new<T>(
+        data: &mut Vec<T>,
+        buffer_type: BufferType,
+        buffer_layout: Vec<BufferLayoutNode>,
+        _buffer_usage: BufferUsage,
+        size: Option<usize>,
+    ) -> SoftwareBuffer {
+        let length = data.len() * size_of::<T>();
+        let mut bytes = vec![0; size.unwrap_or(length).max(length)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr() as *const u8, bytes.as_mut_ptr(), length);
+        }
+
+        let element_count =
+            Self::calculate_element_count(data.len(), buffer_layout.clone(), buffer_type);
+        let data = Arc::new(RwLock::new(bytes));
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.buffers.insert(id, data.clone());
+            id
+        });
+
+        return SoftwareBuffer {
+            id,
+            buffer_layout,
+            element_count,
+            data,
+        };
+    }
+
+    // Same counting as the OpenGL buffers, the element count of vertex
+    // buffers is the number of vertices.
+    fn calculate_element_count(
+        data_len: usize,
+        layout: Vec<BufferLayoutNode>,
+        buffer_type: BufferType,
+    ) -> usize {
+        let layout_size = BufferLayoutNode::get_layout_size(layout);
+        if buffer_type == BufferType::Vertex {
+            return data_len / layout_size;
+        } else {
+            return data_len;
+        }
+    }
+}
+
+impl Drop for SoftwareBuffer {
+    fn drop(&mut self) {
+        // The context may already be gone when the thread exits.
+        let _ = CONTEXT.try_with(|context| {
+            if let Ok(mut context) = context.try_borrow_mut() {
+                context.buffers.remove(&self.id);
+            }
+        });
+    }
+}
+
+impl Bindable for SoftwareBuffer {
+    fn bind(&self) {}
+
+    fn unbind(&self) {}
+}
+
+impl Buffer for SoftwareBuffer {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn get_layout(&self) -> &Vec<BufferLayoutNode> {
+        return &self.buffer_layout;
+    }
+
+    fn get_element_count(&self) -> usize {
+        return self.element_count;
+    }
+
+    // Offset is in bytes, like glBufferSubData.
+    fn add_sub_data(&mut self, data: Vec<f32>, offset: usize) {
+        let length = data.len() * size_of::<f32>();
+        {
+            let mut bytes = self.data.write().unwrap();
+            if bytes.len() < offset + length {
+                bytes.resize(offset + length, 0);
+            }
+            for (index, value) in data.iter().enumerate() {
+                let start = offset + index * size_of::<f32>();
+                bytes[start..start + size_of::<f32>()].copy_from_slice(&value.to_ne_bytes());
+            }
+        }
+
+        self.element_count = if offset == 0 {
+            Self::calculate_element_count(data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        } else {
+            Self::calculate_element_count(offset + data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        };
+    }
+
+    fn set_label(&self, _label: &str) {}
+}
+
+struct Attribute {
+    buffer: usize,
+    offset: usize,
+    stride: usize,
+    node: BufferLayoutNode,
+}
+
+pub struct SoftwareVertexArray {
+    attributes: Vec<Attribute>,
+    vertex_buffers: Vec<Box<dyn Buffer>>,
+    vertex_data: Vec<Arc<RwLock<Vec<u8>>>>,
+    index_buffer: Option<(Box<dyn Buffer>, Arc<RwLock<Vec<u8>>>)>,
+    element_count: usize,
+}
+
+impl SoftwareVertexArray {
+    pub fn new() -> SoftwareVertexArray {
+        return SoftwareVertexArray {
+            attributes: Vec::new(),
+            vertex_buffers: Vec::new(),
+            vertex_data: Vec::new(),
+            index_buffer: None,
+            element_count: 0,
+        };
+    }
+
+    fn get_data(buffer: &Box<dyn Buffer>) -> Arc<RwLock<Vec<u8>>> {
+        return with_context(|context| context.buffers.get(&buffer.get_id()).cloned())
+            .expect("Buffer doesn't belong to the software renderer");
+    }
+
+    // Every other triangle of a strip is flipped so they all keep the
+    // winding of the first one.
+    fn expand_strip(indices: &[u32]) -> Vec<u32> {
+        return indices
+            .windows(3)
+            .enumerate()
+            .flat_map(|(index, triangle)| match index % 2 {
+                0 => [triangle[0], triangle[1], triangle[2]],
+                _ => [triangle[1], triangle[0], triangle[2]],
+            })
+            .collect();
+    }
+
+    // There is no instancing, ranges with instances are drawn once and the
+    // ones without are skipped. All of them count as a single draw.
+    fn draw_ranges(&self, ranges: &[(DrawRange, u32)]) {
+        let indexed = self.index_buffer.is_some();
+        let indices = match indexed {
+            true => self.get_indices(),
+            false => Vec::new(),
+        };
+        let data: Vec<_> = self.vertex_data.iter().map(|data| data.read().unwrap()).collect();
+        with_context(|context| {
+            let program = match &context.program {
+                Some(program) => program.clone(),
+                None => {
+                    log::warn!("Draw call without a bound shader");
+                    return;
+                }
+            };
+
+            let uniforms = program.get_uniforms();
+            let mut count = 0;
+            let mut triangles = 0;
+            for (range, _) in ranges.iter().filter(|(_, instances)| *instances > 0) {
+                let range_indices: Vec<u32> = match indexed {
+                    true => indices
+                        .iter()
+                        .skip(range.first as usize)
+                        .take(range.count as usize)
+                        .map(|index| (*index as i64 + range.base_vertex as i64).max(0) as u32)
+                        .collect(),
+                    false => (range.first..range.first + range.count).collect(),
+                };
+                count += range_indices.len() as u64;
+                triangles += context.topology.get_triangle_count(range_indices.len() as u64);
+                let range_indices = match context.topology {
+                    PrimitiveTopology::TriangleStrip => Self::expand_strip(&range_indices),
+                    _ => range_indices,
+                };
+
+                let vertices: Vec<VertexOutput> = range_indices
+                    .iter()
+                    .map(|index| program.program.vertex(&self.fetch(&data, *index), &uniforms))
+                    .collect();
+                rasterizer::draw_triangles(context, &program, &vertices);
+            }
+            record_draw(context, count, triangles);
+        });
+    }
+
+    fn get_indices(&self) -> Vec<u32> {
+        match &self.index_buffer {
+            Some((buffer, data)) => {
+                let data_type = buffer.get_index_type();
+                let data = data.read().unwrap();
+                let size = data_type.get_size();
+                (0..buffer.get_element_count())
+                    .map(|index| {
+                        let bytes = &data[index * size..(index + 1) * size];
+                        match data_type {
+                            BufferDataType::U8 | BufferDataType::I8 => bytes[0] as u32,
+                            BufferDataType::U16 | BufferDataType::I16 => {
+                                u16::from_ne_bytes([bytes[0], bytes[1]]) as u32
+                            }
+                            _ => u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+                        }
+                    })
+                    .collect()
+            }
+            None => (0..self.element_count as u32).collect(),
+        }
+    }
+
+    // Reads missing components as (0, 0, 0, 1) like OpenGL.
+    fn fetch(&self, data: &[RwLockReadGuard<Vec<u8>>], index: u32) -> Vec<Vector4<f32>> {
+        return self
+            .attributes
+            .iter()
+            .map(|attribute| {
+                let mut value = Vector4::new(0.0, 0.0, 0.0, 1.0);
+                let bytes = &data[attribute.buffer];
+                let start = attribute.offset + attribute.stride * index as usize;
+                let size = attribute.node.data_type.get_size()
+                    * attribute.node.elements as usize;
+                if start + size > bytes.len() {
+                    return value;
+                }
+
+                let mut reader = DataReader::new(
+                    bytes[start..].as_ptr(),
+                    attribute.node.data_type,
+                    attribute.node.normalized,
+                );
+                for component in 0..(attribute.node.elements as usize).min(4) {
+                    value[component] = reader.read();
+                }
+
+                value
+            })
+            .collect();
+    }
+}
+
+impl Bindable for SoftwareVertexArray {
+    fn bind(&self) {}
+
+    fn unbind(&self) {}
+}
+
+impl VertexArray for SoftwareVertexArray {
+    fn draw(&self) {
+        let count = match &self.index_buffer {
+            Some((buffer, _)) => buffer.get_element_count(),
+            None => self.element_count,
+        };
+        self.draw_ranges(&[(
+            DrawRange {
+                first: 0,
+                count: count as u32,
+                base_vertex: 0,
+            },
+            1,
+        )]);
+    }
+
+    fn draw_range(&self, range: DrawRange) {
+        self.draw_ranges(&[(range, 1)]);
+    }
+
+    fn multi_draw(&self, ranges: &[DrawRange]) {
+        let ranges: Vec<_> = ranges.iter().map(|range| (*range, 1)).collect();
+        self.draw_ranges(&ranges);
+    }
+
+    fn draw_indirect(&self, buffer: &Box<dyn Buffer>, offset: usize, count: usize) {
+        let data = Self::get_data(buffer);
+        let commands = read_indirect_commands(&data.read().unwrap(), offset, count, self.index_buffer.is_some());
+        self.draw_ranges(&commands);
+    }
+
+    fn add_buffer(&mut self, buffer: Box<dyn Buffer>, buffer_type: BufferType) {
+        let data = Self::get_data(&buffer);
+        match buffer_type {
+            BufferType::Vertex => {
+                let layout = buffer.get_layout();
+                let mut stride = 0;
+                for node in layout {
+                    stride += node.data_type.get_size() * node.elements as usize;
+                }
+                self.element_count += buffer.get_element_count();
+
+                let mut offset = 0;
+                for node in layout {
+                    self.attributes.push(Attribute {
+                        buffer: self.vertex_buffers.len(),
+                        offset,
+                        stride,
+                        node: *node,
+                    });
+                    offset += node.data_type.get_size() * node.elements as usize;
+                }
+                self.vertex_buffers.push(buffer);
+                self.vertex_data.push(data);
+            }
+            BufferType::Index => {
+                self.index_buffer = Some((buffer, data));
+            }
+            BufferType::Indirect => {
+                log::warn!("Indirect buffers are passed to draw_indirect, not added to vertex arrays");
+            }
+        }
+    }
+
+    fn get_buffers(&self) -> &Vec<Box<dyn Buffer>> {
+        return &self.vertex_buffers;
+    }
+
+    fn add_buffer_sub_data(&mut self, data: Vec<f32>, offset: usize, buffer_index: usize) {
+        let old_buffer_size = self.vertex_buffers[buffer_index].get_element_count();
+        self.vertex_buffers[buffer_index].add_sub_data(data, offset);
+        self.element_count += self.vertex_buffers[buffer_index].get_element_count() - old_buffer_size;
+    }
+}
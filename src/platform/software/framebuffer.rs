@@ -0,0 +1,278 @@
+use std::sync::{Arc, Mutex};
+
+use crate::render::{
+    buffer::BufferDataType,
+    framebuffer::{
+        ColorAttachment, DepthStencilFormat, Framebuffer, FramebufferDescriptor, FramebufferError,
+        FramebufferSize,
+    },
+    texture::{Texture, TextureFormat},
+};
+
+use super::{
+    texture::{SoftwareTexture, TextureData},
+    with_context, RenderTarget,
+};
+
+// Matches the minimum OpenGL guarantees.
+pub const MAX_COLOR_ATTACHMENTS: usize = 8;
+
+pub struct FramebufferState {
+    id: u32,
+    width: u32,
+    height: u32,
+    samples: u32,
+    color_attachments: Vec<(SoftwareTexture, ColorAttachment)>,
+    depth_attachment: Option<SoftwareTexture>,
+    depth_stencil: DepthStencilFormat,
+    scale: Option<f32>,
+}
+
+impl FramebufferState {
+    // Reallocates the attachments in place so textures handed out before
+    // keep referring to them.
+    fn allocate(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::EmptySize);
+        }
+
+        for (texture, attachment) in &self.color_attachments {
+            *texture.get_data().write().unwrap() =
+                TextureData::new(width, height, 1, attachment.format, attachment.data_type);
+        }
+
+        if let Some(texture) = &self.depth_attachment {
+            let (format, data_type) = SoftwareFramebuffer::get_depth_format(self.depth_stencil);
+            *texture.get_data().write().unwrap() =
+                TextureData::new(width, height, 1, format, data_type);
+        }
+
+        self.width = width;
+        self.height = height;
+
+        return Ok(());
+    }
+}
+
+// Multisampling is accepted but drawing always uses a single sample.
+pub struct SoftwareFramebuffer {
+    state: Arc<Mutex<FramebufferState>>,
+}
+
+impl SoftwareFramebuffer {
+    pub fn new(descriptor: FramebufferDescriptor) -> Result<SoftwareFramebuffer, FramebufferError> {
+        let (width, height, scale) = match descriptor.size {
+            FramebufferSize::Fixed(width, height) => (width, height, None),
+            FramebufferSize::Window(scale) => {
+                let size = with_context(|context| (context.width, context.height));
+                let (width, height) = Self::scale_window_size(size, scale);
+                (width, height, Some(scale))
+            }
+        };
+
+        if descriptor.color_attachments.is_empty()
+            && descriptor.depth_stencil == DepthStencilFormat::None
+        {
+            return Err(FramebufferError::NoAttachments);
+        }
+
+        if descriptor.color_attachments.len() > MAX_COLOR_ATTACHMENTS {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: descriptor.color_attachments.len(),
+                max: MAX_COLOR_ATTACHMENTS,
+            });
+        }
+
+        let color_attachments = descriptor
+            .color_attachments
+            .iter()
+            .map(|attachment| (Self::create_texture(attachment.format, attachment.data_type), *attachment))
+            .collect();
+        let depth_attachment = match descriptor.depth_stencil {
+            DepthStencilFormat::None => None,
+            depth_stencil => {
+                let (format, data_type) = Self::get_depth_format(depth_stencil);
+                Some(Self::create_texture(format, data_type))
+            }
+        };
+
+        let mut state = FramebufferState {
+            id: with_context(|context| context.generate_id()),
+            width: 0,
+            height: 0,
+            samples: descriptor.samples,
+            color_attachments,
+            depth_attachment,
+            depth_stencil: descriptor.depth_stencil,
+            scale,
+        };
+        state.allocate(width, height)?;
+
+        let state = Arc::new(Mutex::new(state));
+        if scale.is_some() {
+            with_context(|context| context.window_framebuffers.push(Arc::downgrade(&state)));
+        }
+
+        return Ok(SoftwareFramebuffer { state });
+    }
+
+    fn create_texture(format: TextureFormat, data_type: BufferDataType) -> SoftwareTexture {
+        return SoftwareTexture::from_data(TextureData::new(0, 0, 1, format, data_type));
+    }
+
+    fn get_depth_format(depth_stencil: DepthStencilFormat) -> (TextureFormat, BufferDataType) {
+        match depth_stencil {
+            DepthStencilFormat::Depth32F => (TextureFormat::Depth, BufferDataType::F32),
+            DepthStencilFormat::Depth24Stencil8 => (TextureFormat::DepthStencil, BufferDataType::U32),
+            _ => (TextureFormat::Depth, BufferDataType::U32),
+        }
+    }
+
+    fn scale_window_size(size: (u32, u32), scale: f32) -> (u32, u32) {
+        return (
+            ((size.0 as f32 * scale) as u32).max(1),
+            ((size.1 as f32 * scale) as u32).max(1),
+        );
+    }
+
+    // Nearest neighbour scaling of the first layer.
+    fn scale_texels(source: &TextureData, width: u32, height: u32) -> Vec<f32> {
+        let mut values = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let texel = source.get_texel(
+                    (x * source.width / width).min(source.width - 1),
+                    (y * source.height / height).min(source.height - 1),
+                    0,
+                );
+                values.extend_from_slice(texel.as_slice());
+            }
+        }
+
+        return values;
+    }
+}
+
+impl Framebuffer for SoftwareFramebuffer {
+    fn get_id(&self) -> u32 {
+        return self.state.lock().unwrap().id;
+    }
+
+    fn bind(&self) {
+        let state = self.state.lock().unwrap();
+        let target = RenderTarget {
+            colors: state
+                .color_attachments
+                .iter()
+                .map(|(texture, _)| texture.get_data().clone())
+                .collect(),
+            depth: state
+                .depth_attachment
+                .as_ref()
+                .map(|texture| texture.get_data().clone()),
+        };
+
+        with_context(|context| {
+            context.target = Some(target);
+            context.viewport = (0, 0, state.width, state.height);
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context.target = None;
+            context.viewport = (0, 0, context.width, context.height);
+        });
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        let state = self.state.lock().unwrap();
+        return (state.width, state.height);
+    }
+
+    fn get_samples(&self) -> u32 {
+        return self.state.lock().unwrap().samples;
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        return self.state.lock().unwrap().allocate(width, height);
+    }
+
+    fn get_color_attachment_count(&self) -> usize {
+        return self.state.lock().unwrap().color_attachments.len();
+    }
+
+    fn get_color_attachment(&self, index: usize) -> Option<Box<dyn Texture>> {
+        let state = self.state.lock().unwrap();
+        return state
+            .color_attachments
+            .get(index)
+            .map(|(texture, _)| Box::new(texture.clone()) as Box<dyn Texture>);
+    }
+
+    fn get_depth_attachment(&self) -> Option<Box<dyn Texture>> {
+        return self
+            .state
+            .lock()
+            .unwrap()
+            .depth_attachment
+            .clone()
+            .map(|texture| Box::new(texture) as Box<dyn Texture>);
+    }
+
+    // Copies through the texture interface, so targets of other backends
+    // work as well.
+    fn resolve(&self, target: Option<&Box<dyn Framebuffer>>) -> Result<(), FramebufferError> {
+        let (target_width, target_height) = match target {
+            Some(target) => target.get_size(),
+            None => with_context(|context| (context.width, context.height)),
+        };
+
+        let state = self.state.lock().unwrap();
+        let target_attachments = match target {
+            Some(target) if target.get_id() == state.id => return Ok(()),
+            Some(target) => target.get_color_attachment_count(),
+            None => 1,
+        };
+
+        for index in 0..state.color_attachments.len().min(target_attachments) {
+            let values = {
+                let source = state.color_attachments[index].0.get_data().read().unwrap();
+                Self::scale_texels(&source, target_width, target_height)
+            };
+
+            match target {
+                Some(target) => {
+                    if let Some(texture) = target.get_color_attachment(index) {
+                        texture.upload_layer(0, values.as_ptr() as *const u8, BufferDataType::F32);
+                    }
+                }
+                None => with_context(|context| {
+                    let mut color = context.color.write().unwrap();
+                    color.upload(0, 1, values.as_ptr() as *const u8, BufferDataType::F32);
+                }),
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+// Window sized framebuffers that were dropped are pruned here.
+pub fn resize_window_framebuffers(width: u32, height: u32) {
+    let framebuffers = with_context(|context| {
+        context
+            .window_framebuffers
+            .retain(|framebuffer| framebuffer.strong_count() > 0);
+        context.window_framebuffers.clone()
+    });
+
+    for framebuffer in framebuffers.iter().filter_map(|framebuffer| framebuffer.upgrade()) {
+        let mut state = framebuffer.lock().unwrap();
+        let (width, height) =
+            SoftwareFramebuffer::scale_window_size((width, height), state.scale.unwrap_or(1.0));
+        if let Err(error) = state.allocate(width, height) {
+            log::warn!("Failed to resize framebuffer with error\n{}", error);
+        }
+    }
+}
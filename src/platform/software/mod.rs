@@ -0,0 +1,687 @@
+pub mod buffer;
+pub mod framebuffer;
+pub mod pipeline;
+pub mod rasterizer;
+pub mod shader;
+pub mod texture;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Instant,
+};
+
+use nalgebra::Vector4;
+
+use crate::render::{
+    buffer::BufferDataType,
+    capabilities::Capabilities,
+    debug::DebugSeverity,
+    framebuffer::Framebuffer,
+    pipeline::PrimitiveTopology,
+    render_api::RenderAPI,
+    render_queue::RenderQueue,
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    stats::{FrameStats, ScopeTiming, StateCacheStats},
+    texture::{PixelBuffer, TextureFormat},
+    window::{FramebufferResize, Window},
+};
+
+use self::{framebuffer::FramebufferState, shader::ProgramState, texture::TextureData};
+
+thread_local! {
+    // Like a current OpenGL context every thread gets its own, so tests
+    // running in parallel don't see each other's state.
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::new(0, 0));
+}
+
+pub fn with_context<T>(function: impl FnOnce(&mut Context) -> T) -> T {
+    return CONTEXT.with(|context| function(&mut context.borrow_mut()));
+}
+
+pub struct RenderTarget {
+    pub colors: Vec<Arc<RwLock<TextureData>>>,
+    pub depth: Option<Arc<RwLock<TextureData>>>,
+}
+
+pub struct Context {
+    next_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub color: Arc<RwLock<TextureData>>,
+    pub depth: Arc<RwLock<TextureData>>,
+    pub target: Option<RenderTarget>,
+    pub viewport: (i32, i32, u32, u32),
+
+    pub clear_color: Vector4<f32>,
+    pub clear_depth: f32,
+    pub clear_stencil: i32,
+    pub depth_state: DepthState,
+    pub blend_state: BlendState,
+    pub blend_constant: Vector4<f32>,
+    pub color_mask: ColorMask,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub scissor: Option<ScissorRect>,
+    pub stencil_state: StencilState,
+    // Only set by pipelines, draws without one use triangle lists.
+    pub topology: PrimitiveTopology,
+
+    pub program: Option<Arc<ProgramState>>,
+    pub textures: HashMap<u32, Arc<RwLock<TextureData>>>,
+    pub buffers: HashMap<u32, Arc<RwLock<Vec<u8>>>>,
+    pub window_framebuffers: Vec<Weak<Mutex<FramebufferState>>>,
+
+    pub stats: FrameStats,
+    last_stats: FrameStats,
+    frame_start: Option<Instant>,
+    scopes: Vec<(String, Instant)>,
+}
+
+impl Context {
+    fn new(width: u32, height: u32) -> Context {
+        return Context {
+            next_id: 1,
+            width,
+            height,
+            color: Arc::new(RwLock::new(Self::create_surface(
+                width,
+                height,
+                TextureFormat::RGBA,
+            ))),
+            depth: Arc::new(RwLock::new(Self::create_surface(
+                width,
+                height,
+                TextureFormat::DepthStencil,
+            ))),
+            target: None,
+            viewport: (0, 0, width, height),
+
+            clear_color: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            clear_depth: 1.0,
+            clear_stencil: 0,
+            depth_state: DepthState::default(),
+            blend_state: BlendState::DISABLED,
+            blend_constant: Vector4::zeros(),
+            color_mask: ColorMask::ALL,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            scissor: None,
+            stencil_state: StencilState::default(),
+            topology: PrimitiveTopology::Triangles,
+
+            program: None,
+            textures: HashMap::new(),
+            buffers: HashMap::new(),
+            window_framebuffers: Vec::new(),
+
+            stats: FrameStats::default(),
+            last_stats: FrameStats::default(),
+            frame_start: None,
+            scopes: Vec::new(),
+        };
+    }
+
+    // The window surfaces are RGBA8 and 24 bit depth with 8 bit stencil.
+    fn create_surface(width: u32, height: u32, format: TextureFormat) -> TextureData {
+        return TextureData::new(width, height, 1, format, BufferDataType::U8);
+    }
+
+    pub fn generate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        return id;
+    }
+
+    pub fn get_target(&self) -> (Vec<Arc<RwLock<TextureData>>>, Option<Arc<RwLock<TextureData>>>) {
+        match &self.target {
+            Some(target) => (target.colors.clone(), target.depth.clone()),
+            None => (vec![self.color.clone()], Some(self.depth.clone())),
+        }
+    }
+
+    pub fn get_target_size(&self) -> (u32, u32) {
+        let (colors, depth) = self.get_target();
+        match colors.first().or(depth.as_ref()) {
+            Some(surface) => {
+                let surface = surface.read().unwrap();
+                (surface.width, surface.height)
+            }
+            None => (0, 0),
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        *self.color.write().unwrap() = Self::create_surface(width, height, TextureFormat::RGBA);
+        *self.depth.write().unwrap() =
+            Self::create_surface(width, height, TextureFormat::DepthStencil);
+    }
+}
+
+pub struct SoftwareAPI {
+    initalized: bool,
+    framebuffer_resize: FramebufferResize,
+}
+
+impl RenderAPI for SoftwareAPI {
+    fn init(&self, window: &mut Box<dyn Window>) {
+        let (width, height) = window.get_framebuffer_size();
+        with_context(|context| *context = Context::new(width, height));
+    }
+
+    fn clear(&self) {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        with_context(|context| {
+            let (colors, depth) = context.get_target();
+            let (width, height) = context.get_target_size();
+
+            for color in colors {
+                let mut color = color.write().unwrap();
+                for y in 0..height {
+                    for x in 0..width {
+                        color.set_texel(x, y, 0, context.clear_color);
+                    }
+                }
+            }
+
+            if let Some(depth) = depth {
+                let mut depth = depth.write().unwrap();
+                let stencil = (context.clear_stencil as u32 & 0xff) as f32;
+                for y in 0..height {
+                    for x in 0..width {
+                        let mut texel = depth.get_texel(x, y, 0);
+                        texel.x = context.clear_depth;
+                        texel.y = stencil;
+                        depth.set_texel(x, y, 0, texel);
+                    }
+                }
+            }
+        });
+    }
+
+    fn begin<'a>(&self) -> RenderQueue<'a> {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        if let Some((width, height)) = self.framebuffer_resize.take() {
+            self.resize(width, height);
+        }
+
+        // Every render target drawn until the window presents counts
+        // towards the same frame.
+        with_context(|context| {
+            if context.frame_start.is_none() {
+                context.frame_start = Some(Instant::now());
+            }
+        });
+        self.clear();
+
+        return RenderQueue::new();
+    }
+
+    fn end(&self, mut queue: RenderQueue) {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        queue.execute();
+    }
+
+    fn set_render_target(&self, target: Option<&Box<dyn Framebuffer>>) {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        match target {
+            Some(framebuffer) => framebuffer.bind(),
+            None => with_context(|context| {
+                context.target = None;
+                context.viewport = (0, 0, context.width, context.height);
+            }),
+        }
+    }
+
+    fn capture_frame(&self) -> PixelBuffer {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        return with_context(|context| {
+            let color = context.color.read().unwrap();
+            let mut frame = PixelBuffer {
+                width: color.width,
+                height: color.height,
+                format: TextureFormat::RGBA,
+                data: color.read(BufferDataType::U8),
+            };
+            frame.flip_vertically();
+
+            frame
+        });
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        with_context(|context| {
+            context.resize(width, height);
+            if context.target.is_none() {
+                context.viewport = (0, 0, width, height);
+            }
+        });
+        framebuffer::resize_window_framebuffers(width, height);
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        with_context(|context| context.viewport = (x, y, width, height));
+    }
+
+    fn set_clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        with_context(|context| context.clear_color = Vector4::new(red, green, blue, alpha));
+    }
+
+    fn set_clear_depth(&self, depth: f32) {
+        with_context(|context| context.clear_depth = depth);
+    }
+
+    fn set_clear_stencil(&self, stencil: i32) {
+        with_context(|context| context.clear_stencil = stencil);
+    }
+
+    fn set_depth_state(&self, state: DepthState) {
+        with_context(|context| context.depth_state = state);
+    }
+
+    // Fragments are only written to the first color attachment, so the
+    // state of the other targets is ignored.
+    fn set_blend_state(&self, target: Option<u32>, state: BlendState) {
+        if target.unwrap_or(0) == 0 {
+            with_context(|context| context.blend_state = state);
+        }
+    }
+
+    fn set_blend_constant(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        with_context(|context| context.blend_constant = Vector4::new(red, green, blue, alpha));
+    }
+
+    fn set_color_mask(&self, target: Option<u32>, mask: ColorMask) {
+        if target.unwrap_or(0) == 0 {
+            with_context(|context| context.color_mask = mask);
+        }
+    }
+
+    fn set_cull_mode(&self, mode: CullMode) {
+        with_context(|context| context.cull_mode = mode);
+    }
+
+    fn set_front_face(&self, face: FrontFace) {
+        with_context(|context| context.front_face = face);
+    }
+
+    // Only filled polygons are rasterized.
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        if mode != PolygonMode::Fill {
+            log::warn!("The software renderer only supports filled polygons");
+        }
+    }
+
+    fn set_scissor(&self, rect: Option<ScissorRect>) {
+        with_context(|context| context.scissor = rect);
+    }
+
+    fn set_stencil_state(&self, state: StencilState) {
+        with_context(|context| context.stencil_state = state);
+    }
+
+    // Textures are only bounded by memory and framebuffers accept samples
+    // but render single sampled.
+    fn capabilities(&self) -> Capabilities {
+        if !self.initalized {
+            panic!("Software API was not initalized!");
+        }
+
+        return Capabilities {
+            vendor: String::from("librender"),
+            renderer: String::from("Software rasterizer"),
+            max_texture_size: 16384,
+            max_color_attachments: framebuffer::MAX_COLOR_ATTACHMENTS as u32,
+            max_samples: 1,
+            ..Capabilities::minimum()
+        };
+    }
+
+    // There is no driver to skip calls to.
+    fn get_state_cache_stats(&self) -> StateCacheStats {
+        return StateCacheStats::default();
+    }
+
+    fn reset_state_cache_stats(&self) {}
+
+    fn get_frame_stats(&self) -> FrameStats {
+        return with_context(|context| context.last_stats.clone());
+    }
+
+    fn end_frame(&self) {
+        end_frame();
+    }
+
+    fn set_debug_severity(&self, _severity: DebugSeverity) {}
+
+    fn begin_scope(&self, name: &str) {
+        with_context(|context| context.scopes.push((String::from(name), Instant::now())));
+    }
+
+    fn end_scope(&self) {
+        with_context(|context| match context.scopes.pop() {
+            Some((name, start)) => context.stats.scopes.push(ScopeTiming {
+                name,
+                gpu_time: start.elapsed(),
+            }),
+            None => log::warn!("GPU timing scope ended without being started"),
+        });
+    }
+
+    // Uploads are read tightly packed regardless.
+    fn disable_align_restrictions(&self) {}
+
+    fn enable_align_restrictions(&self) {}
+}
+
+impl SoftwareAPI {
+    pub fn new(window: &mut Box<dyn Window>) -> SoftwareAPI {
+        let api = SoftwareAPI {
+            initalized: true,
+            framebuffer_resize: window.get_framebuffer_resize(),
+        };
+        api.init(window);
+
+        return api;
+    }
+}
+
+// Called through RenderAPI::end_frame. Drawing happens synchronously, so
+// the device time is the time from the first begin until then.
+pub fn end_frame() {
+    with_context(|context| {
+        if let Some(start) = context.frame_start.take() {
+            let mut stats = std::mem::take(&mut context.stats);
+            stats.gpu_time = Some(start.elapsed());
+            context.last_stats = stats;
+        }
+    });
+}
+
+pub fn record_draw(context: &mut Context, vertices: u64, triangles: u64) {
+    context.stats.draw_calls += 1;
+    context.stats.vertices += vertices;
+    context.stats.triangles += triangles;
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "text")]
+    use std::rc::Rc;
+
+    use nalgebra::Vector4;
+
+    #[cfg(feature = "text")]
+    use crate::render::{
+        drawable_text::DrawableText,
+        glyph::{self, Glyph},
+        software::register_software_shader,
+        texture::{create_texture, TextureFormat},
+    };
+    use crate::render::{
+        buffer::{BufferDataType, BufferLayoutNode},
+        mesh::{Drawable, Mesh},
+        pipeline::{create_pipeline, PipelineDescriptor},
+        render_api::{create_api_with_backend, Backend, RenderAPI},
+        render_state::{BlendState, CullMode, DepthState},
+        software::{create_software_shader, TextureSampler, Uniforms, VertexOutput},
+        window::create_null_window,
+    };
+
+    const SIZE: u32 = 8;
+    const FULLSCREEN: [[f32; 3]; 3] = [[-1.0, -1.0, 0.0], [3.0, -1.0, 0.0], [-1.0, 3.0, 0.0]];
+
+    fn create_api() -> Box<dyn RenderAPI> {
+        let mut window = create_null_window(SIZE, SIZE);
+        let api = create_api_with_backend(&mut window, Backend::Software);
+        api.set_clear_color(0.0, 0.0, 1.0, 1.0);
+
+        return api;
+    }
+
+    // Clip space positions filled with a constant color.
+    fn create_triangle(
+        positions: [[f32; 3]; 3],
+        color: [f32; 4],
+        descriptor: PipelineDescriptor,
+    ) -> Mesh {
+        let color = Vector4::from(color);
+        let shader = create_software_shader((
+            |attributes: &[Vector4<f32>], _: &Uniforms| VertexOutput {
+                position: attributes[0],
+                varyings: Vec::new(),
+            },
+            move |_: &[f32], _: &Uniforms, _: &dyn TextureSampler| Some(color),
+        ));
+        let layout = vec![BufferLayoutNode {
+            elements: 3,
+            data_type: BufferDataType::F32,
+            normalized: false,
+        }];
+        let pipeline = create_pipeline(
+            shader,
+            PipelineDescriptor {
+                vertex_layout: layout,
+                ..descriptor
+            },
+        )
+        .unwrap();
+
+        return Mesh::from_pipeline(positions.concat(), None, pipeline);
+    }
+
+    fn no_depth() -> PipelineDescriptor {
+        return PipelineDescriptor {
+            depth: DepthState {
+                test: false,
+                write: false,
+                ..DepthState::default()
+            },
+            ..PipelineDescriptor::new(Vec::new())
+        };
+    }
+
+    // Submits every mesh at its depth and draws the queue.
+    fn draw(api: &dyn RenderAPI, meshes: Vec<(Mesh, f32)>) {
+        let mut drawables: Vec<(Box<dyn Drawable>, f32)> = meshes
+            .into_iter()
+            .map(|(mesh, depth)| (Box::new(mesh) as Box<dyn Drawable>, depth))
+            .collect();
+        let mut queue = api.begin();
+        for (drawable, depth) in drawables.iter_mut() {
+            queue.submit_at_depth(drawable, *depth);
+        }
+        api.end(queue);
+    }
+
+    // Rows of the capture go from top to bottom.
+    fn get_pixel(api: &dyn RenderAPI, x: u32, y: u32) -> [u8; 4] {
+        let frame = api.capture_frame();
+        let start = ((y * frame.width + x) * 4) as usize;
+        let bytes = frame.data.as_bytes();
+
+        return [bytes[start], bytes[start + 1], bytes[start + 2], bytes[start + 3]];
+    }
+
+    #[test]
+    fn rasterizes_triangle() {
+        let api = create_api();
+        let triangle = create_triangle(
+            [[-1.0, -1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, 1.0, 0.0]],
+            [1.0, 0.0, 0.0, 1.0],
+            PipelineDescriptor::new(Vec::new()),
+        );
+        draw(&*api, vec![(triangle, 0.0)]);
+
+        assert_eq!(get_pixel(&*api, 0, SIZE - 1), [255, 0, 0, 255]);
+        assert_eq!(get_pixel(&*api, SIZE - 1, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn depth_test_keeps_nearest() {
+        let api = create_api();
+        let near = create_triangle(
+            FULLSCREEN.map(|[x, y, _]| [x, y, -0.5]),
+            [0.0, 1.0, 0.0, 1.0],
+            PipelineDescriptor::new(Vec::new()),
+        );
+        // Drawn after the near one through its render pass.
+        let mut far = create_triangle(
+            FULLSCREEN.map(|[x, y, _]| [x, y, 0.5]),
+            [1.0, 0.0, 0.0, 1.0],
+            PipelineDescriptor::new(Vec::new()),
+        );
+        far.set_render_pass(1);
+        draw(&*api, vec![(far, 0.0), (near, 0.0)]);
+
+        assert_eq!(get_pixel(&*api, SIZE / 2, SIZE / 2), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn blends_with_alpha() {
+        let api = create_api();
+        let triangle = create_triangle(
+            FULLSCREEN,
+            [1.0, 0.0, 0.0, 0.5],
+            PipelineDescriptor {
+                blend: BlendState::ALPHA,
+                ..no_depth()
+            },
+        );
+        draw(&*api, vec![(triangle, 0.0)]);
+
+        let [red, green, blue, _] = get_pixel(&*api, SIZE / 2, SIZE / 2);
+        assert!(red.abs_diff(128) <= 1 && green == 0 && blue.abs_diff(128) <= 1);
+    }
+
+    #[test]
+    fn culls_back_faces() {
+        let api = create_api();
+        let clockwise = create_triangle(
+            [FULLSCREEN[0], FULLSCREEN[2], FULLSCREEN[1]],
+            [1.0, 0.0, 0.0, 1.0],
+            PipelineDescriptor {
+                cull_mode: CullMode::Back,
+                ..no_depth()
+            },
+        );
+        draw(&*api, vec![(clockwise, 0.0)]);
+
+        assert_eq!(get_pixel(&*api, SIZE / 2, SIZE / 2), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn queue_draws_passes_in_order_and_transparent_back_to_front() {
+        let api = create_api();
+        let mut overlay = create_triangle(FULLSCREEN, [1.0, 0.0, 0.0, 1.0], no_depth());
+        let opaque = create_triangle(FULLSCREEN, [0.0, 1.0, 0.0, 1.0], no_depth());
+        overlay.set_render_pass(1);
+        draw(&*api, vec![(overlay, 0.0), (opaque, 0.0)]);
+
+        assert_eq!(get_pixel(&*api, SIZE / 2, SIZE / 2), [255, 0, 0, 255]);
+
+        let mut near = create_triangle(FULLSCREEN, [0.0, 1.0, 0.0, 1.0], no_depth());
+        let mut far = create_triangle(FULLSCREEN, [1.0, 0.0, 0.0, 1.0], no_depth());
+        near.set_transparent(true);
+        far.set_transparent(true);
+        draw(&*api, vec![(near, 1.0), (far, 10.0)]);
+
+        assert_eq!(get_pixel(&*api, SIZE / 2, SIZE / 2), [0, 255, 0, 255]);
+    }
+
+    // Text turns depth writes off, the next clear still has to reset the
+    // depth left by the scene.
+    #[cfg(feature = "text")]
+    #[test]
+    fn clear_resets_depth_after_text() {
+        let mut api: Rc<dyn RenderAPI> = Rc::from(create_api());
+        register_software_shader(
+            "clear_text.glsl",
+            (
+                |attributes: &[Vector4<f32>], _: &Uniforms| VertexOutput {
+                    position: Vector4::new(attributes[0].x, attributes[0].y, 0.0, 1.0),
+                    varyings: Vec::new(),
+                },
+                |_: &[f32], _: &Uniforms, _: &dyn TextureSampler| Some(Vector4::new(1.0, 1.0, 1.0, 1.0)),
+            ),
+        );
+        let pixel = [255u8];
+        let glyph = Glyph {
+            texture: create_texture(pixel.as_ptr(), 1, 1, TextureFormat::Red, BufferDataType::U8),
+            size: (1, 1),
+            bearing: (0, 1),
+            advance: 1 << 6,
+        };
+        glyph::cache_glyph('a', glyph);
+
+        let near = create_triangle(
+            FULLSCREEN.map(|[x, y, _]| [x, y, -0.5]),
+            [0.0, 1.0, 0.0, 1.0],
+            PipelineDescriptor::new(Vec::new()),
+        );
+        let text = DrawableText::new("a", (0.0, 0.0), 0.0, "font.ttf", "clear_text.glsl", &mut api);
+        let mut drawables: Vec<Box<dyn Drawable>> = vec![Box::new(near), Box::new(text)];
+        let mut queue = api.begin();
+        for drawable in drawables.iter_mut() {
+            queue.submit(drawable);
+        }
+        api.end(queue);
+
+        let far = create_triangle(
+            FULLSCREEN.map(|[x, y, _]| [x, y, 0.5]),
+            [1.0, 0.0, 0.0, 1.0],
+            PipelineDescriptor::new(Vec::new()),
+        );
+        draw(&*api, vec![(far, 0.0)]);
+
+        assert_eq!(get_pixel(&*api, SIZE / 2, SIZE / 2), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn frame_stats_cover_draws_until_end_frame() {
+        let api = create_api();
+        draw(&*api, vec![(create_triangle(FULLSCREEN, [1.0, 0.0, 0.0, 1.0], no_depth()), 0.0)]);
+        draw(&*api, vec![(create_triangle(FULLSCREEN, [0.0, 1.0, 0.0, 1.0], no_depth()), 0.0)]);
+        assert_eq!(api.get_frame_stats().draw_calls, 0);
+
+        api.end_frame();
+        let stats = api.get_frame_stats();
+        assert_eq!(stats.draw_calls, 2);
+        assert_eq!(stats.triangles, 2);
+        assert!(stats.gpu_time.is_some());
+    }
+}
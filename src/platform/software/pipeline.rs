@@ -0,0 +1,64 @@
+use crate::render::{
+    buffer::Bindable,
+    pipeline::{Pipeline, PipelineDescriptor, PipelineError, PrimitiveTopology},
+    render_state::PolygonMode,
+    shader::Shader,
+};
+
+use super::with_context;
+
+// The rasterizer only draws filled triangles, strips are split into lists
+// before they reach it.
+pub struct SoftwarePipeline {
+    shader: Box<dyn Shader>,
+    descriptor: PipelineDescriptor,
+}
+
+impl SoftwarePipeline {
+    pub fn new(
+        shader: Box<dyn Shader>,
+        descriptor: PipelineDescriptor,
+    ) -> Result<SoftwarePipeline, PipelineError> {
+        match descriptor.topology {
+            PrimitiveTopology::Triangles | PrimitiveTopology::TriangleStrip => {}
+            topology => return Err(PipelineError::UnsupportedTopology(topology)),
+        }
+
+        if descriptor.polygon_mode != PolygonMode::Fill {
+            log::warn!("The software renderer only supports filled polygons");
+        }
+
+        return Ok(SoftwarePipeline { shader, descriptor });
+    }
+}
+
+impl Pipeline for SoftwarePipeline {
+    fn get_shader(&self) -> &Box<dyn Shader> {
+        return &self.shader;
+    }
+
+    fn get_descriptor(&self) -> &PipelineDescriptor {
+        return &self.descriptor;
+    }
+}
+
+impl Bindable for SoftwarePipeline {
+    fn bind(&self) {
+        self.shader.bind();
+        let descriptor = &self.descriptor;
+        with_context(|context| {
+            context.topology = descriptor.topology;
+            context.blend_state = descriptor.blend;
+            context.color_mask = descriptor.color_mask;
+            context.depth_state = descriptor.depth;
+            context.stencil_state = descriptor.stencil;
+            context.cull_mode = descriptor.cull_mode;
+            context.front_face = descriptor.front_face;
+        });
+    }
+
+    fn unbind(&self) {
+        self.shader.unbind();
+        with_context(|context| context.topology = PrimitiveTopology::Triangles);
+    }
+}
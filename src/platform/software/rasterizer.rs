@@ -0,0 +1,456 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+
+use nalgebra::{Vector2, Vector3, Vector4};
+
+use crate::render::{
+    render_state::{
+        BlendFactor, BlendOperation, CompareFunction, CullMode, FrontFace, StencilFaceState,
+        StencilOperation,
+    },
+    software::{TextureSampler, Uniforms, VertexOutput},
+    texture::TextureFormat,
+};
+
+use super::{shader::ProgramState, texture::TextureData, Context};
+
+struct Sampler<'a> {
+    textures: HashMap<u32, RwLockReadGuard<'a, TextureData>>,
+}
+
+impl<'a> TextureSampler for Sampler<'a> {
+    fn sample(&self, slot: u32, coordinates: Vector2<f32>) -> Vector4<f32> {
+        return self.sample_layer(slot, coordinates, 0);
+    }
+
+    fn sample_layer(&self, slot: u32, coordinates: Vector2<f32>, layer: u32) -> Vector4<f32> {
+        match self.textures.get(&slot) {
+            Some(texture) => texture.sample(coordinates, layer),
+            None => Vector4::zeros(),
+        }
+    }
+
+    fn sample_cube(&self, slot: u32, direction: Vector3<f32>) -> Vector4<f32> {
+        match self.textures.get(&slot) {
+            Some(texture) => texture.sample_cube(direction),
+            None => Vector4::zeros(),
+        }
+    }
+}
+
+struct ScreenVertex {
+    position: Vector3<f32>,
+    inverse_w: f32,
+    // Divided by w so they can be interpolated linearly in screen space.
+    varyings: Vec<f32>,
+}
+
+// Pixel bounds drawing is restricted to, max exclusive.
+#[derive(Clone, Copy)]
+struct Bounds {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+// Rasterizes a triangle list into the bound render target. Returns the
+// number of triangles that weren't clipped or culled.
+pub fn draw_triangles(context: &Context, program: &ProgramState, vertices: &[VertexOutput]) -> u64 {
+    let (colors, depth) = context.get_target();
+    let (width, height) = context.get_target_size();
+    let is_target = |texture: &Arc<RwLock<TextureData>>| {
+        colors.iter().chain(depth.iter()).any(|target| Arc::ptr_eq(target, texture))
+    };
+
+    // Textures that are also being drawn to read as unbound instead of
+    // deadlocking, sampling them is undefined in OpenGL as well.
+    let sampler = Sampler {
+        textures: context
+            .textures
+            .iter()
+            .filter(|(_, texture)| !is_target(texture))
+            .map(|(slot, texture)| (*slot, texture.read().unwrap()))
+            .collect(),
+    };
+    let mut color = colors.first().map(|texture| texture.write().unwrap());
+    let mut depth = depth.as_ref().map(|texture| texture.write().unwrap());
+    let uniforms = program.get_uniforms();
+
+    let (x, y, viewport_width, viewport_height) = context.viewport;
+    let mut bounds = Bounds {
+        min_x: x.max(0),
+        min_y: y.max(0),
+        max_x: (x + viewport_width as i32).min(width as i32),
+        max_y: (y + viewport_height as i32).min(height as i32),
+    };
+    if let Some(scissor) = context.scissor {
+        bounds.min_x = bounds.min_x.max(scissor.x);
+        bounds.min_y = bounds.min_y.max(scissor.y);
+        bounds.max_x = bounds.max_x.min(scissor.x + scissor.width as i32);
+        bounds.max_y = bounds.max_y.min(scissor.y + scissor.height as i32);
+    }
+
+    let mut drawn = 0;
+    for triangle in vertices.chunks_exact(3) {
+        let polygon = clip_near(triangle);
+        for index in 1..polygon.len().saturating_sub(1) {
+            let screen = [
+                to_screen(&polygon[0], context.viewport),
+                to_screen(&polygon[index], context.viewport),
+                to_screen(&polygon[index + 1], context.viewport),
+            ];
+
+            let mut fragments = Fragments {
+                context,
+                program,
+                uniforms: &uniforms,
+                sampler: &sampler,
+                color: color.as_deref_mut(),
+                depth: depth.as_deref_mut(),
+            };
+            if fragments.rasterize(&screen, bounds) {
+                drawn += 1;
+            }
+        }
+    }
+
+    return drawn;
+}
+
+// Sutherland-Hodgman against the near plane, the other planes are handled
+// by the pixel bounds and the depth range check.
+fn clip_near(triangle: &[VertexOutput]) -> Vec<VertexOutput> {
+    let distance = |vertex: &VertexOutput| vertex.position.z + vertex.position.w;
+    let mut polygon = Vec::new();
+    for index in 0..3 {
+        let current = &triangle[index];
+        let next = &triangle[(index + 1) % 3];
+        let (current_distance, next_distance) = (distance(current), distance(next));
+
+        if current_distance >= 0.0 {
+            polygon.push(copy_vertex(current));
+        }
+        if (current_distance >= 0.0) != (next_distance >= 0.0) {
+            let factor = current_distance / (current_distance - next_distance);
+            polygon.push(lerp_vertex(current, next, factor));
+        }
+    }
+
+    if polygon.iter().any(|vertex| vertex.position.w <= f32::EPSILON) {
+        return Vec::new();
+    }
+
+    return polygon;
+}
+
+fn copy_vertex(vertex: &VertexOutput) -> VertexOutput {
+    return VertexOutput {
+        position: vertex.position,
+        varyings: vertex.varyings.clone(),
+    };
+}
+
+fn lerp_vertex(from: &VertexOutput, to: &VertexOutput, factor: f32) -> VertexOutput {
+    return VertexOutput {
+        position: from.position.lerp(&to.position, factor),
+        varyings: from
+            .varyings
+            .iter()
+            .zip(&to.varyings)
+            .map(|(from, to)| from + (to - from) * factor)
+            .collect(),
+    };
+}
+
+fn to_screen(vertex: &VertexOutput, viewport: (i32, i32, u32, u32)) -> ScreenVertex {
+    let (x, y, width, height) = viewport;
+    let inverse_w = 1.0 / vertex.position.w;
+    let ndc = vertex.position.xyz() * inverse_w;
+
+    return ScreenVertex {
+        position: Vector3::new(
+            x as f32 + (ndc.x + 1.0) * 0.5 * width as f32,
+            y as f32 + (ndc.y + 1.0) * 0.5 * height as f32,
+            (ndc.z + 1.0) * 0.5,
+        ),
+        inverse_w,
+        varyings: vertex.varyings.iter().map(|value| value * inverse_w).collect(),
+    };
+}
+
+fn edge(from: &Vector3<f32>, to: &Vector3<f32>, x: f32, y: f32) -> f32 {
+    return (to.x - from.x) * (y - from.y) - (to.y - from.y) * (x - from.x);
+}
+
+// Pixels exactly on an edge belong to the triangle only for top and left
+// edges, so triangles sharing an edge don't draw it twice.
+fn is_top_left(from: &Vector3<f32>, to: &Vector3<f32>) -> bool {
+    return (from.y == to.y && to.x < from.x) || to.y < from.y;
+}
+
+struct Fragments<'a, 'b> {
+    context: &'a Context,
+    program: &'a ProgramState,
+    uniforms: &'a Uniforms,
+    sampler: &'a Sampler<'b>,
+    color: Option<&'a mut TextureData>,
+    depth: Option<&'a mut TextureData>,
+}
+
+impl<'a, 'b> Fragments<'a, 'b> {
+    fn rasterize(&mut self, vertices: &[ScreenVertex; 3], bounds: Bounds) -> bool {
+        let signed_area = edge(&vertices[0].position, &vertices[1].position, vertices[2].position.x, vertices[2].position.y);
+        if signed_area == 0.0 || !signed_area.is_finite() {
+            return false;
+        }
+
+        // Window coordinates have y up, so counter clockwise is positive.
+        let front = (signed_area > 0.0) == (self.context.front_face == FrontFace::CounterClockwise);
+        let culled = match self.context.cull_mode {
+            CullMode::None => false,
+            CullMode::Front => front,
+            CullMode::Back => !front,
+            CullMode::FrontAndBack => true,
+        };
+        if culled {
+            return false;
+        }
+
+        let [a, b, c] = if signed_area > 0.0 {
+            [&vertices[0], &vertices[1], &vertices[2]]
+        } else {
+            [&vertices[0], &vertices[2], &vertices[1]]
+        };
+        let area = signed_area.abs();
+
+        let min_x = (a.position.x.min(b.position.x).min(c.position.x).floor() as i32).max(bounds.min_x);
+        let min_y = (a.position.y.min(b.position.y).min(c.position.y).floor() as i32).max(bounds.min_y);
+        let max_x = (a.position.x.max(b.position.x).max(c.position.x).ceil() as i32).min(bounds.max_x);
+        let max_y = (a.position.y.max(b.position.y).max(c.position.y).ceil() as i32).min(bounds.max_y);
+
+        let top_left = [
+            is_top_left(&b.position, &c.position),
+            is_top_left(&c.position, &a.position),
+            is_top_left(&a.position, &b.position),
+        ];
+        let mut varyings = vec![0.0; a.varyings.len()];
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (sample_x, sample_y) = (x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    edge(&b.position, &c.position, sample_x, sample_y),
+                    edge(&c.position, &a.position, sample_x, sample_y),
+                    edge(&a.position, &b.position, sample_x, sample_y),
+                ];
+                let inside = weights
+                    .iter()
+                    .zip(top_left)
+                    .all(|(weight, top_left)| *weight > 0.0 || (*weight == 0.0 && top_left));
+                if !inside {
+                    continue;
+                }
+
+                let [l0, l1, l2] = weights.map(|weight| weight / area);
+                let z = l0 * a.position.z + l1 * b.position.z + l2 * c.position.z;
+                if !(0.0..=1.0).contains(&z) {
+                    continue;
+                }
+
+                let inverse_w = l0 * a.inverse_w + l1 * b.inverse_w + l2 * c.inverse_w;
+                for (index, varying) in varyings.iter_mut().enumerate() {
+                    *varying = (l0 * a.varyings[index] + l1 * b.varyings[index] + l2 * c.varyings[index])
+                        / inverse_w;
+                }
+
+                self.shade(x as u32, y as u32, z, front, &varyings);
+            }
+        }
+
+        return true;
+    }
+
+    fn shade(&mut self, x: u32, y: u32, z: f32, front: bool, varyings: &[f32]) {
+        let output = match self.program.program.fragment(varyings, self.uniforms, self.sampler) {
+            Some(output) => output,
+            None => return,
+        };
+
+        if !self.test_depth_stencil(x, y, z, front) {
+            return;
+        }
+
+        let context = self.context;
+        let color = match self.color.as_deref_mut() {
+            Some(color) => color,
+            None => return,
+        };
+
+        let mut source = output;
+        if color.is_normalized() {
+            source = source.map(|value| value.clamp(0.0, 1.0));
+        }
+
+        let destination = color.get_texel(x, y, 0);
+        let state = context.blend_state;
+        let mut result = if state.enabled {
+            let constant = context.blend_constant;
+            let src_color = blend_factor(state.src_color, source, destination, constant);
+            let dst_color = blend_factor(state.dst_color, source, destination, constant);
+            let src_alpha = blend_factor(state.src_alpha, source, destination, constant);
+            let dst_alpha = blend_factor(state.dst_alpha, source, destination, constant);
+
+            let rgb = blend_operation(state.color_operation, source, destination, src_color, dst_color);
+            let alpha = blend_operation(state.alpha_operation, source, destination, src_alpha, dst_alpha);
+            Vector4::new(rgb.x, rgb.y, rgb.z, alpha.w)
+        } else {
+            source
+        };
+
+        if color.is_normalized() {
+            result = result.map(|value| value.clamp(0.0, 1.0));
+        }
+
+        let mask = context.color_mask;
+        let masked = Vector4::new(
+            if mask.red { result.x } else { destination.x },
+            if mask.green { result.y } else { destination.y },
+            if mask.blue { result.z } else { destination.z },
+            if mask.alpha { result.w } else { destination.w },
+        );
+
+        color.set_texel(x, y, 0, expand_written(color.format, masked));
+    }
+
+    // Runs the stencil and depth tests and applies the stencil operations,
+    // a missing depth attachment passes both like in OpenGL.
+    fn test_depth_stencil(&mut self, x: u32, y: u32, z: f32, front: bool) -> bool {
+        let context = self.context;
+        let depth = match self.depth.as_deref_mut() {
+            Some(depth) => depth,
+            None => return true,
+        };
+
+        let mut texel = depth.get_texel(x, y, 0);
+        let has_stencil = depth.format == TextureFormat::DepthStencil;
+        let stencil_state = context.stencil_state;
+        let stencil_enabled = stencil_state.enabled && has_stencil;
+        let face = if front { stencil_state.front } else { stencil_state.back };
+        let stored = texel.y as u32;
+
+        if stencil_enabled {
+            let reference = stencil_state.reference as u32 & stencil_state.read_mask;
+            if !compare(face.compare, reference as f32, (stored & stencil_state.read_mask) as f32) {
+                texel.y = apply_stencil(face.fail, stored, stencil_state.reference, stencil_state.write_mask);
+                depth.set_texel(x, y, 0, texel);
+                return false;
+            }
+        }
+
+        let depth_state = context.depth_state;
+        let passed = !depth_state.test || compare(depth_state.compare, z, texel.x);
+        if stencil_enabled {
+            let operation = Self::get_stencil_operation(face, passed);
+            texel.y = apply_stencil(operation, stored, stencil_state.reference, stencil_state.write_mask);
+        }
+        if passed && depth_state.test && depth_state.write {
+            texel.x = z;
+        }
+
+        depth.set_texel(x, y, 0, texel);
+        return passed;
+    }
+
+    fn get_stencil_operation(face: StencilFaceState, depth_passed: bool) -> StencilOperation {
+        if depth_passed {
+            return face.pass;
+        }
+
+        return face.depth_fail;
+    }
+}
+
+// Keeps the channels a format doesn't store at their defaults.
+fn expand_written(format: TextureFormat, value: Vector4<f32>) -> Vector4<f32> {
+    match format {
+        TextureFormat::RGB => Vector4::new(value.x, value.y, value.z, 1.0),
+        TextureFormat::Red => Vector4::new(value.x, 0.0, 0.0, 1.0),
+        TextureFormat::Green => Vector4::new(0.0, value.y, 0.0, 1.0),
+        TextureFormat::Blue => Vector4::new(0.0, 0.0, value.z, 1.0),
+        TextureFormat::Alpha => Vector4::new(0.0, 0.0, 0.0, value.w),
+        _ => value,
+    }
+}
+
+pub fn compare(function: CompareFunction, incoming: f32, stored: f32) -> bool {
+    match function {
+        CompareFunction::Never => false,
+        CompareFunction::Less => incoming < stored,
+        CompareFunction::Equal => incoming == stored,
+        CompareFunction::LessEqual => incoming <= stored,
+        CompareFunction::Greater => incoming > stored,
+        CompareFunction::NotEqual => incoming != stored,
+        CompareFunction::GreaterEqual => incoming >= stored,
+        CompareFunction::Always => true,
+    }
+}
+
+// Stencil values are 8 bits wide.
+fn apply_stencil(operation: StencilOperation, stored: u32, reference: i32, write_mask: u32) -> f32 {
+    let value = match operation {
+        StencilOperation::Keep => stored,
+        StencilOperation::Zero => 0,
+        StencilOperation::Replace => reference as u32,
+        StencilOperation::Increment => (stored + 1).min(0xff),
+        StencilOperation::IncrementWrap => (stored + 1) & 0xff,
+        StencilOperation::Decrement => stored.saturating_sub(1),
+        StencilOperation::DecrementWrap => stored.wrapping_sub(1) & 0xff,
+        StencilOperation::Invert => !stored,
+    };
+
+    return (((stored & !write_mask) | (value & write_mask)) & 0xff) as f32;
+}
+
+fn blend_factor(
+    factor: BlendFactor,
+    source: Vector4<f32>,
+    destination: Vector4<f32>,
+    constant: Vector4<f32>,
+) -> Vector4<f32> {
+    let one = Vector4::repeat(1.0);
+    match factor {
+        BlendFactor::Zero => Vector4::zeros(),
+        BlendFactor::One => one,
+        BlendFactor::SrcColor => source,
+        BlendFactor::OneMinusSrcColor => one - source,
+        BlendFactor::DstColor => destination,
+        BlendFactor::OneMinusDstColor => one - destination,
+        BlendFactor::SrcAlpha => Vector4::repeat(source.w),
+        BlendFactor::OneMinusSrcAlpha => Vector4::repeat(1.0 - source.w),
+        BlendFactor::DstAlpha => Vector4::repeat(destination.w),
+        BlendFactor::OneMinusDstAlpha => Vector4::repeat(1.0 - destination.w),
+        BlendFactor::ConstantColor => constant,
+        BlendFactor::OneMinusConstantColor => one - constant,
+    }
+}
+
+// Min and max ignore the factors.
+fn blend_operation(
+    operation: BlendOperation,
+    source: Vector4<f32>,
+    destination: Vector4<f32>,
+    source_factor: Vector4<f32>,
+    destination_factor: Vector4<f32>,
+) -> Vector4<f32> {
+    let weighted_source = source.component_mul(&source_factor);
+    let weighted_destination = destination.component_mul(&destination_factor);
+    match operation {
+        BlendOperation::Add => weighted_source + weighted_destination,
+        BlendOperation::Subtract => weighted_source - weighted_destination,
+        BlendOperation::ReverseSubtract => weighted_destination - weighted_source,
+        BlendOperation::Min => source.inf(&destination),
+        BlendOperation::Max => source.sup(&destination),
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use nalgebra::{Matrix4, Vector3, Vector4};
+use once_cell::sync::Lazy;
+
+use crate::render::{
+    buffer::Bindable,
+    shader::{Shader, ShaderAttribute, UniformValue},
+    software::{SoftwareProgram, TextureSampler, Uniforms, VertexOutput},
+};
+
+use super::with_context;
+
+// Programs standing in for GLSL files, keyed by the path they are loaded
+// from. Shared by all threads since registering usually happens once.
+static PROGRAMS: Lazy<Mutex<HashMap<String, Arc<dyn SoftwareProgram>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn register_program(path: &str, program: Arc<dyn SoftwareProgram>) {
+    PROGRAMS.lock().unwrap().insert(String::from(path), program);
+}
+
+// Used for GLSL without a registered program. Passes the first attribute
+// through as the position and draws white.
+struct FallbackProgram;
+
+impl SoftwareProgram for FallbackProgram {
+    fn vertex(&self, attributes: &[Vector4<f32>], _uniforms: &Uniforms) -> VertexOutput {
+        return VertexOutput {
+            position: attributes.first().copied().unwrap_or_else(Vector4::zeros),
+            varyings: Vec::new(),
+        };
+    }
+
+    fn fragment(
+        &self,
+        _varyings: &[f32],
+        _uniforms: &Uniforms,
+        _textures: &dyn TextureSampler,
+    ) -> Option<Vector4<f32>> {
+        return Some(Vector4::repeat(1.0));
+    }
+}
+
+pub struct ProgramState {
+    pub program: Arc<dyn SoftwareProgram>,
+    uniforms: Mutex<Uniforms>,
+}
+
+impl ProgramState {
+    pub fn get_uniforms(&self) -> Uniforms {
+        return self.uniforms.lock().unwrap().clone();
+    }
+}
+
+pub struct SoftwareShader {
+    id: u32,
+    state: Arc<ProgramState>,
+}
+
+impl SoftwareShader {
+    pub fn new() -> SoftwareShader {
+        return SoftwareShader::new_with_program(Arc::new(FallbackProgram));
+    }
+
+    pub fn new_from_file(file_path: &str) -> SoftwareShader {
+        let mut shader = SoftwareShader::new();
+        shader.load_from_file(file_path);
+
+        return shader;
+    }
+
+    pub fn new_from_source(source: &str) -> SoftwareShader {
+        let mut shader = SoftwareShader::new();
+        shader.load_from_source(source);
+
+        return shader;
+    }
+
+    pub fn new_with_program(program: Arc<dyn SoftwareProgram>) -> SoftwareShader {
+        return SoftwareShader {
+            id: with_context(|context| context.generate_id()),
+            state: Arc::new(ProgramState {
+                program,
+                uniforms: Mutex::new(Uniforms::default()),
+            }),
+        };
+    }
+
+    fn set_program(&mut self, program: Arc<dyn SoftwareProgram>) {
+        self.state = Arc::new(ProgramState {
+            program,
+            uniforms: Mutex::new(self.state.get_uniforms()),
+        });
+    }
+
+    fn set_uniform(&self, name: &str, value: UniformValue) {
+        self.state.uniforms.lock().unwrap().set(name, value);
+    }
+}
+
+impl Shader for SoftwareShader {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    // Files don't have to exist when a program was registered for them.
+    fn load_from_file(&mut self, file_path: &str) {
+        let program = PROGRAMS.lock().unwrap().get(file_path).cloned();
+        match program {
+            Some(program) => self.set_program(program),
+            None => {
+                let file_content =
+                    fs::read_to_string(file_path).expect("Couldn't read shader file");
+                self.load_from_source(&file_content);
+            }
+        }
+    }
+
+    fn load_from_source(&mut self, _source: &str) {
+        log::warn!("GLSL can't run on the software renderer, using the fallback program");
+        self.set_program(Arc::new(FallbackProgram));
+    }
+
+    fn set_label(&self, _label: &str) {}
+
+    // Programs take whatever attributes the vertex array has.
+    fn get_attributes(&self) -> Option<Vec<ShaderAttribute>> {
+        return None;
+    }
+
+    fn submit_uniform_i32(&self, name: &str, value: i32) {
+        self.set_uniform(name, UniformValue::I32(value));
+    }
+
+    fn submit_uniform_vec3(&self, name: &str, value: Vector3<f32>) {
+        self.set_uniform(name, UniformValue::Vec3(value));
+    }
+
+    fn submit_uniform_mat4x4(&self, name: &str, data: Matrix4<f32>) {
+        self.set_uniform(name, UniformValue::Mat4(data));
+    }
+}
+
+impl Bindable for SoftwareShader {
+    fn bind(&self) {
+        with_context(|context| context.program = Some(self.state.clone()));
+    }
+
+    fn unbind(&self) {
+        with_context(|context| context.program = None);
+    }
+}
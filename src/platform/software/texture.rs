@@ -0,0 +1,325 @@
+use std::sync::{Arc, RwLock};
+
+use nalgebra::{Vector2, Vector3, Vector4};
+
+use crate::render::{
+    buffer::{BufferDataType, DataReader},
+    texture::{PixelBuffer, PixelData, Texture, TextureDimension, TextureFormat},
+    texture_container::{CompressedTexture, TextureLoadError},
+    texture_decoder::{decode_to_rgba, get_decoded_data_type},
+};
+
+use super::with_context;
+
+// Texels are expanded to RGBA floats the way shaders see them, e.g. red
+// textures read as (r, 0, 0, 1). Depth textures keep the depth in red and
+// the stencil value in green.
+pub struct TextureData {
+    pub format: TextureFormat,
+    pub data_type: BufferDataType,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub texels: Vec<Vector4<f32>>,
+}
+
+impl TextureData {
+    pub fn new(
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> TextureData {
+        let empty = match format {
+            TextureFormat::Alpha => Vector4::new(0.0, 0.0, 0.0, 0.0),
+            _ => Vector4::new(0.0, 0.0, 0.0, 1.0),
+        };
+
+        return TextureData {
+            format,
+            data_type,
+            width,
+            height,
+            layers,
+            texels: vec![empty; (width * height * layers) as usize],
+        };
+    }
+
+    pub fn get_layer_length(&self) -> usize {
+        return (self.width * self.height) as usize;
+    }
+
+    // Fixed point formats clamp what is written to them.
+    pub fn is_normalized(&self) -> bool {
+        match self.data_type {
+            BufferDataType::F32 | BufferDataType::F64 => false,
+            _ => true,
+        }
+    }
+
+    pub fn get_texel(&self, x: u32, y: u32, layer: u32) -> Vector4<f32> {
+        return self.texels[(layer * self.width * self.height + y * self.width + x) as usize];
+    }
+
+    pub fn set_texel(&mut self, x: u32, y: u32, layer: u32, texel: Vector4<f32>) {
+        let index = (layer * self.width * self.height + y * self.width + x) as usize;
+        self.texels[index] = texel;
+    }
+
+    pub fn upload(&mut self, first_layer: u32, layers: u32, data: *const u8, data_type: BufferDataType) {
+        let channels = self.format.get_channel_count() as usize;
+        let length = self.get_layer_length() * layers as usize;
+        let mut reader = DataReader::new(data, data_type, true);
+        let offset = self.get_layer_length() * first_layer as usize;
+        for texel in &mut self.texels[offset..offset + length] {
+            let mut values = [0.0; 4];
+            for value in values.iter_mut().take(channels) {
+                *value = reader.read();
+            }
+            *texel = Self::expand(self.format, values);
+        }
+    }
+
+    pub fn read(&self, data_type: BufferDataType) -> PixelData {
+        let channels = self.format.get_channel_count() as usize;
+        let mut values = Vec::with_capacity(self.texels.len() * channels);
+        for texel in &self.texels {
+            values.extend_from_slice(&Self::collapse(self.format, *texel)[..channels]);
+        }
+
+        return PixelData::from_f32(&values, data_type);
+    }
+
+    // Bilinear with clamp to edge, texel centers sit at half coordinates.
+    pub fn sample(&self, coordinates: Vector2<f32>, layer: u32) -> Vector4<f32> {
+        if self.texels.is_empty() {
+            return Vector4::zeros();
+        }
+
+        let layer = layer.min(self.layers - 1);
+        let x = (coordinates.x * self.width as f32 - 0.5).clamp(0.0, (self.width - 1) as f32);
+        let y = (coordinates.y * self.height as f32 - 0.5).clamp(0.0, (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let top = self.get_texel(x0, y0, layer).lerp(&self.get_texel(x1, y0, layer), fx);
+        let bottom = self.get_texel(x0, y1, layer).lerp(&self.get_texel(x1, y1, layer), fx);
+
+        return top.lerp(&bottom, fy);
+    }
+
+    // Picks the face along the major axis, using the face orientations of
+    // the OpenGL specification.
+    pub fn sample_cube(&self, direction: Vector3<f32>) -> Vector4<f32> {
+        let absolute = direction.abs();
+        let (face, major, s, t) = if absolute.x >= absolute.y && absolute.x >= absolute.z {
+            if direction.x > 0.0 {
+                (0, absolute.x, -direction.z, -direction.y)
+            } else {
+                (1, absolute.x, direction.z, -direction.y)
+            }
+        } else if absolute.y >= absolute.z {
+            if direction.y > 0.0 {
+                (2, absolute.y, direction.x, direction.z)
+            } else {
+                (3, absolute.y, direction.x, -direction.z)
+            }
+        } else if direction.z > 0.0 {
+            (4, absolute.z, direction.x, -direction.y)
+        } else {
+            (5, absolute.z, -direction.x, -direction.y)
+        };
+
+        if major == 0.0 {
+            return Vector4::zeros();
+        }
+
+        let coordinates = Vector2::new((s / major + 1.0) * 0.5, (t / major + 1.0) * 0.5);
+        return self.sample(coordinates, face);
+    }
+
+    fn expand(format: TextureFormat, values: [f32; 4]) -> Vector4<f32> {
+        let [first, second, third, fourth] = values;
+        match format {
+            TextureFormat::RGBA => Vector4::new(first, second, third, fourth),
+            TextureFormat::RGB => Vector4::new(first, second, third, 1.0),
+            TextureFormat::Red | TextureFormat::Depth | TextureFormat::DepthStencil => {
+                Vector4::new(first, 0.0, 0.0, 1.0)
+            }
+            TextureFormat::Green => Vector4::new(0.0, first, 0.0, 1.0),
+            TextureFormat::Blue => Vector4::new(0.0, 0.0, first, 1.0),
+            TextureFormat::Alpha => Vector4::new(0.0, 0.0, 0.0, first),
+        }
+    }
+
+    fn collapse(format: TextureFormat, texel: Vector4<f32>) -> [f32; 4] {
+        match format {
+            TextureFormat::RGBA => [texel.x, texel.y, texel.z, texel.w],
+            TextureFormat::RGB => [texel.x, texel.y, texel.z, 0.0],
+            TextureFormat::Red | TextureFormat::Depth | TextureFormat::DepthStencil => {
+                [texel.x, 0.0, 0.0, 0.0]
+            }
+            TextureFormat::Green => [texel.y, 0.0, 0.0, 0.0],
+            TextureFormat::Blue => [texel.z, 0.0, 0.0, 0.0],
+            TextureFormat::Alpha => [texel.w, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SoftwareTexture {
+    id: u32,
+    data: Arc<RwLock<TextureData>>,
+}
+
+impl SoftwareTexture {
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> SoftwareTexture {
+        return SoftwareTexture::new_with_dimension(
+            TextureDimension::Texture2D,
+            width,
+            height,
+            1,
+            data,
+            format,
+            data_type,
+        );
+    }
+
+    // Same layout as the OpenGL textures, layers are tightly packed one
+    // after another and data may be null.
+    pub fn new_with_dimension(
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> SoftwareTexture {
+        let (height, layers) = match dimension {
+            TextureDimension::Texture1D => (1, 1),
+            TextureDimension::Texture2D => (height, 1),
+            TextureDimension::Texture2DArray | TextureDimension::Texture3D => (height, depth),
+            TextureDimension::Cube => (height, 6),
+            TextureDimension::CubeArray => (height, depth * 6),
+        };
+
+        let mut texture_data = TextureData::new(width, height, layers, format, data_type);
+        if !data.is_null() {
+            texture_data.upload(0, layers, data, data_type);
+        }
+
+        return SoftwareTexture::from_data(texture_data);
+    }
+
+    pub fn new_cubemap(
+        size: u32,
+        faces: [*const u8; 6],
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> SoftwareTexture {
+        let mut texture_data =
+            TextureData::new(size, size, 6, format, data_type);
+        for (face, data) in faces.iter().enumerate() {
+            if !data.is_null() {
+                texture_data.upload(face as u32, 1, *data, data_type);
+            }
+        }
+
+        return SoftwareTexture::from_data(texture_data);
+    }
+
+    // Only the base level is kept, formats are decoded to RGBA8 first, or
+    // to RGBA F32 for BC6H.
+    pub fn new_compressed(texture: &CompressedTexture) -> Result<SoftwareTexture, TextureLoadError> {
+        let (width, height) = texture.get_level_size(0);
+        let images = (texture.layers * texture.faces) as usize;
+        let image_size = texture.format.get_image_size(width, height);
+        let data_type = get_decoded_data_type(texture.format);
+        let mut pixels = Vec::new();
+        for image in 0..images {
+            let data = &texture.levels[0][image * image_size..(image + 1) * image_size];
+            pixels.extend_from_slice(decode_to_rgba(texture.format, width, height, data)?.as_bytes());
+        }
+
+        let mut texture_data = TextureData::new(
+            width,
+            height,
+            images as u32,
+            TextureFormat::RGBA,
+            data_type,
+        );
+        texture_data.upload(0, images as u32, pixels.as_ptr(), data_type);
+
+        return Ok(SoftwareTexture::from_data(texture_data));
+    }
+
+    pub fn from_data(data: TextureData) -> SoftwareTexture {
+        return SoftwareTexture {
+            id: with_context(|context| context.generate_id()),
+            data: Arc::new(RwLock::new(data)),
+        };
+    }
+
+    pub fn get_data(&self) -> &Arc<RwLock<TextureData>> {
+        return &self.data;
+    }
+}
+
+impl Texture for SoftwareTexture {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn bind(&self, slot: u32) {
+        with_context(|context| {
+            context.textures.insert(slot, self.data.clone());
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context
+                .textures
+                .retain(|_, data| !Arc::ptr_eq(data, &self.data));
+        });
+    }
+
+    fn get_size(&self, level: u32) -> (u32, u32) {
+        let data = self.data.read().unwrap();
+        return ((data.width >> level).max(1), (data.height >> level).max(1));
+    }
+
+    fn get_format(&self) -> TextureFormat {
+        return self.data.read().unwrap().format;
+    }
+
+    // Layers, slices and cube faces are stacked vertically.
+    fn read_pixels(&self, _level: u32, data_type: BufferDataType) -> PixelBuffer {
+        let data = self.data.read().unwrap();
+        return PixelBuffer {
+            width: data.width,
+            height: data.height * data.layers,
+            format: data.format,
+            data: data.read(data_type),
+        };
+    }
+
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType) {
+        self.data.write().unwrap().upload(layer, 1, data, data_type);
+    }
+
+    fn is_ready(&self) -> bool {
+        return true;
+    }
+
+    fn set_label(&self, _label: &str) {}
+}
@@ -0,0 +1,388 @@
+use std::{
+    mem::size_of,
+    sync::{Arc, RwLock},
+};
+
+use wgpu::util::DeviceExt;
+
+use crate::render::buffer::{
+    Bindable, Buffer, BufferDataType, BufferLayoutNode, BufferType, BufferUsage, DataReader,
+    DrawRange, VertexArray,
+};
+
+use super::{get_gpu, pipeline, with_context, Gpu, CONTEXT};
+
+// The bytes are kept on the CPU as well, wgpu buffers can't grow and
+// writes have to be aligned to 4 bytes.
+pub struct BufferState {
+    pub buffer: Arc<wgpu::Buffer>,
+    pub index_format: wgpu::IndexFormat,
+    usage: wgpu::BufferUsages,
+    data: Vec<u8>,
+}
+
+impl BufferState {
+    fn new(gpu: &Gpu, data: Vec<u8>, usage: wgpu::BufferUsages, index_format: wgpu::IndexFormat) -> BufferState {
+        return BufferState {
+            buffer: Arc::new(Self::create_buffer(gpu, &data, usage)),
+            index_format,
+            usage,
+            data,
+        };
+    }
+
+    fn create_buffer(gpu: &Gpu, data: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        let mut contents = data.to_vec();
+        contents.resize(Self::align(contents.len()).max(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+
+        return gpu.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &contents,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        });
+    }
+
+    fn align(length: usize) -> usize {
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        return (length + alignment - 1) / alignment * alignment;
+    }
+
+    fn write(&mut self, gpu: &Gpu, offset: usize, bytes: &[u8]) {
+        let end = offset + bytes.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0);
+        }
+        self.data[offset..end].copy_from_slice(bytes);
+
+        if Self::align(end) as u64 > self.buffer.size() {
+            self.buffer = Arc::new(Self::create_buffer(gpu, &self.data, self.usage));
+            return;
+        }
+
+        let alignment = wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        let start = offset / alignment * alignment;
+        let mut range = self.data[start..end.min(self.data.len())].to_vec();
+        range.resize(Self::align(range.len()), 0);
+        gpu.queue.write_buffer(&self.buffer, start as u64, &range);
+    }
+}
+
+// Vertex attributes are converted to floats when the buffer is created,
+// which is what OpenGL does with them when drawing.
+pub struct WgpuBuffer {
+    id: u32,
+    buffer_layout: Vec<BufferLayoutNode>,
+    element_count: usize,
+    state: Arc<RwLock<BufferState>>,
+}
+
+impl WgpuBuffer {
+    pub fn new<T>(
+        data: &mut Vec<T>,
+        buffer_type: BufferType,
+        buffer_layout: Vec<BufferLayoutNode>,
+        _buffer_usage: BufferUsage,
+        size: Option<usize>,
+    ) -> WgpuBuffer {
+        let length = data.len() * size_of::<T>();
+        let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, length) };
+        let (mut bytes, usage, index_format) = match buffer_type {
+            BufferType::Vertex => (
+                Self::convert_vertices(bytes, &buffer_layout),
+                wgpu::BufferUsages::VERTEX,
+                wgpu::IndexFormat::Uint32,
+            ),
+            BufferType::Index => {
+                let data_type = match buffer_layout.first() {
+                    Some(node) => node.data_type,
+                    None => BufferDataType::U32,
+                };
+                let (bytes, index_format) = Self::convert_indices(bytes, data_type);
+                (bytes, wgpu::BufferUsages::INDEX, index_format)
+            }
+            // Writable from compute shaders.
+            BufferType::Indirect => (
+                bytes.to_vec(),
+                wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::STORAGE,
+                wgpu::IndexFormat::Uint32,
+            ),
+        };
+        if let Some(size) = size {
+            let size = Self::get_converted_size(size, buffer_type, &buffer_layout);
+            bytes.resize(bytes.len().max(size), 0);
+        }
+
+        let element_count =
+            Self::calculate_element_count(data.len(), buffer_layout.clone(), buffer_type);
+        let state = Arc::new(RwLock::new(BufferState::new(&get_gpu(), bytes, usage, index_format)));
+        let id = with_context(|context| {
+            let id = context.generate_id();
+            context.buffers.insert(id, state.clone());
+            id
+        });
+
+        return WgpuBuffer {
+            id,
+            buffer_layout,
+            element_count,
+            state,
+        };
+    }
+
+    fn convert_vertices(bytes: &[u8], layout: &[BufferLayoutNode]) -> Vec<u8> {
+        if layout.iter().all(|node| node.data_type == BufferDataType::F32) {
+            return bytes.to_vec();
+        }
+
+        let stride: usize = layout
+            .iter()
+            .map(|node| node.data_type.get_size() * node.elements as usize)
+            .sum();
+        let mut converted = Vec::new();
+        for vertex in bytes.chunks_exact(stride.max(1)) {
+            let mut offset = 0;
+            for node in layout {
+                let mut reader = DataReader::new(vertex[offset..].as_ptr(), node.data_type, node.normalized);
+                for _ in 0..node.elements {
+                    converted.extend_from_slice(&reader.read().to_ne_bytes());
+                }
+                offset += node.data_type.get_size() * node.elements as usize;
+            }
+        }
+
+        return converted;
+    }
+
+    // Reserved sizes are given for the layout the data comes in.
+    fn get_converted_size(size: usize, buffer_type: BufferType, layout: &[BufferLayoutNode]) -> usize {
+        let (original, converted) = match buffer_type {
+            BufferType::Vertex => layout.iter().fold((0, 0), |(original, converted), node| {
+                let elements = node.elements as usize;
+                (original + node.data_type.get_size() * elements, converted + size_of::<f32>() * elements)
+            }),
+            BufferType::Index => match layout.first().map(|node| node.data_type) {
+                Some(BufferDataType::U8 | BufferDataType::I8) => (1, 2),
+                Some(BufferDataType::F64) => (2, 1),
+                _ => (1, 1),
+            },
+            BufferType::Indirect => (1, 1),
+        };
+
+        return size / original.max(1) * converted;
+    }
+
+    // wgpu only takes 16 and 32 bit indices.
+    fn convert_indices(bytes: &[u8], data_type: BufferDataType) -> (Vec<u8>, wgpu::IndexFormat) {
+        match data_type {
+            BufferDataType::U8 | BufferDataType::I8 => (
+                bytes.iter().flat_map(|index| (*index as u16).to_ne_bytes()).collect(),
+                wgpu::IndexFormat::Uint16,
+            ),
+            BufferDataType::U16 | BufferDataType::I16 => (bytes.to_vec(), wgpu::IndexFormat::Uint16),
+            BufferDataType::U32 | BufferDataType::I32 => (bytes.to_vec(), wgpu::IndexFormat::Uint32),
+            BufferDataType::F32 | BufferDataType::F64 => {
+                let mut reader = DataReader::new(bytes.as_ptr(), data_type, false);
+                let count = bytes.len() / data_type.get_size();
+                (
+                    (0..count).flat_map(|_| (reader.read() as u32).to_ne_bytes()).collect(),
+                    wgpu::IndexFormat::Uint32,
+                )
+            }
+        }
+    }
+
+    // Same counting as the OpenGL buffers, the element count of vertex
+    // buffers is the number of vertices.
+    fn calculate_element_count(
+        data_len: usize,
+        layout: Vec<BufferLayoutNode>,
+        buffer_type: BufferType,
+    ) -> usize {
+        let layout_size = BufferLayoutNode::get_layout_size(layout);
+        if buffer_type == BufferType::Vertex {
+            return data_len / layout_size;
+        } else {
+            return data_len;
+        }
+    }
+}
+
+impl Drop for WgpuBuffer {
+    fn drop(&mut self) {
+        // The context may already be gone when the thread exits.
+        let _ = CONTEXT.try_with(|context| {
+            if let Ok(mut context) = context.try_borrow_mut() {
+                context.buffers.remove(&self.id);
+            }
+        });
+    }
+}
+
+impl Bindable for WgpuBuffer {
+    fn bind(&self) {}
+
+    fn unbind(&self) {}
+}
+
+impl Buffer for WgpuBuffer {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn get_layout(&self) -> &Vec<BufferLayoutNode> {
+        return &self.buffer_layout;
+    }
+
+    fn get_element_count(&self) -> usize {
+        return self.element_count;
+    }
+
+    // Offset is in bytes, like glBufferSubData. Queued draws are submitted
+    // first so they still see the old contents.
+    fn add_sub_data(&mut self, data: Vec<f32>, offset: usize) {
+        let gpu = get_gpu();
+        with_context(|context| context.flush());
+
+        let bytes: Vec<u8> = data.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        self.state.write().unwrap().write(&gpu, offset, &bytes);
+
+        self.element_count = if offset == 0 {
+            Self::calculate_element_count(data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        } else {
+            Self::calculate_element_count(offset + data.len(), self.buffer_layout.clone(), BufferType::Vertex)
+        };
+    }
+
+    fn set_label(&self, _label: &str) {}
+}
+
+pub struct WgpuVertexArray {
+    vertex_buffers: Vec<Box<dyn Buffer>>,
+    vertex_layouts: Vec<(Arc<RwLock<BufferState>>, u64, Vec<wgpu::VertexAttribute>)>,
+    index_buffer: Option<(Box<dyn Buffer>, Arc<RwLock<BufferState>>)>,
+    attributes: u32,
+    element_count: usize,
+}
+
+impl WgpuVertexArray {
+    pub fn new() -> WgpuVertexArray {
+        return WgpuVertexArray {
+            vertex_buffers: Vec::new(),
+            vertex_layouts: Vec::new(),
+            index_buffer: None,
+            attributes: 0,
+            element_count: 0,
+        };
+    }
+
+    fn get_state(buffer: &Box<dyn Buffer>) -> Arc<RwLock<BufferState>> {
+        return with_context(|context| context.buffers.get(&buffer.get_id()).cloned())
+            .expect("Buffer doesn't belong to the wgpu renderer");
+    }
+
+    fn submit(&self, call: pipeline::DrawCall) {
+        let vertex_buffers = self
+            .vertex_layouts
+            .iter()
+            .map(|(state, stride, attributes)| pipeline::VertexInput {
+                buffer: state.read().unwrap().buffer.clone(),
+                stride: *stride,
+                attributes: attributes.clone(),
+            })
+            .collect();
+        let index_buffer = self.index_buffer.as_ref().map(|(_, state)| {
+            let state = state.read().unwrap();
+            pipeline::IndexInput {
+                buffer: state.buffer.clone(),
+                format: state.index_format,
+            }
+        });
+
+        with_context(|context| pipeline::draw(context, vertex_buffers, index_buffer, call));
+    }
+
+    fn get_vertex_format(elements: u32) -> wgpu::VertexFormat {
+        match elements {
+            1 => wgpu::VertexFormat::Float32,
+            2 => wgpu::VertexFormat::Float32x2,
+            3 => wgpu::VertexFormat::Float32x3,
+            _ => wgpu::VertexFormat::Float32x4,
+        }
+    }
+}
+
+impl Bindable for WgpuVertexArray {
+    fn bind(&self) {}
+
+    fn unbind(&self) {}
+}
+
+impl VertexArray for WgpuVertexArray {
+    fn draw(&self) {
+        let count = match &self.index_buffer {
+            Some((buffer, _)) => buffer.get_element_count(),
+            None => self.element_count,
+        };
+        self.submit(pipeline::DrawCall::Ranges(vec![DrawRange {
+            first: 0,
+            count: count as u32,
+            base_vertex: 0,
+        }]));
+    }
+
+    fn draw_range(&self, range: DrawRange) {
+        self.submit(pipeline::DrawCall::Ranges(vec![range]));
+    }
+
+    fn multi_draw(&self, ranges: &[DrawRange]) {
+        self.submit(pipeline::DrawCall::Ranges(ranges.to_vec()));
+    }
+
+    fn draw_indirect(&self, buffer: &Box<dyn Buffer>, offset: usize, count: usize) {
+        let state = Self::get_state(buffer);
+        let buffer = state.read().unwrap().buffer.clone();
+        self.submit(pipeline::DrawCall::Indirect {
+            buffer,
+            offset: offset as u64,
+            count: count as u32,
+        });
+    }
+
+    fn add_buffer(&mut self, buffer: Box<dyn Buffer>, buffer_type: BufferType) {
+        let state = Self::get_state(&buffer);
+        match buffer_type {
+            BufferType::Vertex => {
+                let mut attributes = Vec::new();
+                let mut offset = 0;
+                for node in buffer.get_layout() {
+                    attributes.push(wgpu::VertexAttribute {
+                        format: Self::get_vertex_format(node.elements),
+                        offset,
+                        shader_location: self.attributes,
+                    });
+                    self.attributes += 1;
+                    offset += (node.elements as usize * size_of::<f32>()) as u64;
+                }
+                self.element_count += buffer.get_element_count();
+                self.vertex_layouts.push((state, offset, attributes));
+                self.vertex_buffers.push(buffer);
+            }
+            BufferType::Index => {
+                self.index_buffer = Some((buffer, state));
+            }
+            BufferType::Indirect => {
+                log::warn!("Indirect buffers are passed to draw_indirect, not added to vertex arrays");
+            }
+        }
+    }
+
+    fn get_buffers(&self) -> &Vec<Box<dyn Buffer>> {
+        return &self.vertex_buffers;
+    }
+
+    fn add_buffer_sub_data(&mut self, data: Vec<f32>, offset: usize, buffer_index: usize) {
+        let old_buffer_size = self.vertex_buffers[buffer_index].get_element_count();
+        self.vertex_buffers[buffer_index].add_sub_data(data, offset);
+        self.element_count += self.vertex_buffers[buffer_index].get_element_count() - old_buffer_size;
+    }
+}
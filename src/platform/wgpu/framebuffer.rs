@@ -0,0 +1,323 @@
+use std::sync::{Arc, Mutex};
+
+use crate::render::{
+    buffer::BufferDataType,
+    framebuffer::{
+        ColorAttachment, DepthStencilFormat, Framebuffer, FramebufferDescriptor, FramebufferError,
+        FramebufferSize,
+    },
+    texture::{Texture, TextureDimension, TextureFormat},
+};
+
+use super::{
+    get_gpu,
+    texture::{get_texture_format, TextureState, WgpuTexture},
+    with_context, RenderTarget,
+};
+
+// What wgpu guarantees on every adapter.
+pub const MAX_COLOR_ATTACHMENTS: usize = 8;
+pub const MAX_SAMPLES: u32 = 4;
+
+pub struct FramebufferState {
+    id: u32,
+    width: u32,
+    height: u32,
+    samples: u32,
+    color_attachments: Vec<(WgpuTexture, ColorAttachment)>,
+    // Drawn into when multisampled and resolved into the color attachments
+    // at the end of every render pass.
+    multisampled_attachments: Vec<WgpuTexture>,
+    depth_attachment: Option<WgpuTexture>,
+    depth_stencil: DepthStencilFormat,
+    scale: Option<f32>,
+}
+
+impl FramebufferState {
+    // Reallocates the attachments in place so textures handed out before
+    // keep referring to them.
+    fn allocate(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        if width == 0 || height == 0 {
+            return Err(FramebufferError::EmptySize);
+        }
+
+        let gpu = get_gpu();
+        let create = |format: TextureFormat, data_type: BufferDataType, samples: u32| {
+            TextureState::new(&gpu, TextureDimension::Texture2D, width, height, 1, format, data_type, samples)
+        };
+
+        for (texture, attachment) in &self.color_attachments {
+            *texture.get_state().write().unwrap() = create(attachment.format, attachment.data_type, 1);
+        }
+
+        for (texture, (_, attachment)) in self.multisampled_attachments.iter().zip(&self.color_attachments) {
+            *texture.get_state().write().unwrap() =
+                create(attachment.format, attachment.data_type, self.samples);
+        }
+
+        if let Some(texture) = &self.depth_attachment {
+            let (format, data_type) = WgpuFramebuffer::get_depth_format(self.depth_stencil);
+            *texture.get_state().write().unwrap() = create(format, data_type, self.samples);
+        }
+
+        self.width = width;
+        self.height = height;
+
+        return Ok(());
+    }
+}
+
+// wgpu only guarantees 4 samples, fewer are rounded up to it.
+pub struct WgpuFramebuffer {
+    state: Arc<Mutex<FramebufferState>>,
+}
+
+impl WgpuFramebuffer {
+    pub fn new(descriptor: FramebufferDescriptor) -> Result<WgpuFramebuffer, FramebufferError> {
+        let (width, height, scale) = match descriptor.size {
+            FramebufferSize::Fixed(width, height) => (width, height, None),
+            FramebufferSize::Window(scale) => {
+                let size = with_context(|context| (context.width, context.height));
+                let (width, height) = Self::scale_window_size(size, scale);
+                (width, height, Some(scale))
+            }
+        };
+
+        if descriptor.color_attachments.is_empty()
+            && descriptor.depth_stencil == DepthStencilFormat::None
+        {
+            return Err(FramebufferError::NoAttachments);
+        }
+
+        if descriptor.color_attachments.len() > MAX_COLOR_ATTACHMENTS {
+            return Err(FramebufferError::TooManyColorAttachments {
+                requested: descriptor.color_attachments.len(),
+                max: MAX_COLOR_ATTACHMENTS,
+            });
+        }
+
+        if descriptor.samples > MAX_SAMPLES {
+            return Err(FramebufferError::TooManySamples {
+                requested: descriptor.samples,
+                max: MAX_SAMPLES,
+            });
+        }
+        let samples = if descriptor.samples > 1 { MAX_SAMPLES } else { 1 };
+
+        for attachment in &descriptor.color_attachments {
+            let features = get_texture_format(attachment.format, attachment.data_type)
+                .describe()
+                .guaranteed_format_features;
+            if !features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+                || (samples > 1 && !features.flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE))
+            {
+                return Err(FramebufferError::Incomplete(format!(
+                    "{:?} {:?} attachments can't be rendered to",
+                    attachment.format, attachment.data_type
+                )));
+            }
+        }
+
+        let color_attachments = descriptor
+            .color_attachments
+            .iter()
+            .map(|attachment| (Self::create_texture(attachment.format, attachment.data_type), *attachment))
+            .collect();
+        let multisampled_attachments = match samples {
+            1 => Vec::new(),
+            _ => descriptor
+                .color_attachments
+                .iter()
+                .map(|attachment| Self::create_texture(attachment.format, attachment.data_type))
+                .collect(),
+        };
+        let depth_attachment = match descriptor.depth_stencil {
+            DepthStencilFormat::None => None,
+            depth_stencil => {
+                let (format, data_type) = Self::get_depth_format(depth_stencil);
+                Some(Self::create_texture(format, data_type))
+            }
+        };
+
+        let mut state = FramebufferState {
+            id: with_context(|context| context.generate_id()),
+            width: 0,
+            height: 0,
+            samples,
+            color_attachments,
+            multisampled_attachments,
+            depth_attachment,
+            depth_stencil: descriptor.depth_stencil,
+            scale,
+        };
+        state.allocate(width, height)?;
+
+        let state = Arc::new(Mutex::new(state));
+        if scale.is_some() {
+            with_context(|context| context.window_framebuffers.push(Arc::downgrade(&state)));
+        }
+
+        return Ok(WgpuFramebuffer { state });
+    }
+
+    // Placeholder until the framebuffer allocates its attachments.
+    fn create_texture(format: TextureFormat, data_type: BufferDataType) -> WgpuTexture {
+        let state = TextureState::new(&get_gpu(), TextureDimension::Texture2D, 1, 1, 1, format, data_type, 1);
+        return WgpuTexture::from_state(state);
+    }
+
+    // Depth24 has no copyable wgpu format, it is stored as 32 bit float.
+    fn get_depth_format(depth_stencil: DepthStencilFormat) -> (TextureFormat, BufferDataType) {
+        match depth_stencil {
+            DepthStencilFormat::Depth24Stencil8 => (TextureFormat::DepthStencil, BufferDataType::U32),
+            _ => (TextureFormat::Depth, BufferDataType::F32),
+        }
+    }
+
+    fn scale_window_size(size: (u32, u32), scale: f32) -> (u32, u32) {
+        return (
+            ((size.0 as f32 * scale) as u32).max(1),
+            ((size.1 as f32 * scale) as u32).max(1),
+        );
+    }
+}
+
+impl Framebuffer for WgpuFramebuffer {
+    fn get_id(&self) -> u32 {
+        return self.state.lock().unwrap().id;
+    }
+
+    fn bind(&self) {
+        let state = self.state.lock().unwrap();
+        let resolves: Vec<_> = state
+            .color_attachments
+            .iter()
+            .map(|(texture, _)| texture.get_state().clone())
+            .collect();
+        let target = match state.samples {
+            1 => RenderTarget {
+                colors: resolves,
+                resolves: Vec::new(),
+                depth: None,
+            },
+            _ => RenderTarget {
+                colors: state
+                    .multisampled_attachments
+                    .iter()
+                    .map(|texture| texture.get_state().clone())
+                    .collect(),
+                resolves,
+                depth: None,
+            },
+        };
+        let target = RenderTarget {
+            depth: state
+                .depth_attachment
+                .as_ref()
+                .map(|texture| texture.get_state().clone()),
+            ..target
+        };
+
+        with_context(|context| {
+            context.target = Some(target);
+            context.viewport = (0, 0, state.width, state.height);
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context.target = None;
+            context.viewport = (0, 0, context.width, context.height);
+        });
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        let state = self.state.lock().unwrap();
+        return (state.width, state.height);
+    }
+
+    fn get_samples(&self) -> u32 {
+        return self.state.lock().unwrap().samples;
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError> {
+        return self.state.lock().unwrap().allocate(width, height);
+    }
+
+    fn get_color_attachment_count(&self) -> usize {
+        return self.state.lock().unwrap().color_attachments.len();
+    }
+
+    // Multisampled framebuffers hand out the resolved textures.
+    fn get_color_attachment(&self, index: usize) -> Option<Box<dyn Texture>> {
+        let state = self.state.lock().unwrap();
+        return state
+            .color_attachments
+            .get(index)
+            .map(|(texture, _)| Box::new(texture.clone()) as Box<dyn Texture>);
+    }
+
+    fn get_depth_attachment(&self) -> Option<Box<dyn Texture>> {
+        return self
+            .state
+            .lock()
+            .unwrap()
+            .depth_attachment
+            .clone()
+            .map(|texture| Box::new(texture) as Box<dyn Texture>);
+    }
+
+    // Multisampled attachments are resolved by every render pass already,
+    // so this copies on the GPU when size and format match and scales on
+    // the CPU otherwise.
+    fn resolve(&self, target: Option<&Box<dyn Framebuffer>>) -> Result<(), FramebufferError> {
+        let state = self.state.lock().unwrap();
+        let targets = match target {
+            Some(target) if target.get_id() == state.id => return Ok(()),
+            Some(target) => (0..target.get_color_attachment_count())
+                .filter_map(|index| target.get_color_attachment(index))
+                .map(|texture| texture.get_id())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        with_context(|context| {
+            for (index, (texture, _)) in state.color_attachments.iter().enumerate() {
+                let destination = match target {
+                    Some(_) => match targets.get(index) {
+                        Some(id) => context.get_texture(*id),
+                        None => break,
+                    },
+                    None if index == 0 => context.color.clone(),
+                    None => break,
+                };
+
+                match destination {
+                    Some(destination) => context.blit(texture.get_state(), &destination),
+                    None => log::warn!("Framebuffers can only be resolved into targets of the wgpu renderer"),
+                }
+            }
+        });
+
+        return Ok(());
+    }
+}
+
+// Window sized framebuffers that were dropped are pruned here.
+pub fn resize_window_framebuffers(width: u32, height: u32) {
+    let framebuffers = with_context(|context| {
+        context
+            .window_framebuffers
+            .retain(|framebuffer| framebuffer.strong_count() > 0);
+        context.window_framebuffers.clone()
+    });
+
+    for framebuffer in framebuffers.iter().filter_map(|framebuffer| framebuffer.upgrade()) {
+        let mut state = framebuffer.lock().unwrap();
+        let (width, height) =
+            WgpuFramebuffer::scale_window_size((width, height), state.scale.unwrap_or(1.0));
+        if let Err(error) = state.allocate(width, height) {
+            log::warn!("Failed to resize framebuffer with error\n{}", error);
+        }
+    }
+}
@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use naga::ShaderStage;
+
+// Name of the block loose uniforms are gathered into.
+const UNIFORM_BLOCK: &str = "LibrenderUniforms";
+
+// Turns shaders written for OpenGL, GLSL 330 with loose uniforms and
+// combined samplers, into the Vulkan flavour of GLSL naga reads. Both stages
+// go through the same translator so they agree on bindings and locations:
+// - loose uniforms are gathered into one block at set 0, binding 0, uniform
+//   blocks follow from binding 1,
+// - every combined sampler is split into a texture named like the sampler
+//   at set 1, binding 2 * n and a sampler at binding 2 * n + 1, n counting
+//   the samplers in declaration order,
+// - inputs and outputs without a location get one, varyings are matched by
+//   name,
+// - the vertex stage flips y, so images are stored bottom row first like in
+//   OpenGL, and maps depth from [-1, 1] to the [0, 1] range of wgpu.
+// Sources already declaring #version 440 or later are left alone.
+pub struct GlslTranslator {
+    uniforms: Vec<(String, String)>,
+    samplers: Vec<(String, String)>,
+    blocks: Vec<String>,
+    varyings: HashMap<String, u32>,
+    next_varying: u32,
+}
+
+struct Declaration {
+    layout: Option<String>,
+    qualifiers: Vec<String>,
+    storage: String,
+    type_name: String,
+    names: Vec<String>,
+}
+
+impl GlslTranslator {
+    pub fn new(sources: &[&str]) -> GlslTranslator {
+        let mut translator = GlslTranslator {
+            uniforms: Vec::new(),
+            samplers: Vec::new(),
+            blocks: Vec::new(),
+            varyings: HashMap::new(),
+            next_varying: 0,
+        };
+
+        for source in sources.iter().filter(|source| needs_translation(source)) {
+            for line in get_global_lines(source) {
+                if let Some(name) = parse_block_start(line) {
+                    if !translator.blocks.contains(&name) {
+                        translator.blocks.push(name);
+                    }
+                    continue;
+                }
+
+                let declaration = match parse_declaration(line) {
+                    Some(declaration) if declaration.storage == "uniform" => declaration,
+                    _ => continue,
+                };
+                for name in declaration.names {
+                    if split_sampler_type(&declaration.type_name).is_some() {
+                        if !translator.samplers.iter().any(|(known, _)| *known == name) {
+                            translator.samplers.push((name, declaration.type_name.clone()));
+                        }
+                    } else if !is_opaque_type(&declaration.type_name)
+                        && !translator.uniforms.iter().any(|(_, known)| *known == name)
+                    {
+                        translator.uniforms.push((declaration.type_name.clone(), name));
+                    }
+                }
+            }
+        }
+
+        return translator;
+    }
+
+    pub fn translate(&mut self, stage: ShaderStage, source: &str) -> String {
+        if !needs_translation(source) {
+            return String::from(source);
+        }
+
+        let mut output = String::new();
+        let mut depth = 0;
+        let mut has_version = false;
+        let mut inputs = 0;
+        let mut outputs = 0;
+        for line in source.lines() {
+            let global = depth == 0;
+            depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+
+            let trimmed = line.trim();
+            if global && trimmed.starts_with("#version") {
+                has_version = true;
+                output.push_str("#version 450\n");
+                output.push_str(&self.get_uniform_block());
+                continue;
+            }
+
+            if global && trimmed.starts_with("precision ") {
+                output.push('\n');
+                continue;
+            }
+
+            if global {
+                if let Some(name) = parse_block_start(trimmed) {
+                    let binding = self.blocks.iter().position(|block| *block == name).unwrap() + 1;
+                    let rest = &trimmed[trimmed.find("uniform").unwrap()..];
+                    output.push_str(&format!(
+                        "layout(std140, set = 0, binding = {}) {}\n",
+                        binding, rest
+                    ));
+                    continue;
+                }
+
+                if let Some(declaration) = parse_declaration(trimmed) {
+                    let translated = match declaration.storage.as_str() {
+                        "uniform" => self.translate_uniform(&declaration, trimmed),
+                        "in" => self.translate_stage_input(stage, &declaration, &mut inputs),
+                        _ => self.translate_stage_output(stage, &declaration, &mut outputs),
+                    };
+                    output.push_str(&translated);
+                    output.push('\n');
+                    continue;
+                }
+            }
+
+            output.push_str(&self.replace_identifiers(stage, line));
+            output.push('\n');
+        }
+
+        if !has_version {
+            output = format!("#version 450\n{}{}", self.get_uniform_block(), output);
+        }
+
+        if stage == ShaderStage::Vertex {
+            output.push_str(
+                "void main() {\n    \
+                     librender_main();\n    \
+                     gl_Position.y = -gl_Position.y;\n    \
+                     gl_Position.z = (gl_Position.z + gl_Position.w) * 0.5;\n\
+                 }\n",
+            );
+        }
+
+        return output;
+    }
+
+    fn get_uniform_block(&self) -> String {
+        if self.uniforms.is_empty() {
+            return String::new();
+        }
+
+        let members: Vec<String> = self
+            .uniforms
+            .iter()
+            .map(|(type_name, name)| format!("{} {};", type_name, name))
+            .collect();
+        return format!(
+            "layout(std140, set = 0, binding = 0) uniform {} {{ {} }};\n",
+            UNIFORM_BLOCK,
+            members.join(" ")
+        );
+    }
+
+    fn translate_uniform(&self, declaration: &Declaration, line: &str) -> String {
+        match split_sampler_type(&declaration.type_name) {
+            Some((texture_type, sampler_type)) => {
+                let mut translated = Vec::new();
+                for name in &declaration.names {
+                    let index = self.get_sampler_index(name).unwrap();
+                    translated.push(format!(
+                        "layout(set = 1, binding = {}) uniform {} {}; \
+                         layout(set = 1, binding = {}) uniform {} {}_sampler;",
+                        index * 2,
+                        texture_type,
+                        name,
+                        index * 2 + 1,
+                        sampler_type,
+                        name
+                    ));
+                }
+                translated.join(" ")
+            }
+            // Opaque types without a sampler need their layout written out.
+            None if is_opaque_type(&declaration.type_name) => String::from(line),
+            // Part of the uniform block.
+            None => String::new(),
+        }
+    }
+
+    fn translate_stage_input(
+        &mut self,
+        stage: ShaderStage,
+        declaration: &Declaration,
+        inputs: &mut u32,
+    ) -> String {
+        if has_location(declaration) {
+            return declaration.to_string(None);
+        }
+
+        let mut translated = Vec::new();
+        for name in &declaration.names {
+            let count = get_location_count(&declaration.type_name, name);
+            let location = match stage {
+                ShaderStage::Vertex => {
+                    *inputs += count;
+                    *inputs - count
+                }
+                _ => self.get_varying_location(name, count),
+            };
+            translated.push(declaration.to_string_for(name, location));
+        }
+
+        return translated.join(" ");
+    }
+
+    fn translate_stage_output(
+        &mut self,
+        stage: ShaderStage,
+        declaration: &Declaration,
+        outputs: &mut u32,
+    ) -> String {
+        if has_location(declaration) {
+            return declaration.to_string(None);
+        }
+
+        let mut translated = Vec::new();
+        for name in &declaration.names {
+            let count = get_location_count(&declaration.type_name, name);
+            let location = match stage {
+                ShaderStage::Vertex => self.get_varying_location(name, count),
+                _ => {
+                    *outputs += count;
+                    *outputs - count
+                }
+            };
+            translated.push(declaration.to_string_for(name, location));
+        }
+
+        return translated.join(" ");
+    }
+
+    fn get_varying_location(&mut self, name: &str, count: u32) -> u32 {
+        let base_name = get_base_name(name);
+        if let Some(location) = self.varyings.get(base_name) {
+            return *location;
+        }
+
+        let location = self.next_varying;
+        self.next_varying += count;
+        self.varyings.insert(String::from(base_name), location);
+
+        return location;
+    }
+
+    fn replace_identifiers(&self, stage: ShaderStage, line: &str) -> String {
+        let mut output = String::with_capacity(line.len());
+        let mut identifier = String::new();
+        for character in line.chars().chain(std::iter::once('\n')) {
+            if character.is_ascii_alphanumeric() || character == '_' {
+                identifier.push(character);
+                continue;
+            }
+
+            if !identifier.is_empty() {
+                output.push_str(&self.replace_identifier(stage, &identifier));
+                identifier.clear();
+            }
+            if character != '\n' {
+                output.push(character);
+            }
+        }
+
+        return output;
+    }
+
+    fn replace_identifier(&self, stage: ShaderStage, identifier: &str) -> String {
+        match identifier {
+            "gl_VertexID" => return String::from("gl_VertexIndex"),
+            "gl_InstanceID" => return String::from("gl_InstanceIndex"),
+            "main" if stage == ShaderStage::Vertex => return String::from("librender_main"),
+            _ => {}
+        }
+
+        return match self.get_sampler_index(identifier) {
+            Some(index) => format!("{}({}, {}_sampler)", self.samplers[index].1, identifier, identifier),
+            None => String::from(identifier),
+        };
+    }
+
+    fn get_sampler_index(&self, name: &str) -> Option<usize> {
+        return self.samplers.iter().position(|(sampler, _)| sampler == name);
+    }
+}
+
+impl Declaration {
+    fn to_string(&self, location: Option<u32>) -> String {
+        let layout = match (&self.layout, location) {
+            (_, Some(location)) => format!("layout(location = {}) ", location),
+            (Some(layout), None) => format!("layout({}) ", layout),
+            (None, None) => String::new(),
+        };
+
+        let mut parts = self.qualifiers.clone();
+        parts.push(self.storage.clone());
+        parts.push(self.type_name.clone());
+        return format!("{}{} {};", layout, parts.join(" "), self.names.join(", "));
+    }
+
+    fn to_string_for(&self, name: &str, location: u32) -> String {
+        let declaration = Declaration {
+            layout: None,
+            qualifiers: self.qualifiers.clone(),
+            storage: self.storage.clone(),
+            type_name: self.type_name.clone(),
+            names: vec![String::from(name)],
+        };
+
+        return declaration.to_string(Some(location));
+    }
+}
+
+fn needs_translation(source: &str) -> bool {
+    let version = source
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with("#version"))
+        .and_then(|line| line["#version".len()..].split_whitespace().next())
+        .and_then(|version| version.parse::<u32>().ok());
+
+    return match version {
+        Some(version) => version < 440,
+        None => true,
+    };
+}
+
+// Lines outside of any braces, where declarations live.
+fn get_global_lines(source: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    for line in source.lines() {
+        if depth == 0 {
+            lines.push(line.trim());
+        }
+        depth += line.matches('{').count() as i32 - line.matches('}').count() as i32;
+    }
+
+    return lines;
+}
+
+// Name of the uniform block a line starts, e.g. "uniform Matrices {".
+fn parse_block_start(line: &str) -> Option<String> {
+    if line.ends_with(';') {
+        return None;
+    }
+
+    let rest = strip_layout(line).1;
+    let mut tokens = rest.split(|c: char| c.is_whitespace() || c == '{');
+    if tokens.next() != Some("uniform") {
+        return None;
+    }
+
+    return tokens
+        .find(|token| !token.is_empty())
+        .map(String::from);
+}
+
+fn parse_declaration(line: &str) -> Option<Declaration> {
+    const QUALIFIERS: [&str; 5] = ["flat", "smooth", "noperspective", "centroid", "invariant"];
+    const PRECISIONS: [&str; 3] = ["lowp", "mediump", "highp"];
+
+    let line = line.strip_suffix(';')?.trim();
+    let (layout, rest) = strip_layout(line);
+
+    let mut qualifiers = Vec::new();
+    let mut tokens = rest.split_whitespace();
+    let storage = loop {
+        let token = tokens.next()?;
+        match token {
+            "uniform" | "in" | "out" => break String::from(token),
+            _ if QUALIFIERS.contains(&token) => qualifiers.push(String::from(token)),
+            _ if PRECISIONS.contains(&token) => {}
+            _ => return None,
+        }
+    };
+
+    let mut type_name = tokens.next()?;
+    if PRECISIONS.contains(&type_name) {
+        type_name = tokens.next()?;
+    }
+
+    let names: Vec<String> = tokens
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .split(',')
+        .map(|name| name.split_whitespace().collect::<String>())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() || names.iter().any(|name| name.contains('=') || name.contains('{')) {
+        return None;
+    }
+
+    return Some(Declaration {
+        layout: layout.map(String::from),
+        qualifiers,
+        storage,
+        type_name: String::from(type_name),
+        names,
+    });
+}
+
+// Splits "layout(...) rest" into the layout arguments and the rest.
+fn strip_layout(line: &str) -> (Option<&str>, &str) {
+    if !line.starts_with("layout") {
+        return (None, line);
+    }
+
+    return match (line.find('('), line.find(')')) {
+        (Some(start), Some(end)) if start < end => {
+            (Some(line[start + 1..end].trim()), line[end + 1..].trim())
+        }
+        _ => (None, line),
+    };
+}
+
+fn has_location(declaration: &Declaration) -> bool {
+    return match &declaration.layout {
+        Some(layout) => layout.contains("location"),
+        None => false,
+    };
+}
+
+fn is_opaque_type(type_name: &str) -> bool {
+    let type_name = type_name.trim_start_matches(|c| c == 'i' || c == 'u');
+    return type_name.starts_with("sampler")
+        || type_name.starts_with("texture")
+        || type_name.starts_with("image");
+}
+
+// Texture and sampler types a combined sampler splits into, e.g.
+// sampler2DShadow into texture2D and samplerShadow.
+fn split_sampler_type(type_name: &str) -> Option<(String, &'static str)> {
+    let prefix = match type_name.chars().next() {
+        Some(prefix @ ('i' | 'u')) => prefix.to_string(),
+        _ => String::new(),
+    };
+    let dimension = type_name[prefix.len()..].strip_prefix("sampler")?;
+    if dimension.is_empty() || dimension == "Shadow" {
+        return None;
+    }
+
+    return match dimension.strip_suffix("Shadow") {
+        Some(dimension) => Some((format!("{}texture{}", prefix, dimension), "samplerShadow")),
+        None => Some((format!("{}texture{}", prefix, dimension), "sampler")),
+    };
+}
+
+fn get_base_name(name: &str) -> &str {
+    return match name.find('[') {
+        Some(index) => &name[..index],
+        None => name,
+    };
+}
+
+// Matrices take a location per column and arrays one per element.
+fn get_location_count(type_name: &str, name: &str) -> u32 {
+    let columns = match type_name.strip_prefix("mat") {
+        Some(size) => size[..1].parse::<u32>().unwrap_or(1),
+        None => 1,
+    };
+    let elements = match (name.find('['), name.find(']')) {
+        (Some(start), Some(end)) if start < end => name[start + 1..end].parse::<u32>().unwrap_or(1),
+        _ => 1,
+    };
+
+    return columns * elements;
+}
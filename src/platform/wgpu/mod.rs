@@ -0,0 +1,794 @@
+extern crate wgpu;
+
+pub mod buffer;
+pub mod framebuffer;
+pub mod glsl;
+pub mod pipeline;
+pub mod shader;
+pub mod texture;
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock, Weak},
+    time::Instant,
+};
+
+use once_cell::sync::Lazy;
+
+use crate::render::{
+    buffer::BufferDataType,
+    capabilities::Capabilities,
+    debug::{self, DebugSeverity},
+    framebuffer::Framebuffer,
+    pipeline::PrimitiveTopology,
+    render_api::RenderAPI,
+    render_queue::RenderQueue,
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    stats::{FrameStats, ScopeTiming, StateCacheStats},
+    texture::{PixelBuffer, PixelData, TextureDimension, TextureFormat},
+    window::{FramebufferResize, Window},
+};
+
+use self::{
+    buffer::BufferState,
+    framebuffer::{FramebufferState, MAX_COLOR_ATTACHMENTS},
+    pipeline::PipelineCache,
+    shader::ProgramState,
+    texture::{collapse, expand, TextureState},
+};
+
+// Creating a device is slow, so every API created afterwards shares the
+// first one.
+static GPU: Lazy<RwLock<Option<Arc<Gpu>>>> = Lazy::new(|| RwLock::new(None));
+
+thread_local! {
+    // State that OpenGL keeps in the current context, per thread like the
+    // software renderer.
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::new());
+}
+
+pub fn with_context<T>(function: impl FnOnce(&mut Context) -> T) -> T {
+    return CONTEXT.with(|context| function(&mut context.borrow_mut()));
+}
+
+pub fn get_gpu() -> Arc<Gpu> {
+    return GPU
+        .read()
+        .unwrap()
+        .clone()
+        .expect("Wgpu API was not initalized!");
+}
+
+pub struct Gpu {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub samplers: Samplers,
+}
+
+impl Gpu {
+    fn new() -> Gpu {
+        let backends = wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY);
+        let instance = wgpu::Instance::new(backends);
+        let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+            &instance, backends, None,
+        ))
+        .expect("No adapter for the wgpu renderer was found");
+
+        // Wireframes and multi draw indirect are optional in wgpu.
+        let features = adapter.features()
+            & (wgpu::Features::POLYGON_MODE_LINE
+                | wgpu::Features::POLYGON_MODE_POINT
+                | wgpu::Features::MULTI_DRAW_INDIRECT);
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features,
+                limits: adapter.limits(),
+            },
+            None,
+        ))
+        .expect("Failed to create wgpu device");
+        device.on_uncaptured_error(|error| {
+            log::error!(target: debug::LOG_TARGET, "Wgpu error\n{}", error)
+        });
+
+        let samplers = Samplers::new(&device);
+
+        return Gpu {
+            adapter,
+            device,
+            queue,
+            samplers,
+        };
+    }
+}
+
+// Textures don't carry sampler state in this library, every sampler
+// clamps and filters linearly unless the texture can't be filtered.
+pub struct Samplers {
+    pub linear: Arc<wgpu::Sampler>,
+    pub nearest: Arc<wgpu::Sampler>,
+    pub comparison: Arc<wgpu::Sampler>,
+}
+
+impl Samplers {
+    fn new(device: &wgpu::Device) -> Samplers {
+        let create = |filter: wgpu::FilterMode, compare: Option<wgpu::CompareFunction>| {
+            Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                compare,
+                ..Default::default()
+            }))
+        };
+
+        return Samplers {
+            linear: create(wgpu::FilterMode::Linear, None),
+            nearest: create(wgpu::FilterMode::Nearest, None),
+            comparison: create(wgpu::FilterMode::Linear, Some(wgpu::CompareFunction::LessEqual)),
+        };
+    }
+}
+
+// Multisampled targets draw into the colors and resolve into the resolves.
+pub struct RenderTarget {
+    pub colors: Vec<Arc<RwLock<TextureState>>>,
+    pub resolves: Vec<Arc<RwLock<TextureState>>>,
+    pub depth: Option<Arc<RwLock<TextureState>>>,
+}
+
+pub struct Context {
+    next_id: u32,
+    pub width: u32,
+    pub height: u32,
+    // None until the API is created.
+    pub color: Option<Arc<RwLock<TextureState>>>,
+    pub depth: Option<Arc<RwLock<TextureState>>>,
+    pub target: Option<RenderTarget>,
+    pub viewport: (i32, i32, u32, u32),
+    // Draws, clears and copies recorded since the last submit.
+    pub encoder: Option<wgpu::CommandEncoder>,
+
+    pub clear_color: wgpu::Color,
+    pub clear_depth: f32,
+    pub clear_stencil: i32,
+    pub depth_state: DepthState,
+    pub blend_states: Vec<BlendState>,
+    pub blend_constant: wgpu::Color,
+    pub color_masks: Vec<ColorMask>,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+    pub scissor: Option<ScissorRect>,
+    pub stencil_state: StencilState,
+    // Only set by pipelines, draws without one use triangle lists.
+    pub topology: PrimitiveTopology,
+
+    pub program: Option<Arc<ProgramState>>,
+    pub pipelines: PipelineCache,
+    pub textures: HashMap<u32, Arc<RwLock<TextureState>>>,
+    pub buffers: HashMap<u32, Arc<RwLock<BufferState>>>,
+    pub window_framebuffers: Vec<Weak<Mutex<FramebufferState>>>,
+    texture_states: HashMap<u32, Weak<RwLock<TextureState>>>,
+    placeholders: Vec<((wgpu::TextureViewDimension, bool), Arc<RwLock<TextureState>>)>,
+
+    pub stats: FrameStats,
+    last_stats: FrameStats,
+    frame_start: Option<Instant>,
+    scopes: Vec<(String, Instant)>,
+}
+
+impl Context {
+    fn new() -> Context {
+        return Context {
+            next_id: 1,
+            width: 0,
+            height: 0,
+            color: None,
+            depth: None,
+            target: None,
+            viewport: (0, 0, 0, 0),
+            encoder: None,
+
+            clear_color: wgpu::Color::TRANSPARENT,
+            clear_depth: 1.0,
+            clear_stencil: 0,
+            depth_state: DepthState::default(),
+            blend_states: vec![BlendState::DISABLED; MAX_COLOR_ATTACHMENTS],
+            blend_constant: wgpu::Color::TRANSPARENT,
+            color_masks: vec![ColorMask::ALL; MAX_COLOR_ATTACHMENTS],
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            polygon_mode: PolygonMode::Fill,
+            scissor: None,
+            stencil_state: StencilState::default(),
+            topology: PrimitiveTopology::Triangles,
+
+            program: None,
+            pipelines: PipelineCache::new(),
+            textures: HashMap::new(),
+            buffers: HashMap::new(),
+            window_framebuffers: Vec::new(),
+            texture_states: HashMap::new(),
+            placeholders: Vec::new(),
+
+            stats: FrameStats::default(),
+            last_stats: FrameStats::default(),
+            frame_start: None,
+            scopes: Vec::new(),
+        };
+    }
+
+    // The window surfaces are RGBA8 and 24 bit depth with 8 bit stencil.
+    fn create_surface(width: u32, height: u32, format: TextureFormat) -> Arc<RwLock<TextureState>> {
+        let data_type = match format {
+            TextureFormat::DepthStencil => BufferDataType::U32,
+            _ => BufferDataType::U8,
+        };
+
+        return Arc::new(RwLock::new(TextureState::new(
+            &get_gpu(),
+            TextureDimension::Texture2D,
+            width,
+            height,
+            1,
+            format,
+            data_type,
+            1,
+        )));
+    }
+
+    pub fn generate_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        return id;
+    }
+
+    pub fn get_gpu(&self) -> Arc<Gpu> {
+        return get_gpu();
+    }
+
+    // Lets framebuffers find the textures behind attachments of other
+    // framebuffers, dropped textures are pruned on the way.
+    pub fn register_texture(&mut self, state: &Arc<RwLock<TextureState>>) -> u32 {
+        let id = self.generate_id();
+        self.texture_states.retain(|_, state| state.strong_count() > 0);
+        self.texture_states.insert(id, Arc::downgrade(state));
+
+        return id;
+    }
+
+    pub fn get_texture(&self, id: u32) -> Option<Arc<RwLock<TextureState>>> {
+        return self.texture_states.get(&id).and_then(|state| state.upgrade());
+    }
+
+    pub fn get_target(
+        &self,
+    ) -> (
+        Vec<Arc<RwLock<TextureState>>>,
+        Vec<Arc<RwLock<TextureState>>>,
+        Option<Arc<RwLock<TextureState>>>,
+    ) {
+        match &self.target {
+            Some(target) => (target.colors.clone(), target.resolves.clone(), target.depth.clone()),
+            None => (self.color.iter().cloned().collect(), Vec::new(), self.depth.clone()),
+        }
+    }
+
+    // Returns the encoder to record into, it has to be put back into
+    // encoder afterwards.
+    pub fn take_encoder(&mut self) -> wgpu::CommandEncoder {
+        return match self.encoder.take() {
+            Some(encoder) => encoder,
+            None => get_gpu()
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None }),
+        };
+    }
+
+    // Submits the recorded work, needed before anything is read back or
+    // written through the queue.
+    pub fn flush(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            get_gpu().queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    // Black textures for samplers without a texture bound, cached per view
+    // dimension.
+    pub fn get_placeholder(
+        &mut self,
+        dimension: wgpu::TextureViewDimension,
+        is_depth: bool,
+    ) -> Arc<RwLock<TextureState>> {
+        if let Some((_, placeholder)) =
+            self.placeholders.iter().find(|(key, _)| *key == (dimension, is_depth))
+        {
+            return placeholder.clone();
+        }
+
+        let dimension_type = match dimension {
+            wgpu::TextureViewDimension::D1 => TextureDimension::Texture1D,
+            wgpu::TextureViewDimension::D2Array => TextureDimension::Texture2DArray,
+            wgpu::TextureViewDimension::Cube => TextureDimension::Cube,
+            wgpu::TextureViewDimension::CubeArray => TextureDimension::CubeArray,
+            wgpu::TextureViewDimension::D3 => TextureDimension::Texture3D,
+            _ => TextureDimension::Texture2D,
+        };
+        let gpu = get_gpu();
+        let placeholder = match is_depth {
+            // Depth textures can't be uploaded to, they start out zeroed.
+            true => TextureState::new(
+                &gpu,
+                dimension_type,
+                1,
+                1,
+                1,
+                TextureFormat::Depth,
+                BufferDataType::F32,
+                1,
+            ),
+            false => {
+                let state = TextureState::new(
+                    &gpu,
+                    dimension_type,
+                    1,
+                    1,
+                    1,
+                    TextureFormat::RGBA,
+                    BufferDataType::U8,
+                    1,
+                );
+                let texels = [0u8, 0, 0, 255].repeat(state.layers as usize);
+                state.upload(&gpu, 0, state.layers, texels.as_ptr(), BufferDataType::U8);
+                state
+            }
+        };
+
+        let placeholder = Arc::new(RwLock::new(placeholder));
+        self.placeholders.push(((dimension, is_depth), placeholder.clone()));
+
+        return placeholder;
+    }
+
+    // Copies on the GPU when size and format match, otherwise the source is
+    // read back and scaled to the destination with nearest filtering.
+    pub fn blit(&mut self, source: &Arc<RwLock<TextureState>>, destination: &Arc<RwLock<TextureState>>) {
+        if Arc::ptr_eq(source, destination) {
+            return;
+        }
+
+        let gpu = get_gpu();
+        let source = source.read().unwrap();
+        let destination = destination.read().unwrap();
+        if source.width == destination.width
+            && source.height == destination.height
+            && source.texture_format == destination.texture_format
+            && source.samples == 1
+            && destination.samples == 1
+        {
+            let mut encoder = self.take_encoder();
+            encoder.copy_texture_to_texture(
+                source.texture.as_image_copy(),
+                destination.texture.as_image_copy(),
+                wgpu::Extent3d {
+                    width: source.width,
+                    height: source.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            self.encoder = Some(encoder);
+            return;
+        }
+
+        self.flush();
+        let values = match source.read(&gpu, BufferDataType::F32) {
+            PixelData::F32(values) => values,
+            _ => return,
+        };
+
+        let source_channels = source.format.get_channel_count() as usize;
+        let channels = destination.format.get_channel_count() as usize;
+        let mut scaled = Vec::with_capacity((destination.width * destination.height) as usize * channels);
+        for y in 0..destination.height {
+            for x in 0..destination.width {
+                let source_x = (x * source.width / destination.width).min(source.width - 1);
+                let source_y = (y * source.height / destination.height).min(source.height - 1);
+                let offset = (source_y * source.width + source_x) as usize * source_channels;
+                let mut texel = [0.0; 4];
+                texel[..source_channels].copy_from_slice(&values[offset..offset + source_channels]);
+
+                let texel = collapse(destination.format, expand(source.format, texel));
+                scaled.extend_from_slice(&texel[..channels]);
+            }
+        }
+        destination.upload(&gpu, 0, 1, scaled.as_ptr() as *const u8, BufferDataType::F32);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.color = Some(Self::create_surface(width, height, TextureFormat::RGBA));
+        self.depth = Some(Self::create_surface(width, height, TextureFormat::DepthStencil));
+    }
+}
+
+// There is no swap chain, glfw windows don't expose a window handle wgpu
+// accepts, so frames are drawn off screen and read with capture_frame.
+pub struct WgpuAPI {
+    initalized: bool,
+    framebuffer_resize: FramebufferResize,
+}
+
+impl RenderAPI for WgpuAPI {
+    fn init(&self, window: &mut Box<dyn Window>) {
+        {
+            let mut gpu = GPU.write().unwrap();
+            if gpu.is_none() {
+                *gpu = Some(Arc::new(Gpu::new()));
+            }
+        }
+
+        let (width, height) = window.get_framebuffer_size();
+        with_context(|context| {
+            *context = Context::new();
+            context.resize(width, height);
+            context.viewport = (0, 0, width, height);
+        });
+    }
+
+    // Like glClear the depth and stencil are only cleared when writing to
+    // them is enabled, the scissor and color mask are ignored.
+    fn clear(&self) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        with_context(|context| {
+            let (colors, resolves, depth) = context.get_target();
+            if colors.is_empty() && depth.is_none() {
+                return;
+            }
+
+            let colors: Vec<_> = colors.iter().map(|color| color.read().unwrap()).collect();
+            let resolves: Vec<_> = resolves.iter().map(|resolve| resolve.read().unwrap()).collect();
+            let depth = depth.as_ref().map(|depth| depth.read().unwrap());
+            // Render pass clears ignore the color mask and the scissor, the
+            // depth and stencil are cleared whatever their write masks too.
+            let clear_depth = Some(context.clear_depth);
+            let clear_stencil = Some(context.clear_stencil as u32 & 0xff);
+
+            let mut encoder = context.take_encoder();
+            {
+                let color_attachments: Vec<Option<wgpu::RenderPassColorAttachment>> = colors
+                    .iter()
+                    .enumerate()
+                    .map(|(index, color)| {
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: &color.attachment_view,
+                            resolve_target: resolves
+                                .get(index)
+                                .map(|resolve| resolve.attachment_view.as_ref()),
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(context.clear_color),
+                                store: true,
+                            },
+                        })
+                    })
+                    .collect();
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: None,
+                    color_attachments: &color_attachments,
+                    depth_stencil_attachment: depth
+                        .as_ref()
+                        .map(|depth| pipeline::get_depth_attachment(depth, clear_depth, clear_stencil)),
+                });
+            }
+            context.encoder = Some(encoder);
+        });
+    }
+
+    fn begin<'a>(&self) -> RenderQueue<'a> {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        if let Some((width, height)) = self.framebuffer_resize.take() {
+            self.resize(width, height);
+        }
+
+        // Every render target drawn until the window presents counts
+        // towards the same frame.
+        with_context(|context| {
+            if context.frame_start.is_none() {
+                context.frame_start = Some(Instant::now());
+            }
+        });
+        self.clear();
+
+        return RenderQueue::new();
+    }
+
+    // Waits for the GPU so the frame time measured on the CPU covers the
+    // work submitted so far.
+    fn end(&self, mut queue: RenderQueue) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        queue.execute();
+        with_context(|context| context.flush());
+        get_gpu().device.poll(wgpu::Maintain::Wait);
+    }
+
+    fn set_render_target(&self, target: Option<&Box<dyn Framebuffer>>) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        match target {
+            Some(framebuffer) => framebuffer.bind(),
+            None => with_context(|context| {
+                context.target = None;
+                context.viewport = (0, 0, context.width, context.height);
+            }),
+        }
+    }
+
+    fn capture_frame(&self) -> PixelBuffer {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        let gpu = get_gpu();
+        let color = with_context(|context| {
+            context.flush();
+            context.color.clone()
+        })
+        .expect("Wgpu API was not initalized!");
+
+        let color = color.read().unwrap();
+        let mut frame = PixelBuffer {
+            width: color.width,
+            height: color.height,
+            format: TextureFormat::RGBA,
+            data: color.read(&gpu, BufferDataType::U8),
+        };
+        frame.flip_vertically();
+
+        return frame;
+    }
+
+    fn resize(&self, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        with_context(|context| {
+            context.flush();
+            context.resize(width, height);
+            if context.target.is_none() {
+                context.viewport = (0, 0, width, height);
+            }
+        });
+        framebuffer::resize_window_framebuffers(width, height);
+    }
+
+    fn set_viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        with_context(|context| context.viewport = (x, y, width, height));
+    }
+
+    fn set_clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        with_context(|context| context.clear_color = Self::get_color(red, green, blue, alpha));
+    }
+
+    fn set_clear_depth(&self, depth: f32) {
+        with_context(|context| context.clear_depth = depth);
+    }
+
+    fn set_clear_stencil(&self, stencil: i32) {
+        with_context(|context| context.clear_stencil = stencil);
+    }
+
+    fn set_depth_state(&self, state: DepthState) {
+        with_context(|context| context.depth_state = state);
+    }
+
+    fn set_blend_state(&self, target: Option<u32>, state: BlendState) {
+        with_context(|context| match target {
+            Some(target) => {
+                if let Some(blend_state) = context.blend_states.get_mut(target as usize) {
+                    *blend_state = state;
+                }
+            }
+            None => context.blend_states.fill(state),
+        });
+    }
+
+    fn set_blend_constant(&self, red: f32, green: f32, blue: f32, alpha: f32) {
+        with_context(|context| context.blend_constant = Self::get_color(red, green, blue, alpha));
+    }
+
+    fn set_color_mask(&self, target: Option<u32>, mask: ColorMask) {
+        with_context(|context| match target {
+            Some(target) => {
+                if let Some(color_mask) = context.color_masks.get_mut(target as usize) {
+                    *color_mask = mask;
+                }
+            }
+            None => context.color_masks.fill(mask),
+        });
+    }
+
+    fn set_cull_mode(&self, mode: CullMode) {
+        with_context(|context| context.cull_mode = mode);
+    }
+
+    fn set_front_face(&self, face: FrontFace) {
+        with_context(|context| context.front_face = face);
+    }
+
+    // Lines and points need device features, without them polygons stay
+    // filled.
+    fn set_polygon_mode(&self, mode: PolygonMode) {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        let features = get_gpu().device.features();
+        let supported = match mode {
+            PolygonMode::Fill => true,
+            PolygonMode::Line => features.contains(wgpu::Features::POLYGON_MODE_LINE),
+            PolygonMode::Point => features.contains(wgpu::Features::POLYGON_MODE_POINT),
+        };
+        if !supported {
+            log::warn!("{:?} polygons aren't supported by the wgpu device", mode);
+        }
+
+        with_context(|context| context.polygon_mode = mode);
+    }
+
+    fn set_scissor(&self, rect: Option<ScissorRect>) {
+        with_context(|context| context.scissor = rect);
+    }
+
+    fn set_stencil_state(&self, state: StencilState) {
+        with_context(|context| context.stencil_state = state);
+    }
+
+    // The version is the backend wgpu picked and the extensions are the
+    // optional wgpu features of the adapter.
+    fn capabilities(&self) -> Capabilities {
+        if !self.initalized {
+            panic!("Wgpu API was not initalized!");
+        }
+
+        let gpu = get_gpu();
+        let info = gpu.adapter.get_info();
+        let limits = gpu.device.limits();
+        let anisotropic = gpu
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+        let features = format!("{:?}", gpu.adapter.features());
+
+        return Capabilities {
+            version: format!("{:?}", info.backend),
+            shading_language_version: String::from("4.50"),
+            vendor: format!("{:#06x}", info.vendor),
+            renderer: info.name,
+            extensions: features
+                .split(" | ")
+                .filter(|feature| !feature.is_empty() && !feature.starts_with('('))
+                .map(String::from)
+                .collect(),
+            max_texture_size: limits.max_texture_dimension_2d,
+            max_texture_units: limits.max_sampled_textures_per_shader_stage,
+            max_color_attachments: MAX_COLOR_ATTACHMENTS as u32,
+            max_samples: framebuffer::MAX_SAMPLES,
+            max_uniform_block_size: limits.max_uniform_buffer_binding_size,
+            max_anisotropy: anisotropic.then(|| 16.0),
+        };
+    }
+
+    // Pipelines are looked up instead of state being set.
+    fn get_state_cache_stats(&self) -> StateCacheStats {
+        return StateCacheStats::default();
+    }
+
+    fn reset_state_cache_stats(&self) {}
+
+    fn get_frame_stats(&self) -> FrameStats {
+        return with_context(|context| context.last_stats.clone());
+    }
+
+    fn end_frame(&self) {
+        end_frame();
+    }
+
+    // Validation errors are always printed.
+    fn set_debug_severity(&self, _severity: DebugSeverity) {}
+
+    // Scopes wait for the work issued before them, which makes them accurate
+    // but stalls the CPU.
+    fn begin_scope(&self, name: &str) {
+        Self::wait();
+        with_context(|context| context.scopes.push((String::from(name), Instant::now())));
+    }
+
+    fn end_scope(&self) {
+        Self::wait();
+        with_context(|context| match context.scopes.pop() {
+            Some((name, start)) => context.stats.scopes.push(ScopeTiming {
+                name,
+                gpu_time: start.elapsed(),
+            }),
+            None => log::warn!("GPU timing scope ended without being started"),
+        });
+    }
+
+    // Uploads are read tightly packed regardless.
+    fn disable_align_restrictions(&self) {}
+
+    fn enable_align_restrictions(&self) {}
+}
+
+impl WgpuAPI {
+    pub fn new(window: &mut Box<dyn Window>) -> WgpuAPI {
+        let api = WgpuAPI {
+            initalized: true,
+            framebuffer_resize: window.get_framebuffer_resize(),
+        };
+        api.init(window);
+
+        return api;
+    }
+
+    fn get_color(red: f32, green: f32, blue: f32, alpha: f32) -> wgpu::Color {
+        return wgpu::Color {
+            r: red as f64,
+            g: green as f64,
+            b: blue as f64,
+            a: alpha as f64,
+        };
+    }
+
+    fn wait() {
+        with_context(|context| context.flush());
+        get_gpu().device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+// Called through RenderAPI::end_frame, the device time is measured on the
+// CPU from the first begin until then.
+pub fn end_frame() {
+    with_context(|context| {
+        if let Some(start) = context.frame_start.take() {
+            let mut stats = std::mem::take(&mut context.stats);
+            stats.gpu_time = Some(start.elapsed());
+            context.last_stats = stats;
+        }
+    });
+}
+
+pub fn record_draw(context: &mut Context, vertices: u64, triangles: u64) {
+    context.stats.draw_calls += 1;
+    context.stats.vertices += vertices;
+    context.stats.triangles += triangles;
+}
@@ -0,0 +1,685 @@
+use std::{mem::size_of, sync::Arc};
+
+use wgpu::util::DeviceExt;
+
+use crate::render::{
+    buffer::{Bindable, DrawArraysIndirectCommand, DrawElementsIndirectCommand, DrawRange},
+    pipeline::{Pipeline, PipelineDescriptor, PrimitiveTopology},
+    render_state::{
+        BlendFactor, BlendOperation, BlendState, ColorMask, CompareFunction, CullMode, FrontFace,
+        PolygonMode, StencilFaceState, StencilOperation,
+    },
+    shader::Shader,
+};
+
+use super::{record_draw, shader::ProgramState, texture::TextureState, with_context, Context};
+
+// Only holds the state, the wgpu pipelines are created on the first draw
+// with it since they depend on the render target and the bound textures
+// as well.
+pub struct WgpuPipeline {
+    shader: Box<dyn Shader>,
+    descriptor: PipelineDescriptor,
+}
+
+impl WgpuPipeline {
+    pub fn new(shader: Box<dyn Shader>, descriptor: PipelineDescriptor) -> WgpuPipeline {
+        return WgpuPipeline { shader, descriptor };
+    }
+}
+
+impl Pipeline for WgpuPipeline {
+    fn get_shader(&self) -> &Box<dyn Shader> {
+        return &self.shader;
+    }
+
+    fn get_descriptor(&self) -> &PipelineDescriptor {
+        return &self.descriptor;
+    }
+}
+
+impl Bindable for WgpuPipeline {
+    fn bind(&self) {
+        self.shader.bind();
+        let descriptor = &self.descriptor;
+        with_context(|context| {
+            context.topology = descriptor.topology;
+            context.blend_states.fill(descriptor.blend);
+            context.color_masks.fill(descriptor.color_mask);
+            context.depth_state = descriptor.depth;
+            context.stencil_state = descriptor.stencil;
+            context.cull_mode = descriptor.cull_mode;
+            context.front_face = descriptor.front_face;
+            context.polygon_mode = descriptor.polygon_mode;
+        });
+    }
+
+    fn unbind(&self) {
+        self.shader.unbind();
+        with_context(|context| context.topology = PrimitiveTopology::Triangles);
+    }
+}
+
+pub struct VertexInput {
+    pub buffer: Arc<wgpu::Buffer>,
+    pub stride: u64,
+    pub attributes: Vec<wgpu::VertexAttribute>,
+}
+
+pub struct IndexInput {
+    pub buffer: Arc<wgpu::Buffer>,
+    pub format: wgpu::IndexFormat,
+}
+
+// Ranges are drawn one after another in the same render pass.
+pub enum DrawCall {
+    Ranges(Vec<DrawRange>),
+    Indirect {
+        buffer: Arc<wgpu::Buffer>,
+        offset: u64,
+        count: u32,
+    },
+}
+
+impl DrawCall {
+    // Indirect draws only know their vertices on the GPU.
+    fn get_vertex_count(&self) -> u64 {
+        match self {
+            DrawCall::Ranges(ranges) => ranges.iter().map(|range| range.count as u64).sum(),
+            DrawCall::Indirect { .. } => 0,
+        }
+    }
+
+    fn get_triangle_count(&self, topology: PrimitiveTopology) -> u64 {
+        match self {
+            DrawCall::Ranges(ranges) => ranges
+                .iter()
+                .map(|range| topology.get_triangle_count(range.count as u64))
+                .sum(),
+            DrawCall::Indirect { .. } => 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            DrawCall::Ranges(_) => self.get_vertex_count() == 0,
+            DrawCall::Indirect { count, .. } => *count == 0,
+        }
+    }
+}
+
+// wgpu bakes the render state into pipelines, so they are looked up by
+// everything they are created from.
+#[derive(Clone, PartialEq)]
+struct PipelineKey {
+    program: u32,
+    vertex_layouts: Vec<(u64, Vec<wgpu::VertexAttribute>)>,
+    texture_entries: Vec<wgpu::BindGroupLayoutEntry>,
+    targets: Vec<Option<wgpu::ColorTargetState>>,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    primitive: wgpu::PrimitiveState,
+    samples: u32,
+}
+
+struct CachedPipeline {
+    pipeline: wgpu::RenderPipeline,
+    texture_layout: wgpu::BindGroupLayout,
+}
+
+pub struct PipelineCache {
+    pipelines: Vec<(PipelineKey, Arc<CachedPipeline>)>,
+}
+
+impl PipelineCache {
+    pub fn new() -> PipelineCache {
+        return PipelineCache {
+            pipelines: Vec::new(),
+        };
+    }
+
+    pub fn remove_program(&mut self, program: u32) {
+        self.pipelines.retain(|(key, _)| key.program != program);
+    }
+
+    fn get(&mut self, device: &wgpu::Device, program: &ProgramState, key: PipelineKey) -> Arc<CachedPipeline> {
+        if let Some((_, pipeline)) = self.pipelines.iter().find(|(cached, _)| *cached == key) {
+            return pipeline.clone();
+        }
+
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &key.texture_entries,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&program.uniform_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+        let buffers: Vec<wgpu::VertexBufferLayout> = key
+            .vertex_layouts
+            .iter()
+            .map(|(stride, attributes)| wgpu::VertexBufferLayout {
+                array_stride: *stride,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes,
+            })
+            .collect();
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &program.vertex,
+                entry_point: &program.vertex_entry,
+                buffers: &buffers,
+            },
+            primitive: key.primitive,
+            depth_stencil: key.depth_stencil.clone(),
+            multisample: wgpu::MultisampleState {
+                count: key.samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &program.fragment,
+                entry_point: &program.fragment_entry,
+                targets: &key.targets,
+            }),
+            multiview: None,
+        });
+
+        let pipeline = Arc::new(CachedPipeline {
+            pipeline,
+            texture_layout,
+        });
+        self.pipelines.push((key, pipeline.clone()));
+
+        return pipeline;
+    }
+}
+
+// Records the draw into the pending encoder with a render pass of its own,
+// which keeps the ordering of OpenGL between draws, clears and copies.
+pub fn draw(context: &mut Context, vertex_buffers: Vec<VertexInput>, index_buffer: Option<IndexInput>, call: DrawCall) {
+    let program = match &context.program {
+        Some(program) => program.clone(),
+        None => {
+            log::warn!("Draw call without a bound shader");
+            return;
+        }
+    };
+
+    let triangles = call.get_triangle_count(context.topology);
+    record_draw(context, call.get_vertex_count(), triangles);
+    if context.cull_mode == CullMode::FrontAndBack || call.is_empty() {
+        return;
+    }
+
+    let gpu = context.get_gpu().clone();
+    let (colors, resolves, depth) = context.get_target();
+    let colors: Vec<_> = colors.iter().map(|color| color.read().unwrap()).collect();
+    let resolves: Vec<_> = resolves.iter().map(|resolve| resolve.read().unwrap()).collect();
+    let depth = depth.as_ref().map(|depth| depth.read().unwrap());
+    let (width, height) = match colors.first().or(depth.as_ref()) {
+        Some(surface) => (surface.width, surface.height),
+        None => return,
+    };
+    let samples = colors.first().or(depth.as_ref()).map_or(1, |surface| surface.samples);
+
+    let scissor = match get_scissor_rect(context, width, height) {
+        Some(rect) => rect,
+        None => return,
+    };
+
+    // Textures without one bound sample a placeholder, like incomplete
+    // textures read as black in OpenGL.
+    let slots = program.get_slots();
+    let textures: Vec<Arc<std::sync::RwLock<TextureState>>> = program
+        .textures
+        .iter()
+        .zip(slots)
+        .map(|(binding, slot)| match context.textures.get(&slot) {
+            Some(texture) => texture.clone(),
+            None => context.get_placeholder(binding.dimension, matches!(binding.class, naga::ImageClass::Depth { .. })),
+        })
+        .collect();
+    let textures: Vec<_> = textures.iter().map(|texture| texture.read().unwrap()).collect();
+
+    let mut texture_entries = Vec::new();
+    let mut filterable = Vec::new();
+    for (binding, texture) in program.textures.iter().zip(&textures) {
+        let is_filterable = matches!(
+            texture.texture_format.describe().sample_type,
+            wgpu::TextureSampleType::Float { filterable: true }
+        );
+        let (sample_type, multisampled) = match binding.class {
+            naga::ImageClass::Sampled { kind, multi } => (
+                match kind {
+                    naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                    naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                    _ => wgpu::TextureSampleType::Float {
+                        filterable: is_filterable,
+                    },
+                },
+                multi,
+            ),
+            naga::ImageClass::Depth { multi } => (wgpu::TextureSampleType::Depth, multi),
+            naga::ImageClass::Storage { .. } => (wgpu::TextureSampleType::Float { filterable: false }, false),
+        };
+        texture_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: binding.binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: binding.dimension,
+                multisampled,
+            },
+            count: None,
+        });
+        filterable.push((binding.binding, is_filterable && sample_type != wgpu::TextureSampleType::Depth));
+    }
+
+    // Samplers are declared right after the texture they sample.
+    let mut samplers = Vec::new();
+    for sampler in &program.samplers {
+        let paired = filterable
+            .iter()
+            .find(|(binding, _)| binding + 1 == sampler.binding)
+            .map_or(true, |(_, filterable)| *filterable);
+        let (binding_type, sampler_object) = match (sampler.comparison, paired) {
+            (true, _) => (wgpu::SamplerBindingType::Comparison, gpu.samplers.comparison.clone()),
+            (false, true) => (wgpu::SamplerBindingType::Filtering, gpu.samplers.linear.clone()),
+            (false, false) => (wgpu::SamplerBindingType::NonFiltering, gpu.samplers.nearest.clone()),
+        };
+        texture_entries.push(wgpu::BindGroupLayoutEntry {
+            binding: sampler.binding,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Sampler(binding_type),
+            count: None,
+        });
+        samplers.push((sampler.binding, sampler_object));
+    }
+
+    let key = PipelineKey {
+        program: program.id,
+        vertex_layouts: vertex_buffers
+            .iter()
+            .map(|input| (input.stride, input.attributes.clone()))
+            .collect(),
+        texture_entries,
+        targets: colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| match program.fragment_outputs.contains(&(index as u32)) {
+                true => Some(wgpu::ColorTargetState {
+                    format: color.texture_format,
+                    blend: translate_blend_state(context.blend_states[index]),
+                    write_mask: translate_color_mask(context.color_masks[index]),
+                }),
+                false => None,
+            })
+            .collect(),
+        depth_stencil: depth.as_ref().map(|depth| get_depth_stencil_state(context, depth.texture_format)),
+        primitive: get_primitive_state(
+            context,
+            &gpu.device.features(),
+            index_buffer.as_ref().map(|indices| indices.format),
+        ),
+        samples,
+    };
+
+    let uniforms = program.get_uniforms();
+    let uniform_buffers: Vec<wgpu::Buffer> = uniforms
+        .iter()
+        .map(|data| {
+            gpu.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: data,
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        })
+        .collect();
+    let uniform_entries: Vec<wgpu::BindGroupEntry> = program
+        .blocks
+        .iter()
+        .zip(&uniform_buffers)
+        .map(|(block, buffer)| wgpu::BindGroupEntry {
+            binding: block.binding,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    let uniform_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &program.uniform_layout,
+        entries: &uniform_entries,
+    });
+
+    let mut texture_entries: Vec<wgpu::BindGroupEntry> = program
+        .textures
+        .iter()
+        .zip(&textures)
+        .map(|(binding, texture)| wgpu::BindGroupEntry {
+            binding: binding.binding,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        })
+        .collect();
+    texture_entries.extend(samplers.iter().map(|(binding, sampler)| wgpu::BindGroupEntry {
+        binding: *binding,
+        resource: wgpu::BindingResource::Sampler(sampler),
+    }));
+
+    let pipeline = context.pipelines.get(&gpu.device, &program, key);
+    let texture_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.texture_layout,
+        entries: &texture_entries,
+    });
+
+    let mut encoder = context.take_encoder();
+    {
+        let color_attachments: Vec<Option<wgpu::RenderPassColorAttachment>> = colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &color.attachment_view,
+                    resolve_target: resolves.get(index).map(|resolve| resolve.attachment_view.as_ref()),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })
+            })
+            .collect();
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: depth.as_ref().map(|depth| get_depth_attachment(depth, None, None)),
+        });
+
+        pass.set_pipeline(&pipeline.pipeline);
+        pass.set_bind_group(0, &uniform_group, &[]);
+        pass.set_bind_group(1, &texture_group, &[]);
+
+        let (x, y, viewport_width, viewport_height) = context.viewport;
+        pass.set_viewport(
+            x as f32,
+            y as f32,
+            viewport_width.max(1) as f32,
+            viewport_height.max(1) as f32,
+            0.0,
+            1.0,
+        );
+        pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+        pass.set_stencil_reference(context.stencil_state.reference as u32);
+        pass.set_blend_constant(context.blend_constant);
+
+        for (slot, input) in vertex_buffers.iter().enumerate() {
+            pass.set_vertex_buffer(slot as u32, input.buffer.slice(..));
+        }
+        if let Some(indices) = &index_buffer {
+            pass.set_index_buffer(indices.buffer.slice(..), indices.format);
+        }
+        match (&call, index_buffer.is_some()) {
+            (DrawCall::Ranges(ranges), true) => {
+                for range in ranges {
+                    pass.draw_indexed(range.first..range.first + range.count, range.base_vertex, 0..1);
+                }
+            }
+            (DrawCall::Ranges(ranges), false) => {
+                for range in ranges {
+                    pass.draw(range.first..range.first + range.count, 0..1);
+                }
+            }
+            // Without multi draw indirect the commands are issued one by one.
+            (DrawCall::Indirect { buffer, offset, count }, indexed) => {
+                let stride = match indexed {
+                    true => size_of::<DrawElementsIndirectCommand>(),
+                    false => size_of::<DrawArraysIndirectCommand>(),
+                } as u64;
+                let multi_draw = gpu.device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+                match (indexed, multi_draw) {
+                    (true, true) => pass.multi_draw_indexed_indirect(buffer, *offset, *count),
+                    (false, true) => pass.multi_draw_indirect(buffer, *offset, *count),
+                    (true, false) => {
+                        for index in 0..*count as u64 {
+                            pass.draw_indexed_indirect(buffer, offset + index * stride);
+                        }
+                    }
+                    (false, false) => {
+                        for index in 0..*count as u64 {
+                            pass.draw_indirect(buffer, offset + index * stride);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    context.encoder = Some(encoder);
+}
+
+// Loads the attachment unless a clear value is given, the stencil is only
+// touched on formats that have one.
+pub fn get_depth_attachment(
+    depth: &TextureState,
+    clear_depth: Option<f32>,
+    clear_stencil: Option<u32>,
+) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+    let stencil_ops = match depth.texture_format {
+        wgpu::TextureFormat::Depth24PlusStencil8 => Some(wgpu::Operations {
+            load: clear_stencil.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+            store: true,
+        }),
+        _ => None,
+    };
+
+    return wgpu::RenderPassDepthStencilAttachment {
+        view: &depth.attachment_view,
+        depth_ops: Some(wgpu::Operations {
+            load: clear_depth.map_or(wgpu::LoadOp::Load, wgpu::LoadOp::Clear),
+            store: true,
+        }),
+        stencil_ops,
+    };
+}
+
+// Intersected with the target since wgpu rejects rectangles reaching
+// outside of it, None when nothing is left to draw.
+pub fn get_scissor_rect(context: &Context, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let rect = match context.scissor {
+        Some(rect) => rect,
+        None => return Some((0, 0, width, height)),
+    };
+
+    let min_x = rect.x.max(0) as u32;
+    let min_y = rect.y.max(0) as u32;
+    let max_x = ((rect.x + rect.width as i32).max(0) as u32).min(width);
+    let max_y = ((rect.y + rect.height as i32).max(0) as u32).min(height);
+    if min_x >= max_x || min_y >= max_y {
+        return None;
+    }
+
+    return Some((min_x, min_y, max_x - min_x, max_y - min_y));
+}
+
+// Clip space is flipped vertically by the translated shaders, which turns
+// the winding around as well. Indexed strips have to name the index format.
+fn get_primitive_state(
+    context: &Context,
+    features: &wgpu::Features,
+    index_format: Option<wgpu::IndexFormat>,
+) -> wgpu::PrimitiveState {
+    let polygon_mode = match context.polygon_mode {
+        PolygonMode::Line if features.contains(wgpu::Features::POLYGON_MODE_LINE) => {
+            wgpu::PolygonMode::Line
+        }
+        PolygonMode::Point if features.contains(wgpu::Features::POLYGON_MODE_POINT) => {
+            wgpu::PolygonMode::Point
+        }
+        _ => wgpu::PolygonMode::Fill,
+    };
+
+    let (topology, strip_index_format) = match context.topology {
+        PrimitiveTopology::Triangles => (wgpu::PrimitiveTopology::TriangleList, None),
+        PrimitiveTopology::TriangleStrip => (wgpu::PrimitiveTopology::TriangleStrip, index_format),
+        PrimitiveTopology::Lines => (wgpu::PrimitiveTopology::LineList, None),
+        PrimitiveTopology::LineStrip => (wgpu::PrimitiveTopology::LineStrip, index_format),
+        PrimitiveTopology::Points => (wgpu::PrimitiveTopology::PointList, None),
+    };
+
+    return wgpu::PrimitiveState {
+        topology,
+        strip_index_format,
+        front_face: match context.front_face {
+            FrontFace::Clockwise => wgpu::FrontFace::Ccw,
+            FrontFace::CounterClockwise => wgpu::FrontFace::Cw,
+        },
+        cull_mode: match context.cull_mode {
+            CullMode::Front => Some(wgpu::Face::Front),
+            CullMode::Back => Some(wgpu::Face::Back),
+            CullMode::None | CullMode::FrontAndBack => None,
+        },
+        unclipped_depth: false,
+        polygon_mode,
+        conservative: false,
+    };
+}
+
+// Like OpenGL nothing is written to the depth buffer while the test is
+// disabled.
+fn get_depth_stencil_state(context: &Context, format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    let depth = context.depth_state;
+    let stencil = context.stencil_state;
+    let stencil = match stencil.enabled && format == wgpu::TextureFormat::Depth24PlusStencil8 {
+        true => wgpu::StencilState {
+            front: translate_stencil_face(stencil.front),
+            back: translate_stencil_face(stencil.back),
+            read_mask: stencil.read_mask & 0xff,
+            write_mask: stencil.write_mask & 0xff,
+        },
+        false => wgpu::StencilState::default(),
+    };
+
+    return wgpu::DepthStencilState {
+        format,
+        depth_write_enabled: depth.test && depth.write,
+        depth_compare: match depth.test {
+            true => translate_compare(depth.compare),
+            false => wgpu::CompareFunction::Always,
+        },
+        stencil,
+        bias: wgpu::DepthBiasState::default(),
+    };
+}
+
+fn translate_blend_state(state: BlendState) -> Option<wgpu::BlendState> {
+    if !state.enabled {
+        return None;
+    }
+
+    return Some(wgpu::BlendState {
+        color: translate_blend_component(state.src_color, state.dst_color, state.color_operation),
+        alpha: translate_blend_component(state.src_alpha, state.dst_alpha, state.alpha_operation),
+    });
+}
+
+// Factors are ignored by min and max, wgpu requires them to be one.
+fn translate_blend_component(
+    src: BlendFactor,
+    dst: BlendFactor,
+    operation: BlendOperation,
+) -> wgpu::BlendComponent {
+    let operation = translate_blend_operation(operation);
+    return match operation {
+        wgpu::BlendOperation::Min | wgpu::BlendOperation::Max => wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation,
+        },
+        _ => wgpu::BlendComponent {
+            src_factor: translate_blend_factor(src),
+            dst_factor: translate_blend_factor(dst),
+            operation,
+        },
+    };
+}
+
+fn translate_blend_factor(factor: BlendFactor) -> wgpu::BlendFactor {
+    match factor {
+        BlendFactor::Zero => wgpu::BlendFactor::Zero,
+        BlendFactor::One => wgpu::BlendFactor::One,
+        BlendFactor::SrcColor => wgpu::BlendFactor::Src,
+        BlendFactor::OneMinusSrcColor => wgpu::BlendFactor::OneMinusSrc,
+        BlendFactor::DstColor => wgpu::BlendFactor::Dst,
+        BlendFactor::OneMinusDstColor => wgpu::BlendFactor::OneMinusDst,
+        BlendFactor::SrcAlpha => wgpu::BlendFactor::SrcAlpha,
+        BlendFactor::OneMinusSrcAlpha => wgpu::BlendFactor::OneMinusSrcAlpha,
+        BlendFactor::DstAlpha => wgpu::BlendFactor::DstAlpha,
+        BlendFactor::OneMinusDstAlpha => wgpu::BlendFactor::OneMinusDstAlpha,
+        BlendFactor::ConstantColor => wgpu::BlendFactor::Constant,
+        BlendFactor::OneMinusConstantColor => wgpu::BlendFactor::OneMinusConstant,
+    }
+}
+
+fn translate_blend_operation(operation: BlendOperation) -> wgpu::BlendOperation {
+    match operation {
+        BlendOperation::Add => wgpu::BlendOperation::Add,
+        BlendOperation::Subtract => wgpu::BlendOperation::Subtract,
+        BlendOperation::ReverseSubtract => wgpu::BlendOperation::ReverseSubtract,
+        BlendOperation::Min => wgpu::BlendOperation::Min,
+        BlendOperation::Max => wgpu::BlendOperation::Max,
+    }
+}
+
+fn translate_color_mask(mask: ColorMask) -> wgpu::ColorWrites {
+    let mut writes = wgpu::ColorWrites::empty();
+    if mask.red {
+        writes |= wgpu::ColorWrites::RED;
+    }
+    if mask.green {
+        writes |= wgpu::ColorWrites::GREEN;
+    }
+    if mask.blue {
+        writes |= wgpu::ColorWrites::BLUE;
+    }
+    if mask.alpha {
+        writes |= wgpu::ColorWrites::ALPHA;
+    }
+
+    return writes;
+}
+
+fn translate_compare(compare: CompareFunction) -> wgpu::CompareFunction {
+    match compare {
+        CompareFunction::Never => wgpu::CompareFunction::Never,
+        CompareFunction::Less => wgpu::CompareFunction::Less,
+        CompareFunction::Equal => wgpu::CompareFunction::Equal,
+        CompareFunction::LessEqual => wgpu::CompareFunction::LessEqual,
+        CompareFunction::Greater => wgpu::CompareFunction::Greater,
+        CompareFunction::NotEqual => wgpu::CompareFunction::NotEqual,
+        CompareFunction::GreaterEqual => wgpu::CompareFunction::GreaterEqual,
+        CompareFunction::Always => wgpu::CompareFunction::Always,
+    }
+}
+
+fn translate_stencil_face(face: StencilFaceState) -> wgpu::StencilFaceState {
+    return wgpu::StencilFaceState {
+        compare: translate_compare(face.compare),
+        fail_op: translate_stencil_operation(face.fail),
+        depth_fail_op: translate_stencil_operation(face.depth_fail),
+        pass_op: translate_stencil_operation(face.pass),
+    };
+}
+
+fn translate_stencil_operation(operation: StencilOperation) -> wgpu::StencilOperation {
+    match operation {
+        StencilOperation::Keep => wgpu::StencilOperation::Keep,
+        StencilOperation::Zero => wgpu::StencilOperation::Zero,
+        StencilOperation::Replace => wgpu::StencilOperation::Replace,
+        StencilOperation::Increment => wgpu::StencilOperation::IncrementClamp,
+        StencilOperation::IncrementWrap => wgpu::StencilOperation::IncrementWrap,
+        StencilOperation::Decrement => wgpu::StencilOperation::DecrementClamp,
+        StencilOperation::DecrementWrap => wgpu::StencilOperation::DecrementWrap,
+        StencilOperation::Invert => wgpu::StencilOperation::Invert,
+    }
+}
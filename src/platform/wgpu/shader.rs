@@ -0,0 +1,481 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    num::NonZeroU64,
+    sync::{Arc, Mutex},
+};
+
+use nalgebra::{Matrix4, Vector3};
+
+use crate::render::{
+    buffer::Bindable,
+    shader::{Shader, ShaderAttribute},
+};
+
+use super::{get_gpu, glsl::GlslTranslator, with_context, CONTEXT};
+
+const VERTEX_KEY: &str = "vertex";
+const FRAGMENT_KEY: &str = "fragment";
+
+pub struct UniformMember {
+    name: String,
+    offset: u32,
+    size: u32,
+    // Distance between elements of arrays.
+    stride: Option<u32>,
+}
+
+pub struct UniformBlock {
+    pub binding: u32,
+    pub size: u32,
+    members: Vec<UniformMember>,
+}
+
+pub struct TextureBinding {
+    pub name: String,
+    pub binding: u32,
+    pub dimension: wgpu::TextureViewDimension,
+    pub class: naga::ImageClass,
+}
+
+pub struct SamplerBinding {
+    pub binding: u32,
+    pub comparison: bool,
+}
+
+// Bindings follow the layout the GLSL translator produces: uniform blocks
+// in group 0, textures in group 1 each followed by its sampler. WGSL
+// sources have to use the same layout.
+pub struct ProgramState {
+    pub id: u32,
+    pub vertex: Arc<wgpu::ShaderModule>,
+    pub fragment: Arc<wgpu::ShaderModule>,
+    pub vertex_entry: String,
+    pub fragment_entry: String,
+    pub fragment_outputs: Vec<u32>,
+    pub attributes: Vec<ShaderAttribute>,
+    pub blocks: Vec<UniformBlock>,
+    pub textures: Vec<TextureBinding>,
+    pub samplers: Vec<SamplerBinding>,
+    pub uniform_layout: wgpu::BindGroupLayout,
+    uniforms: Mutex<Vec<Vec<u8>>>,
+    // Texture slot sampled by each texture binding, in declaration order by
+    // default like OpenGL assigns them when linking.
+    slots: Mutex<Vec<u32>>,
+}
+
+impl ProgramState {
+    fn new(
+        id: u32,
+        vertex: (Arc<wgpu::ShaderModule>, &naga::Module),
+        fragment: (Arc<wgpu::ShaderModule>, &naga::Module),
+    ) -> Result<ProgramState, String> {
+        let vertex_entry = Self::get_entry_point(vertex.1, naga::ShaderStage::Vertex)?;
+        let fragment_entry = Self::get_entry_point(fragment.1, naga::ShaderStage::Fragment)?;
+
+        let mut blocks: Vec<UniformBlock> = Vec::new();
+        let mut textures: Vec<TextureBinding> = Vec::new();
+        let mut samplers: Vec<SamplerBinding> = Vec::new();
+        for module in [vertex.1, fragment.1] {
+            for (_, variable) in module.global_variables.iter() {
+                let binding = match &variable.binding {
+                    Some(binding) => binding,
+                    None => continue,
+                };
+                if binding.group > 1 {
+                    return Err(format!("Bind group {} is not supported, only 0 and 1 are", binding.group));
+                }
+
+                match &module.types[variable.ty].inner {
+                    naga::TypeInner::Struct { members, span }
+                        if variable.space == naga::AddressSpace::Uniform =>
+                    {
+                        if blocks.iter().any(|block| block.binding == binding.binding) {
+                            continue;
+                        }
+                        blocks.push(UniformBlock {
+                            binding: binding.binding,
+                            size: *span,
+                            members: members
+                                .iter()
+                                .map(|member| UniformMember {
+                                    name: member.name.clone().unwrap_or_default(),
+                                    offset: member.offset,
+                                    size: module.types[member.ty].inner.size(&module.constants),
+                                    stride: match module.types[member.ty].inner {
+                                        naga::TypeInner::Array { stride, .. } => Some(stride),
+                                        _ => None,
+                                    },
+                                })
+                                .collect(),
+                        });
+                    }
+                    naga::TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    } => {
+                        if textures.iter().any(|texture| texture.binding == binding.binding) {
+                            continue;
+                        }
+                        textures.push(TextureBinding {
+                            name: variable.name.clone().unwrap_or_default(),
+                            binding: binding.binding,
+                            dimension: match (dim, arrayed) {
+                                (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                                (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                                (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                                (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                                (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                                (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+                            },
+                            class: *class,
+                        });
+                    }
+                    naga::TypeInner::Sampler { comparison } => {
+                        if !samplers.iter().any(|sampler| sampler.binding == binding.binding) {
+                            samplers.push(SamplerBinding {
+                                binding: binding.binding,
+                                comparison: *comparison,
+                            });
+                        }
+                    }
+                    _ => return Err(format!("Unsupported resource {:?}", variable.name)),
+                }
+            }
+        }
+        blocks.sort_by_key(|block| block.binding);
+        textures.sort_by_key(|texture| texture.binding);
+
+        let uniform_entries: Vec<wgpu::BindGroupLayoutEntry> = blocks
+            .iter()
+            .map(|block| wgpu::BindGroupLayoutEntry {
+                binding: block.binding,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(block.size as u64),
+                },
+                count: None,
+            })
+            .collect();
+        let uniform_layout = get_gpu()
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &uniform_entries,
+            });
+
+        let attributes = Self::get_attributes(vertex.1, &vertex_entry);
+        let entry_point = fragment.1.entry_points.iter().find(|entry_point| entry_point.name == fragment_entry);
+        let fragment_outputs = match entry_point.and_then(|entry_point| entry_point.function.result.as_ref()) {
+            Some(result) => Self::get_locations(fragment.1, result.ty, result.binding.as_ref()),
+            None => Vec::new(),
+        };
+
+        return Ok(ProgramState {
+            id,
+            vertex: vertex.0,
+            fragment: fragment.0,
+            vertex_entry,
+            fragment_entry,
+            fragment_outputs,
+            attributes,
+            uniforms: Mutex::new(blocks.iter().map(|block| vec![0; block.size as usize]).collect()),
+            slots: Mutex::new((0..textures.len() as u32).collect()),
+            blocks,
+            textures,
+            samplers,
+            uniform_layout,
+        });
+    }
+
+    fn get_entry_point(module: &naga::Module, stage: naga::ShaderStage) -> Result<String, String> {
+        return module
+            .entry_points
+            .iter()
+            .find(|entry_point| entry_point.stage == stage)
+            .map(|entry_point| entry_point.name.clone())
+            .ok_or_else(|| format!("Shader has no {:?} entry point", stage));
+    }
+
+    // Inputs are arguments of the entry point or members of struct
+    // arguments, built-ins like the vertex index have no location.
+    fn get_attributes(module: &naga::Module, entry: &str) -> Vec<ShaderAttribute> {
+        let entry_point = match module.entry_points.iter().find(|entry_point| entry_point.name == entry) {
+            Some(entry_point) => entry_point,
+            None => return Vec::new(),
+        };
+
+        let mut attributes = Vec::new();
+        for argument in &entry_point.function.arguments {
+            let mut inputs = vec![(argument.name.clone(), argument.ty, argument.binding.clone())];
+            if let naga::TypeInner::Struct { members, .. } = &module.types[argument.ty].inner {
+                inputs = members
+                    .iter()
+                    .map(|member| (member.name.clone(), member.ty, member.binding.clone()))
+                    .collect();
+            }
+
+            for (name, ty, binding) in inputs {
+                if let Some(naga::Binding::Location { location, .. }) = binding {
+                    let (components, kind) = match module.types[ty].inner {
+                        naga::TypeInner::Vector { size, kind, .. } => (size as u32, Some(kind)),
+                        naga::TypeInner::Scalar { kind, .. } => (1, Some(kind)),
+                        _ => (1, None),
+                    };
+                    attributes.push(ShaderAttribute {
+                        name: name.unwrap_or_default(),
+                        location,
+                        components,
+                        integer: matches!(kind, Some(naga::ScalarKind::Sint | naga::ScalarKind::Uint)),
+                    });
+                }
+            }
+        }
+
+        return attributes;
+    }
+
+    // Outputs are either a single value or a struct of them.
+    fn get_locations(
+        module: &naga::Module,
+        ty: naga::Handle<naga::Type>,
+        binding: Option<&naga::Binding>,
+    ) -> Vec<u32> {
+        if let Some(naga::Binding::Location { location, .. }) = binding {
+            return vec![*location];
+        }
+
+        return match &module.types[ty].inner {
+            naga::TypeInner::Struct { members, .. } => members
+                .iter()
+                .filter_map(|member| match member.binding {
+                    Some(naga::Binding::Location { location, .. }) => Some(location),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    pub fn get_uniforms(&self) -> Vec<Vec<u8>> {
+        return self.uniforms.lock().unwrap().clone();
+    }
+
+    pub fn get_slots(&self) -> Vec<u32> {
+        return self.slots.lock().unwrap().clone();
+    }
+
+    // Elements of arrays are written as "name[index]", unknown names are
+    // ignored like OpenGL ignores uniforms that were optimized out.
+    fn set_uniform(&self, name: &str, bytes: &[u8]) {
+        let (name, index) = match (name.find('['), name.find(']')) {
+            (Some(start), Some(end)) if start < end => {
+                (&name[..start], name[start + 1..end].parse::<u32>().unwrap_or(0))
+            }
+            _ => (name, 0),
+        };
+
+        let mut uniforms = self.uniforms.lock().unwrap();
+        for (block, data) in self.blocks.iter().zip(uniforms.iter_mut()) {
+            let member = match block.members.iter().find(|member| member.name == name) {
+                Some(member) => member,
+                None => continue,
+            };
+
+            let element_offset = index * member.stride.unwrap_or(0);
+            if element_offset >= member.size {
+                return;
+            }
+            let start = (member.offset + element_offset) as usize;
+            let length = bytes.len().min((member.size - element_offset) as usize);
+            data[start..start + length].copy_from_slice(&bytes[..length]);
+            return;
+        }
+    }
+
+    fn set_slot(&self, name: &str, slot: u32) -> bool {
+        return match self.textures.iter().position(|texture| texture.name == name) {
+            Some(index) => {
+                self.slots.lock().unwrap()[index] = slot;
+                true
+            }
+            None => false,
+        };
+    }
+}
+
+impl Drop for ProgramState {
+    fn drop(&mut self) {
+        // The context may already be gone when the thread exits.
+        let _ = CONTEXT.try_with(|context| {
+            if let Ok(mut context) = context.try_borrow_mut() {
+                context.pipelines.remove_program(self.id);
+            }
+        });
+    }
+}
+
+// Sources with "#shader" sections are GLSL written for the OpenGL
+// renderer, anything else is read as WGSL with a vertex and a fragment
+// entry point.
+pub struct WgpuShader {
+    id: u32,
+    state: Option<Arc<ProgramState>>,
+}
+
+impl WgpuShader {
+    pub fn new() -> WgpuShader {
+        return WgpuShader {
+            id: with_context(|context| context.generate_id()),
+            state: None,
+        };
+    }
+
+    pub fn new_from_file(file_path: &str) -> WgpuShader {
+        let mut shader = WgpuShader::new();
+        shader.load_from_file(file_path);
+
+        return shader;
+    }
+
+    pub fn new_from_source(source: &str) -> WgpuShader {
+        let mut shader = WgpuShader::new();
+        shader.load_from_source(source);
+
+        return shader;
+    }
+
+    fn load_glsl(source: &str) -> Result<ProgramState, String> {
+        let mut stages: HashMap<&str, &str> = HashMap::new();
+        for section in source.split("#shader ").filter(|section| !section.trim().is_empty()) {
+            let line_end = section.find('\n').unwrap_or(section.len());
+            let (name, stage_source) = section.split_at(line_end);
+            stages.insert(name.trim(), stage_source);
+        }
+
+        let vertex_source = stages.get(VERTEX_KEY).ok_or("Shader has no vertex stage")?;
+        let fragment_source = stages.get(FRAGMENT_KEY).ok_or("Shader has no fragment stage")?;
+        let mut translator = GlslTranslator::new(&[vertex_source, fragment_source]);
+        let vertex = Self::parse_glsl(&mut translator, naga::ShaderStage::Vertex, vertex_source)?;
+        let fragment = Self::parse_glsl(&mut translator, naga::ShaderStage::Fragment, fragment_source)?;
+
+        return ProgramState::new(
+            Self::generate_program_id(),
+            (Arc::new(Self::create_module(&vertex)), &vertex),
+            (Arc::new(Self::create_module(&fragment)), &fragment),
+        );
+    }
+
+    fn parse_glsl(
+        translator: &mut GlslTranslator,
+        stage: naga::ShaderStage,
+        source: &str,
+    ) -> Result<naga::Module, String> {
+        let source = translator.translate(stage, source);
+        return naga::front::glsl::Parser::default()
+            .parse(&naga::front::glsl::Options::from(stage), &source)
+            .map_err(|errors| {
+                let messages: Vec<String> = errors
+                    .iter()
+                    .map(|error| {
+                        let location = error.meta.location(&source);
+                        format!("{:?} {}:{}: {}", stage, location.line_number, location.line_position, error.kind)
+                    })
+                    .collect();
+                messages.join("\n")
+            });
+    }
+
+    fn load_wgsl(source: &str) -> Result<ProgramState, String> {
+        let module = naga::front::wgsl::parse_str(source).map_err(|error| error.emit_to_string(source))?;
+        let shader_module = Arc::new(Self::create_module(&module));
+
+        return ProgramState::new(
+            Self::generate_program_id(),
+            (shader_module.clone(), &module),
+            (shader_module, &module),
+        );
+    }
+
+    fn create_module(module: &naga::Module) -> wgpu::ShaderModule {
+        return get_gpu().device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Naga(Cow::Owned(module.clone())),
+        });
+    }
+
+    // Pipelines are cached by program, so every load gets a new id.
+    fn generate_program_id() -> u32 {
+        return with_context(|context| context.generate_id());
+    }
+
+    fn set_uniform(&self, name: &str, bytes: &[u8]) {
+        if let Some(state) = &self.state {
+            state.set_uniform(name, bytes);
+        }
+    }
+}
+
+impl Shader for WgpuShader {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn load_from_file(&mut self, file_path: &str) {
+        let file_content = fs::read_to_string(file_path).expect("Couldn't read shader file");
+        self.load_from_source(&file_content);
+    }
+
+    fn load_from_source(&mut self, source: &str) {
+        let program = match source.contains("#shader ") {
+            true => Self::load_glsl(source),
+            false => Self::load_wgsl(source),
+        };
+
+        match program {
+            Ok(program) => self.state = Some(Arc::new(program)),
+            Err(error) => log::error!("Failed to compile shader with error\n{}", error),
+        }
+    }
+
+    fn set_label(&self, _label: &str) {}
+
+    fn get_attributes(&self) -> Option<Vec<ShaderAttribute>> {
+        return self.state.as_ref().map(|state| state.attributes.clone());
+    }
+
+    // Integers naming a texture select its slot like sampler uniforms do in
+    // OpenGL.
+    fn submit_uniform_i32(&self, name: &str, value: i32) {
+        if let Some(state) = &self.state {
+            if state.set_slot(name, value as u32) {
+                return;
+            }
+        }
+        self.set_uniform(name, &value.to_ne_bytes());
+    }
+
+    fn submit_uniform_vec3(&self, name: &str, value: Vector3<f32>) {
+        let bytes: Vec<u8> = value.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        self.set_uniform(name, &bytes);
+    }
+
+    fn submit_uniform_mat4x4(&self, name: &str, data: Matrix4<f32>) {
+        let bytes: Vec<u8> = data.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        self.set_uniform(name, &bytes);
+    }
+}
+
+impl Bindable for WgpuShader {
+    fn bind(&self) {
+        with_context(|context| context.program = self.state.clone());
+    }
+
+    fn unbind(&self) {
+        with_context(|context| context.program = None);
+    }
+}
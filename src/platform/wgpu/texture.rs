@@ -0,0 +1,536 @@
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, RwLock},
+};
+
+use crate::render::{
+    buffer::{BufferDataType, DataReader},
+    texture::{PixelBuffer, PixelData, Texture, TextureDimension, TextureFormat},
+    texture_container::{CompressedTexture, TextureLoadError},
+    texture_decoder::{decode_to_rgba, get_decoded_data_type},
+};
+
+use super::{get_gpu, with_context, Gpu};
+
+// Row 0 is the bottom row like in OpenGL, the translated shaders flip
+// clip space so rendered images are stored the same way.
+pub struct TextureState {
+    pub texture: Arc<wgpu::Texture>,
+    // Covers all layers with the dimension shaders declare.
+    pub view: Arc<wgpu::TextureView>,
+    // First layer, what framebuffers render into.
+    pub attachment_view: Arc<wgpu::TextureView>,
+    pub format: TextureFormat,
+    pub texture_format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub samples: u32,
+}
+
+impl TextureState {
+    pub fn new(
+        gpu: &Gpu,
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        depth: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+        samples: u32,
+    ) -> TextureState {
+        let (height, layers) = match dimension {
+            TextureDimension::Texture1D => (1, 1),
+            TextureDimension::Texture2D => (height, 1),
+            TextureDimension::Texture2DArray | TextureDimension::Texture3D => (height, depth),
+            TextureDimension::Cube => (height, 6),
+            TextureDimension::CubeArray => (height, depth * 6),
+        };
+
+        let texture_format = get_texture_format(format, data_type);
+        let features = texture_format.describe().guaranteed_format_features;
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING;
+        if samples <= 1 {
+            usage |= wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST;
+        }
+        let layered = matches!(dimension, TextureDimension::Texture1D | TextureDimension::Texture3D);
+        if !layered && features.allowed_usages.contains(wgpu::TextureUsages::RENDER_ATTACHMENT) {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+
+        // wgpu rejects empty textures, framebuffers allocate before they
+        // know their size.
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: layers.max(1),
+            },
+            mip_level_count: 1,
+            sample_count: samples.max(1),
+            dimension: match dimension {
+                TextureDimension::Texture1D => wgpu::TextureDimension::D1,
+                TextureDimension::Texture3D => wgpu::TextureDimension::D3,
+                _ => wgpu::TextureDimension::D2,
+            },
+            format: texture_format,
+            usage,
+        });
+
+        // Sampling a combined depth stencil texture reads the depth.
+        let aspect = match format {
+            TextureFormat::DepthStencil => wgpu::TextureAspect::DepthOnly,
+            _ => wgpu::TextureAspect::All,
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(get_view_dimension(dimension)),
+            aspect,
+            ..Default::default()
+        });
+        let attachment_view = match layered {
+            true => texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            false => texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            }),
+        };
+
+        return TextureState {
+            texture: Arc::new(texture),
+            view: Arc::new(view),
+            attachment_view: Arc::new(attachment_view),
+            format,
+            texture_format,
+            width,
+            height,
+            layers,
+            samples: samples.max(1),
+        };
+    }
+
+    // Layers are tightly packed one after another.
+    pub fn upload(&self, gpu: &Gpu, first_layer: u32, layers: u32, data: *const u8, data_type: BufferDataType) {
+        if data.is_null() || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        // wgpu doesn't allow copies into depth formats.
+        if matches!(self.format, TextureFormat::Depth | TextureFormat::DepthStencil) {
+            log::warn!("Depth textures can't be uploaded to with the wgpu renderer");
+            return;
+        }
+
+        let texel_size = self.texture_format.describe().block_size as usize;
+        let texel_count = (self.width * self.height * layers) as usize;
+        let bytes = match (self.format, data_type, self.texture_format) {
+            (TextureFormat::RGBA, BufferDataType::U8, wgpu::TextureFormat::Rgba8Unorm)
+            | (TextureFormat::Red, BufferDataType::U8, wgpu::TextureFormat::R8Unorm) => unsafe {
+                std::slice::from_raw_parts(data, texel_count * texel_size).to_vec()
+            },
+            _ => {
+                let channels = self.format.get_channel_count() as usize;
+                let mut reader = DataReader::new(data, data_type, true);
+                let mut bytes = Vec::with_capacity(texel_count * texel_size);
+                for _ in 0..texel_count {
+                    let mut values = [0.0; 4];
+                    for value in values.iter_mut().take(channels) {
+                        *value = reader.read();
+                    }
+                    encode_texel(self.texture_format, expand(self.format, values), &mut bytes);
+                }
+                bytes
+            }
+        };
+
+        gpu.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: first_layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &bytes,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(self.width * texel_size as u32),
+                rows_per_image: NonZeroU32::new(self.height),
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: layers,
+            },
+        );
+    }
+
+    // Copies through a mapped buffer and waits for it, pending draws have
+    // to be flushed before.
+    pub fn read(&self, gpu: &Gpu, data_type: BufferDataType) -> PixelData {
+        let channels = self.format.get_channel_count() as usize;
+        let texel_count = (self.width * self.height * self.layers) as usize;
+        if self.format == TextureFormat::DepthStencil || self.samples > 1 || texel_count == 0 {
+            if texel_count > 0 {
+                log::warn!("Multisampled and depth stencil textures can't be read with the wgpu renderer");
+            }
+            return PixelData::from_f32(&vec![0.0; texel_count * channels], data_type);
+        }
+
+        let texel_size = self.texture_format.describe().block_size as u32;
+        let row_size = self.width * texel_size;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_size = (row_size + alignment - 1) / alignment * alignment;
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (padded_row_size * self.height * self.layers) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: match self.format {
+                    TextureFormat::Depth => wgpu::TextureAspect::DepthOnly,
+                    _ => wgpu::TextureAspect::All,
+                },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_size),
+                    rows_per_image: NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: self.layers,
+            },
+        );
+        gpu.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        gpu.device.poll(wgpu::Maintain::Wait);
+
+        let mut values = Vec::with_capacity(texel_count * channels);
+        {
+            let bytes = slice.get_mapped_range();
+            for row in bytes.chunks(padded_row_size as usize) {
+                for texel in row[..row_size as usize].chunks(texel_size as usize) {
+                    let texel = decode_texel(self.texture_format, texel);
+                    values.extend_from_slice(&collapse(self.format, texel)[..channels]);
+                }
+            }
+        }
+        buffer.unmap();
+
+        return PixelData::from_f32(&values, data_type);
+    }
+}
+
+// Only 8 bit and half float formats are filterable everywhere, wider types
+// are stored as half floats. Green, blue and alpha textures are expanded to
+// RGBA so they sample like in OpenGL.
+pub fn get_texture_format(format: TextureFormat, data_type: BufferDataType) -> wgpu::TextureFormat {
+    let single_channel = format == TextureFormat::Red;
+    match (format, data_type) {
+        (TextureFormat::Depth, _) => wgpu::TextureFormat::Depth32Float,
+        (TextureFormat::DepthStencil, _) => wgpu::TextureFormat::Depth24PlusStencil8,
+        (_, BufferDataType::U8) if single_channel => wgpu::TextureFormat::R8Unorm,
+        (_, BufferDataType::U8) => wgpu::TextureFormat::Rgba8Unorm,
+        (_, BufferDataType::I8) if single_channel => wgpu::TextureFormat::R8Snorm,
+        (_, BufferDataType::I8) => wgpu::TextureFormat::Rgba8Snorm,
+        _ if single_channel => wgpu::TextureFormat::R16Float,
+        _ => wgpu::TextureFormat::Rgba16Float,
+    }
+}
+
+pub fn get_view_dimension(dimension: TextureDimension) -> wgpu::TextureViewDimension {
+    match dimension {
+        TextureDimension::Texture1D => wgpu::TextureViewDimension::D1,
+        TextureDimension::Texture2D => wgpu::TextureViewDimension::D2,
+        TextureDimension::Texture2DArray => wgpu::TextureViewDimension::D2Array,
+        TextureDimension::Texture3D => wgpu::TextureViewDimension::D3,
+        TextureDimension::Cube => wgpu::TextureViewDimension::Cube,
+        TextureDimension::CubeArray => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+pub fn expand(format: TextureFormat, values: [f32; 4]) -> [f32; 4] {
+    let [first, second, third, fourth] = values;
+    match format {
+        TextureFormat::RGBA => [first, second, third, fourth],
+        TextureFormat::RGB => [first, second, third, 1.0],
+        TextureFormat::Red | TextureFormat::Depth | TextureFormat::DepthStencil => {
+            [first, 0.0, 0.0, 1.0]
+        }
+        TextureFormat::Green => [0.0, first, 0.0, 1.0],
+        TextureFormat::Blue => [0.0, 0.0, first, 1.0],
+        TextureFormat::Alpha => [0.0, 0.0, 0.0, first],
+    }
+}
+
+pub fn collapse(format: TextureFormat, texel: [f32; 4]) -> [f32; 4] {
+    match format {
+        TextureFormat::Green => [texel[1], 0.0, 0.0, 0.0],
+        TextureFormat::Blue => [texel[2], 0.0, 0.0, 0.0],
+        TextureFormat::Alpha => [texel[3], 0.0, 0.0, 0.0],
+        _ => texel,
+    }
+}
+
+fn encode_texel(format: wgpu::TextureFormat, texel: [f32; 4], bytes: &mut Vec<u8>) {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::R8Unorm => {
+            for value in &texel[..format.describe().components as usize] {
+                bytes.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        wgpu::TextureFormat::Rgba8Snorm | wgpu::TextureFormat::R8Snorm => {
+            for value in &texel[..format.describe().components as usize] {
+                bytes.push((value.clamp(-1.0, 1.0) * 127.0).round() as i8 as u8);
+            }
+        }
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::R16Float => {
+            for value in &texel[..format.describe().components as usize] {
+                bytes.extend_from_slice(&to_half(*value).to_le_bytes());
+            }
+        }
+        _ => bytes.extend_from_slice(&texel[0].to_le_bytes()),
+    }
+}
+
+fn decode_texel(format: wgpu::TextureFormat, bytes: &[u8]) -> [f32; 4] {
+    let mut texel = [0.0, 0.0, 0.0, 1.0];
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::R8Unorm => {
+            for (value, byte) in texel.iter_mut().zip(bytes) {
+                *value = *byte as f32 / 255.0;
+            }
+        }
+        wgpu::TextureFormat::Rgba8Snorm | wgpu::TextureFormat::R8Snorm => {
+            for (value, byte) in texel.iter_mut().zip(bytes) {
+                *value = (*byte as i8 as f32 / 127.0).max(-1.0);
+            }
+        }
+        wgpu::TextureFormat::Rgba16Float | wgpu::TextureFormat::R16Float => {
+            for (value, half) in texel.iter_mut().zip(bytes.chunks(2)) {
+                *value = from_half(u16::from_le_bytes([half[0], half[1]]));
+            }
+        }
+        _ => texel[0] = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+
+    return texel;
+}
+
+// Rounds to nearest, out of range values become infinity.
+fn to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    if value.is_nan() {
+        return sign | 0x7e00;
+    }
+
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent >= 31 {
+        return sign | 0x7c00;
+    }
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = (mantissa >> shift) + ((mantissa >> (shift - 1)) & 1);
+        return sign | half as u16;
+    }
+
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    return sign | (half + ((mantissa >> 12) & 1)) as u16;
+}
+
+fn from_half(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+
+    return sign
+        * match exponent {
+            0 => mantissa * 2f32.powi(-24),
+            31 if mantissa == 0.0 => f32::INFINITY,
+            31 => f32::NAN,
+            _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+        };
+}
+
+#[derive(Clone)]
+pub struct WgpuTexture {
+    id: u32,
+    state: Arc<RwLock<TextureState>>,
+}
+
+impl WgpuTexture {
+    pub fn new(
+        width: u32,
+        height: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> WgpuTexture {
+        return WgpuTexture::new_with_dimension(
+            TextureDimension::Texture2D,
+            width,
+            height,
+            1,
+            data,
+            format,
+            data_type,
+        );
+    }
+
+    pub fn new_with_dimension(
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        depth: u32,
+        data: *const u8,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> WgpuTexture {
+        let gpu = get_gpu();
+        let state = TextureState::new(&gpu, dimension, width, height, depth, format, data_type, 1);
+        state.upload(&gpu, 0, state.layers, data, data_type);
+
+        return WgpuTexture::from_state(state);
+    }
+
+    pub fn new_cubemap(
+        size: u32,
+        faces: [*const u8; 6],
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> WgpuTexture {
+        let gpu = get_gpu();
+        let state =
+            TextureState::new(&gpu, TextureDimension::Cube, size, size, 1, format, data_type, 1);
+        for (face, data) in faces.iter().enumerate() {
+            state.upload(&gpu, face as u32, 1, *data, data_type);
+        }
+
+        return WgpuTexture::from_state(state);
+    }
+
+    // Block compressed formats need optional device features, so every
+    // format is decoded and only the base level is uploaded.
+    pub fn new_compressed(texture: &CompressedTexture) -> Result<WgpuTexture, TextureLoadError> {
+        let (width, height) = texture.get_level_size(0);
+        let images = (texture.layers * texture.faces) as usize;
+        let image_size = texture.format.get_image_size(width, height);
+        let data_type = get_decoded_data_type(texture.format);
+        let mut pixels = Vec::new();
+        for image in 0..images {
+            let data = &texture.levels[0][image * image_size..(image + 1) * image_size];
+            pixels.extend_from_slice(decode_to_rgba(texture.format, width, height, data)?.as_bytes());
+        }
+
+        let dimension = match (texture.faces, texture.layers) {
+            (6, 1) => TextureDimension::Cube,
+            (6, _) => TextureDimension::CubeArray,
+            (_, 1) => TextureDimension::Texture2D,
+            _ => TextureDimension::Texture2DArray,
+        };
+
+        return Ok(WgpuTexture::new_with_dimension(
+            dimension,
+            width,
+            height,
+            texture.layers,
+            pixels.as_ptr(),
+            TextureFormat::RGBA,
+            data_type,
+        ));
+    }
+
+    pub fn from_state(state: TextureState) -> WgpuTexture {
+        let state = Arc::new(RwLock::new(state));
+        let id = with_context(|context| context.register_texture(&state));
+
+        return WgpuTexture { id, state };
+    }
+
+    pub fn get_state(&self) -> &Arc<RwLock<TextureState>> {
+        return &self.state;
+    }
+}
+
+impl Texture for WgpuTexture {
+    fn get_id(&self) -> u32 {
+        return self.id;
+    }
+
+    fn bind(&self, slot: u32) {
+        with_context(|context| {
+            context.textures.insert(slot, self.state.clone());
+        });
+    }
+
+    fn unbind(&self) {
+        with_context(|context| {
+            context
+                .textures
+                .retain(|_, state| !Arc::ptr_eq(state, &self.state));
+        });
+    }
+
+    fn get_size(&self, level: u32) -> (u32, u32) {
+        let state = self.state.read().unwrap();
+        return ((state.width >> level).max(1), (state.height >> level).max(1));
+    }
+
+    fn get_format(&self) -> TextureFormat {
+        return self.state.read().unwrap().format;
+    }
+
+    // Layers, slices and cube faces are stacked vertically.
+    fn read_pixels(&self, _level: u32, data_type: BufferDataType) -> PixelBuffer {
+        let gpu = get_gpu();
+        with_context(|context| context.flush());
+
+        let state = self.state.read().unwrap();
+        return PixelBuffer {
+            width: state.width,
+            height: state.height * state.layers,
+            format: state.format,
+            data: state.read(&gpu, data_type),
+        };
+    }
+
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType) {
+        let gpu = get_gpu();
+        with_context(|context| context.flush());
+        self.state.read().unwrap().upload(&gpu, layer, 1, data, data_type);
+    }
+
+    fn is_ready(&self) -> bool {
+        return true;
+    }
+
+    // Labels can only be given when creating wgpu objects.
+    fn set_label(&self, _label: &str) {}
+}
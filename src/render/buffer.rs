@@ -1,14 +1,26 @@
+#[cfg(feature = "opengl")]
 use crate::platform::opengl::{buffer::OglBuffer, vertex_array::OglVertexArray};
+#[cfg(feature = "recording")]
+use crate::platform::recording::buffer::{RecordingBuffer, RecordingVertexArray};
+#[cfg(feature = "software")]
+use crate::platform::software::buffer::{SoftwareBuffer, SoftwareVertexArray};
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::buffer::{WgpuBuffer, WgpuVertexArray};
+
+use super::render_api::{get_backend, Backend};
 
 #[allow(dead_code)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BufferType {
     Vertex,
     Index,
+    // Holds DrawArraysIndirectCommand or DrawElementsIndirectCommand
+    // entries read by indirect draws.
+    Indirect,
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BufferDataType {
     F32,
     F64,
@@ -21,20 +33,160 @@ pub enum BufferDataType {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BufferUsage {
     Static,
     Dynamic,
     Stream
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct BufferLayoutNode {
     pub elements: u32,
     pub data_type: BufferDataType,
     pub normalized: bool,
 }
 
+impl BufferDataType {
+    pub fn get_size(&self) -> usize {
+        match self {
+            BufferDataType::F64 => 8,
+            BufferDataType::F32 | BufferDataType::I32 | BufferDataType::U32 => 4,
+            BufferDataType::I16 | BufferDataType::U16 => 2,
+            BufferDataType::I8 | BufferDataType::U8 => 1,
+        }
+    }
+}
+
+// Part of a vertex array drawn on its own. First and count are indices when
+// the array has an index buffer and vertices otherwise, the base vertex is
+// added to every index and ignored without one.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DrawRange {
+    pub first: u32,
+    pub count: u32,
+    pub base_vertex: i32,
+}
+
+// Laid out like the commands OpenGL and wgpu read from indirect buffers.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DrawArraysIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first: u32,
+    pub base_instance: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DrawElementsIndirectCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+// Reads commands from the bytes of an indirect buffer for backends that
+// draw on the CPU, as ranges with their instance count. Commands reaching
+// past the end are dropped.
+#[cfg(any(feature = "software", feature = "recording"))]
+pub(crate) fn read_indirect_commands(
+    bytes: &[u8],
+    offset: usize,
+    count: usize,
+    indexed: bool,
+) -> Vec<(DrawRange, u32)> {
+    let stride = match indexed {
+        true => std::mem::size_of::<DrawElementsIndirectCommand>(),
+        false => std::mem::size_of::<DrawArraysIndirectCommand>(),
+    };
+    let read = |command: &[u8], index: usize| {
+        let start = index * 4;
+        u32::from_ne_bytes([command[start], command[start + 1], command[start + 2], command[start + 3]])
+    };
+
+    return (0..count)
+        .map(|index| offset + index * stride)
+        .take_while(|start| start + stride <= bytes.len())
+        .map(|start| {
+            let command = &bytes[start..start + stride];
+            let range = match indexed {
+                true => DrawRange {
+                    first: read(command, 2),
+                    count: read(command, 0),
+                    base_vertex: read(command, 3) as i32,
+                },
+                false => DrawRange {
+                    first: read(command, 2),
+                    count: read(command, 0),
+                    base_vertex: 0,
+                },
+            };
+            (range, read(command, 1))
+        })
+        .collect();
+}
+
+// Reads tightly packed values of one type as floats, integers are mapped
+// to [0, 1] or [-1, 1] when normalized.
+#[cfg(any(feature = "software", feature = "wgpu"))]
+pub(crate) struct DataReader {
+    pointer: *const u8,
+    data_type: BufferDataType,
+    normalized: bool,
+}
+
+#[cfg(any(feature = "software", feature = "wgpu"))]
+impl DataReader {
+    pub fn new(pointer: *const u8, data_type: BufferDataType, normalized: bool) -> DataReader {
+        return DataReader {
+            pointer,
+            data_type,
+            normalized,
+        };
+    }
+
+    pub fn read(&mut self) -> f32 {
+        let value = unsafe {
+            match self.data_type {
+                BufferDataType::F32 => std::ptr::read_unaligned(self.pointer as *const f32),
+                BufferDataType::F64 => std::ptr::read_unaligned(self.pointer as *const f64) as f32,
+                BufferDataType::I8 => self.normalize(*self.pointer as i8 as f32, i8::MAX as f32),
+                BufferDataType::U8 => self.normalize(*self.pointer as f32, u8::MAX as f32),
+                BufferDataType::I16 => self.normalize(
+                    std::ptr::read_unaligned(self.pointer as *const i16) as f32,
+                    i16::MAX as f32,
+                ),
+                BufferDataType::U16 => self.normalize(
+                    std::ptr::read_unaligned(self.pointer as *const u16) as f32,
+                    u16::MAX as f32,
+                ),
+                BufferDataType::I32 => self.normalize(
+                    std::ptr::read_unaligned(self.pointer as *const i32) as f32,
+                    i32::MAX as f32,
+                ),
+                BufferDataType::U32 => self.normalize(
+                    std::ptr::read_unaligned(self.pointer as *const u32) as f32,
+                    u32::MAX as f32,
+                ),
+            }
+        };
+
+        self.pointer = unsafe { self.pointer.add(self.data_type.get_size()) };
+        return value;
+    }
+
+    fn normalize(&self, value: f32, max: f32) -> f32 {
+        if !self.normalized {
+            return value;
+        }
+
+        return (value / max).max(-1.0);
+    }
+}
+
 impl BufferLayoutNode {
     pub fn get_layout_size(layout: Vec<BufferLayoutNode>) -> usize {
         let mut size = 0;
@@ -51,14 +203,51 @@ pub trait Bindable {
 }
 
 pub trait Buffer: Bindable {
+    fn get_id(&self) -> u32;
     fn get_layout(&self) -> &Vec<BufferLayoutNode>;
     fn get_element_count(&self) -> usize;
     // TODO: Make generic
     fn add_sub_data(&mut self, data: Vec<f32>, offset: usize);
+    // Shown in debug messages and graphics debuggers.
+    fn set_label(&self, label: &str);
+
+    // Type of the indices in index buffers, ones created without a layout
+    // hold u32.
+    fn get_index_type(&self) -> BufferDataType {
+        return self
+            .get_layout()
+            .first()
+            .map_or(BufferDataType::U32, |node| node.data_type);
+    }
+}
+
+// Types index buffers can hold.
+pub trait IndexType: Copy {
+    const DATA_TYPE: BufferDataType;
+}
+
+impl IndexType for u8 {
+    const DATA_TYPE: BufferDataType = BufferDataType::U8;
+}
+
+impl IndexType for u16 {
+    const DATA_TYPE: BufferDataType = BufferDataType::U16;
+}
+
+impl IndexType for u32 {
+    const DATA_TYPE: BufferDataType = BufferDataType::U32;
 }
 
 pub trait VertexArray: Bindable {
     fn draw(&self);
+    fn draw_range(&self, range: DrawRange);
+    // Draws all ranges with a single call where the backend can.
+    fn multi_draw(&self, ranges: &[DrawRange]);
+    // Draws `count` commands read from an indirect buffer, starting `offset`
+    // bytes in. They are DrawElementsIndirectCommand entries when the array
+    // has indices and DrawArraysIndirectCommand entries otherwise, so the
+    // buffer can be filled by compute shaders.
+    fn draw_indirect(&self, buffer: &Box<dyn Buffer>, offset: usize, count: usize);
     fn add_buffer(&mut self, buffer: Box<dyn Buffer>, buffer_type: BufferType);
     fn get_buffers(&self) -> &Vec<Box<dyn Buffer>>;
     // TODO: Make generic
@@ -72,9 +261,91 @@ pub fn create_buffer<T>(
     buffer_usage: BufferUsage,
     size: Option<usize>
 ) -> Box<dyn Buffer> {
-    return Box::new(OglBuffer::new(data, buffer_type, buffer_layout, buffer_usage, size));
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglBuffer::new(data, buffer_type, buffer_layout, buffer_usage, size)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareBuffer::new(data, buffer_type, buffer_layout, buffer_usage, size)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingBuffer::new(data, buffer_type, buffer_layout, buffer_usage, size)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuBuffer::new(data, buffer_type, buffer_layout, buffer_usage, size)),
+    }
+}
+
+// The index type is stored in the layout of the buffer.
+pub fn create_index_buffer<T: IndexType>(indices: &[T], buffer_usage: BufferUsage) -> Box<dyn Buffer> {
+    let layout = vec![BufferLayoutNode {
+        elements: 1,
+        data_type: T::DATA_TYPE,
+        normalized: false,
+    }];
+    return create_buffer(&mut indices.to_vec(), BufferType::Index, layout, buffer_usage, None);
+}
+
+// Stores the indices as u16 when they fit and as u32 otherwise. u8 indices
+// are slow on many GPUs and missing in wgpu, so they are only used when
+// asked for through create_index_buffer.
+pub fn create_compact_index_buffer(indices: &[u32], buffer_usage: BufferUsage) -> Box<dyn Buffer> {
+    let max = indices.iter().copied().max().unwrap_or(0);
+    if max <= u16::MAX as u32 {
+        let indices: Vec<u16> = indices.iter().map(|index| *index as u16).collect();
+        return create_index_buffer(&indices, buffer_usage);
+    }
+
+    return create_index_buffer(indices, buffer_usage);
 }
 
 pub fn create_vertex_array() -> Box<dyn VertexArray> {
-    return Box::new(OglVertexArray::new());
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglVertexArray::new()),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareVertexArray::new()),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingVertexArray::new()),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuVertexArray::new()),
+    }
+}
+
+#[cfg(all(test, feature = "recording"))]
+mod tests {
+    use super::*;
+    use crate::render::{render_api::create_api_with_backend, window::create_null_window};
+
+    #[test]
+    fn compact_index_buffer_fits_largest_index() {
+        let mut window = create_null_window(16, 16);
+        let _api = create_api_with_backend(&mut window, Backend::Recording);
+
+        let cases = [
+            (vec![], BufferDataType::U16),
+            (vec![0, 1, 2], BufferDataType::U16),
+            (vec![0, u8::MAX as u32 + 1], BufferDataType::U16),
+            (vec![u16::MAX as u32, 0], BufferDataType::U16),
+            (vec![0, u16::MAX as u32 + 1], BufferDataType::U32),
+        ];
+        for (indices, data_type) in cases {
+            let buffer = create_compact_index_buffer(&indices, BufferUsage::Static);
+            assert_eq!(buffer.get_index_type(), data_type, "indices {:?}", indices);
+            assert_eq!(buffer.get_element_count(), indices.len());
+        }
+    }
+
+    #[test]
+    fn index_type_round_trips_through_layout() {
+        let mut window = create_null_window(16, 16);
+        let _api = create_api_with_backend(&mut window, Backend::Recording);
+
+        let buffer = create_index_buffer(&[0u8, 1, 2], BufferUsage::Static);
+        assert_eq!(buffer.get_index_type(), BufferDataType::U8);
+        let buffer = create_index_buffer(&[0u16, 1, 2], BufferUsage::Static);
+        assert_eq!(buffer.get_index_type(), BufferDataType::U16);
+        let buffer = create_index_buffer(&[0u32, 1, 2], BufferUsage::Static);
+        assert_eq!(buffer.get_index_type(), BufferDataType::U32);
+
+        let buffer = create_buffer(&mut vec![0u32, 1, 2], BufferType::Index, Vec::new(), BufferUsage::Static, None);
+        assert_eq!(buffer.get_index_type(), BufferDataType::U32);
+    }
 }
@@ -0,0 +1,46 @@
+// Limits and identification of the device behind a render API, queried once
+// when the API is created.
+#[derive(Clone, Default, Debug)]
+pub struct Capabilities {
+    // As reported by the driver, CPU backends name themselves.
+    pub version: String,
+    pub shading_language_version: String,
+    pub vendor: String,
+    pub renderer: String,
+    pub extensions: Vec<String>,
+
+    // Width and height of 2D textures.
+    pub max_texture_size: u32,
+    // Texture slots usable by a single draw.
+    pub max_texture_units: u32,
+    pub max_color_attachments: u32,
+    pub max_samples: u32,
+    // In bytes.
+    pub max_uniform_block_size: u32,
+    // None when anisotropic filtering isn't supported.
+    pub max_anisotropy: Option<f32>,
+}
+
+impl Capabilities {
+    // What every OpenGL 3.3 context guarantees, the CPU backends start from
+    // these so code tuned on them behaves the same on a GPU.
+    pub fn minimum() -> Capabilities {
+        return Capabilities {
+            version: String::from("3.3"),
+            shading_language_version: String::from("3.30"),
+            vendor: String::new(),
+            renderer: String::new(),
+            extensions: Vec::new(),
+            max_texture_size: 1024,
+            max_texture_units: 16,
+            max_color_attachments: 8,
+            max_samples: 4,
+            max_uniform_block_size: 16384,
+            max_anisotropy: None,
+        };
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        return self.extensions.iter().any(|extension| extension == name);
+    }
+}
@@ -0,0 +1,542 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc, Arc, Mutex,
+    },
+};
+
+use crate::event::Event;
+
+use super::{
+    buffer::{
+        create_buffer, create_vertex_array, Buffer, BufferDataType, BufferLayoutNode, BufferType,
+        BufferUsage, DrawRange, VertexArray,
+    },
+    framebuffer::{create_framebuffer, Framebuffer, FramebufferDescriptor},
+    mesh::Drawable,
+    pipeline::{create_pipeline, Pipeline, PipelineDescriptor},
+    render_api::RenderAPI,
+    render_queue::RenderQueue,
+    shader::{create_shader_from_file, create_shader_from_source, Shader, UniformValue},
+    texture::{create_texture, PixelBuffer, Texture, TextureFormat},
+    window::Window,
+};
+
+// Shared by all threads so command buffers recorded in parallel never hand
+// out the same handle.
+static NEXT_HANDLE: AtomicU32 = AtomicU32::new(1);
+
+// Names a resource before the render thread created it. Commands recorded
+// after the one creating it may use it right away.
+pub struct Handle<T> {
+    id: u32,
+    resource: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new() -> Handle<T> {
+        return Handle {
+            id: NEXT_HANDLE.fetch_add(1, Ordering::Relaxed),
+            resource: PhantomData,
+        };
+    }
+
+    pub fn get_id(&self) -> u32 {
+        return self.id;
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Handle<T> {
+        return *self;
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Handle<T>) -> bool {
+        return self.id == other.id;
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.id)
+    }
+}
+
+// Resources created by command buffers, they only live on the render thread.
+pub struct Resources {
+    objects: HashMap<u32, Box<dyn Any>>,
+}
+
+impl Resources {
+    fn new() -> Resources {
+        return Resources {
+            objects: HashMap::new(),
+        };
+    }
+
+    // None before the command creating the resource ran, after it was
+    // destroyed or when creating it failed.
+    pub fn get<T: 'static>(&self, handle: Handle<T>) -> Option<&T> {
+        return self.objects.get(&handle.id).and_then(|object| object.downcast_ref());
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        return self.objects.get_mut(&handle.id).and_then(|object| object.downcast_mut());
+    }
+
+    pub fn insert<T: 'static>(&mut self, handle: Handle<T>, resource: T) {
+        self.objects.insert(handle.id, Box::new(resource));
+    }
+
+    pub fn remove<T: 'static>(&mut self, handle: Handle<T>) -> Option<T> {
+        let object = self.objects.remove(&handle.id)?;
+        match object.downcast() {
+            Ok(resource) => return Some(*resource),
+            Err(object) => {
+                self.objects.insert(handle.id, object);
+                return None;
+            }
+        }
+    }
+}
+
+// Everything commands can reach while they run on the render thread.
+pub struct RenderContext {
+    // Declared first so resources are dropped, deleting their objects,
+    // while the window's context still exists.
+    pub resources: Resources,
+    pub api: Box<dyn RenderAPI>,
+    pub window: Box<dyn Window>,
+    // Open from a begin command until the matching end.
+    queue: Option<RenderQueue<'static>>,
+    submitted: Vec<(Handle<Box<dyn Drawable>>, f32)>,
+    events: Arc<Mutex<Vec<Event>>>,
+    should_close: Arc<AtomicBool>,
+}
+
+impl RenderContext {
+    pub(crate) fn new(
+        api: Box<dyn RenderAPI>,
+        window: Box<dyn Window>,
+        events: Arc<Mutex<Vec<Event>>>,
+        should_close: Arc<AtomicBool>,
+    ) -> RenderContext {
+        return RenderContext {
+            resources: Resources::new(),
+            api,
+            window,
+            queue: None,
+            submitted: Vec::new(),
+            events,
+            should_close,
+        };
+    }
+
+    // Drawables are taken out of the resources while the queue borrows
+    // them and put back once it was drawn.
+    fn end_frame(&mut self) {
+        let queue = match self.queue.take() {
+            Some(queue) => queue,
+            None => {
+                log::warn!("End command without a begin");
+                return;
+            }
+        };
+
+        let mut drawables = Vec::new();
+        for (handle, depth) in std::mem::take(&mut self.submitted) {
+            match self.resources.remove(handle) {
+                Some(drawable) => drawables.push((handle, drawable, depth)),
+                None => missing_resource("submit"),
+            }
+        }
+
+        let mut queue: RenderQueue = queue;
+        for (_, drawable, depth) in drawables.iter_mut() {
+            queue.submit_at_depth(drawable, *depth);
+        }
+        self.api.end(queue);
+
+        for (handle, drawable, _) in drawables {
+            self.resources.insert(handle, drawable);
+        }
+    }
+}
+
+fn missing_resource(command: &str) {
+    log::warn!("{} command uses a handle without a resource", command);
+}
+
+// Backends read a whole layer from the pointer, shorter data would be read
+// past its end.
+fn check_layer_size(
+    command: &str,
+    data: &[u8],
+    (width, height): (u32, u32),
+    format: TextureFormat,
+    data_type: BufferDataType,
+) -> bool {
+    let layer_size = (width * height * format.get_channel_count()) as usize * data_type.get_size();
+    if data.len() != layer_size {
+        log::error!(
+            "{} command has {} bytes of pixels but a layer takes {}, the command was skipped",
+            command,
+            data.len(),
+            layer_size
+        );
+        return false;
+    }
+
+    return true;
+}
+
+type Command = Box<dyn FnOnce(&mut RenderContext) + Send>;
+
+// Draws, uploads and state changes recorded on any thread and run in order
+// by the render thread once submitted, see RenderThread::submit. Resources
+// are created through it as well and named by handles until then.
+#[derive(Default)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> CommandBuffer {
+        return CommandBuffer {
+            commands: Vec::new(),
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.commands.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.commands.is_empty();
+    }
+
+    // Runs any code on the render thread, for calls without a command of
+    // their own.
+    pub fn execute<F>(&mut self, command: F)
+    where
+        F: FnOnce(&mut RenderContext) + Send + 'static,
+    {
+        self.commands.push(Box::new(command));
+    }
+
+    pub(crate) fn run(self, context: &mut RenderContext) {
+        for command in self.commands {
+            command(context);
+        }
+    }
+
+    // The resource is created on the render thread, None leaves the handle
+    // without one.
+    pub fn create<T, F>(&mut self, create: F) -> Handle<T>
+    where
+        T: 'static,
+        F: FnOnce(&mut RenderContext) -> Option<T> + Send + 'static,
+    {
+        let handle = Handle::new();
+        self.execute(move |context| {
+            if let Some(resource) = create(context) {
+                context.resources.insert(handle, resource);
+            }
+        });
+        return handle;
+    }
+
+    pub fn destroy<T: 'static>(&mut self, handle: Handle<T>) {
+        self.execute(move |context| {
+            context.resources.remove(handle);
+        });
+    }
+
+    pub fn create_buffer<T: Send + 'static>(
+        &mut self,
+        mut data: Vec<T>,
+        buffer_type: BufferType,
+        buffer_layout: Vec<BufferLayoutNode>,
+        buffer_usage: BufferUsage,
+    ) -> Handle<Box<dyn Buffer>> {
+        return self.create(move |_| {
+            Some(create_buffer(&mut data, buffer_type, buffer_layout, buffer_usage, None))
+        });
+    }
+
+    pub fn update_buffer(&mut self, buffer: Handle<Box<dyn Buffer>>, data: Vec<f32>, offset: usize) {
+        self.execute(move |context| match context.resources.get_mut(buffer) {
+            Some(buffer) => buffer.add_sub_data(data, offset),
+            None => missing_resource("update_buffer"),
+        });
+    }
+
+    // The buffers move into the vertex array and their handles are left
+    // without a resource.
+    pub fn create_vertex_array(
+        &mut self,
+        buffers: Vec<(Handle<Box<dyn Buffer>>, BufferType)>,
+    ) -> Handle<Box<dyn VertexArray>> {
+        return self.create(move |context| {
+            let mut vertex_array = create_vertex_array();
+            for (buffer, buffer_type) in buffers {
+                match context.resources.remove(buffer) {
+                    Some(buffer) => vertex_array.add_buffer(buffer, buffer_type),
+                    None => missing_resource("create_vertex_array"),
+                }
+            }
+            Some(vertex_array)
+        });
+    }
+
+    pub fn update_vertex_array(
+        &mut self,
+        vertex_array: Handle<Box<dyn VertexArray>>,
+        data: Vec<f32>,
+        offset: usize,
+        buffer_index: usize,
+    ) {
+        self.execute(move |context| match context.resources.get_mut(vertex_array) {
+            Some(vertex_array) => vertex_array.add_buffer_sub_data(data, offset, buffer_index),
+            None => missing_resource("update_vertex_array"),
+        });
+    }
+
+    pub fn create_shader_from_source(&mut self, source: &str) -> Handle<Box<dyn Shader>> {
+        let source = source.to_string();
+        return self.create(move |_| Some(create_shader_from_source(&source)));
+    }
+
+    pub fn create_shader_from_file(&mut self, file_path: &str) -> Handle<Box<dyn Shader>> {
+        let file_path = file_path.to_string();
+        return self.create(move |_| Some(create_shader_from_file(&file_path)));
+    }
+
+    // The shader moves into the pipeline. Errors are logged and leave the
+    // handle without a pipeline.
+    pub fn create_pipeline(
+        &mut self,
+        shader: Handle<Box<dyn Shader>>,
+        descriptor: PipelineDescriptor,
+    ) -> Handle<Box<dyn Pipeline>> {
+        return self.create(move |context| {
+            let shader = match context.resources.remove(shader) {
+                Some(shader) => shader,
+                None => {
+                    missing_resource("create_pipeline");
+                    return None;
+                }
+            };
+
+            match create_pipeline(shader, descriptor) {
+                Ok(pipeline) => Some(pipeline),
+                Err(error) => {
+                    log::error!("Failed to create pipeline: {}", error);
+                    None
+                }
+            }
+        });
+    }
+
+    // Data is one tightly packed layer, any other size is logged and leaves
+    // the handle without a texture. Uploads are checked the same way.
+    pub fn create_texture(
+        &mut self,
+        data: Vec<u8>,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+    ) -> Handle<Box<dyn Texture>> {
+        return self.create(move |_| {
+            if !check_layer_size("create_texture", &data, (width, height), format, data_type) {
+                return None;
+            }
+
+            Some(create_texture(data.as_ptr(), width, height, format, data_type))
+        });
+    }
+
+    pub fn upload_texture_layer(
+        &mut self,
+        texture: Handle<Box<dyn Texture>>,
+        layer: u32,
+        data: Vec<u8>,
+        data_type: BufferDataType,
+    ) {
+        self.execute(move |context| match context.resources.get(texture) {
+            Some(texture) => {
+                let size = texture.get_size(0);
+                if check_layer_size("upload_texture_layer", &data, size, texture.get_format(), data_type) {
+                    texture.upload_layer(layer, data.as_ptr(), data_type);
+                }
+            }
+            None => missing_resource("upload_texture_layer"),
+        });
+    }
+
+    // Errors are logged and leave the handle without a framebuffer.
+    pub fn create_framebuffer(&mut self, descriptor: FramebufferDescriptor) -> Handle<Box<dyn Framebuffer>> {
+        return self.create(move |_| match create_framebuffer(descriptor) {
+            Ok(framebuffer) => Some(framebuffer),
+            Err(error) => {
+                log::error!("Failed to create framebuffer: {}", error);
+                None
+            }
+        });
+    }
+
+    // Drawables can't be sent between threads, so they are built on the
+    // render thread from resources created before.
+    pub fn create_drawable<F>(&mut self, create: F) -> Handle<Box<dyn Drawable>>
+    where
+        F: FnOnce(&mut RenderContext) -> Option<Box<dyn Drawable>> + Send + 'static,
+    {
+        return self.create(create);
+    }
+
+    // None targets the window again.
+    pub fn set_render_target(&mut self, target: Option<Handle<Box<dyn Framebuffer>>>) {
+        self.execute(move |context| match target {
+            Some(target) => match context.resources.get(target) {
+                Some(framebuffer) => context.api.set_render_target(Some(framebuffer)),
+                None => missing_resource("set_render_target"),
+            },
+            None => context.api.set_render_target(None),
+        });
+    }
+
+    pub fn set_viewport(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.execute(move |context| context.api.set_viewport(x, y, width, height));
+    }
+
+    pub fn set_clear_color(&mut self, red: f32, green: f32, blue: f32, alpha: f32) {
+        self.execute(move |context| context.api.set_clear_color(red, green, blue, alpha));
+    }
+
+    pub fn clear(&mut self) {
+        self.execute(|context| context.api.clear());
+    }
+
+    // Drawables submitted until the matching end are drawn sorted by the
+    // render queue, everything else is drawn in the order it was recorded.
+    pub fn begin(&mut self) {
+        self.execute(|context| {
+            if context.queue.is_some() {
+                log::warn!("Begin command without an end");
+                context.end_frame();
+            }
+            context.queue = Some(context.api.begin());
+        });
+    }
+
+    pub fn end(&mut self) {
+        self.execute(|context| context.end_frame());
+    }
+
+    // Depth is the distance from the camera.
+    pub fn submit(&mut self, drawable: Handle<Box<dyn Drawable>>, depth: f32) {
+        self.execute(move |context| match context.queue {
+            Some(_) => context.submitted.push((drawable, depth)),
+            None => log::warn!("Submit command outside of begin and end"),
+        });
+    }
+
+    pub fn bind_pipeline(&mut self, pipeline: Handle<Box<dyn Pipeline>>) {
+        self.execute(move |context| match context.resources.get(pipeline) {
+            Some(pipeline) => pipeline.bind(),
+            None => missing_resource("bind_pipeline"),
+        });
+    }
+
+    pub fn bind_texture(&mut self, texture: Handle<Box<dyn Texture>>, slot: u32) {
+        self.execute(move |context| match context.resources.get(texture) {
+            Some(texture) => texture.bind(slot),
+            None => missing_resource("bind_texture"),
+        });
+    }
+
+    // Binds the shader of the pipeline to set the uniform.
+    pub fn set_uniform(&mut self, pipeline: Handle<Box<dyn Pipeline>>, name: &str, value: UniformValue) {
+        let name = name.to_string();
+        self.execute(move |context| {
+            let shader = match context.resources.get(pipeline) {
+                Some(pipeline) => pipeline.get_shader(),
+                None => return missing_resource("set_uniform"),
+            };
+
+            shader.bind();
+            match value {
+                UniformValue::I32(value) => shader.submit_uniform_i32(&name, value),
+                UniformValue::Vec3(value) => shader.submit_uniform_vec3(&name, value),
+                UniformValue::Mat4(value) => shader.submit_uniform_mat4x4(&name, value),
+            }
+        });
+    }
+
+    pub fn draw(&mut self, vertex_array: Handle<Box<dyn VertexArray>>) {
+        self.execute(move |context| match context.resources.get(vertex_array) {
+            Some(vertex_array) => vertex_array.draw(),
+            None => missing_resource("draw"),
+        });
+    }
+
+    pub fn draw_range(&mut self, vertex_array: Handle<Box<dyn VertexArray>>, range: DrawRange) {
+        self.execute(move |context| match context.resources.get(vertex_array) {
+            Some(vertex_array) => vertex_array.draw_range(range),
+            None => missing_resource("draw_range"),
+        });
+    }
+
+    pub fn multi_draw(&mut self, vertex_array: Handle<Box<dyn VertexArray>>, ranges: Vec<DrawRange>) {
+        self.execute(move |context| match context.resources.get(vertex_array) {
+            Some(vertex_array) => vertex_array.multi_draw(&ranges),
+            None => missing_resource("multi_draw"),
+        });
+    }
+
+    pub fn draw_indirect(
+        &mut self,
+        vertex_array: Handle<Box<dyn VertexArray>>,
+        buffer: Handle<Box<dyn Buffer>>,
+        offset: usize,
+        count: usize,
+    ) {
+        self.execute(move |context| {
+            match (context.resources.get(vertex_array), context.resources.get(buffer)) {
+                (Some(vertex_array), Some(buffer)) => vertex_array.draw_indirect(buffer, offset, count),
+                _ => missing_resource("draw_indirect"),
+            }
+        });
+    }
+
+    // The pixels arrive once the render thread reached the command.
+    pub fn capture_frame(&mut self) -> mpsc::Receiver<PixelBuffer> {
+        let (sender, receiver) = mpsc::channel();
+        self.execute(move |context| {
+            let _ = sender.send(context.api.capture_frame());
+        });
+        return receiver;
+    }
+
+    // Shows the frame and collects the window events, see
+    // RenderThread::take_events.
+    pub fn present(&mut self) {
+        self.execute(|context| {
+            context.api.end_frame();
+            context.window.update();
+            let events = context.window.get_events();
+            context.events.lock().unwrap().extend(events);
+            context.should_close.store(context.window.should_close(), Ordering::Relaxed);
+        });
+    }
+}
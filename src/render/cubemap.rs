@@ -0,0 +1,147 @@
+#[cfg(any(feature = "software", feature = "wgpu"))]
+use std::f32::consts::PI;
+
+#[cfg(any(feature = "software", feature = "wgpu"))]
+use nalgebra::Vector3;
+
+#[cfg(any(feature = "software", feature = "wgpu"))]
+use super::buffer::DataReader;
+use super::{buffer::BufferDataType, texture::TextureFormat};
+
+// Face positions in cells for +X, -X, +Y, -Y, +Z, -Z.
+const HORIZONTAL_CROSS: [(usize, usize); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+const VERTICAL_CROSS: [(usize, usize); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)];
+
+// Cuts the six faces out of a horizontal or vertical cross, the pixels keep
+// their format and data type. Returns the face size with the faces.
+pub(crate) fn split_cross(
+    data: *const u8,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+) -> (u32, [Vec<u8>; 6]) {
+    let (size, layout) = if width * 3 == height * 4 {
+        (width / 4, HORIZONTAL_CROSS)
+    } else if width * 4 == height * 3 {
+        (width / 3, VERTICAL_CROSS)
+    } else {
+        panic!("Cubemap cross must have a 4:3 or 3:4 aspect ratio");
+    };
+
+    let pixel_size = format.get_channel_count() as usize * data_type.get_size();
+    let row_size = width as usize * pixel_size;
+    let face_row_size = size as usize * pixel_size;
+    let source = unsafe { std::slice::from_raw_parts(data, row_size * height as usize) };
+
+    let faces = std::array::from_fn(|face| {
+        let (column, row) = layout[face];
+        let mut pixels = Vec::with_capacity(face_row_size * size as usize);
+        for y in 0..size as usize {
+            let start = (row * size as usize + y) * row_size + column * face_row_size;
+            pixels.extend_from_slice(&source[start..start + face_row_size]);
+        }
+
+        // The bottom cell of a vertical cross is stored upside down.
+        if layout == VERTICAL_CROSS && face == 5 {
+            pixels = pixels
+                .chunks_exact(pixel_size)
+                .rev()
+                .flatten()
+                .copied()
+                .collect();
+        }
+
+        pixels
+    });
+
+    return (size, faces);
+}
+
+// Same projection as the OpenGL conversion shader, run on the CPU for the
+// backends without one. Faces are RGB F32 like the converted cubemaps of
+// OpenGL, integer sources are read normalized.
+#[cfg(any(feature = "software", feature = "wgpu"))]
+pub(crate) fn project_equirectangular(
+    data: *const u8,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+    size: u32,
+) -> [Vec<f32>; 6] {
+    let channels = format.get_channel_count() as usize;
+    let mut reader = DataReader::new(data, data_type, true);
+    let source: Vec<f32> = (0..width as usize * height as usize * channels)
+        .map(|_| reader.read())
+        .collect();
+
+    let texel = |x: i64, y: i64| -> Vector3<f32> {
+        let x = x.rem_euclid(width as i64) as usize;
+        let y = y.clamp(0, height as i64 - 1) as usize;
+        let start = (y * width as usize + x) * channels;
+        let mut color = Vector3::zeros();
+        for channel in 0..channels.min(3) {
+            color[channel] = source[start + channel];
+        }
+        color
+    };
+
+    // Bilinear with the longitude wrapping around.
+    let sample = |u: f32, v: f32| -> Vector3<f32> {
+        let x = u * width as f32 - 0.5;
+        let y = v * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1, y0) * fx;
+        let bottom = texel(x0, y0 + 1) * (1.0 - fx) + texel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+
+    return std::array::from_fn(|face| {
+        let mut pixels = Vec::with_capacity(size as usize * size as usize * 3);
+        for y in 0..size {
+            for x in 0..size {
+                let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let direction = get_direction(face, u, v).normalize();
+                let color = sample(
+                    direction.z.atan2(direction.x) / (2.0 * PI) + 0.5,
+                    0.5 - direction.y.asin() / PI,
+                );
+                pixels.extend_from_slice(color.as_slice());
+            }
+        }
+
+        pixels
+    });
+}
+
+// Major axis table from the OpenGL specification.
+#[cfg(any(feature = "software", feature = "wgpu"))]
+fn get_direction(face: usize, u: f32, v: f32) -> Vector3<f32> {
+    match face {
+        0 => Vector3::new(1.0, -v, -u),
+        1 => Vector3::new(-1.0, -v, u),
+        2 => Vector3::new(u, 1.0, v),
+        3 => Vector3::new(u, -1.0, -v),
+        4 => Vector3::new(u, -v, 1.0),
+        _ => Vector3::new(-u, -v, -1.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_horizontal_cross() {
+        // One red pixel per cell, holding the cell index.
+        let cells: Vec<u8> = (0..12).collect();
+        let (size, faces) = split_cross(cells.as_ptr(), 4, 3, TextureFormat::Red, BufferDataType::U8);
+
+        assert_eq!(size, 1);
+        assert_eq!(faces.map(|face| face[0]), [6, 4, 1, 9, 5, 7]);
+    }
+}
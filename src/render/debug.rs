@@ -0,0 +1,15 @@
+// Log target of everything reported by the driver or wgpu, so it can be
+// filtered separately from the rest of the crate.
+#[cfg(any(feature = "opengl", feature = "wgpu"))]
+pub(crate) const LOG_TARGET: &str = "librender::gl";
+
+// Ordered from least to most severe, messages below the filter set through
+// the render API are dropped before reaching the logger.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
@@ -4,7 +4,9 @@ use super::{
     buffer::{create_buffer, create_vertex_array, BufferType, BufferUsage, VertexArray},
     glyph::Glyph,
     mesh::Drawable,
+    pipeline::{check_vertex_array, create_pipeline, Pipeline, PipelineDescriptor},
     render_api::RenderAPI,
+    render_state::{BlendState, DepthState},
     shader::{create_shader_from_file, Shader},
 };
 
@@ -15,12 +17,16 @@ pub struct DrawableText {
     scale: f32,
 
     vertex_array: Box<dyn VertexArray>,
-    shader: Box<dyn Shader>,
+    pipeline: Box<dyn Pipeline>,
 }
 
 impl Drawable for DrawableText {
     fn draw(&mut self) {
-        self.shader.bind();
+        if !check_vertex_array(&self.pipeline, &self.vertex_array) {
+            return;
+        }
+
+        self.pipeline.bind();
         self.vertex_array.bind();
 
         let mut advance = 0.0;
@@ -34,7 +40,11 @@ impl Drawable for DrawableText {
     }
 
     fn get_shader(&self) -> &Box<dyn Shader> {
-        return &self.shader;
+        return self.pipeline.get_shader();
+    }
+
+    fn is_transparent(&self) -> bool {
+        return true;
     }
 }
 
@@ -64,7 +74,7 @@ impl DrawableText {
         let vertex_buffer = create_buffer(
             &mut vertices.clone(),
             BufferType::Vertex,
-            buffer_layout,
+            buffer_layout.clone(),
             BufferUsage::Dynamic,
             Some(6 * 4 * size_of::<f32>()),
         );
@@ -72,7 +82,21 @@ impl DrawableText {
 
         vertex_array.add_buffer(vertex_buffer, BufferType::Vertex);
 
+        // Text is drawn over the scene with the glyph edges blended.
         let shader = create_shader_from_file(shader_path);
+        let descriptor = PipelineDescriptor {
+            blend: BlendState::ALPHA,
+            depth: DepthState {
+                test: false,
+                write: false,
+                ..DepthState::default()
+            },
+            ..PipelineDescriptor::new(buffer_layout)
+        };
+        let pipeline = match create_pipeline(shader, descriptor) {
+            Ok(pipeline) => pipeline,
+            Err(error) => panic!("Failed to create pipeline for {} with error\n{}", shader_path, error),
+        };
 
         return Self {
             string: String::from(text),
@@ -81,7 +105,7 @@ impl DrawableText {
             scale: scale,
 
             vertex_array: vertex_array,
-            shader: shader,
+            pipeline: pipeline,
         };
     }
 
@@ -100,3 +124,55 @@ impl DrawableText {
         render_api.enable_align_restrictions();
     }
 }
+
+#[cfg(all(test, feature = "recording"))]
+mod tests {
+    use super::*;
+    use crate::render::{
+        buffer::BufferDataType,
+        glyph,
+        recording::{self, Command},
+        render_api::{create_api_with_backend, Backend},
+        texture::{create_texture, TextureFormat},
+        window::create_null_window,
+    };
+
+    #[test]
+    fn draws_one_quad_per_glyph() {
+        let mut window = create_null_window(64, 64);
+        let mut api: Rc<dyn RenderAPI> =
+            Rc::from(create_api_with_backend(&mut window, Backend::Recording));
+
+        // Glyphs are cached up front so no font is loaded.
+        let pixels = [255u8; 6];
+        for character in ['h', 'i'] {
+            let glyph = Glyph {
+                texture: create_texture(pixels.as_ptr(), 2, 3, TextureFormat::Red, BufferDataType::U8),
+                size: (2, 3),
+                bearing: (0, 3),
+                advance: 4 << 6,
+            };
+            glyph::cache_glyph(character, glyph);
+        }
+
+        let mut text = DrawableText::new("hi", (1.0, 2.0), 1.0, "font.ttf", "text.glsl", &mut api);
+        recording::take_commands();
+        text.draw();
+
+        let draws: Vec<_> = recording::get_commands()
+            .into_iter()
+            .filter_map(|command| match command {
+                Command::Draw(draw) => Some(draw),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(draws.len(), 2);
+
+        let mut advance = 0.0;
+        for (draw, glyph) in draws.iter().zip(&text.glyphs) {
+            assert_eq!(draw.count, 6);
+            assert_eq!(draw.textures, vec![(0, glyph.texture.get_id())]);
+            assert_eq!(draw.get_vertices(0), glyph.get_vertices((1.0, 2.0), 1.0, &mut advance));
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fmt, fs, io,
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+};
+
+use super::{render_api::RenderAPI, texture::PixelBuffer};
+
+// Frames waiting for the encoder, recording blocks once this many queued up
+// so memory doesn't grow when encoding is slower than capturing.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameRecorderError {
+    Stopped,
+}
+
+impl fmt::Display for FrameRecorderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRecorderError::Stopped => write!(f, "Frame recorder thread has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for FrameRecorderError {}
+
+// Captures frames on the render thread and leaves the PNG encoding to a
+// worker, frames are written as <directory>/<prefix>_000000.png onwards.
+pub struct FrameRecorder {
+    sender: Option<SyncSender<(String, PixelBuffer)>>,
+    worker: Option<JoinHandle<()>>,
+    directory: String,
+    prefix: String,
+    frame: u32,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: &str, prefix: &str) -> io::Result<FrameRecorder> {
+        fs::create_dir_all(directory)?;
+
+        let (sender, receiver) = mpsc::sync_channel::<(String, PixelBuffer)>(MAX_QUEUED_FRAMES);
+        let worker = thread::spawn(move || {
+            for (path, frame) in receiver {
+                if let Err(error) = frame.save_png(&path) {
+                    log::error!("Failed to save frame {} with error\n{}", path, error);
+                }
+            }
+        });
+
+        return Ok(FrameRecorder {
+            sender: Some(sender),
+            worker: Some(worker),
+            directory: String::from(directory),
+            prefix: String::from(prefix),
+            frame: 0,
+        });
+    }
+
+    // Empty frames, e.g. of a minimized window, are skipped. Fails once the
+    // recorder finished or its worker stopped.
+    pub fn record(&mut self, api: &Box<dyn RenderAPI>) -> Result<(), FrameRecorderError> {
+        let sender = self.sender.as_ref().ok_or(FrameRecorderError::Stopped)?;
+        let frame = api.capture_frame();
+        if frame.width == 0 || frame.height == 0 {
+            return Ok(());
+        }
+
+        let path = format!("{}/{}_{:06}.png", self.directory, self.prefix, self.frame);
+        sender.send((path, frame)).map_err(|_| FrameRecorderError::Stopped)?;
+        self.frame += 1;
+
+        return Ok(());
+    }
+
+    pub fn get_frame_count(&self) -> u32 {
+        return self.frame;
+    }
+
+    // Blocks until every recorded frame is written.
+    pub fn finish(&mut self) {
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            worker.join().expect("Frame recorder thread panicked");
+        }
+    }
+}
+
+impl Drop for FrameRecorder {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(all(test, feature = "software"))]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::render::{
+        render_api::{create_api_with_backend, Backend},
+        window::create_null_window,
+    };
+
+    #[test]
+    fn skips_empty_frames_and_fails_after_finishing() {
+        let directory = std::env::temp_dir().join(format!("librender_frames_{}", std::process::id()));
+        let directory = directory.to_str().unwrap();
+        let mut recorder = FrameRecorder::new(directory, "frame").unwrap();
+
+        let mut window = create_null_window(0, 0);
+        let api = create_api_with_backend(&mut window, Backend::Software);
+        recorder.record(&api).unwrap();
+        api.resize(4, 4);
+        recorder.record(&api).unwrap();
+        recorder.finish();
+
+        assert_eq!(recorder.get_frame_count(), 1);
+        assert!(Path::new(&format!("{}/frame_000000.png", directory)).exists());
+        assert_eq!(recorder.record(&api), Err(FrameRecorderError::Stopped));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
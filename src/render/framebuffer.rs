@@ -0,0 +1,117 @@
+use std::fmt;
+
+#[cfg(feature = "opengl")]
+use crate::platform::opengl::framebuffer::OglFramebuffer;
+#[cfg(feature = "recording")]
+use crate::platform::recording::framebuffer::RecordingFramebuffer;
+#[cfg(feature = "software")]
+use crate::platform::software::framebuffer::SoftwareFramebuffer;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::framebuffer::WgpuFramebuffer;
+
+use super::{
+    buffer::BufferDataType,
+    render_api::{get_backend, Backend},
+    texture::{Texture, TextureFormat},
+};
+
+#[derive(Clone, Copy)]
+pub struct ColorAttachment {
+    pub format: TextureFormat,
+    pub data_type: BufferDataType,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DepthStencilFormat {
+    None,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+}
+
+// Window sized targets are scaled from the window size and resized along
+// with it, fixed targets keep their size until resized by hand.
+#[derive(Clone, Copy)]
+pub enum FramebufferSize {
+    Fixed(u32, u32),
+    Window(f32),
+}
+
+#[derive(Clone)]
+pub struct FramebufferDescriptor {
+    pub size: FramebufferSize,
+    pub color_attachments: Vec<ColorAttachment>,
+    pub depth_stencil: DepthStencilFormat,
+    // Zero or one disables multisampling.
+    pub samples: u32,
+}
+
+#[derive(Debug)]
+pub enum FramebufferError {
+    EmptySize,
+    NoAttachments,
+    TooManyColorAttachments { requested: usize, max: usize },
+    TooManySamples { requested: u32, max: u32 },
+    SizeMismatch,
+    Incomplete(String),
+}
+
+impl fmt::Display for FramebufferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FramebufferError::EmptySize => write!(f, "Framebuffer size can't be zero"),
+            FramebufferError::NoAttachments => write!(f, "Framebuffer has no attachments"),
+            FramebufferError::TooManyColorAttachments { requested, max } => write!(
+                f,
+                "Framebuffer requests {} color attachments but only {} are supported",
+                requested, max
+            ),
+            FramebufferError::TooManySamples { requested, max } => write!(
+                f,
+                "Framebuffer requests {} samples but only {} are supported",
+                requested, max
+            ),
+            FramebufferError::SizeMismatch => {
+                write!(f, "Multisampled framebuffers can only be resolved into targets of the same size")
+            }
+            FramebufferError::Incomplete(reason) => write!(f, "Framebuffer is incomplete: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for FramebufferError {}
+
+// Like textures it can't leave the thread that created it, the framebuffer
+// and its attachments are deleted on drop with that thread's context.
+pub trait Framebuffer {
+    fn get_id(&self) -> u32;
+    fn bind(&self);
+    fn unbind(&self);
+    fn get_size(&self) -> (u32, u32);
+    fn get_samples(&self) -> u32;
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), FramebufferError>;
+    fn get_color_attachment_count(&self) -> usize;
+    // None when the index is out of range. Multisampled attachments can't be
+    // sampled and are None as well, resolve the framebuffer first.
+    fn get_color_attachment(&self, index: usize) -> Option<Box<dyn Texture>>;
+    fn get_depth_attachment(&self) -> Option<Box<dyn Texture>>;
+    // Blits every color attachment into the matching attachment of the
+    // target, None resolves the first attachment into the window.
+    fn resolve(&self, target: Option<&Box<dyn Framebuffer>>) -> Result<(), FramebufferError>;
+}
+
+pub fn create_framebuffer(
+    descriptor: FramebufferDescriptor,
+) -> Result<Box<dyn Framebuffer>, FramebufferError> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Ok(Box::new(OglFramebuffer::new(descriptor)?)),
+        #[cfg(feature = "software")]
+        Backend::Software => Ok(Box::new(SoftwareFramebuffer::new(descriptor)?)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Ok(Box::new(RecordingFramebuffer::new(descriptor)?)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Ok(Box::new(WgpuFramebuffer::new(descriptor)?)),
+    }
+}
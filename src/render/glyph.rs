@@ -1,15 +1,19 @@
-use dashmap::DashMap;
-use once_cell::sync::Lazy;
+use std::{cell::RefCell, collections::HashMap};
 
-use crate::platform::opengl::texture::TextureFormat;
+use super::texture::TextureFormat;
 
 use super::{
     buffer::{BufferDataType, BufferLayoutNode},
+    render_api::{get_backend, Backend},
     texture::{create_texture, Texture},
 };
 
 // TODO: Different fonts, make glyph factory.
-static GLYPH_CACHE: Lazy<DashMap<char, Glyph>> = Lazy::new(|| DashMap::new());
+thread_local! {
+    // Glyph textures belong to the backend and context they were created
+    // for, so every thread keeps its own glyphs per backend.
+    static GLYPH_CACHE: RefCell<HashMap<(Backend, char), Glyph>> = RefCell::new(HashMap::new());
+}
 
 #[derive(Clone)]
 pub struct Glyph {
@@ -21,9 +25,8 @@ pub struct Glyph {
 
 impl Glyph {
     pub fn new(character: char, font_path: &str, font_size: u32) -> Glyph {
-        let cached_glyph = GLYPH_CACHE.get(&character);
-        if cached_glyph.is_some() {
-            return cached_glyph.unwrap().clone();
+        if let Some(glyph) = get_cached_glyph(character) {
+            return glyph;
         }
 
         let lib = freetype::Library::init().unwrap();
@@ -44,17 +47,15 @@ impl Glyph {
             BufferDataType::U8,
         );
 
-        GLYPH_CACHE.insert(
-            character,
-            Glyph {
-                texture,
-                size: (bitmap.width() as u32, bitmap.rows() as u32),
-                bearing: (glyph.bitmap_left() as u32, glyph.bitmap_top() as u32),
-                advance: glyph.advance().x as u32,
-            },
-        );
+        let glyph = Glyph {
+            texture,
+            size: (bitmap.width() as u32, bitmap.rows() as u32),
+            bearing: (glyph.bitmap_left() as u32, glyph.bitmap_top() as u32),
+            advance: glyph.advance().x as u32,
+        };
+        cache_glyph(character, glyph.clone());
 
-        return GLYPH_CACHE.get(&character).unwrap().clone();
+        return glyph;
     }
 
     pub fn get_buffer_layout() -> Vec<BufferLayoutNode> {
@@ -109,3 +110,53 @@ impl Glyph {
         return vertices;
     }
 }
+
+fn get_cached_glyph(character: char) -> Option<Glyph> {
+    return GLYPH_CACHE.with(|cache| cache.borrow().get(&(get_backend(), character)).cloned());
+}
+
+// Also lets tests draw text without loading a font.
+pub(crate) fn cache_glyph(character: char, glyph: Glyph) {
+    GLYPH_CACHE.with(|cache| cache.borrow_mut().insert((get_backend(), character), glyph));
+}
+
+#[cfg(all(test, feature = "software", feature = "recording"))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::render::{render_api::create_api_with_backend, window::create_null_window};
+
+    fn create_glyph() -> Glyph {
+        let pixels = [255u8; 4];
+        return Glyph {
+            texture: create_texture(pixels.as_ptr(), 2, 2, TextureFormat::Red, BufferDataType::U8),
+            size: (2, 2),
+            bearing: (0, 2),
+            advance: 3 << 6,
+        };
+    }
+
+    #[test]
+    fn caches_glyphs_per_backend_and_thread() {
+        let mut window = create_null_window(16, 16);
+        let _recording = create_api_with_backend(&mut window, Backend::Recording);
+        let glyph = create_glyph();
+        let id = glyph.texture.get_id();
+        cache_glyph('a', glyph);
+        assert_eq!(get_cached_glyph('a').map(|glyph| glyph.texture.get_id()), Some(id));
+
+        let _software = create_api_with_backend(&mut window, Backend::Software);
+        assert!(get_cached_glyph('a').is_none());
+
+        let _recording = create_api_with_backend(&mut window, Backend::Recording);
+        assert!(get_cached_glyph('a').is_some());
+        thread::spawn(|| {
+            let mut window = create_null_window(16, 16);
+            let _recording = create_api_with_backend(&mut window, Backend::Recording);
+            assert!(get_cached_glyph('a').is_none());
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 use super::{
     buffer::{
-        create_buffer, create_vertex_array, BufferLayoutNode, BufferType, BufferUsage,
-        VertexArray,
+        create_buffer, create_compact_index_buffer, create_vertex_array, BufferLayoutNode,
+        BufferType, BufferUsage, VertexArray,
     },
+    pipeline::{check_vertex_array, create_pipeline, Pipeline, PipelineDescriptor, PipelineError},
+    render_state::BlendState,
     shader::{create_shader_from_file, Shader},
     texture::Texture
 };
@@ -10,17 +12,36 @@ use super::{
 pub trait Drawable {
     fn draw(&mut self);
     fn get_shader(&self) -> &Box<dyn Shader>;
+
+    // Used to build the sort key when submitted to a render queue.
+    fn get_texture(&self) -> Option<&Box<dyn Texture>> {
+        return None;
+    }
+
+    fn get_render_pass(&self) -> u8 {
+        return 0;
+    }
+
+    fn is_transparent(&self) -> bool {
+        return false;
+    }
 }
 
 pub struct Mesh {
-    vertex_array: Box<dyn VertexArray>,    
-    shader: Box<dyn Shader>,
+    vertex_array: Box<dyn VertexArray>,
+    pipeline: Box<dyn Pipeline>,
     texture: Option<Box<dyn Texture>>,
+    render_pass: u8,
+    transparent: bool,
 }
 
 impl Drawable for Mesh {
     fn draw(&mut self) {
-        self.shader.bind();
+        if !check_vertex_array(&self.pipeline, &self.vertex_array) {
+            return;
+        }
+
+        self.pipeline.bind();
         self.vertex_array.bind();
 
         if let Some(texture) = &self.texture {
@@ -31,59 +52,86 @@ impl Drawable for Mesh {
     }
 
     fn get_shader(&self) -> &Box<dyn Shader> {
-        return &self.shader;
+        return self.pipeline.get_shader();
+    }
+
+    fn get_texture(&self) -> Option<&Box<dyn Texture>> {
+        return self.texture.as_ref();
+    }
+
+    fn get_render_pass(&self) -> u8 {
+        return self.render_pass;
+    }
+
+    fn is_transparent(&self) -> bool {
+        return self.transparent;
     }
 }
 
 impl Mesh {
-    pub fn new(vertices: Vec<f32>, buffer_layout: Vec<BufferLayoutNode>, shader_path: &str) -> Self {
+    // Fails when the shader doesn't match the buffer layout.
+    pub fn new(vertices: Vec<f32>, buffer_layout: Vec<BufferLayoutNode>, shader_path: &str) -> Result<Self, PipelineError> {
+        let pipeline = Self::create_default_pipeline(buffer_layout, shader_path)?;
+        return Ok(Self::from_pipeline(vertices, None, pipeline));
+    }
+
+    pub fn new_indexed(vertices: Vec<f32>, indices: Vec<u32>, buffer_layout: Vec<BufferLayoutNode>, shader_path: &str) -> Result<Self, PipelineError> {
+        let pipeline = Self::create_default_pipeline(buffer_layout, shader_path)?;
+        return Ok(Self::from_pipeline(vertices, Some(indices), pipeline));
+    }
+
+    // The vertices are laid out like the pipeline's vertex layout says.
+    pub fn from_pipeline(vertices: Vec<f32>, indices: Option<Vec<u32>>, pipeline: Box<dyn Pipeline>) -> Self {
         let mut vertex_array = create_vertex_array();
         let vertex_buffer = create_buffer(
             &mut vertices.clone(),
             BufferType::Vertex,
-            buffer_layout,
+            pipeline.get_descriptor().vertex_layout.clone(),
             BufferUsage::Static,
             None,
         );
-
         vertex_array.add_buffer(vertex_buffer, BufferType::Vertex);
 
-        let shader = create_shader_from_file(shader_path);
+        // Indices are stored as u8 or u16 when they fit.
+        if let Some(indices) = indices {
+            let index_buffer = create_compact_index_buffer(&indices, BufferUsage::Static);
+            vertex_array.add_buffer(index_buffer, BufferType::Index);
+        }
 
         return Self {
             vertex_array: vertex_array,
-            shader: shader,
+            pipeline: pipeline,
             texture: None,
+            render_pass: 0,
+            transparent: false,
         };
     }
 
-    pub fn new_indexed(vertices: Vec<f32>, indices: Vec<u32>, buffer_layout: Vec<BufferLayoutNode>, shader_path: &str) -> Self {
-        let mut vertex_array = create_vertex_array();
-        let vertex_buffer = create_buffer(
-            &mut vertices.clone(),
-            BufferType::Vertex,
-            buffer_layout,
-            BufferUsage::Static,
-            None,
-        );
+    // Blends with alpha like the OpenGL renderer does by default, so
+    // transparent meshes don't need their own pipeline.
+    fn create_default_pipeline(buffer_layout: Vec<BufferLayoutNode>, shader_path: &str) -> Result<Box<dyn Pipeline>, PipelineError> {
+        let shader = create_shader_from_file(shader_path);
+        let descriptor = PipelineDescriptor {
+            blend: BlendState::ALPHA,
+            ..PipelineDescriptor::new(buffer_layout)
+        };
 
-        let index_buffer = create_buffer(
-            &mut indices.clone(),
-            BufferType::Index,
-            Vec::new(),
-            BufferUsage::Static,
-            None,
-        );
+        return create_pipeline(shader, descriptor);
+    }
 
-        vertex_array.add_buffer(vertex_buffer, BufferType::Vertex);
-        vertex_array.add_buffer(index_buffer, BufferType::Index);
+    pub fn get_pipeline(&self) -> &Box<dyn Pipeline> {
+        return &self.pipeline;
+    }
 
-        let shader = create_shader_from_file(shader_path);
+    pub fn set_texture(&mut self, texture: Box<dyn Texture>) {
+        self.texture = Some(texture);
+    }
 
-        return Self {
-            vertex_array: vertex_array,
-            shader: shader,
-            texture: None,
-        };
+    pub fn set_render_pass(&mut self, render_pass: u8) {
+        self.render_pass = render_pass;
+    }
+
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
     }
 }
@@ -1,8 +1,28 @@
 pub mod buffer;
+pub mod capabilities;
+pub mod command_buffer;
+pub mod cubemap;
+pub mod debug;
+#[cfg(feature = "text")]
 pub mod glyph;
+#[cfg(feature = "text")]
 pub mod drawable_text;
+pub mod frame_recorder;
+pub mod framebuffer;
 pub mod mesh;
+pub mod pipeline;
+#[cfg(feature = "recording")]
+pub mod recording;
 pub mod render_api;
+pub mod render_queue;
+pub mod render_state;
+pub mod render_thread;
 pub mod shader;
+pub mod skybox;
+#[cfg(feature = "software")]
+pub mod software;
+pub mod stats;
 pub mod texture;
+pub mod texture_container;
+pub mod texture_decoder;
 pub mod window;
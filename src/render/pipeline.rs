@@ -0,0 +1,254 @@
+use std::fmt;
+
+#[cfg(feature = "opengl")]
+use crate::platform::opengl::pipeline::OglPipeline;
+#[cfg(feature = "recording")]
+use crate::platform::recording::pipeline::RecordingPipeline;
+#[cfg(feature = "software")]
+use crate::platform::software::pipeline::SoftwarePipeline;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::pipeline::WgpuPipeline;
+
+use super::{
+    buffer::{Bindable, BufferLayoutNode, VertexArray},
+    render_api::{get_backend, Backend},
+    render_state::{BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, StencilState},
+    shader::{Shader, ShaderAttribute},
+};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrimitiveTopology {
+    Triangles,
+    TriangleStrip,
+    Lines,
+    LineStrip,
+    Points,
+}
+
+impl PrimitiveTopology {
+    // Lines and points draw no triangles.
+    pub fn get_triangle_count(&self, vertices: u64) -> u64 {
+        match self {
+            PrimitiveTopology::Triangles => vertices / 3,
+            PrimitiveTopology::TriangleStrip => vertices.saturating_sub(2),
+            _ => 0,
+        }
+    }
+}
+
+// Everything a draw depends on besides its buffers, textures and uniforms.
+// The vertex layout is the one of the buffers drawn with the pipeline,
+// attribute locations count up across the buffers like in vertex arrays.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PipelineDescriptor {
+    pub vertex_layout: Vec<BufferLayoutNode>,
+    pub topology: PrimitiveTopology,
+    // Applies to every color attachment.
+    pub blend: BlendState,
+    pub color_mask: ColorMask,
+    pub depth: DepthState,
+    pub stencil: StencilState,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    pub polygon_mode: PolygonMode,
+}
+
+impl PipelineDescriptor {
+    // Opaque triangles with the default depth test and no culling.
+    pub fn new(vertex_layout: Vec<BufferLayoutNode>) -> PipelineDescriptor {
+        return PipelineDescriptor {
+            vertex_layout,
+            topology: PrimitiveTopology::Triangles,
+            blend: BlendState::DISABLED,
+            color_mask: ColorMask::ALL,
+            depth: DepthState::default(),
+            stencil: StencilState::default(),
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            polygon_mode: PolygonMode::Fill,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    MissingAttribute { name: String, location: u32 },
+    AttributeSizeMismatch { name: String, location: u32, shader: u32, layout: u32 },
+    IntegerAttribute { name: String, location: u32 },
+    VertexArrayMismatch { pipeline: Vec<BufferLayoutNode>, vertex_array: Vec<BufferLayoutNode> },
+    UnsupportedTopology(PrimitiveTopology),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::MissingAttribute { name, location } => write!(
+                f,
+                "Shader reads {} at location {} but the vertex layout doesn't provide it",
+                name, location
+            ),
+            PipelineError::AttributeSizeMismatch {
+                name,
+                location,
+                shader,
+                layout,
+            } => write!(
+                f,
+                "Shader reads {} components of {} at location {} but the vertex layout provides {}",
+                shader, name, location, layout
+            ),
+            PipelineError::IntegerAttribute { name, location } => write!(
+                f,
+                "Shader reads {} at location {} as integers but vertex attributes are passed as floats",
+                name, location
+            ),
+            PipelineError::VertexArrayMismatch {
+                pipeline,
+                vertex_array,
+            } => write!(
+                f,
+                "Vertex array buffers are laid out as {:?} but the pipeline expects {:?}",
+                vertex_array, pipeline
+            ),
+            PipelineError::UnsupportedTopology(topology) => {
+                write!(f, "{:?} can't be drawn by this renderer", topology)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+// Created once and never changed. Binding it binds the shader and sets all
+// of its state, so draws don't depend on whatever was set before them. The
+// state stays set after the draw like state set through the render API.
+pub trait Pipeline: Bindable {
+    fn get_shader(&self) -> &Box<dyn Shader>;
+    fn get_descriptor(&self) -> &PipelineDescriptor;
+}
+
+// The shader has to be loaded already, it is checked against the vertex
+// layout where the backend can reflect its inputs.
+pub fn create_pipeline(
+    shader: Box<dyn Shader>,
+    descriptor: PipelineDescriptor,
+) -> Result<Box<dyn Pipeline>, PipelineError> {
+    if let Some(attributes) = shader.get_attributes() {
+        validate_attributes(&attributes, &descriptor.vertex_layout)?;
+    }
+
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Ok(Box::new(OglPipeline::new(shader, descriptor))),
+        #[cfg(feature = "software")]
+        Backend::Software => Ok(Box::new(SoftwarePipeline::new(shader, descriptor)?)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Ok(Box::new(RecordingPipeline::new(shader, descriptor))),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Ok(Box::new(WgpuPipeline::new(shader, descriptor))),
+    }
+}
+
+// Inputs the layout doesn't provide would read a constant and components
+// the shader doesn't read are dropped, both are almost always mistakes.
+// Fewer components are fine, they are padded with (0, 0, 0, 1). Integer
+// inputs are rejected since every backend converts attributes to floats.
+fn validate_attributes(
+    attributes: &[ShaderAttribute],
+    layout: &[BufferLayoutNode],
+) -> Result<(), PipelineError> {
+    for attribute in attributes {
+        if attribute.integer {
+            return Err(PipelineError::IntegerAttribute {
+                name: attribute.name.clone(),
+                location: attribute.location,
+            });
+        }
+
+        let node = match layout.get(attribute.location as usize) {
+            Some(node) => node,
+            None => {
+                return Err(PipelineError::MissingAttribute {
+                    name: attribute.name.clone(),
+                    location: attribute.location,
+                })
+            }
+        };
+
+        if node.elements > attribute.components {
+            return Err(PipelineError::AttributeSizeMismatch {
+                name: attribute.name.clone(),
+                location: attribute.location,
+                shader: attribute.components,
+                layout: node.elements,
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+// The buffers of a vertex array drawn with a pipeline have to be laid out
+// like its vertex layout, with the locations counting up across them.
+pub fn validate_vertex_array(
+    descriptor: &PipelineDescriptor,
+    vertex_array: &Box<dyn VertexArray>,
+) -> Result<(), PipelineError> {
+    let layout: Vec<BufferLayoutNode> = vertex_array
+        .get_buffers()
+        .iter()
+        .flat_map(|buffer| buffer.get_layout().iter().copied())
+        .collect();
+
+    if layout != descriptor.vertex_layout {
+        return Err(PipelineError::VertexArrayMismatch {
+            pipeline: descriptor.vertex_layout.clone(),
+            vertex_array: layout,
+        });
+    }
+
+    return Ok(());
+}
+
+// Drawables check their buffers before every draw in debug builds, a
+// mismatch is logged and the draw skipped.
+pub(crate) fn check_vertex_array(pipeline: &Box<dyn Pipeline>, vertex_array: &Box<dyn VertexArray>) -> bool {
+    if !cfg!(debug_assertions) {
+        return true;
+    }
+
+    match validate_vertex_array(pipeline.get_descriptor(), vertex_array) {
+        Ok(()) => return true,
+        Err(error) => {
+            log::error!("Skipping draw, {}", error);
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::buffer::BufferDataType;
+
+    #[test]
+    fn rejects_integer_attributes() {
+        let attributes = [ShaderAttribute {
+            name: String::from("a_joints"),
+            location: 0,
+            components: 4,
+            integer: true,
+        }];
+        let layout = [BufferLayoutNode {
+            elements: 4,
+            data_type: BufferDataType::U8,
+            normalized: false,
+        }];
+
+        assert!(matches!(
+            validate_attributes(&attributes, &layout),
+            Err(PipelineError::IntegerAttribute { location: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::platform::recording::with_context;
+
+use super::{
+    buffer::{BufferDataType, BufferType, BufferUsage},
+    pipeline::{PipelineDescriptor, PrimitiveTopology},
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    shader::UniformValue,
+    texture::{TextureDimension, TextureFormat},
+    texture_container::CompressedFormat,
+};
+
+// Calls made to the recording backend in the order they were issued. Ids
+// are unique across all objects of a thread's recording context.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    CreateBuffer {
+        id: u32,
+        buffer_type: BufferType,
+        usage: BufferUsage,
+        data: Vec<u8>,
+    },
+    UpdateBuffer {
+        id: u32,
+        offset: usize,
+        data: Vec<f32>,
+    },
+    BindBuffer(u32),
+    UnbindBuffer(u32),
+
+    CreateVertexArray(u32),
+    AddBuffer {
+        vertex_array: u32,
+        buffer: u32,
+        buffer_type: BufferType,
+    },
+    BindVertexArray(u32),
+    UnbindVertexArray(u32),
+    Draw(DrawCommand),
+    MultiDraw(Vec<DrawCommand>),
+    // Holds the draws read from the buffer when it was issued.
+    DrawIndirect {
+        buffer: u32,
+        offset: usize,
+        draws: Vec<DrawCommand>,
+    },
+
+    CreateShader(u32),
+    LoadShaderFile {
+        id: u32,
+        path: String,
+    },
+    LoadShaderSource {
+        id: u32,
+        source: String,
+    },
+    BindShader(u32),
+    UnbindShader(u32),
+    SetUniform {
+        shader: u32,
+        name: String,
+        value: UniformValue,
+    },
+
+    CreatePipeline {
+        id: u32,
+        shader: u32,
+        descriptor: PipelineDescriptor,
+    },
+    BindPipeline(u32),
+    UnbindPipeline(u32),
+
+    // Data is None when the texture was created without contents.
+    CreateTexture {
+        id: u32,
+        dimension: TextureDimension,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: TextureFormat,
+        data_type: BufferDataType,
+        data: Option<Vec<u8>>,
+    },
+    CreateCompressedTexture {
+        id: u32,
+        format: CompressedFormat,
+        width: u32,
+        height: u32,
+        levels: usize,
+    },
+    UploadTextureLayer {
+        id: u32,
+        layer: u32,
+        data: Vec<u8>,
+    },
+    BindTexture {
+        id: u32,
+        slot: u32,
+    },
+    UnbindTexture(u32),
+
+    CreateFramebuffer {
+        id: u32,
+        width: u32,
+        height: u32,
+    },
+    ResizeFramebuffer {
+        id: u32,
+        width: u32,
+        height: u32,
+    },
+    BindFramebuffer(u32),
+    UnbindFramebuffer(u32),
+    // A target of None resolves into the window.
+    ResolveFramebuffer {
+        id: u32,
+        target: Option<u32>,
+    },
+
+    SetLabel {
+        id: u32,
+        label: String,
+    },
+
+    Begin,
+    End,
+    Clear,
+    SetRenderTarget(Option<u32>),
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    SetViewport {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    SetClearColor([f32; 4]),
+    SetClearDepth(f32),
+    SetClearStencil(i32),
+    SetDepthState(DepthState),
+    SetBlendState {
+        target: Option<u32>,
+        state: BlendState,
+    },
+    SetBlendConstant([f32; 4]),
+    SetColorMask {
+        target: Option<u32>,
+        mask: ColorMask,
+    },
+    SetCullMode(CullMode),
+    SetFrontFace(FrontFace),
+    SetPolygonMode(PolygonMode),
+    SetScissor(Option<ScissorRect>),
+    SetStencilState(StencilState),
+    BeginScope(String),
+    EndScope,
+    SetAlignRestrictions(bool),
+}
+
+// Everything a draw depends on, captured when it was issued.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DrawCommand {
+    pub vertex_array: u32,
+    pub shader: Option<u32>,
+    pub pipeline: Option<u32>,
+    // Triangles unless a pipeline says otherwise.
+    pub topology: PrimitiveTopology,
+    // Bound textures as (slot, id) ordered by slot.
+    pub textures: Vec<(u32, u32)>,
+    // None is the window.
+    pub target: Option<u32>,
+    // First and count are indices for indexed draws, vertices otherwise.
+    pub first: u32,
+    pub count: usize,
+    pub base_vertex: i32,
+    pub instances: u32,
+    pub indexed: bool,
+    // Contents of the vertex buffers in the order they were added.
+    pub vertex_buffers: Vec<Vec<u8>>,
+}
+
+impl DrawCommand {
+    // Reads a vertex buffer as tightly packed floats.
+    pub fn get_vertices(&self, buffer: usize) -> Vec<f32> {
+        return self.vertex_buffers[buffer]
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+    }
+}
+
+// The log belongs to the calling thread like an OpenGL context, so tests running in parallel don't see each other's calls.
+pub fn get_commands() -> Vec<Command> {
+    return with_context(|context| context.commands.clone());
+}
+
+pub fn take_commands() -> Vec<Command> {
+    return with_context(|context| std::mem::take(&mut context.commands));
+}
+
+// Multi and indirect draws are split into the draws they hold.
+pub fn get_draw_commands() -> Vec<DrawCommand> {
+    return get_commands()
+        .into_iter()
+        .flat_map(|command| match command {
+            Command::Draw(draw) => vec![draw],
+            Command::MultiDraw(draws) | Command::DrawIndirect { draws, .. } => draws,
+            _ => Vec::new(),
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector3;
+
+    use super::*;
+    use crate::render::{
+        buffer::{create_buffer, create_vertex_array, BufferLayoutNode},
+        render_api::{create_api_with_backend, Backend},
+        shader::create_shader_from_source,
+        texture::create_texture,
+        window::create_null_window,
+    };
+
+    fn to_bytes(values: &[f32]) -> Vec<u8> {
+        return values.iter().flat_map(|value| value.to_ne_bytes()).collect();
+    }
+
+    #[test]
+    fn records_uploads_uniforms_and_binds() {
+        let mut window = create_null_window(4, 4);
+        let _api = create_api_with_backend(&mut window, Backend::Recording);
+        take_commands();
+
+        let layout = vec![BufferLayoutNode {
+            elements: 1,
+            data_type: BufferDataType::F32,
+            normalized: false,
+        }];
+        let buffer = create_buffer(
+            &mut vec![0.0f32, 1.0, 2.0],
+            BufferType::Vertex,
+            layout,
+            BufferUsage::Static,
+            None,
+        );
+        let buffer_id = buffer.get_id();
+        let mut vertex_array = create_vertex_array();
+        vertex_array.add_buffer(buffer, BufferType::Vertex);
+        vertex_array.add_buffer_sub_data(vec![3.0, 4.0, 5.0], 0, 0);
+
+        let shader = create_shader_from_source("");
+        shader.bind();
+        shader.submit_uniform_vec3("u_color", Vector3::new(1.0, 0.0, 0.0));
+        let pixel: [u8; 4] = [255; 4];
+        let texture = create_texture(pixel.as_ptr(), 1, 1, TextureFormat::RGBA, BufferDataType::U8);
+        texture.bind(2);
+
+        vertex_array.bind();
+        vertex_array.draw();
+
+        let commands = get_commands();
+        assert!(commands.contains(&Command::CreateBuffer {
+            id: buffer_id,
+            buffer_type: BufferType::Vertex,
+            usage: BufferUsage::Static,
+            data: to_bytes(&[0.0, 1.0, 2.0]),
+        }));
+        assert!(commands.contains(&Command::UpdateBuffer {
+            id: buffer_id,
+            offset: 0,
+            data: vec![3.0, 4.0, 5.0],
+        }));
+        assert!(commands.contains(&Command::SetUniform {
+            shader: shader.get_id(),
+            name: String::from("u_color"),
+            value: UniformValue::Vec3(Vector3::new(1.0, 0.0, 0.0)),
+        }));
+        assert!(commands.contains(&Command::BindTexture {
+            id: texture.get_id(),
+            slot: 2,
+        }));
+
+        // Draws capture the bound state and the updated buffer contents.
+        let draws = get_draw_commands();
+        assert_eq!(draws.len(), 1);
+        assert_eq!(draws[0].shader, Some(shader.get_id()));
+        assert_eq!(draws[0].textures, vec![(2, texture.get_id())]);
+        assert_eq!(draws[0].count, 3);
+        assert_eq!(draws[0].get_vertices(0), vec![3.0, 4.0, 5.0]);
+    }
+}
@@ -1,19 +1,149 @@
-use crate::{platform::opengl::OglAPI, render::window::Window};
+use std::cell::Cell;
 
-use super::mesh::{Drawable};
+#[cfg(feature = "opengl")]
+use crate::platform::opengl::OglAPI;
+#[cfg(feature = "recording")]
+use crate::platform::recording::RecordingAPI;
+#[cfg(feature = "software")]
+use crate::platform::software::SoftwareAPI;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::WgpuAPI;
+use crate::render::window::Window;
 
-pub trait RenderAPI: Send + Sync {
+use super::{
+    capabilities::Capabilities,
+    debug::DebugSeverity,
+    framebuffer::Framebuffer,
+    render_queue::RenderQueue,
+    render_state::{
+        BlendState, ColorMask, CullMode, DepthState, FrontFace, PolygonMode, ScissorRect,
+        StencilState,
+    },
+    stats::{FrameStats, StateCacheStats},
+    texture::PixelBuffer,
+};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Backend {
+    #[cfg(feature = "opengl")]
+    OpenGL,
+    #[cfg(feature = "software")]
+    Software,
+    // Records every call instead of drawing, see render::recording.
+    #[cfg(feature = "recording")]
+    Recording,
+    // Vulkan, Metal or DirectX 12, whichever wgpu picks for the system.
+    #[cfg(feature = "wgpu")]
+    Wgpu,
+}
+
+impl Default for Backend {
+    // OpenGL when enabled, otherwise the first enabled CPU backend and
+    // wgpu last since it needs a GPU but no window.
+    fn default() -> Backend {
+        #[cfg(feature = "opengl")]
+        return Backend::OpenGL;
+        #[cfg(all(not(feature = "opengl"), feature = "software"))]
+        return Backend::Software;
+        #[cfg(all(not(feature = "opengl"), not(feature = "software"), feature = "recording"))]
+        return Backend::Recording;
+        #[cfg(all(
+            not(feature = "opengl"),
+            not(feature = "software"),
+            not(feature = "recording")
+        ))]
+        return Backend::Wgpu;
+    }
+}
+
+thread_local! {
+    // Resources are created for the backend of the API created last on the
+    // same thread, like objects of the current OpenGL context.
+    static BACKEND: Cell<Backend> = Cell::new(Backend::default());
+}
+
+// Belongs to the thread it was created on since that's where the context
+// is current, render from other threads through a RenderThread.
+pub trait RenderAPI {
     fn init(&self, window: &mut Box<dyn Window>);
+    // Clears the whole bound target, whatever the scissor and write masks.
     fn clear(&self);
     
-    fn begin(&self);
-    fn end(&self);
-    fn submit(&self, drawable: &mut Box<dyn Drawable>);
+    // Drawables are submitted to the returned queue and drawn sorted by end.
+    // Every render target needs its own begin and end since begin clears
+    // the bound target.
+    fn begin<'a>(&self) -> RenderQueue<'a>;
+    fn end(&self, queue: RenderQueue);
+
+    // None targets the window again.
+    fn set_render_target(&self, target: Option<&Box<dyn Framebuffer>>);
+    // Reads the window contents as top to bottom RGBA8.
+    fn capture_frame(&self) -> PixelBuffer;
+    // Called from begin when the window framebuffer changes size. Window
+    // sized render targets follow it and the viewport is reset when drawing
+    // to the window.
+    fn resize(&self, width: u32, height: u32);
+    // Lasts until the next resize or render target change, for split screen
+    // set it again for every view.
+    fn set_viewport(&self, x: i32, y: i32, width: u32, height: u32);
+
+    fn set_clear_color(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn set_clear_depth(&self, depth: f32);
+    fn set_clear_stencil(&self, stencil: i32);
+    fn set_depth_state(&self, state: DepthState);
+    // A target applies the state to that color attachment only, None to all.
+    fn set_blend_state(&self, target: Option<u32>, state: BlendState);
+    fn set_blend_constant(&self, red: f32, green: f32, blue: f32, alpha: f32);
+    fn set_color_mask(&self, target: Option<u32>, mask: ColorMask);
+    fn set_cull_mode(&self, mode: CullMode);
+    fn set_front_face(&self, face: FrontFace);
+    fn set_polygon_mode(&self, mode: PolygonMode);
+    // None disables the scissor test.
+    fn set_scissor(&self, rect: Option<ScissorRect>);
+    fn set_stencil_state(&self, state: StencilState);
+
+    // Limits of the device, use them to pick quality settings.
+    fn capabilities(&self) -> Capabilities;
+
+    fn get_state_cache_stats(&self) -> StateCacheStats;
+    fn reset_state_cache_stats(&self);
+    // Statistics of the latest frame whose GPU timings are available, which
+    // lags a few frames behind the one being drawn. Frames cover every begin
+    // and end pair since the previous end_frame.
+    fn get_frame_stats(&self) -> FrameStats;
+    // Call once per frame before the window presents.
+    fn end_frame(&self);
+    // Driver messages below the severity are dropped, the rest are logged
+    // under the librender::gl target. Defaults to Low.
+    fn set_debug_severity(&self, severity: DebugSeverity);
+    // Times the GPU work issued until the matching end_scope, scopes may
+    // nest and are reported with the frame they ended in.
+    fn begin_scope(&self, name: &str);
+    fn end_scope(&self);
 
     fn enable_align_restrictions(&self);
     fn disable_align_restrictions(&self);
 }
 
 pub fn create_api(window: &mut Box<dyn Window>) -> Box<dyn RenderAPI> {
-    return Box::new(OglAPI::new(window));
+    return create_api_with_backend(window, Backend::default());
+}
+
+pub fn create_api_with_backend(window: &mut Box<dyn Window>, backend: Backend) -> Box<dyn RenderAPI> {
+    BACKEND.with(|current| current.set(backend));
+    match backend {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglAPI::new(window)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareAPI::new(window)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingAPI::new(window)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuAPI::new(window)),
+    }
+}
+
+pub(crate) fn get_backend() -> Backend {
+    return BACKEND.with(|backend| backend.get());
 }
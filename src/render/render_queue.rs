@@ -0,0 +1,96 @@
+use super::mesh::Drawable;
+
+// From the most significant bits down: render pass, transparency, then
+// shader and texture for opaque draws followed by their depth front to
+// back. Transparent draws put the inverted depth first so they are drawn
+// back to front regardless of state changes. Shader and texture IDs are
+// kept whole so they never collide.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SortKey(u128);
+
+impl SortKey {
+    pub fn new(render_pass: u8, transparent: bool, shader: u32, texture: u32, depth: f32) -> SortKey {
+        let depth = Self::quantize_depth(depth);
+        let shader = shader as u128;
+        let texture = texture as u128;
+
+        let key = if transparent {
+            (1 << 119) | ((0xff_ffff - depth) << 64) | (shader << 32) | texture
+        } else {
+            (shader << 56) | (texture << 24) | depth
+        };
+
+        return SortKey(((render_pass as u128) << 120) | key);
+    }
+
+    pub fn get_render_pass(&self) -> u8 {
+        return (self.0 >> 120) as u8;
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        return self.0 & (1 << 119) != 0;
+    }
+
+    // Positive floats order the same as their bits, the top 24 bits keep
+    // enough precision for sorting.
+    fn quantize_depth(depth: f32) -> u128 {
+        return (depth.max(0.0).to_bits() >> 7) as u128 & 0xff_ffff;
+    }
+}
+
+pub struct RenderQueue<'a> {
+    commands: Vec<(SortKey, &'a mut Box<dyn Drawable>)>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> RenderQueue<'a> {
+        return RenderQueue {
+            commands: Vec::new(),
+        };
+    }
+
+    // Submits at depth 0, transparent drawables are only drawn back to
+    // front when submitted with submit_at_depth.
+    pub fn submit(&mut self, drawable: &'a mut Box<dyn Drawable>) {
+        self.submit_at_depth(drawable, 0.0);
+    }
+
+    // Depth is the distance from the camera.
+    pub fn submit_at_depth(&mut self, drawable: &'a mut Box<dyn Drawable>, depth: f32) {
+        let key = SortKey::new(
+            drawable.get_render_pass(),
+            drawable.is_transparent(),
+            drawable.get_shader().get_id(),
+            drawable.get_texture().map_or(0, |texture| texture.get_id()),
+            depth,
+        );
+
+        self.submit_with_key(drawable, key);
+    }
+
+    pub fn submit_with_key(&mut self, drawable: &'a mut Box<dyn Drawable>, key: SortKey) {
+        self.commands.push((key, drawable));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.commands.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.commands.is_empty();
+    }
+
+    // The sort is stable so draws with equal keys keep submission order.
+    pub fn execute(&mut self) {
+        self.commands.sort_by_key(|(key, _)| *key);
+        for (_, drawable) in self.commands.drain(..) {
+            drawable.draw();
+        }
+    }
+}
+
+impl<'a> Default for RenderQueue<'a> {
+    fn default() -> Self {
+        return RenderQueue::new();
+    }
+}
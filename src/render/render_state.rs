@@ -0,0 +1,223 @@
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompareFunction {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    DstColor,
+    OneMinusDstColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlendOperation {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrontFace {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StencilOperation {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DepthState {
+    pub test: bool,
+    pub write: bool,
+    pub compare: CompareFunction,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        return DepthState {
+            test: true,
+            write: true,
+            compare: CompareFunction::Less,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlendState {
+    pub enabled: bool,
+    pub color_operation: BlendOperation,
+    pub src_color: BlendFactor,
+    pub dst_color: BlendFactor,
+    pub alpha_operation: BlendOperation,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+}
+
+impl BlendState {
+    pub const DISABLED: BlendState = BlendState {
+        enabled: false,
+        color_operation: BlendOperation::Add,
+        src_color: BlendFactor::One,
+        dst_color: BlendFactor::Zero,
+        alpha_operation: BlendOperation::Add,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::Zero,
+    };
+
+    pub const ALPHA: BlendState = BlendState {
+        enabled: true,
+        color_operation: BlendOperation::Add,
+        src_color: BlendFactor::SrcAlpha,
+        dst_color: BlendFactor::OneMinusSrcAlpha,
+        alpha_operation: BlendOperation::Add,
+        src_alpha: BlendFactor::SrcAlpha,
+        dst_alpha: BlendFactor::OneMinusSrcAlpha,
+    };
+
+    pub const ADDITIVE: BlendState = BlendState {
+        enabled: true,
+        color_operation: BlendOperation::Add,
+        src_color: BlendFactor::One,
+        dst_color: BlendFactor::One,
+        alpha_operation: BlendOperation::Add,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::One,
+    };
+}
+
+impl Default for BlendState {
+    fn default() -> Self {
+        return BlendState::DISABLED;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StencilFaceState {
+    pub compare: CompareFunction,
+    pub fail: StencilOperation,
+    pub depth_fail: StencilOperation,
+    pub pass: StencilOperation,
+}
+
+impl Default for StencilFaceState {
+    fn default() -> Self {
+        return StencilFaceState {
+            compare: CompareFunction::Always,
+            fail: StencilOperation::Keep,
+            depth_fail: StencilOperation::Keep,
+            pass: StencilOperation::Keep,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StencilState {
+    pub enabled: bool,
+    pub front: StencilFaceState,
+    pub back: StencilFaceState,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl Default for StencilState {
+    fn default() -> Self {
+        return StencilState {
+            enabled: false,
+            front: StencilFaceState::default(),
+            back: StencilFaceState::default(),
+            reference: 0,
+            read_mask: u32::MAX,
+            write_mask: u32::MAX,
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ColorMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl ColorMask {
+    pub const ALL: ColorMask = ColorMask {
+        red: true,
+        green: true,
+        blue: true,
+        alpha: true,
+    };
+
+    pub const NONE: ColorMask = ColorMask {
+        red: false,
+        green: false,
+        blue: false,
+        alpha: false,
+    };
+}
+
+impl Default for ColorMask {
+    fn default() -> Self {
+        return ColorMask::ALL;
+    }
+}
+
+// Origin is the bottom left corner of the render target.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScissorRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
@@ -0,0 +1,173 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::event::Event;
+
+use super::{
+    command_buffer::{CommandBuffer, RenderContext},
+    render_api::{create_api_with_backend, Backend},
+    window::Window,
+};
+
+// The thread stops when a command panics, its window and resources are
+// gone then and later command buffers are dropped.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderThreadError {
+    Stopped,
+}
+
+impl fmt::Display for RenderThreadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderThreadError::Stopped => write!(f, "Render thread has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for RenderThreadError {}
+
+enum Message {
+    Submit(CommandBuffer),
+    Finish(mpsc::Sender<()>),
+}
+
+// Owns the window and the render API on a thread of its own, so the context
+// is only ever used by the thread it was made current on. Other threads
+// record command buffers and submit them, they run in submission order.
+// GLFW windows must live on the main thread on macOS, use the headless or
+// null windows there.
+pub struct RenderThread {
+    sender: Option<mpsc::Sender<Message>>,
+    thread: Option<thread::JoinHandle<()>>,
+    events: Arc<Mutex<Vec<Event>>>,
+    should_close: Arc<AtomicBool>,
+}
+
+impl RenderThread {
+    // The window is created on the render thread since windows can't move
+    // between threads.
+    pub fn new<F>(create_window: F, backend: Backend) -> RenderThread
+    where
+        F: FnOnce() -> Box<dyn Window> + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let should_close = Arc::new(AtomicBool::new(false));
+
+        let thread_events = events.clone();
+        let thread_should_close = should_close.clone();
+        let thread = thread::Builder::new()
+            .name(String::from("render"))
+            .spawn(move || {
+                let mut window = create_window();
+                let api = create_api_with_backend(&mut window, backend);
+                let mut context = RenderContext::new(api, window, thread_events, thread_should_close);
+                for message in receiver {
+                    match message {
+                        Message::Submit(commands) => commands.run(&mut context),
+                        Message::Finish(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the render thread");
+
+        return RenderThread {
+            sender: Some(sender),
+            thread: Some(thread),
+            events,
+            should_close,
+        };
+    }
+
+    // Fails once the thread stopped. A command buffer submitted while it
+    // is stopping is dropped without an error, finish reports that.
+    pub fn submit(&self, commands: CommandBuffer) -> Result<(), RenderThreadError> {
+        return self.send(Message::Submit(commands));
+    }
+
+    // Blocks until every command buffer submitted before has run, fails if
+    // the thread stopped before it got there.
+    pub fn finish(&self) -> Result<(), RenderThreadError> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Message::Finish(sender))?;
+
+        return receiver.recv().map_err(|_| RenderThreadError::Stopped);
+    }
+
+    // Events collected by present commands since the last call.
+    pub fn take_events(&self) -> Vec<Event> {
+        return std::mem::take(&mut *self.events.lock().unwrap());
+    }
+
+    // As of the latest present command.
+    pub fn should_close(&self) -> bool {
+        return self.should_close.load(Ordering::Relaxed);
+    }
+
+    fn send(&self, message: Message) -> Result<(), RenderThreadError> {
+        return match &self.sender {
+            Some(sender) => sender.send(message).map_err(|_| RenderThreadError::Stopped),
+            None => Err(RenderThreadError::Stopped),
+        };
+    }
+}
+
+impl Drop for RenderThread {
+    // Runs the remaining command buffers, then the resources, API and window
+    // are dropped on the render thread.
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("Render thread panicked");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "recording"))]
+mod tests {
+    use super::*;
+    use crate::render::{buffer::BufferDataType, texture::TextureFormat, window::create_null_window};
+
+    fn create_thread() -> RenderThread {
+        return RenderThread::new(|| create_null_window(4, 4), Backend::Recording);
+    }
+
+    #[test]
+    fn refuses_textures_with_wrong_sizes() {
+        let thread = create_thread();
+        let mut commands = CommandBuffer::new();
+        let valid = commands.create_texture(vec![0; 16], 2, 2, TextureFormat::RGBA, BufferDataType::U8);
+        let truncated = commands.create_texture(vec![0; 15], 2, 2, TextureFormat::RGBA, BufferDataType::U8);
+        let (sender, receiver) = mpsc::channel();
+        commands.execute(move |context| {
+            let _ = sender.send((
+                context.resources.get(valid).is_some(),
+                context.resources.get(truncated).is_some(),
+            ));
+        });
+        thread.submit(commands).unwrap();
+
+        assert_eq!(receiver.recv().unwrap(), (true, false));
+    }
+
+    #[test]
+    fn reports_panicked_commands() {
+        let thread = create_thread();
+        let mut commands = CommandBuffer::new();
+        commands.execute(|_| panic!("Command failed"));
+        thread.submit(commands).unwrap();
+
+        assert_eq!(thread.finish(), Err(RenderThreadError::Stopped));
+        assert_eq!(thread.submit(CommandBuffer::new()), Err(RenderThreadError::Stopped));
+    }
+}
@@ -1,20 +1,86 @@
 use nalgebra::{Matrix4, Vector3};
 
+#[cfg(feature = "opengl")]
 use crate::platform::opengl::shader::OglShader;
+#[cfg(feature = "recording")]
+use crate::platform::recording::shader::RecordingShader;
+#[cfg(feature = "software")]
+use crate::platform::software::shader::SoftwareShader;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::shader::WgpuShader;
 
-use super::buffer::Bindable;
+use super::{
+    buffer::Bindable,
+    render_api::{get_backend, Backend},
+};
+
+// A vertex input of a linked shader. Matrices take one location per column.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ShaderAttribute {
+    pub name: String,
+    pub location: u32,
+    pub components: u32,
+    // Declared as int or uint, vertex data only ever reaches shaders as
+    // floats.
+    pub integer: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UniformValue {
+    I32(i32),
+    Vec3(Vector3<f32>),
+    Mat4(Matrix4<f32>),
+}
 
 pub trait Shader: Bindable {
+    fn get_id(&self) -> u32;
     fn load_from_file(&mut self, file_path: &str);
+    fn load_from_source(&mut self, source: &str);
+    // Shown in debug messages and graphics debuggers.
+    fn set_label(&self, label: &str);
+    // None when the backend can't reflect the shader's inputs.
+    fn get_attributes(&self) -> Option<Vec<ShaderAttribute>>;
 
+    fn submit_uniform_i32(&self, name: &str, value: i32);
     fn submit_uniform_vec3(&self, name: &str, value: Vector3<f32>);
     fn submit_uniform_mat4x4(&self, name: &str, data: Matrix4<f32>);
 }
 
 pub fn create_shader() -> Box<dyn Shader> {
-    return Box::new(OglShader::new());
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglShader::new()),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareShader::new()),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingShader::new()),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuShader::new()),
+    }
 }
 
 pub fn create_shader_from_file(file_path: &str) -> Box<dyn Shader> {
-    return Box::new(OglShader::new_from_file(file_path));
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglShader::new_from_file(file_path)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareShader::new_from_file(file_path)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingShader::new_from_file(file_path)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuShader::new_from_file(file_path)),
+    }
+}
+
+pub fn create_shader_from_source(source: &str) -> Box<dyn Shader> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglShader::new_from_source(source)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareShader::new_from_source(source)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingShader::new_from_source(source)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuShader::new_from_source(source)),
+    }
 }
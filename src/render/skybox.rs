@@ -0,0 +1,123 @@
+use nalgebra::Matrix4;
+
+use super::{
+    buffer::{
+        create_buffer, create_vertex_array, BufferDataType, BufferLayoutNode, BufferType,
+        BufferUsage, VertexArray,
+    },
+    mesh::Drawable,
+    pipeline::{create_pipeline, Pipeline, PipelineDescriptor},
+    shader::{create_shader_from_source, Shader},
+    texture::Texture,
+};
+
+// Depth is pushed just short of the far plane so the skybox passes the
+// default depth test against a cleared buffer but loses to any geometry.
+const SKYBOX_SHADER: &str = "#shader vertex
+#version 330 core
+
+layout (location = 0) in vec3 a_position;
+
+out vec3 v_direction;
+
+uniform mat4 u_view_projection;
+
+void main() {
+    v_direction = a_position;
+    vec4 position = u_view_projection * vec4(a_position, 1.0);
+    gl_Position = vec4(position.xy, position.w * 0.99999, position.w);
+}
+
+#shader fragment
+#version 330 core
+
+in vec3 v_direction;
+out vec4 color;
+
+uniform samplerCube u_skybox;
+
+void main() {
+    color = texture(u_skybox, v_direction);
+}
+";
+
+#[rustfmt::skip]
+const CUBE_VERTICES: [f32; 108] = [
+    -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+];
+
+pub struct Skybox {
+    vertex_array: Box<dyn VertexArray>,
+    pipeline: Box<dyn Pipeline>,
+    cubemap: Box<dyn Texture>,
+}
+
+impl Drawable for Skybox {
+    fn draw(&mut self) {
+        self.pipeline.bind();
+        self.vertex_array.bind();
+        self.cubemap.bind(0);
+
+        self.vertex_array.draw();
+    }
+
+    fn get_shader(&self) -> &Box<dyn Shader> {
+        return self.pipeline.get_shader();
+    }
+
+    fn get_texture(&self) -> Option<&Box<dyn Texture>> {
+        return Some(&self.cubemap);
+    }
+}
+
+impl Skybox {
+    pub fn new(cubemap: Box<dyn Texture>) -> Self {
+        let buffer_layout = vec![BufferLayoutNode {
+            elements: 3,
+            data_type: BufferDataType::F32,
+            normalized: false,
+        }];
+
+        let mut vertex_array = create_vertex_array();
+        let vertex_buffer = create_buffer(
+            &mut CUBE_VERTICES.to_vec(),
+            BufferType::Vertex,
+            buffer_layout.clone(),
+            BufferUsage::Static,
+            None,
+        );
+
+        vertex_array.add_buffer(vertex_buffer, BufferType::Vertex);
+
+        // The cube is seen from inside, so nothing may be culled.
+        let shader = create_shader_from_source(SKYBOX_SHADER);
+        let pipeline = create_pipeline(shader, PipelineDescriptor::new(buffer_layout))
+            .expect("Skybox shader doesn't match its vertices");
+
+        return Self {
+            vertex_array: vertex_array,
+            pipeline: pipeline,
+            cubemap: cubemap,
+        };
+    }
+
+    pub fn set_camera(&self, view: Matrix4<f32>, projection: Matrix4<f32>) {
+        let rotation = view.fixed_slice::<3, 3>(0, 0).into_owned().to_homogeneous();
+
+        let shader = self.pipeline.get_shader();
+        shader.bind();
+        shader
+            .submit_uniform_mat4x4("u_view_projection", projection * rotation);
+    }
+}
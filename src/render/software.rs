@@ -0,0 +1,102 @@
+use std::{collections::HashMap, sync::Arc};
+
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+
+use crate::platform::software::shader::{self, SoftwareShader};
+
+use super::shader::{Shader, UniformValue};
+
+// Values submitted through the shader, looked up by their GLSL names.
+#[derive(Clone, Default, Debug)]
+pub struct Uniforms {
+    values: HashMap<String, UniformValue>,
+}
+
+impl Uniforms {
+    pub fn get(&self, name: &str) -> Option<UniformValue> {
+        return self.values.get(name).copied();
+    }
+
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        match self.get(name) {
+            Some(UniformValue::I32(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_vec3(&self, name: &str) -> Option<Vector3<f32>> {
+        match self.get(name) {
+            Some(UniformValue::Vec3(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mat4(&self, name: &str) -> Option<Matrix4<f32>> {
+        match self.get(name) {
+            Some(UniformValue::Mat4(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set(&mut self, name: &str, value: UniformValue) {
+        self.values.insert(String::from(name), value);
+    }
+}
+
+pub struct VertexOutput {
+    // Clip space position, like gl_Position.
+    pub position: Vector4<f32>,
+    // Interpolated perspective correctly across the triangle.
+    pub varyings: Vec<f32>,
+}
+
+// Reads the textures bound to each slot, filtered linearly and clamped to
+// the edge like the OpenGL defaults. Unbound slots read as black.
+pub trait TextureSampler {
+    fn sample(&self, slot: u32, coordinates: Vector2<f32>) -> Vector4<f32>;
+    fn sample_layer(&self, slot: u32, coordinates: Vector2<f32>, layer: u32) -> Vector4<f32>;
+    fn sample_cube(&self, slot: u32, direction: Vector3<f32>) -> Vector4<f32>;
+}
+
+// Shader of the software backend. Attributes are indexed by location and
+// padded to four components with (0, 0, 0, 1) like GLSL inputs.
+pub trait SoftwareProgram: Send + Sync {
+    fn vertex(&self, attributes: &[Vector4<f32>], uniforms: &Uniforms) -> VertexOutput;
+    // Only the first color attachment is written, None discards the fragment.
+    fn fragment(
+        &self,
+        varyings: &[f32],
+        uniforms: &Uniforms,
+        textures: &dyn TextureSampler,
+    ) -> Option<Vector4<f32>>;
+}
+
+// Lets a pair of closures be used as a program.
+impl<V, F> SoftwareProgram for (V, F)
+where
+    V: Fn(&[Vector4<f32>], &Uniforms) -> VertexOutput + Send + Sync,
+    F: Fn(&[f32], &Uniforms, &dyn TextureSampler) -> Option<Vector4<f32>> + Send + Sync,
+{
+    fn vertex(&self, attributes: &[Vector4<f32>], uniforms: &Uniforms) -> VertexOutput {
+        return (self.0)(attributes, uniforms);
+    }
+
+    fn fragment(
+        &self,
+        varyings: &[f32],
+        uniforms: &Uniforms,
+        textures: &dyn TextureSampler,
+    ) -> Option<Vector4<f32>> {
+        return (self.1)(varyings, uniforms, textures);
+    }
+}
+
+pub fn create_software_shader<P: SoftwareProgram + 'static>(program: P) -> Box<dyn Shader> {
+    return Box::new(SoftwareShader::new_with_program(Arc::new(program)));
+}
+
+// GLSL can't run on the CPU, shaders the crate loads from files, e.g. for
+// meshes and text, use the program registered under their path instead.
+pub fn register_software_shader<P: SoftwareProgram + 'static>(path: &str, program: P) {
+    shader::register_program(path, Arc::new(program));
+}
@@ -0,0 +1,80 @@
+use std::{fmt, time::Duration};
+
+// Calls that reached the driver and calls the state cache skipped since
+// the last reset.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StateCacheStats {
+    pub program_binds: u64,
+    pub vertex_array_binds: u64,
+    pub buffer_binds: u64,
+    pub texture_binds: u64,
+    pub state_changes: u64,
+
+    pub skipped_program_binds: u64,
+    pub skipped_vertex_array_binds: u64,
+    pub skipped_buffer_binds: u64,
+    pub skipped_texture_binds: u64,
+    pub skipped_state_changes: u64,
+}
+
+impl StateCacheStats {
+    pub fn get_issued_calls(&self) -> u64 {
+        return self.program_binds
+            + self.vertex_array_binds
+            + self.buffer_binds
+            + self.texture_binds
+            + self.state_changes;
+    }
+
+    pub fn get_skipped_calls(&self) -> u64 {
+        return self.skipped_program_binds
+            + self.skipped_vertex_array_binds
+            + self.skipped_buffer_binds
+            + self.skipped_texture_binds
+            + self.skipped_state_changes;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScopeTiming {
+    pub name: String,
+    pub gpu_time: Duration,
+}
+
+// Work issued between begin and end. GPU times arrive a few frames late,
+// so the stats describe the latest frame whose timings are available.
+#[derive(Clone, Default, Debug)]
+pub struct FrameStats {
+    pub draw_calls: u64,
+    pub vertices: u64,
+    pub triangles: u64,
+    pub state_changes: u64,
+    pub buffer_bytes_uploaded: u64,
+    pub texture_bytes_uploaded: u64,
+    pub gpu_time: Option<Duration>,
+    pub scopes: Vec<ScopeTiming>,
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "draws: {}, vertices: {}, triangles: {}, state changes: {}",
+            self.draw_calls, self.vertices, self.triangles, self.state_changes
+        )?;
+        writeln!(
+            f,
+            "uploaded: {} buffer bytes, {} texture bytes",
+            self.buffer_bytes_uploaded, self.texture_bytes_uploaded
+        )?;
+        match self.gpu_time {
+            Some(time) => writeln!(f, "gpu: {:.3} ms", time.as_secs_f64() * 1000.0)?,
+            None => writeln!(f, "gpu: unavailable")?,
+        }
+        for scope in &self.scopes {
+            writeln!(f, "  {}: {:.3} ms", scope.name, scope.gpu_time.as_secs_f64() * 1000.0)?;
+        }
+
+        return Ok(());
+    }
+}
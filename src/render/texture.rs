@@ -1,12 +1,110 @@
+use std::{ffi::c_void, fs::File, io::BufWriter, mem::size_of_val};
+
 use dyn_clone::DynClone;
+use image::{
+    codecs::hdr::HdrEncoder,
+    error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
+    ColorType, DynamicImage, ImageError, ImageFormat, ImageResult, Rgb,
+};
+
+#[cfg(feature = "opengl")]
+use crate::platform::opengl::{
+    async_texture::{self, OglAsyncTexture},
+    texture::OglTexture,
+};
+#[cfg(feature = "recording")]
+use crate::platform::recording::texture::RecordingTexture;
+#[cfg(feature = "software")]
+use crate::platform::software::texture::SoftwareTexture;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::texture::WgpuTexture;
+
+use super::{
+    buffer::BufferDataType,
+    cubemap,
+    render_api::{get_backend, Backend},
+};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextureFormat {
+    RGBA,
+    RGB,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Depth,
+    DepthStencil,
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextureDimension {
+    Texture1D,
+    Texture2D,
+    Texture2DArray,
+    Texture3D,
+    Cube,
+    CubeArray,
+}
+
+impl TextureFormat {
+    pub fn get_channel_count(&self) -> u32 {
+        match self {
+            TextureFormat::RGBA => 4,
+            TextureFormat::RGB => 3,
+            TextureFormat::Red
+            | TextureFormat::Green
+            | TextureFormat::Blue
+            | TextureFormat::Alpha
+            | TextureFormat::Depth
+            | TextureFormat::DepthStencil => 1,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum PixelData {
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+}
 
-use crate::platform::opengl::texture::{OglTexture, TextureFormat};
+#[derive(Clone)]
+pub struct PixelBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub data: PixelData,
+}
 
-use super::buffer::BufferDataType;
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ImageFileFormat {
+    Png,
+    Exr,
+    Hdr,
+}
 
-pub trait Texture: Send + Sync + DynClone {
+// Stays on the thread it was created on, dropping it deletes the GPU object
+// through the context current there.
+pub trait Texture: DynClone {
+    fn get_id(&self) -> u32;
     fn bind(&self, slot: u32);
     fn unbind(&self);
+
+    fn get_size(&self, level: u32) -> (u32, u32);
+    fn get_format(&self) -> TextureFormat;
+    fn read_pixels(&self, level: u32, data_type: BufferDataType) -> PixelBuffer;
+    fn upload_layer(&self, layer: u32, data: *const u8, data_type: BufferDataType);
+    fn is_ready(&self) -> bool;
+    // Shown in debug messages and graphics debuggers.
+    fn set_label(&self, label: &str);
 }
 
 dyn_clone::clone_trait_object!(Texture);
@@ -18,5 +116,399 @@ pub fn create_texture(
     format: TextureFormat,
     data_type: BufferDataType,
 ) -> Box<dyn Texture> {
-    return Box::new(OglTexture::new(width, height, data, format, data_type));
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglTexture::new(width, height, data, format, data_type)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareTexture::new(width, height, data, format, data_type)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingTexture::new(width, height, data, format, data_type)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuTexture::new(width, height, data, format, data_type)),
+    }
+}
+
+pub fn create_texture_with_dimension(
+    dimension: TextureDimension,
+    data: *const u8,
+    width: u32,
+    height: u32,
+    depth: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+) -> Box<dyn Texture> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglTexture::new_with_dimension(
+            dimension, width, height, depth, data, format, data_type,
+        )),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareTexture::new_with_dimension(
+            dimension, width, height, depth, data, format, data_type,
+        )),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingTexture::new_with_dimension(
+            dimension, width, height, depth, data, format, data_type,
+        )),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuTexture::new_with_dimension(
+            dimension, width, height, depth, data, format, data_type,
+        )),
+    }
+}
+
+// Decoding runs on a worker thread and the upload is spread over the
+// following frames, the texture binds a placeholder until it is ready.
+// The other backends decode right away and upload in one go.
+pub fn create_texture_async<F>(decode: F) -> Box<dyn Texture>
+where
+    F: FnOnce() -> Option<PixelBuffer> + Send + 'static,
+{
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglAsyncTexture::new(decode)),
+        #[cfg(feature = "software")]
+        Backend::Software => match decode() {
+            Some(pixels) => Box::new(SoftwareTexture::new(
+                pixels.width,
+                pixels.height,
+                pixels.data.as_bytes().as_ptr(),
+                pixels.format,
+                pixels.data.get_data_type(),
+            )),
+            None => {
+                log::warn!("Failed to decode texture, using the placeholder");
+                let pixel: [u8; 4] = [128, 128, 128, 255];
+                Box::new(SoftwareTexture::new(1, 1, pixel.as_ptr(), TextureFormat::RGBA, BufferDataType::U8))
+            }
+        },
+        #[cfg(feature = "recording")]
+        Backend::Recording => match decode() {
+            Some(pixels) => Box::new(RecordingTexture::from_pixels(&pixels)),
+            None => {
+                log::warn!("Failed to decode texture, using the placeholder");
+                let pixel: [u8; 4] = [128, 128, 128, 255];
+                Box::new(RecordingTexture::new(1, 1, pixel.as_ptr(), TextureFormat::RGBA, BufferDataType::U8))
+            }
+        },
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => match decode() {
+            Some(pixels) => Box::new(WgpuTexture::new(
+                pixels.width,
+                pixels.height,
+                pixels.data.as_bytes().as_ptr(),
+                pixels.format,
+                pixels.data.get_data_type(),
+            )),
+            None => {
+                log::warn!("Failed to decode texture, using the placeholder");
+                let pixel: [u8; 4] = [128, 128, 128, 255];
+                Box::new(WgpuTexture::new(1, 1, pixel.as_ptr(), TextureFormat::RGBA, BufferDataType::U8))
+            }
+        },
+    }
+}
+
+pub fn load_texture_async(path: &str) -> Box<dyn Texture> {
+    let path = String::from(path);
+    return create_texture_async(move || PixelBuffer::load(&path));
+}
+
+// Bytes streamed to the GPU per frame for textures created asynchronously.
+#[cfg(feature = "opengl")]
+pub fn set_texture_upload_budget(bytes: usize) {
+    async_texture::set_upload_budget(bytes);
+}
+
+pub fn create_cubemap_texture(
+    faces: [*const u8; 6],
+    size: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+) -> Box<dyn Texture> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglTexture::new_cubemap(size, faces, format, data_type)),
+        #[cfg(feature = "software")]
+        Backend::Software => Box::new(SoftwareTexture::new_cubemap(size, faces, format, data_type)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingTexture::new_cubemap(size, faces, format, data_type)),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Box::new(WgpuTexture::new_cubemap(size, faces, format, data_type)),
+    }
+}
+
+// The recording renderer only logs the conversion and never reads the
+// source data.
+pub fn create_cubemap_texture_from_cross(
+    data: *const u8,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+) -> Box<dyn Texture> {
+    #[cfg(feature = "recording")]
+    if get_backend() == Backend::Recording {
+        let size = if width * 3 == height * 4 { width / 4 } else { width / 3 };
+        return Box::new(RecordingTexture::new_converted_cubemap(size, format, data_type));
+    }
+
+    let (size, faces) = cubemap::split_cross(data, width, height, format, data_type);
+    let faces = [0, 1, 2, 3, 4, 5].map(|face| faces[face].as_ptr());
+    return create_cubemap_texture(faces, size, format, data_type);
+}
+
+// OpenGL converts on the GPU, the software and wgpu renderers project the
+// faces on the CPU.
+#[cfg_attr(
+    not(any(feature = "opengl", feature = "software", feature = "wgpu")),
+    allow(unused_variables)
+)]
+pub fn create_cubemap_texture_from_equirectangular(
+    data: *const u8,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+    data_type: BufferDataType,
+    size: u32,
+) -> Box<dyn Texture> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Box::new(OglTexture::new_cubemap_from_equirectangular(
+            width, height, data, format, data_type, size,
+        )),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Box::new(RecordingTexture::new_converted_cubemap(size, format, data_type)),
+        #[cfg(any(feature = "software", feature = "wgpu"))]
+        _ => {
+            let faces = cubemap::project_equirectangular(data, width, height, format, data_type, size);
+            let faces = [0, 1, 2, 3, 4, 5].map(|face| faces[face].as_ptr() as *const u8);
+            create_cubemap_texture(faces, size, TextureFormat::RGB, BufferDataType::F32)
+        }
+    }
+}
+
+pub fn save_texture(
+    texture: &Box<dyn Texture>,
+    level: u32,
+    path: &str,
+    file_format: ImageFileFormat,
+) -> ImageResult<()> {
+    match file_format {
+        ImageFileFormat::Png => texture.read_pixels(level, BufferDataType::U8).save_png(path),
+        ImageFileFormat::Exr => texture.read_pixels(level, BufferDataType::F32).save_exr(path),
+        ImageFileFormat::Hdr => texture.read_pixels(level, BufferDataType::F32).save_hdr(path),
+    }
+}
+
+impl PixelData {
+    // There is no F64 variant, doubles are read and stored as F32.
+    pub fn new(length: usize, data_type: BufferDataType) -> PixelData {
+        match data_type {
+            BufferDataType::U8 => PixelData::U8(vec![0; length]),
+            BufferDataType::I8 => PixelData::I8(vec![0; length]),
+            BufferDataType::U16 => PixelData::U16(vec![0; length]),
+            BufferDataType::I16 => PixelData::I16(vec![0; length]),
+            BufferDataType::U32 => PixelData::U32(vec![0; length]),
+            BufferDataType::I32 => PixelData::I32(vec![0; length]),
+            BufferDataType::F32 | BufferDataType::F64 => PixelData::F32(vec![0.0; length]),
+        }
+    }
+
+    // Normalized integer types are converted like glReadPixels does.
+    pub fn from_f32(values: &[f32], data_type: BufferDataType) -> PixelData {
+        fn normalize<T>(values: &[f32], max: f32, min: f32, convert: impl Fn(f32) -> T) -> Vec<T> {
+            return values
+                .iter()
+                .map(|value| convert((value * max).round().clamp(min, max)))
+                .collect();
+        }
+
+        match data_type {
+            BufferDataType::U8 => {
+                PixelData::U8(normalize(values, u8::MAX as f32, 0.0, |v| v as u8))
+            }
+            BufferDataType::I8 => {
+                PixelData::I8(normalize(values, i8::MAX as f32, i8::MIN as f32, |v| v as i8))
+            }
+            BufferDataType::U16 => {
+                PixelData::U16(normalize(values, u16::MAX as f32, 0.0, |v| v as u16))
+            }
+            BufferDataType::I16 => {
+                PixelData::I16(normalize(values, i16::MAX as f32, i16::MIN as f32, |v| v as i16))
+            }
+            BufferDataType::U32 => {
+                PixelData::U32(normalize(values, u32::MAX as f32, 0.0, |v| v as u32))
+            }
+            BufferDataType::I32 => {
+                PixelData::I32(normalize(values, i32::MAX as f32, i32::MIN as f32, |v| v as i32))
+            }
+            BufferDataType::F32 | BufferDataType::F64 => PixelData::F32(values.to_vec()),
+        }
+    }
+
+    pub fn get_data_type(&self) -> BufferDataType {
+        match self {
+            PixelData::U8(_) => BufferDataType::U8,
+            PixelData::I8(_) => BufferDataType::I8,
+            PixelData::U16(_) => BufferDataType::U16,
+            PixelData::I16(_) => BufferDataType::I16,
+            PixelData::U32(_) => BufferDataType::U32,
+            PixelData::I32(_) => BufferDataType::I32,
+            PixelData::F32(_) => BufferDataType::F32,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            PixelData::U8(data) => data,
+            PixelData::I8(data) => Self::to_bytes(data),
+            PixelData::U16(data) => Self::to_bytes(data),
+            PixelData::I16(data) => Self::to_bytes(data),
+            PixelData::U32(data) => Self::to_bytes(data),
+            PixelData::I32(data) => Self::to_bytes(data),
+            PixelData::F32(data) => Self::to_bytes(data),
+        }
+    }
+
+    pub fn as_mut_ptr(&mut self) -> *mut c_void {
+        match self {
+            PixelData::U8(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::I8(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::U16(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::I16(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::U32(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::I32(data) => data.as_mut_ptr() as *mut c_void,
+            PixelData::F32(data) => data.as_mut_ptr() as *mut c_void,
+        }
+    }
+
+    fn to_bytes<T>(data: &[T]) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+    }
+}
+
+impl PixelBuffer {
+    pub fn load(path: &str) -> Option<PixelBuffer> {
+        let image = match image::open(path) {
+            Ok(image) => image,
+            Err(error) => {
+                log::error!("Failed to load texture {} with error\n{}", path, error);
+                return None;
+            }
+        };
+
+        return Some(match image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                let image = image.into_rgba32f();
+                PixelBuffer {
+                    width: image.width(),
+                    height: image.height(),
+                    format: TextureFormat::RGBA,
+                    data: PixelData::F32(image.into_raw()),
+                }
+            }
+            _ => {
+                let image = image.into_rgba8();
+                PixelBuffer {
+                    width: image.width(),
+                    height: image.height(),
+                    format: TextureFormat::RGBA,
+                    data: PixelData::U8(image.into_raw()),
+                }
+            }
+        });
+    }
+
+    // GL stores rows bottom to top while image files expect them top to
+    // bottom.
+    pub fn flip_vertically(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let row_length = (self.width * self.format.get_channel_count()) as usize;
+        match &mut self.data {
+            PixelData::U8(data) => Self::flip_rows(data, row_length),
+            PixelData::I8(data) => Self::flip_rows(data, row_length),
+            PixelData::U16(data) => Self::flip_rows(data, row_length),
+            PixelData::I16(data) => Self::flip_rows(data, row_length),
+            PixelData::U32(data) => Self::flip_rows(data, row_length),
+            PixelData::I32(data) => Self::flip_rows(data, row_length),
+            PixelData::F32(data) => Self::flip_rows(data, row_length),
+        }
+    }
+
+    pub fn save_png(&self, path: &str) -> ImageResult<()> {
+        let data = match &self.data {
+            PixelData::U8(data) => data,
+            _ => return Err(Self::unsupported(ImageFormat::Png, "PNG export requires U8 pixel data")),
+        };
+
+        let color_type = match self.format {
+            TextureFormat::RGBA => ColorType::Rgba8,
+            TextureFormat::RGB => ColorType::Rgb8,
+            _ => ColorType::L8,
+        };
+
+        return image::save_buffer(path, data, self.width, self.height, color_type);
+    }
+
+    pub fn save_exr(&self, path: &str) -> ImageResult<()> {
+        let (data, color_type) = match self.format {
+            TextureFormat::RGBA => (self.get_float_data(ImageFormat::OpenExr)?.clone(), ColorType::Rgba32F),
+            _ => (self.get_rgb_float_data(ImageFormat::OpenExr)?, ColorType::Rgb32F),
+        };
+
+        let bytes: Vec<u8> = data.iter().flat_map(|value| value.to_ne_bytes()).collect();
+
+        return image::save_buffer(path, &bytes, self.width, self.height, color_type);
+    }
+
+    pub fn save_hdr(&self, path: &str) -> ImageResult<()> {
+        let pixels: Vec<Rgb<f32>> = self
+            .get_rgb_float_data(ImageFormat::Hdr)?
+            .chunks_exact(3)
+            .map(|pixel| Rgb([pixel[0], pixel[1], pixel[2]]))
+            .collect();
+
+        let file = File::create(path).map_err(ImageError::IoError)?;
+        let encoder = HdrEncoder::new(BufWriter::new(file));
+
+        return encoder.encode(&pixels, self.width as usize, self.height as usize);
+    }
+
+    fn flip_rows<T>(data: &mut [T], row_length: usize) {
+        let rows = data.len() / row_length;
+        for row in 0..rows / 2 {
+            let (top, bottom) = data.split_at_mut((rows - row - 1) * row_length);
+            top[row * row_length..(row + 1) * row_length].swap_with_slice(&mut bottom[..row_length]);
+        }
+    }
+
+    fn get_float_data(&self, format: ImageFormat) -> ImageResult<&Vec<f32>> {
+        match &self.data {
+            PixelData::F32(data) => return Ok(data),
+            _ => return Err(Self::unsupported(format, "Float image export requires F32 pixel data")),
+        }
+    }
+
+    fn get_rgb_float_data(&self, format: ImageFormat) -> ImageResult<Vec<f32>> {
+        let data = self.get_float_data(format)?;
+        return Ok(match self.format {
+            TextureFormat::RGB => data.clone(),
+            TextureFormat::RGBA => data
+                .chunks_exact(4)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                .collect(),
+            _ => data.iter().flat_map(|value| [*value, *value, *value]).collect(),
+        });
+    }
+
+    fn unsupported(format: ImageFormat, message: &str) -> ImageError {
+        return ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+            ImageFormatHint::Exact(format),
+            UnsupportedErrorKind::GenericFeature(message.to_string()),
+        ));
+    }
 }
@@ -0,0 +1,468 @@
+use std::{fmt, fs, io};
+
+use ddsfile::{Caps2, D3DFormat, Dds, DxgiFormat, MiscFlag};
+
+#[cfg(feature = "opengl")]
+use crate::platform::opengl::texture::OglTexture;
+#[cfg(feature = "recording")]
+use crate::platform::recording::texture::RecordingTexture;
+#[cfg(feature = "software")]
+use crate::platform::software::texture::SoftwareTexture;
+#[cfg(feature = "wgpu")]
+use crate::platform::wgpu::texture::WgpuTexture;
+
+use super::{
+    render_api::{get_backend, Backend},
+    texture::Texture,
+};
+
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompressedFormat {
+    Bc1Rgb,
+    Bc1Rgba,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5,
+    Bc6hUnsigned,
+    Bc6hSigned,
+    Bc7,
+    Etc2Rgb,
+    Etc2RgbA1,
+    Etc2Rgba,
+}
+
+impl CompressedFormat {
+    pub fn get_block_size(&self) -> usize {
+        match self {
+            CompressedFormat::Bc1Rgb
+            | CompressedFormat::Bc1Rgba
+            | CompressedFormat::Bc4
+            | CompressedFormat::Etc2Rgb
+            | CompressedFormat::Etc2RgbA1 => 8,
+            _ => 16,
+        }
+    }
+
+    // Saturates instead of overflowing for sizes read from broken headers,
+    // which no data is long enough for.
+    pub fn get_image_size(&self, width: u32, height: u32) -> usize {
+        let blocks_x = (width as usize + 3) / 4;
+        let blocks_y = (height as usize + 3) / 4;
+
+        return blocks_x
+            .max(1)
+            .saturating_mul(blocks_y.max(1))
+            .saturating_mul(self.get_block_size());
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureLoadError {
+    Io(io::Error),
+    Ktx2(ktx2::ParseError),
+    Dds(ddsfile::Error),
+    UnsupportedFormat(String),
+    UnsupportedLayout(String),
+    Truncated,
+}
+
+impl fmt::Display for TextureLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureLoadError::Io(error) => write!(f, "Couldn't read texture file: {}", error),
+            TextureLoadError::Ktx2(error) => write!(f, "Invalid KTX2 file: {:?}", error),
+            TextureLoadError::Dds(error) => write!(f, "Invalid DDS file: {}", error),
+            TextureLoadError::UnsupportedFormat(format) => {
+                write!(f, "Unsupported texture format {}", format)
+            }
+            TextureLoadError::UnsupportedLayout(layout) => {
+                write!(f, "Unsupported texture layout: {}", layout)
+            }
+            TextureLoadError::Truncated => write!(f, "Texture data is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for TextureLoadError {}
+
+// Every level holds all images of that level, ordered layer by layer and
+// face by face within a layer.
+pub struct CompressedTexture {
+    pub format: CompressedFormat,
+    pub srgb: bool,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub faces: u32,
+    pub array: bool,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedTexture {
+    pub fn from_ktx2(bytes: &[u8]) -> Result<CompressedTexture, TextureLoadError> {
+        let reader = ktx2::Reader::new(bytes).map_err(TextureLoadError::Ktx2)?;
+        let header = reader.header();
+
+        if let Some(scheme) = header.supercompression_scheme {
+            return Err(TextureLoadError::UnsupportedFormat(format!("{:?}", scheme)));
+        }
+        if header.pixel_depth > 1 {
+            return Err(TextureLoadError::UnsupportedLayout(String::from(
+                "compressed 3D textures",
+            )));
+        }
+
+        let (format, srgb) = match header.format {
+            Some(format) => Self::translate_ktx2_format(format)?,
+            None => return Err(TextureLoadError::UnsupportedFormat(String::from("undefined"))),
+        };
+
+        let texture = CompressedTexture {
+            format,
+            srgb,
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            layers: header.layer_count.max(1),
+            faces: header.face_count,
+            array: header.layer_count > 0,
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+        };
+        texture.validate()?;
+
+        return Ok(texture);
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<CompressedTexture, TextureLoadError> {
+        let dds = Dds::read(bytes).map_err(TextureLoadError::Dds)?;
+
+        let (format, srgb) = if let Some(format) = dds.get_dxgi_format() {
+            Self::translate_dxgi_format(format)?
+        } else if let Some(format) = dds.get_d3d_format() {
+            Self::translate_d3d_format(format)?
+        } else {
+            match dds.header.spf.fourcc.as_ref().map(|fourcc| fourcc.0) {
+                Some(ddsfile::FourCC::ATI1) | Some(ddsfile::FourCC::BC4_UNORM) => {
+                    (CompressedFormat::Bc4, false)
+                }
+                Some(ddsfile::FourCC::ATI2) => (CompressedFormat::Bc5, false),
+                fourcc => {
+                    return Err(TextureLoadError::UnsupportedFormat(format!("{:?}", fourcc)))
+                }
+            }
+        };
+
+        let (faces, layers, array) = match &dds.header10 {
+            Some(header10) => (
+                if header10.misc_flag.contains(MiscFlag::TEXTURECUBE) { 6 } else { 1 },
+                header10.array_size.max(1),
+                header10.array_size > 1,
+            ),
+            None => (
+                if dds.header.caps2.contains(Caps2::CUBEMAP) { 6 } else { 1 },
+                1,
+                false,
+            ),
+        };
+        if dds.get_depth() > 1 {
+            return Err(TextureLoadError::UnsupportedLayout(String::from(
+                "compressed 3D textures",
+            )));
+        }
+
+        // DDS stores the full mip chain of every image one after another,
+        // regroup it so every level holds all of its images.
+        let width = dds.get_width();
+        let height = dds.get_height();
+        let level_count = dds.get_num_mipmap_levels().max(1) as usize;
+        if level_count > get_max_level_count(width, height) {
+            return Err(TextureLoadError::UnsupportedLayout(format!(
+                "{} levels for a {}x{} texture",
+                level_count, width, height
+            )));
+        }
+        let image_count = layers as usize * faces as usize;
+        let mut levels = vec![Vec::new(); level_count];
+        let mut offset = 0;
+        for _ in 0..image_count {
+            for (level, data) in levels.iter_mut().enumerate() {
+                let size = format.get_image_size(
+                    (width >> level).max(1),
+                    (height >> level).max(1),
+                );
+                if size > dds.data.len() - offset {
+                    return Err(TextureLoadError::Truncated);
+                }
+                data.extend_from_slice(&dds.data[offset..offset + size]);
+                offset += size;
+            }
+        }
+
+        let texture = CompressedTexture {
+            format,
+            srgb,
+            width,
+            height,
+            layers,
+            faces,
+            array,
+            levels,
+        };
+        texture.validate()?;
+
+        return Ok(texture);
+    }
+
+    pub fn get_level_size(&self, level: usize) -> (u32, u32) {
+        return ((self.width >> level).max(1), (self.height >> level).max(1));
+    }
+
+    fn validate(&self) -> Result<(), TextureLoadError> {
+        if self.faces != 1 && self.faces != 6 {
+            return Err(TextureLoadError::UnsupportedLayout(format!(
+                "{} faces",
+                self.faces
+            )));
+        }
+        if self.levels.len() > get_max_level_count(self.width, self.height) {
+            return Err(TextureLoadError::UnsupportedLayout(format!(
+                "{} levels for a {}x{} texture",
+                self.levels.len(),
+                self.width,
+                self.height
+            )));
+        }
+
+        let image_count = self.layers as usize * self.faces as usize;
+        for (level, data) in self.levels.iter().enumerate() {
+            let (width, height) = self.get_level_size(level);
+            let image_size = self.format.get_image_size(width, height);
+            if data.len() < image_size.saturating_mul(image_count) {
+                return Err(TextureLoadError::Truncated);
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn translate_ktx2_format(
+        format: ktx2::Format,
+    ) -> Result<(CompressedFormat, bool), TextureLoadError> {
+        return Ok(match format {
+            ktx2::Format::BC1_RGB_UNORM_BLOCK => (CompressedFormat::Bc1Rgb, false),
+            ktx2::Format::BC1_RGB_SRGB_BLOCK => (CompressedFormat::Bc1Rgb, true),
+            ktx2::Format::BC1_RGBA_UNORM_BLOCK => (CompressedFormat::Bc1Rgba, false),
+            ktx2::Format::BC1_RGBA_SRGB_BLOCK => (CompressedFormat::Bc1Rgba, true),
+            ktx2::Format::BC2_UNORM_BLOCK => (CompressedFormat::Bc2, false),
+            ktx2::Format::BC2_SRGB_BLOCK => (CompressedFormat::Bc2, true),
+            ktx2::Format::BC3_UNORM_BLOCK => (CompressedFormat::Bc3, false),
+            ktx2::Format::BC3_SRGB_BLOCK => (CompressedFormat::Bc3, true),
+            ktx2::Format::BC4_UNORM_BLOCK => (CompressedFormat::Bc4, false),
+            ktx2::Format::BC5_UNORM_BLOCK => (CompressedFormat::Bc5, false),
+            ktx2::Format::BC6H_UFLOAT_BLOCK => (CompressedFormat::Bc6hUnsigned, false),
+            ktx2::Format::BC6H_SFLOAT_BLOCK => (CompressedFormat::Bc6hSigned, false),
+            ktx2::Format::BC7_UNORM_BLOCK => (CompressedFormat::Bc7, false),
+            ktx2::Format::BC7_SRGB_BLOCK => (CompressedFormat::Bc7, true),
+            ktx2::Format::ETC2_R8G8B8_UNORM_BLOCK => (CompressedFormat::Etc2Rgb, false),
+            ktx2::Format::ETC2_R8G8B8_SRGB_BLOCK => (CompressedFormat::Etc2Rgb, true),
+            ktx2::Format::ETC2_R8G8B8A1_UNORM_BLOCK => (CompressedFormat::Etc2RgbA1, false),
+            ktx2::Format::ETC2_R8G8B8A1_SRGB_BLOCK => (CompressedFormat::Etc2RgbA1, true),
+            ktx2::Format::ETC2_R8G8B8A8_UNORM_BLOCK => (CompressedFormat::Etc2Rgba, false),
+            ktx2::Format::ETC2_R8G8B8A8_SRGB_BLOCK => (CompressedFormat::Etc2Rgba, true),
+            format => return Err(TextureLoadError::UnsupportedFormat(format!("{:?}", format))),
+        });
+    }
+
+    fn translate_dxgi_format(
+        format: DxgiFormat,
+    ) -> Result<(CompressedFormat, bool), TextureLoadError> {
+        return Ok(match format {
+            DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => (CompressedFormat::Bc1Rgba, false),
+            DxgiFormat::BC1_UNorm_sRGB => (CompressedFormat::Bc1Rgba, true),
+            DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => (CompressedFormat::Bc2, false),
+            DxgiFormat::BC2_UNorm_sRGB => (CompressedFormat::Bc2, true),
+            DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => (CompressedFormat::Bc3, false),
+            DxgiFormat::BC3_UNorm_sRGB => (CompressedFormat::Bc3, true),
+            DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => (CompressedFormat::Bc4, false),
+            DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => (CompressedFormat::Bc5, false),
+            DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => {
+                (CompressedFormat::Bc6hUnsigned, false)
+            }
+            DxgiFormat::BC6H_SF16 => (CompressedFormat::Bc6hSigned, false),
+            DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => (CompressedFormat::Bc7, false),
+            DxgiFormat::BC7_UNorm_sRGB => (CompressedFormat::Bc7, true),
+            format => return Err(TextureLoadError::UnsupportedFormat(format!("{:?}", format))),
+        });
+    }
+
+    fn translate_d3d_format(
+        format: D3DFormat,
+    ) -> Result<(CompressedFormat, bool), TextureLoadError> {
+        return Ok(match format {
+            D3DFormat::DXT1 => (CompressedFormat::Bc1Rgba, false),
+            D3DFormat::DXT3 => (CompressedFormat::Bc2, false),
+            D3DFormat::DXT5 => (CompressedFormat::Bc3, false),
+            // Same blocks as DXT3 and DXT5 but with premultiplied alpha,
+            // which would be blended wrong.
+            D3DFormat::DXT2 | D3DFormat::DXT4 => {
+                return Err(TextureLoadError::UnsupportedFormat(format!(
+                    "{:?} with premultiplied alpha",
+                    format
+                )))
+            }
+            format => return Err(TextureLoadError::UnsupportedFormat(format!("{:?}", format))),
+        });
+    }
+}
+
+// A full mip chain ends at 1x1.
+fn get_max_level_count(width: u32, height: u32) -> usize {
+    return (32 - width.max(height).max(1).leading_zeros()) as usize;
+}
+
+pub fn load_ktx2(path: &str) -> Result<CompressedTexture, TextureLoadError> {
+    let bytes = fs::read(path).map_err(TextureLoadError::Io)?;
+
+    return CompressedTexture::from_ktx2(&bytes);
+}
+
+pub fn load_dds(path: &str) -> Result<CompressedTexture, TextureLoadError> {
+    let bytes = fs::read(path).map_err(TextureLoadError::Io)?;
+
+    return CompressedTexture::from_dds(&bytes);
+}
+
+pub fn create_compressed_texture(
+    texture: &CompressedTexture,
+) -> Result<Box<dyn Texture>, TextureLoadError> {
+    match get_backend() {
+        #[cfg(feature = "opengl")]
+        Backend::OpenGL => Ok(Box::new(OglTexture::new_compressed(texture)?)),
+        #[cfg(feature = "software")]
+        Backend::Software => Ok(Box::new(SoftwareTexture::new_compressed(texture)?)),
+        #[cfg(feature = "recording")]
+        Backend::Recording => Ok(Box::new(RecordingTexture::new_compressed(texture))),
+        #[cfg(feature = "wgpu")]
+        Backend::Wgpu => Ok(Box::new(WgpuTexture::new_compressed(texture)?)),
+    }
+}
+
+pub fn create_texture_from_file(path: &str) -> Result<Box<dyn Texture>, TextureLoadError> {
+    let texture = if path.to_lowercase().ends_with(".dds") {
+        load_dds(path)?
+    } else {
+        load_ktx2(path)?
+    };
+
+    return create_compressed_texture(&texture);
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    use super::*;
+
+    // A BC1 KTX2 file with a minimal data format descriptor.
+    fn build_ktx2(width: u32, height: u32, levels: &[&[u8]]) -> Vec<u8> {
+        let descriptor_offset = 80 + 24 * levels.len() as u32;
+        let mut bytes = vec![0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+        for value in [131, 1, width, height, 0, 0, 1, levels.len() as u32, 0, descriptor_offset, 4, 0, 0] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        bytes.extend_from_slice(&[0; 16]);
+
+        let mut offset = descriptor_offset as u64 + 4;
+        for level in levels {
+            for value in [offset, level.len() as u64, level.len() as u64] {
+                bytes.extend_from_slice(&u64::to_le_bytes(value));
+            }
+            offset += level.len() as u64;
+        }
+        bytes.extend_from_slice(&u32::to_le_bytes(4));
+        for level in levels {
+            bytes.extend_from_slice(level);
+        }
+
+        return bytes;
+    }
+
+    fn build_dds(levels: u32) -> Vec<u8> {
+        let dds = Dds::new_dxgi(NewDxgiParams {
+            height: 4,
+            width: 4,
+            depth: None,
+            format: DxgiFormat::BC7_UNorm,
+            mipmap_levels: Some(levels),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Straight,
+        })
+        .unwrap();
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+
+        return bytes;
+    }
+
+    #[test]
+    fn reads_ktx2() {
+        let texture = CompressedTexture::from_ktx2(&build_ktx2(8, 4, &[&[1; 16], &[2; 8]])).unwrap();
+
+        assert_eq!(texture.format, CompressedFormat::Bc1Rgb);
+        assert_eq!((texture.width, texture.height, texture.layers, texture.faces), (8, 4, 1, 1));
+        assert_eq!(texture.levels, vec![vec![1; 16], vec![2; 8]]);
+    }
+
+    #[test]
+    fn rejects_malformed_ktx2() {
+        assert!(matches!(
+            CompressedTexture::from_ktx2(b"KTX 20"),
+            Err(TextureLoadError::Ktx2(_))
+        ));
+
+        let mut bytes = build_ktx2(4, 4, &[&[0; 8]]);
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(CompressedTexture::from_ktx2(&bytes), Err(TextureLoadError::Ktx2(_))));
+
+        // Levels shorter than their size, huge sizes and more levels than
+        // the mip chain has.
+        let bytes = build_ktx2(8, 4, &[&[0; 8]]);
+        assert!(matches!(CompressedTexture::from_ktx2(&bytes), Err(TextureLoadError::Truncated)));
+        let bytes = build_ktx2(u32::MAX, u32::MAX, &[&[0; 8]]);
+        assert!(matches!(CompressedTexture::from_ktx2(&bytes), Err(TextureLoadError::Truncated)));
+        let bytes = build_ktx2(1, 1, &[&[0; 8], &[0; 8]]);
+        assert!(matches!(
+            CompressedTexture::from_ktx2(&bytes),
+            Err(TextureLoadError::UnsupportedLayout(_))
+        ));
+    }
+
+    #[test]
+    fn reads_dds() {
+        let texture = CompressedTexture::from_dds(&build_dds(3)).unwrap();
+
+        assert_eq!(texture.format, CompressedFormat::Bc7);
+        assert_eq!(texture.levels.iter().map(|level| level.len()).collect::<Vec<_>>(), [16, 16, 16]);
+    }
+
+    #[test]
+    fn rejects_malformed_dds() {
+        assert!(matches!(CompressedTexture::from_dds(b"DDS "), Err(TextureLoadError::Dds(_))));
+
+        let mut bytes = build_dds(3);
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(CompressedTexture::from_dds(&bytes), Err(TextureLoadError::Truncated)));
+
+        // The mip count in the header.
+        let mut bytes = build_dds(3);
+        bytes[28..32].copy_from_slice(&u32::to_le_bytes(40));
+        assert!(matches!(
+            CompressedTexture::from_dds(&bytes),
+            Err(TextureLoadError::UnsupportedLayout(_))
+        ));
+    }
+}
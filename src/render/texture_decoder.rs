@@ -0,0 +1,1072 @@
+use super::{
+    buffer::BufferDataType,
+    texture::PixelData,
+    texture_container::{CompressedFormat, TextureLoadError},
+};
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+// Subset of every texel, one bit per texel for two subsets and two bits
+// per texel for three subsets.
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xaa685050, 0x6a5a5040, 0x5a5a4200, 0x5450a0a8, 0xa5a50000, 0xa0a05050, 0x5555a0a0, 0x5a5a5050,
+    0xaa550000, 0xaa555500, 0xaaaa5500, 0x90909090, 0x94949494, 0xa4a4a4a4, 0xa9a59450, 0x2a0a4250,
+    0xa5945040, 0x0a425054, 0xa5a5a500, 0x55a0a0a0, 0xa8a85454, 0x6a6a4040, 0xa4a45000, 0x1a1a0500,
+    0x0050a4a4, 0xaaa59090, 0x14696914, 0x69691400, 0xa08585a0, 0xaa821414, 0x50a4a450, 0x6a5a0200,
+    0xa9a58000, 0x5090a0a8, 0xa8a09050, 0x24242424, 0x00aa5500, 0x24924924, 0x24499224, 0x50a50a50,
+    0x500aa550, 0xaaaa4444, 0x66660000, 0xa5a0a5a0, 0x50a050a0, 0x69286928, 0x44aaaa44, 0x66666600,
+    0xaa444444, 0x54a854a8, 0x95809580, 0x96969600, 0xa85454a8, 0x80959580, 0xaa141414, 0x96960000,
+    0xaaaa1414, 0xa05050a0, 0xa0a5a5a0, 0x96000000, 0x40804080, 0xa9a8a9a8, 0xaaaaaa44, 0x2a4a5254,
+];
+
+const BC7_ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2, 2,
+    15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+const BC7_ANCHORS_3_SECOND: [usize; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5, 10,
+    8, 13, 15, 12, 3, 3,
+];
+
+const BC7_ANCHORS_3_THIRD: [usize; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+type Block = [[u8; 4]; 16];
+
+// BC6H holds HDR colors and decodes to floats, every other format to bytes.
+pub fn get_decoded_data_type(format: CompressedFormat) -> BufferDataType {
+    match format {
+        CompressedFormat::Bc6hUnsigned | CompressedFormat::Bc6hSigned => BufferDataType::F32,
+        _ => BufferDataType::U8,
+    }
+}
+
+// Decodes a single image of 4x4 blocks into tightly packed RGBA pixels of
+// the decoded data type.
+pub fn decode_to_rgba(
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+) -> Result<PixelData, TextureLoadError> {
+    if data.len() < format.get_image_size(width, height) {
+        return Err(TextureLoadError::Truncated);
+    }
+
+    let decode_block: fn(&[u8]) -> Block = match format {
+        CompressedFormat::Bc1Rgb => |block| decode_bc1(block, false),
+        CompressedFormat::Bc1Rgba => |block| decode_bc1(block, true),
+        CompressedFormat::Bc2 => decode_bc2,
+        CompressedFormat::Bc3 => decode_bc3,
+        CompressedFormat::Bc4 => decode_bc4,
+        CompressedFormat::Bc5 => decode_bc5,
+        CompressedFormat::Bc7 => decode_bc7,
+        CompressedFormat::Etc2Rgb => |block| decode_etc2(block, false),
+        CompressedFormat::Etc2RgbA1 => |block| decode_etc2(block, true),
+        CompressedFormat::Etc2Rgba => decode_etc2_eac,
+        CompressedFormat::Bc6hUnsigned => {
+            return Ok(PixelData::F32(decode_blocks(format, width, height, data, |block| {
+                decode_bc6h(block, false)
+            })))
+        }
+        CompressedFormat::Bc6hSigned => {
+            return Ok(PixelData::F32(decode_blocks(format, width, height, data, |block| {
+                decode_bc6h(block, true)
+            })))
+        }
+    };
+
+    return Ok(PixelData::U8(decode_blocks(format, width, height, data, decode_block)));
+}
+
+fn decode_blocks<T: Copy + Default>(
+    format: CompressedFormat,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    decode_block: impl Fn(&[u8]) -> [[T; 4]; 16],
+) -> Vec<T> {
+    let (width, height) = (width as usize, height as usize);
+    let block_size = format.get_block_size();
+    let blocks_x = (width + 3) / 4;
+    let blocks_y = (height + 3) / 4;
+    let mut pixels = vec![T::default(); width * height * 4];
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let offset = (block_y * blocks_x + block_x) * block_size;
+            let block = decode_block(&data[offset..offset + block_size]);
+            for (texel, color) in block.iter().enumerate() {
+                let x = block_x * 4 + texel % 4;
+                let y = block_y * 4 + texel / 4;
+                if x < width && y < height {
+                    let pixel = (y * width + x) * 4;
+                    pixels[pixel..pixel + 4].copy_from_slice(color);
+                }
+            }
+        }
+    }
+
+    return pixels;
+}
+
+fn expand_565(color: u16) -> [u32; 3] {
+    let r = ((color >> 11) & 0x1f) as u32;
+    let g = ((color >> 5) & 0x3f) as u32;
+    let b = (color & 0x1f) as u32;
+
+    return [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)];
+}
+
+fn decode_bc1_colors(block: &[u8], allow_transparent: bool) -> Block {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let c0 = expand_565(color0);
+    let c1 = expand_565(color1);
+    let mut palette = [[0u8; 4]; 4];
+    for channel in 0..3 {
+        palette[0][channel] = c0[channel] as u8;
+        palette[1][channel] = c1[channel] as u8;
+        if color0 > color1 || !allow_transparent {
+            palette[2][channel] = ((2 * c0[channel] + c1[channel]) / 3) as u8;
+            palette[3][channel] = ((c0[channel] + 2 * c1[channel]) / 3) as u8;
+        } else {
+            palette[2][channel] = ((c0[channel] + c1[channel]) / 2) as u8;
+        }
+    }
+    palette[0][3] = 255;
+    palette[1][3] = 255;
+    palette[2][3] = 255;
+    palette[3][3] = if color0 <= color1 && allow_transparent { 0 } else { 255 };
+
+    let mut texels = [[0; 4]; 16];
+    for (texel, color) in texels.iter_mut().enumerate() {
+        *color = palette[((indices >> (texel * 2)) & 0x3) as usize];
+    }
+
+    return texels;
+}
+
+fn decode_bc1(block: &[u8], alpha: bool) -> Block {
+    let mut texels = decode_bc1_colors(block, true);
+    if !alpha {
+        for color in texels.iter_mut() {
+            color[3] = 255;
+        }
+    }
+
+    return texels;
+}
+
+fn decode_bc2(block: &[u8]) -> Block {
+    let alpha = u64::from_le_bytes(block[0..8].try_into().unwrap());
+    let mut texels = decode_bc1_colors(&block[8..16], false);
+    for (texel, color) in texels.iter_mut().enumerate() {
+        color[3] = ((alpha >> (texel * 4)) & 0xf) as u8 * 17;
+    }
+
+    return texels;
+}
+
+fn decode_bc3(block: &[u8]) -> Block {
+    let alpha = decode_bc4_channel(&block[0..8]);
+    let mut texels = decode_bc1_colors(&block[8..16], false);
+    for (texel, color) in texels.iter_mut().enumerate() {
+        color[3] = alpha[texel];
+    }
+
+    return texels;
+}
+
+fn decode_bc4_channel(block: &[u8]) -> [u8; 16] {
+    let value0 = block[0] as u32;
+    let value1 = block[1] as u32;
+    let mut palette = [0u8; 8];
+    palette[0] = value0 as u8;
+    palette[1] = value1 as u8;
+    if value0 > value1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * value0 + i as u32 * value1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * value0 + i as u32 * value1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bytes);
+
+    let mut values = [0; 16];
+    for (texel, value) in values.iter_mut().enumerate() {
+        *value = palette[((indices >> (texel * 3)) & 0x7) as usize];
+    }
+
+    return values;
+}
+
+fn decode_bc4(block: &[u8]) -> Block {
+    let red = decode_bc4_channel(block);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, color) in texels.iter_mut().enumerate() {
+        color[0] = red[texel];
+    }
+
+    return texels;
+}
+
+fn decode_bc5(block: &[u8]) -> Block {
+    let red = decode_bc4_channel(&block[0..8]);
+    let green = decode_bc4_channel(&block[8..16]);
+    let mut texels = [[0, 0, 0, 255]; 16];
+    for (texel, color) in texels.iter_mut().enumerate() {
+        color[0] = red[texel];
+        color[1] = green[texel];
+    }
+
+    return texels;
+}
+
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.position) as u32 & ((1u32 << count) - 1);
+        self.position += count;
+
+        return value;
+    }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: true, index_bits: 3, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, endpoint_p_bits: false, shared_p_bits: false, index_bits: 2, secondary_index_bits: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, endpoint_p_bits: true, shared_p_bits: false, index_bits: 4, secondary_index_bits: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, endpoint_p_bits: true, shared_p_bits: false, index_bits: 2, secondary_index_bits: 0 },
+];
+
+fn bc7_weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn bc7_interpolate(endpoint0: u32, endpoint1: u32, weight: u32) -> u8 {
+    return (((64 - weight) * endpoint0 + weight * endpoint1 + 32) >> 6) as u8;
+}
+
+fn decode_bc7(block: &[u8]) -> Block {
+    let mut reader = BitReader {
+        bits: u128::from_le_bytes(block[0..16].try_into().unwrap()),
+        position: 0,
+    };
+
+    let mode_index = block[0].trailing_zeros() as usize;
+    if mode_index >= BC7_MODES.len() {
+        return [[0; 4]; 16];
+    }
+    let mode = &BC7_MODES[mode_index];
+    reader.read(mode_index as u32 + 1);
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // Endpoints are stored channel by channel, two per subset.
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        endpoint[3] = if mode.alpha_bits > 0 {
+            reader.read(mode.alpha_bits)
+        } else {
+            255
+        };
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_p_bits || mode.shared_p_bits {
+        let mut p_bits = [0u32; 6];
+        if mode.endpoint_p_bits {
+            for p_bit in p_bits.iter_mut().take(endpoint_count) {
+                *p_bit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let p_bit = reader.read(1);
+                p_bits[subset * 2] = p_bit;
+                p_bits[subset * 2 + 1] = p_bit;
+            }
+        }
+
+        for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits).take(endpoint_count) {
+            for channel in 0..3 {
+                endpoint[channel] = (endpoint[channel] << 1) | p_bit;
+            }
+            if mode.alpha_bits > 0 {
+                endpoint[3] = (endpoint[3] << 1) | p_bit;
+            }
+        }
+
+        color_bits += 1;
+        if mode.alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for channel in 0..3 {
+            endpoint[channel] = expand_bits(endpoint[channel], color_bits);
+        }
+        if alpha_bits > 0 {
+            endpoint[3] = expand_bits(endpoint[3], alpha_bits);
+        }
+    }
+
+    let subset_of = |texel: usize| -> usize {
+        match mode.subsets {
+            2 => ((BC7_PARTITIONS_2[partition] >> texel) & 1) as usize,
+            3 => ((BC7_PARTITIONS_3[partition] >> (texel * 2)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |texel: usize| -> bool {
+        match mode.subsets {
+            2 => texel == 0 || texel == BC7_ANCHORS_2[partition],
+            3 => {
+                texel == 0
+                    || texel == BC7_ANCHORS_3_SECOND[partition]
+                    || texel == BC7_ANCHORS_3_THIRD[partition]
+            }
+            _ => texel == 0,
+        }
+    };
+
+    let mut indices = [0u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let bits = if is_anchor(texel) { mode.index_bits - 1 } else { mode.index_bits };
+        *index = reader.read(bits);
+    }
+
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            let bits = if texel == 0 {
+                mode.secondary_index_bits - 1
+            } else {
+                mode.secondary_index_bits
+            };
+            *index = reader.read(bits);
+        }
+    }
+
+    let mut texels = [[0u8; 4]; 16];
+    for (texel, color) in texels.iter_mut().enumerate() {
+        let subset = subset_of(texel);
+        let endpoint0 = endpoints[subset * 2];
+        let endpoint1 = endpoints[subset * 2 + 1];
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = bc7_weight(mode.index_bits, indices[texel]);
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                bc7_weight(mode.index_bits, indices[texel]),
+                bc7_weight(mode.secondary_index_bits, secondary_indices[texel]),
+            )
+        } else {
+            (
+                bc7_weight(mode.secondary_index_bits, secondary_indices[texel]),
+                bc7_weight(mode.index_bits, indices[texel]),
+            )
+        };
+
+        for channel in 0..3 {
+            color[channel] = bc7_interpolate(endpoint0[channel], endpoint1[channel], color_weight);
+        }
+        color[3] = bc7_interpolate(endpoint0[3], endpoint1[3], alpha_weight);
+
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+    }
+
+    return texels;
+}
+
+// BC6H header fields, the red, green and blue channels of the endpoints w
+// and x of the first subset, y and z of the second, then the partition.
+const RW: usize = 0;
+const GW: usize = 1;
+const BW: usize = 2;
+const RX: usize = 3;
+const GX: usize = 4;
+const BX: usize = 5;
+const RY: usize = 6;
+const GY: usize = 7;
+const BY: usize = 8;
+const RZ: usize = 9;
+const GZ: usize = 10;
+const BZ: usize = 11;
+const D: usize = 12;
+
+struct Bc6hMode {
+    value: u32,
+    subsets: usize,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    // Runs of field bits in stream order, from the first bit to the last.
+    // Some modes store the high endpoint bits reversed.
+    fields: &'static [(usize, u32, u32)],
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { value: 0, subsets: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (GZ, 4, 4),
+        (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4),
+        (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 1, subsets: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        (GY, 5, 5), (GZ, 4, 5), (RW, 0, 6), (BZ, 0, 1), (BY, 4, 4), (GW, 0, 6), (BY, 5, 5), (BZ, 2, 2),
+        (GY, 4, 4), (BW, 0, 6), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3), (GX, 0, 5),
+        (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 2, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 4), (RW, 10, 10), (GY, 0, 3), (GX, 0, 3),
+        (GW, 10, 10), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3),
+        (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 6, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (GZ, 4, 4), (GY, 0, 3),
+        (GX, 0, 4), (GW, 10, 10), (GZ, 0, 3), (BX, 0, 3), (BW, 10, 10), (BZ, 1, 1), (BY, 0, 3),
+        (RY, 0, 3), (BZ, 0, 0), (BZ, 2, 2), (RZ, 0, 3), (GY, 4, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 10, subsets: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 10, 10), (BY, 4, 4), (GY, 0, 3),
+        (GX, 0, 3), (GW, 10, 10), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BW, 10, 10), (BY, 0, 3),
+        (RY, 0, 3), (BZ, 1, 2), (RZ, 0, 3), (BZ, 4, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 14, subsets: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        (RW, 0, 8), (BY, 4, 4), (GW, 0, 8), (GY, 4, 4), (BW, 0, 8), (BZ, 4, 4), (RX, 0, 4), (GZ, 4, 4),
+        (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3), (RY, 0, 4),
+        (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 18, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        (RW, 0, 7), (GZ, 4, 4), (BY, 4, 4), (GW, 0, 7), (BZ, 2, 2), (GY, 4, 4), (BW, 0, 7), (BZ, 3, 4),
+        (RX, 0, 5), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3),
+        (RY, 0, 5), (RZ, 0, 5), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 22, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        (RW, 0, 7), (BZ, 0, 0), (BY, 4, 4), (GW, 0, 7), (GY, 5, 4), (BW, 0, 7), (GZ, 5, 5), (BZ, 4, 4),
+        (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 5), (GZ, 0, 3), (BX, 0, 4), (BZ, 1, 1), (BY, 0, 3),
+        (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 26, subsets: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        (RW, 0, 7), (BZ, 1, 1), (BY, 4, 4), (GW, 0, 7), (BY, 5, 5), (GY, 4, 4), (BW, 0, 7), (BZ, 5, 4),
+        (RX, 0, 4), (GZ, 4, 4), (GY, 0, 3), (GX, 0, 4), (BZ, 0, 0), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3),
+        (RY, 0, 4), (BZ, 2, 2), (RZ, 0, 4), (BZ, 3, 3), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 30, subsets: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        (RW, 0, 5), (GZ, 4, 4), (BZ, 0, 1), (BY, 4, 4), (GW, 0, 5), (GY, 5, 5), (BY, 5, 5), (BZ, 2, 2),
+        (GY, 4, 4), (BW, 0, 5), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 0, 5), (GY, 0, 3),
+        (GX, 0, 5), (GZ, 0, 3), (BX, 0, 5), (BY, 0, 3), (RY, 0, 5), (RZ, 0, 5), (D, 0, 4),
+    ] },
+    Bc6hMode { value: 3, subsets: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 9), (GX, 0, 9), (BX, 0, 9),
+    ] },
+    Bc6hMode { value: 7, subsets: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 8), (RW, 10, 10), (GX, 0, 8), (GW, 10, 10),
+        (BX, 0, 8), (BW, 10, 10),
+    ] },
+    Bc6hMode { value: 11, subsets: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 7), (RW, 11, 10), (GX, 0, 7), (GW, 11, 10),
+        (BX, 0, 7), (BW, 11, 10),
+    ] },
+    Bc6hMode { value: 15, subsets: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        (RW, 0, 9), (GW, 0, 9), (BW, 0, 9), (RX, 0, 3), (RW, 15, 10), (GX, 0, 3), (GW, 15, 10),
+        (BX, 0, 3), (BW, 15, 10),
+    ] },
+];
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+
+    return (value << shift) >> shift;
+}
+
+// Scales an endpoint to the full 16 bit range before interpolation.
+fn bc6h_unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            return value;
+        }
+        if value == (1 << bits) - 1 {
+            return 0xffff;
+        }
+        return ((value << 16) + 0x8000) >> bits;
+    }
+
+    if bits >= 16 {
+        return value;
+    }
+    let magnitude = value.abs();
+    let unquantized = if magnitude == 0 {
+        0
+    } else if magnitude >= (1 << (bits - 1)) - 1 {
+        0x7fff
+    } else {
+        ((magnitude << 15) + 0x4000) >> (bits - 1)
+    };
+
+    return if value < 0 { -unquantized } else { unquantized };
+}
+
+// Scales an interpolated value to the bits of a half float.
+fn bc6h_finish(value: i32, signed: bool) -> u16 {
+    if !signed {
+        return ((value * 31) >> 6) as u16;
+    }
+
+    let magnitude = ((value.abs() * 31) >> 5) as u16;
+    return if value < 0 { 0x8000 | magnitude } else { magnitude };
+}
+
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((half >> 10) & 0x1f) as i32;
+    let mantissa = (half & 0x3ff) as f32;
+    let magnitude = match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    };
+
+    return sign * magnitude;
+}
+
+fn decode_bc6h(block: &[u8], signed: bool) -> [[f32; 4]; 16] {
+    let mut reader = BitReader {
+        bits: u128::from_le_bytes(block[0..16].try_into().unwrap()),
+        position: 0,
+    };
+
+    // Two bit modes leave the other three bits to the endpoints.
+    let mut value = reader.read(2);
+    if value > 1 {
+        value |= reader.read(3) << 2;
+    }
+    let mode = match BC6H_MODES.iter().find(|mode| mode.value == value) {
+        Some(mode) => mode,
+        // Reserved modes decode to black.
+        None => return [[0.0, 0.0, 0.0, 1.0]; 16],
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, first, last) in mode.fields {
+        for step in 0..first.abs_diff(last) + 1 {
+            let bit = if first <= last { first + step } else { first - step };
+            fields[field] |= (reader.read(1) as i32) << bit;
+        }
+    }
+    let partition = fields[D] as usize;
+
+    // The other endpoints are deltas from w in the transformed modes.
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0i32; 3]; 4];
+    for channel in 0..3 {
+        for (endpoint, values) in endpoints.iter_mut().enumerate().take(endpoint_count) {
+            values[channel] = fields[endpoint * 3 + channel];
+        }
+
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], mode.endpoint_bits);
+        }
+        let base = endpoints[0][channel];
+        for values in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            if mode.transformed {
+                let delta = sign_extend(values[channel], mode.delta_bits[channel]);
+                values[channel] = (base + delta) & ((1 << mode.endpoint_bits) - 1);
+            }
+            if signed {
+                values[channel] = sign_extend(values[channel], mode.endpoint_bits);
+            }
+        }
+    }
+    for values in endpoints.iter_mut().take(endpoint_count) {
+        for value in values.iter_mut() {
+            *value = bc6h_unquantize(*value, mode.endpoint_bits, signed);
+        }
+    }
+
+    let index_bits = if mode.subsets == 2 { 3 } else { 4 };
+    let mut texels = [[0.0, 0.0, 0.0, 1.0]; 16];
+    for (texel, color) in texels.iter_mut().enumerate() {
+        let (subset, anchor) = if mode.subsets == 2 {
+            (
+                ((BC7_PARTITIONS_2[partition] >> texel) & 1) as usize,
+                texel == 0 || texel == BC7_ANCHORS_2[partition],
+            )
+        } else {
+            (0, texel == 0)
+        };
+        let index = reader.read(if anchor { index_bits - 1 } else { index_bits });
+        let weight = bc7_weight(index_bits, index) as i32;
+
+        let endpoint0 = endpoints[subset * 2];
+        let endpoint1 = endpoints[subset * 2 + 1];
+        for channel in 0..3 {
+            let value = ((64 - weight) * endpoint0[channel] + weight * endpoint1[channel] + 32) >> 6;
+            color[channel] = half_to_f32(bc6h_finish(value, signed));
+        }
+    }
+
+    return texels;
+}
+
+fn expand_bits(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+
+    return value | (value >> bits);
+}
+
+fn clamp_color(value: i32) -> u8 {
+    return value.clamp(0, 255) as u8;
+}
+
+fn add_distance(color: [i32; 3], distance: i32) -> [u8; 4] {
+    return [
+        clamp_color(color[0] + distance),
+        clamp_color(color[1] + distance),
+        clamp_color(color[2] + distance),
+        255,
+    ];
+}
+
+// ETC texels are stored column by column, with the most significant index
+// bits in the upper half of the low word.
+fn get_etc_index(bits: u64, x: usize, y: usize) -> usize {
+    let texel = x * 4 + y;
+    let lsb = (bits >> texel) & 1;
+    let msb = (bits >> (texel + 16)) & 1;
+
+    return (msb * 2 + lsb) as usize;
+}
+
+fn decode_etc2(block: &[u8], punchthrough: bool) -> Block {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let diff_bit = (bits >> 33) & 1 == 1;
+    let flip = (bits >> 32) & 1 == 1;
+    let opaque = !punchthrough || diff_bit;
+    let mut texels = [[0u8; 4]; 16];
+
+    if !punchthrough && !diff_bit {
+        let extend = |value: u64| -> i32 { ((value & 0xf) * 17) as i32 };
+        let base = [
+            [extend(bits >> 60), extend(bits >> 52), extend(bits >> 44)],
+            [extend(bits >> 56), extend(bits >> 48), extend(bits >> 40)],
+        ];
+        let tables = [((bits >> 37) & 0x7) as usize, ((bits >> 34) & 0x7) as usize];
+        decode_etc_subblocks(bits, base, tables, flip, true, &mut texels);
+
+        return texels;
+    }
+
+    let red = ((bits >> 59) & 0x1f) as i32;
+    let green = ((bits >> 51) & 0x1f) as i32;
+    let blue = ((bits >> 43) & 0x1f) as i32;
+    let signed = |value: u64| -> i32 { (((value & 0x7) as i32) << 29) >> 29 };
+    let red2 = red + signed(bits >> 56);
+    let green2 = green + signed(bits >> 48);
+    let blue2 = blue + signed(bits >> 40);
+
+    if !(0..32).contains(&red2) {
+        let extend = |value: u64| -> i32 { ((value & 0xf) * 17) as i32 };
+        let color1 = [
+            extend(((bits >> 57) & 0xc) | ((bits >> 56) & 0x3)),
+            extend(bits >> 52),
+            extend(bits >> 48),
+        ];
+        let color2 = [extend(bits >> 44), extend(bits >> 40), extend(bits >> 36)];
+        let distance = ETC_DISTANCES[(((bits >> 33) & 0x6) | ((bits >> 32) & 0x1)) as usize];
+        let palette = [
+            add_distance(color1, 0),
+            add_distance(color2, distance),
+            add_distance(color2, 0),
+            add_distance(color2, -distance),
+        ];
+        decode_etc_palette(bits, palette, opaque, &mut texels);
+    } else if !(0..32).contains(&green2) {
+        let extend = |value: u64| -> i32 { ((value & 0xf) * 17) as i32 };
+        let raw1 = [
+            (bits >> 59) & 0xf,
+            ((bits >> 55) & 0xe) | ((bits >> 52) & 0x1),
+            ((bits >> 48) & 0x8) | ((bits >> 47) & 0x7),
+        ];
+        let raw2 = [(bits >> 43) & 0xf, (bits >> 39) & 0xf, (bits >> 35) & 0xf];
+        let value1 = (raw1[0] << 8) | (raw1[1] << 4) | raw1[2];
+        let value2 = (raw2[0] << 8) | (raw2[1] << 4) | raw2[2];
+        let distance_index = ((bits >> 32) & 0x4)
+            | ((bits >> 31) & 0x2)
+            | if value1 >= value2 { 1 } else { 0 };
+        let distance = ETC_DISTANCES[distance_index as usize];
+        let color1 = [extend(raw1[0]), extend(raw1[1]), extend(raw1[2])];
+        let color2 = [extend(raw2[0]), extend(raw2[1]), extend(raw2[2])];
+        let palette = [
+            add_distance(color1, distance),
+            add_distance(color1, -distance),
+            add_distance(color2, distance),
+            add_distance(color2, -distance),
+        ];
+        decode_etc_palette(bits, palette, opaque, &mut texels);
+    } else if !(0..32).contains(&blue2) {
+        let extend6 = |value: u64| -> i32 {
+            let value = (value & 0x3f) as i32;
+            (value << 2) | (value >> 4)
+        };
+        let extend7 = |value: u64| -> i32 {
+            let value = (value & 0x7f) as i32;
+            (value << 1) | (value >> 6)
+        };
+        let origin = [
+            extend6(bits >> 57),
+            extend7(((bits >> 50) & 0x40) | ((bits >> 49) & 0x3f)),
+            extend6(((bits >> 43) & 0x20) | ((bits >> 40) & 0x18) | ((bits >> 39) & 0x7)),
+        ];
+        let horizontal = [
+            extend6(((bits >> 33) & 0x3e) | ((bits >> 32) & 0x1)),
+            extend7(bits >> 25),
+            extend6(bits >> 19),
+        ];
+        let vertical = [extend6(bits >> 13), extend7(bits >> 6), extend6(bits)];
+        for (texel, color) in texels.iter_mut().enumerate() {
+            let x = (texel % 4) as i32;
+            let y = (texel / 4) as i32;
+            for channel in 0..3 {
+                let value = x * (horizontal[channel] - origin[channel])
+                    + y * (vertical[channel] - origin[channel])
+                    + 4 * origin[channel]
+                    + 2;
+                color[channel] = clamp_color(value >> 2);
+            }
+            color[3] = 255;
+        }
+    } else {
+        let extend = |value: i32| -> i32 { (value << 3) | (value >> 2) };
+        let base = [
+            [extend(red), extend(green), extend(blue)],
+            [extend(red2), extend(green2), extend(blue2)],
+        ];
+        let tables = [((bits >> 37) & 0x7) as usize, ((bits >> 34) & 0x7) as usize];
+        decode_etc_subblocks(bits, base, tables, flip, opaque, &mut texels);
+    }
+
+    return texels;
+}
+
+fn decode_etc_subblocks(
+    bits: u64,
+    base: [[i32; 3]; 2],
+    tables: [usize; 2],
+    flip: bool,
+    opaque: bool,
+    texels: &mut Block,
+) {
+    for (texel, color) in texels.iter_mut().enumerate() {
+        let x = texel % 4;
+        let y = texel / 4;
+        let subblock = if flip { (y >= 2) as usize } else { (x >= 2) as usize };
+        let index = get_etc_index(bits, x, y);
+
+        // Without the opaque bit the two small modifiers are replaced by
+        // zero and a fully transparent texel.
+        if !opaque && index == 2 {
+            *color = [0, 0, 0, 0];
+            continue;
+        }
+        let modifiers = ETC_MODIFIERS[tables[subblock]];
+        let modifier = match index {
+            0 if !opaque => 0,
+            0 => modifiers[0],
+            1 => modifiers[1],
+            2 => -modifiers[0],
+            _ => -modifiers[1],
+        };
+
+        *color = add_distance(base[subblock], modifier);
+    }
+}
+
+fn decode_etc_palette(bits: u64, palette: [[u8; 4]; 4], opaque: bool, texels: &mut Block) {
+    for (texel, color) in texels.iter_mut().enumerate() {
+        let index = get_etc_index(bits, texel % 4, texel / 4);
+        *color = if !opaque && index == 2 {
+            [0, 0, 0, 0]
+        } else {
+            palette[index]
+        };
+    }
+}
+
+fn decode_etc2_eac(block: &[u8]) -> Block {
+    let bits = u64::from_be_bytes(block[0..8].try_into().unwrap());
+    let base = ((bits >> 56) & 0xff) as i32;
+    let multiplier = ((bits >> 52) & 0xf) as i32;
+    let modifiers = EAC_MODIFIERS[((bits >> 48) & 0xf) as usize];
+
+    let mut texels = decode_etc2(&block[8..16], false);
+    for (texel, color) in texels.iter_mut().enumerate() {
+        let x = texel % 4;
+        let y = texel / 4;
+        let index = ((bits >> (45 - 3 * (x * 4 + y))) & 0x7) as usize;
+        color[3] = clamp_color(base + modifiers[index] * multiplier);
+    }
+
+    return texels;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Packs fields of (value, bit count) least significant bit first.
+    fn pack(fields: &[(u128, u32)]) -> Vec<u8> {
+        let mut bits = 0u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= value << position;
+            position += count;
+        }
+
+        return bits.to_le_bytes().to_vec();
+    }
+
+    fn decode_bytes(format: CompressedFormat, block: &[u8]) -> Vec<u8> {
+        match decode_to_rgba(format, 4, 4, block).unwrap() {
+            PixelData::U8(pixels) => pixels,
+            _ => panic!("Expected bytes"),
+        }
+    }
+
+    fn decode_floats(format: CompressedFormat, block: &[u8]) -> Vec<f32> {
+        match decode_to_rgba(format, 4, 4, block).unwrap() {
+            PixelData::F32(pixels) => pixels,
+            _ => panic!("Expected floats"),
+        }
+    }
+
+    #[test]
+    fn decodes_bc1() {
+        // Red and blue endpoints, the second texel uses the blue one.
+        let pixels = decode_bytes(CompressedFormat::Bc1Rgb, &[0x00, 0xf8, 0x1f, 0x00, 0x04, 0, 0, 0]);
+        assert_eq!(pixels[0..8], [255, 0, 0, 255, 0, 0, 255, 255]);
+
+        // Endpoints in ascending order select the transparent palette.
+        let pixels = decode_bytes(CompressedFormat::Bc1Rgba, &[0x1f, 0x00, 0x00, 0xf8, 0x03, 0, 0, 0]);
+        assert_eq!(pixels[0..4], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decodes_bc2() {
+        let mut block = vec![0x08, 0, 0, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[0x00, 0xf8, 0x00, 0x00, 0, 0, 0, 0]);
+        let pixels = decode_bytes(CompressedFormat::Bc2, &block);
+
+        assert_eq!(pixels[0..8], [255, 0, 0, 136, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decodes_bc3() {
+        // The first texel selects the second alpha endpoint, the second the
+        // one interpolated a seventh of the way.
+        let mut block = vec![255, 0, 0x11, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[0xe0, 0x07, 0x00, 0x00, 0, 0, 0, 0]);
+        let pixels = decode_bytes(CompressedFormat::Bc3, &block);
+
+        assert_eq!(pixels[0..8], [0, 255, 0, 0, 0, 255, 0, 218]);
+    }
+
+    #[test]
+    fn decodes_bc4_and_bc5() {
+        // Ascending endpoints use the palette with explicit zero and full.
+        let red = [10, 20, 0x7e, 0, 0, 0, 0, 0];
+        let pixels = decode_bytes(CompressedFormat::Bc4, &red);
+        assert_eq!(pixels[0..12], [0, 0, 0, 255, 255, 0, 0, 255, 20, 0, 0, 255]);
+
+        let mut block = red.to_vec();
+        block.extend_from_slice(&[200, 100, 0, 0, 0, 0, 0, 0]);
+        let pixels = decode_bytes(CompressedFormat::Bc5, &block);
+        assert_eq!(pixels[0..4], [0, 200, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_bc6h() {
+        // Mode 11 with one subset, 495 unquantizes to exactly 1.0 and the
+        // largest endpoint to the largest half float.
+        let block = pack(&[
+            (3, 5),
+            (495, 10),
+            (495, 10),
+            (495, 10),
+            (1023, 10),
+            (1023, 10),
+            (1023, 10),
+            (0, 3),
+            (15, 4),
+        ]);
+        let pixels = decode_floats(CompressedFormat::Bc6hUnsigned, &block);
+        assert_eq!(pixels[0..8], [1.0, 1.0, 1.0, 1.0, 65504.0, 65504.0, 65504.0, 1.0]);
+
+        let block = pack(&[(3, 5), (1024 - 247, 10)]);
+        let pixels = decode_floats(CompressedFormat::Bc6hSigned, &block);
+        assert_eq!(pixels[0..4], [-(0.5 + 1009.0 / 2048.0), 0.0, 0.0, 1.0]);
+
+        // Mode 1 with two subsets, the zero deltas give every endpoint the
+        // red of the base endpoint.
+        let block = pack(&[(0, 2), (0, 3), (495, 10)]);
+        let pixels = decode_floats(CompressedFormat::Bc6hUnsigned, &block);
+        assert_eq!(pixels[0..4], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixels[60..64], [1.0, 0.0, 0.0, 1.0]);
+
+        // Reserved modes are black.
+        let pixels = decode_floats(CompressedFormat::Bc6hUnsigned, &pack(&[(19, 5)]));
+        assert_eq!(pixels[0..4], [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn decodes_bc7() {
+        // Mode 6, the endpoints take the p bit as their lowest bit.
+        let block = pack(&[
+            (64, 7),
+            (127, 7),
+            (0, 7),
+            (64, 7),
+            (0, 7),
+            (0, 7),
+            (0, 7),
+            (127, 7),
+            (0, 7),
+            (1, 1),
+            (0, 1),
+            (0, 3),
+            (15, 4),
+        ]);
+        let pixels = decode_bytes(CompressedFormat::Bc7, &block);
+
+        assert_eq!(pixels[0..8], [255, 129, 1, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decodes_etc2() {
+        // Individual mode with gray base colors, the first texel subtracts
+        // the small modifier and the others add it.
+        let pixels = decode_bytes(CompressedFormat::Etc2Rgb, &[0x88, 0x88, 0x88, 0x00, 0, 0x01, 0, 0]);
+        assert_eq!(pixels[0..8], [134, 134, 134, 255, 138, 138, 138, 255]);
+
+        // Without the opaque bit the first texel is transparent and the
+        // others use the base color.
+        let pixels = decode_bytes(CompressedFormat::Etc2RgbA1, &[0x80, 0x80, 0x80, 0x00, 0, 0x01, 0, 0]);
+        assert_eq!(pixels[0..8], [0, 0, 0, 0, 132, 132, 132, 255]);
+    }
+
+    #[test]
+    fn decodes_etc2_eac() {
+        let mut block = vec![128, 0x10, 0, 0, 0, 0, 0, 0];
+        block.extend_from_slice(&[0x88, 0x88, 0x88, 0x00, 0, 0, 0, 0]);
+        let pixels = decode_bytes(CompressedFormat::Etc2Rgba, &block);
+
+        assert_eq!(pixels[0..4], [138, 138, 138, 125]);
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        assert!(matches!(
+            decode_to_rgba(CompressedFormat::Bc7, 8, 4, &[0; 16]),
+            Err(TextureLoadError::Truncated)
+        ));
+    }
+}
@@ -1,18 +1,106 @@
-use std::ffi::c_void;
+use std::{
+    ffi::c_void,
+    sync::{Arc, Mutex},
+};
 
-use crate::{platform::glfw as trash_glfw, event::Event};
+use crate::event::Event;
+#[cfg(feature = "glfw")]
+use crate::platform::glfw as trash_glfw;
+#[cfg(any(feature = "software", feature = "recording", feature = "wgpu"))]
+use crate::platform::null_window::NullWindow;
+#[cfg(feature = "headless")]
+use crate::platform::osmesa::OsMesaWindow;
+
+// Windows publish framebuffer resizes here so the render API created for
+// them can follow without seeing the window events.
+#[derive(Clone, Default)]
+pub struct FramebufferResize(Arc<Mutex<Option<(u32, u32)>>>);
+
+impl FramebufferResize {
+    #[cfg(feature = "glfw")]
+    pub(crate) fn notify(&self, width: u32, height: u32) {
+        *self.0.lock().unwrap() = Some((width, height));
+    }
+
+    pub(crate) fn take(&self) -> Option<(u32, u32)> {
+        return self.0.lock().unwrap().take();
+    }
+}
 
 pub trait Window {
     fn close(&mut self);
+    // Size in screen coordinates, which differs from the framebuffer size
+    // in pixels on HiDPI displays.
     fn get_size(&self) -> (f32, f32);
+    fn get_framebuffer_size(&self) -> (u32, u32);
     fn get_events(&mut self) -> Vec<Event>;
     fn get_proc_addr(&mut self, procname: &str) -> *const c_void;
     fn should_close(&self) -> bool;
     fn update(&mut self);
     fn set_title(&mut self, title: &str);
     fn toggle_mouse_grab(&mut self);
+
+    // Windows that never resize hand out one that stays empty.
+    fn get_framebuffer_resize(&self) -> FramebufferResize {
+        return FramebufferResize::default();
+    }
+}
+
+// OpenGL flavour a window asks the driver for. Desktop contexts are core
+// profile and forward compatible, the only kind macOS offers past 2.1.
+// Drivers may hand out a newer version than requested.
+#[cfg(feature = "glfw")]
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContextRequest {
+    Core { major: u32, minor: u32 },
+    Es { major: u32, minor: u32 },
+}
+
+#[cfg(feature = "glfw")]
+impl ContextRequest {
+    pub const CORE_3_3: ContextRequest = ContextRequest::Core { major: 3, minor: 3 };
+    pub const CORE_4_5: ContextRequest = ContextRequest::Core { major: 4, minor: 5 };
+    pub const ES_3_0: ContextRequest = ContextRequest::Es { major: 3, minor: 0 };
+}
+
+#[cfg(feature = "glfw")]
+impl Default for ContextRequest {
+    fn default() -> Self {
+        return ContextRequest::CORE_3_3;
+    }
 }
 
+#[cfg(feature = "glfw")]
 pub fn create_window() -> Box<dyn Window> {
     return trash_glfw::GlfwWindow::new();
 }
+
+#[cfg(feature = "glfw")]
+pub fn create_multisampled_window(samples: u32) -> Box<dyn Window> {
+    return trash_glfw::GlfwWindow::new_with_hints(samples, false, ContextRequest::default());
+}
+
+// Requests a debug context so the driver reports misuse through the log.
+#[cfg(feature = "glfw")]
+pub fn create_debug_window() -> Box<dyn Window> {
+    return trash_glfw::GlfwWindow::new_with_hints(0, true, ContextRequest::default());
+}
+
+#[cfg(feature = "glfw")]
+pub fn create_window_with_context(request: ContextRequest) -> Box<dyn Window> {
+    return trash_glfw::GlfwWindow::new_with_hints(0, false, request);
+}
+
+// Renders off-screen without a display, e.g. on CI through Mesa's llvmpipe.
+#[cfg(feature = "headless")]
+pub fn create_headless_window(width: u32, height: u32) -> Box<dyn Window> {
+    return OsMesaWindow::new(width, height);
+}
+
+// Window without a GL context for the software, recording and wgpu
+// renderers.
+#[cfg(any(feature = "software", feature = "recording", feature = "wgpu"))]
+pub fn create_null_window(width: u32, height: u32) -> Box<dyn Window> {
+    return NullWindow::new(width, height);
+}